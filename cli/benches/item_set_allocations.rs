@@ -0,0 +1,114 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use tree_sitter_cli::generate::build_tables::build_parse_table::build_parse_table;
+use tree_sitter_cli::generate::build_tables::item::ParseItemSet;
+use tree_sitter_cli::generate::grammars::{InputGrammar, Variable, VariableType};
+use tree_sitter_cli::generate::node_types::get_variable_info;
+use tree_sitter_cli::generate::prepare_grammar::prepare_grammar;
+use tree_sitter_cli::generate::rules::Rule;
+
+struct CountingAllocator;
+
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const RULE_COUNT: usize = 500;
+const REPETITION_COUNT: usize = 20;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let start = ALLOCATION_COUNT.load(Ordering::Relaxed);
+    f();
+    ALLOCATION_COUNT.load(Ordering::Relaxed) - start
+}
+
+// Each rule consists of a keyword followed by the next rule, so nearly every
+// parse state contains a single item.
+fn linear_grammar() -> InputGrammar {
+    InputGrammar {
+        variables: (0..RULE_COUNT)
+            .map(|i| Variable {
+                name: format!("rule_{i}"),
+                kind: VariableType::Named,
+                rule: if i + 1 < RULE_COUNT {
+                    Rule::seq(vec![
+                        Rule::String(format!("keyword_{i}")),
+                        Rule::NamedSymbol(format!("rule_{}", i + 1)),
+                    ])
+                } else {
+                    Rule::String("end".to_string())
+                },
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn main() {
+    let grammar = linear_grammar();
+    let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+        prepare_grammar(&grammar).unwrap();
+    let variable_info =
+        get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+
+    let mut parse_state_info = Vec::new();
+    let time = Instant::now();
+    let build_allocations = count_allocations(|| {
+        for _ in 0..REPETITION_COUNT {
+            parse_state_info =
+                build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info)
+                    .unwrap()
+                    .2;
+        }
+    });
+    let duration = time.elapsed() / REPETITION_COUNT as u32;
+
+    let item_sets = parse_state_info
+        .into_iter()
+        .map(|(_, item_set)| item_set)
+        .collect::<Vec<_>>();
+    let single_item_count = item_sets
+        .iter()
+        .filter(|item_set| item_set.entries.len() == 1)
+        .count();
+
+    // Compare the cost of copying every item set against copying the same
+    // entries into plain vectors.
+    let item_set_allocations = count_allocations(|| {
+        let copies: Vec<ParseItemSet> = item_sets.to_vec();
+        drop(copies);
+    });
+    let vec_allocations = count_allocations(|| {
+        let copies = item_sets
+            .iter()
+            .map(|item_set| item_set.entries.to_vec())
+            .collect::<Vec<_>>();
+        drop(copies);
+    });
+
+    eprintln!("\nLinear grammar with {RULE_COUNT} rules:");
+    eprintln!(
+        "  {} parse states, {single_item_count} with a single item",
+        item_sets.len()
+    );
+    eprintln!(
+        "  build time: {} ms, allocations: {}",
+        duration.as_millis(),
+        build_allocations / REPETITION_COUNT
+    );
+    eprintln!("  allocations to copy item sets: {item_set_allocations}");
+    eprintln!("  allocations to copy entries as vectors: {vec_allocations}");
+}
@@ -0,0 +1,116 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tree_sitter_cli::generate::build_tables::build_parse_table::build_parse_table_with_item_set_builder;
+use tree_sitter_cli::generate::build_tables::item_set_builder::ParseItemSetBuilder;
+use tree_sitter_cli::generate::grammars::{InputGrammar, Variable, VariableType};
+use tree_sitter_cli::generate::node_types::get_variable_info;
+use tree_sitter_cli::generate::prepare_grammar::prepare_grammar;
+use tree_sitter_cli::generate::rules::{Rule, Symbol, TokenSet};
+
+struct CountingAllocator;
+
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const KEYWORD_COUNT: usize = 1000;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let start = ALLOCATION_COUNT.load(Ordering::Relaxed);
+    f();
+    ALLOCATION_COUNT.load(Ordering::Relaxed) - start
+}
+
+// A statement can begin with any of many keywords, so the FIRST set of
+// `statement` and the lookaheads in the start state contain every terminal.
+fn keyword_grammar() -> InputGrammar {
+    InputGrammar {
+        variables: vec![
+            Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::seq(vec![
+                    Rule::NamedSymbol("statement".to_string()),
+                    Rule::String(";".to_string()),
+                ]),
+            },
+            Variable {
+                name: "statement".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(
+                    (0..KEYWORD_COUNT)
+                        .map(|i| Rule::String(format!("keyword_{i}")))
+                        .collect(),
+                ),
+            },
+        ],
+        ..Default::default()
+    }
+}
+
+// Insert every terminal into a set, in increasing order, which is the order in
+// which the builder usually discovers them.
+fn fill(mut set: TokenSet, terminal_count: usize) -> TokenSet {
+    for i in 0..terminal_count {
+        set.insert(Symbol::terminal(i));
+    }
+    set
+}
+
+fn main() {
+    let grammar = keyword_grammar();
+    let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+        prepare_grammar(&grammar).unwrap();
+    let variable_info =
+        get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+    let terminal_count = lexical_grammar.variables.len();
+
+    let build_allocations = |token_set_capacity| {
+        count_allocations(|| {
+            let item_set_builder = ParseItemSetBuilder::with_token_set_capacity(
+                &syntax_grammar,
+                &lexical_grammar,
+                &inlines,
+                token_set_capacity,
+            );
+            build_parse_table_with_item_set_builder(
+                &syntax_grammar,
+                &lexical_grammar,
+                &inlines,
+                &variable_info,
+                item_set_builder,
+            )
+            .unwrap();
+        })
+    };
+    let unhinted_build_allocations = build_allocations(0);
+    let hinted_build_allocations = build_allocations(terminal_count);
+    let unhinted_allocations = count_allocations(|| {
+        fill(TokenSet::new(), terminal_count);
+    });
+    let hinted_allocations = count_allocations(|| {
+        fill(TokenSet::with_capacity(terminal_count), terminal_count);
+    });
+
+    eprintln!("\nGrammar with {terminal_count} terminals:");
+    eprintln!(
+        "  allocations to build the parse table without a capacity hint: {unhinted_build_allocations}"
+    );
+    eprintln!(
+        "  allocations to build the parse table with a capacity hint: {hinted_build_allocations}"
+    );
+    eprintln!("  allocations to fill a set without a capacity hint: {unhinted_allocations}");
+    eprintln!("  allocations to fill a set with a capacity hint: {hinted_allocations}");
+}
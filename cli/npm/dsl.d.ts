@@ -137,6 +137,18 @@ interface Grammar<
     previous: Rule[],
   ) => RuleOrLiteral[];
 
+  /**
+   * An array of pairs of a rule and a symbol or token. Each rule is inlined
+   * only where it directly follows the symbol or token it is paired with.
+   *
+   * @param $ grammar rules
+   * @param previous array of pairs from the base schema, if any
+   */
+  inline_after?: (
+    $: GrammarSymbols<RuleName | BaseGrammarRuleName>,
+    previous: [Rule, Rule][],
+  ) => [RuleOrLiteral, RuleOrLiteral][];
+
   /**
    * A list of hidden rule names that should be considered supertypes in the
    * generated node types file.
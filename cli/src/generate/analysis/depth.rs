@@ -0,0 +1,93 @@
+use crate::generate::grammars::SyntaxGrammar;
+
+/// Compute the maximum number of entries that the parse stack can hold while
+/// parsing with the given grammar, or `None` if the depth is unbounded. That is
+/// the case when a variable is recursive, including the auxiliary variables
+/// that implement repetitions, or when the grammar has extra tokens, which can
+/// be pushed onto the stack any number of times.
+pub fn max_stack_depth(syntax_grammar: &SyntaxGrammar) -> Option<usize> {
+    // While a variable is being visited, its depth is `None`, so that reaching
+    // it again indicates recursion.
+    fn variable_depth(
+        syntax_grammar: &SyntaxGrammar,
+        variable_index: usize,
+        depths: &mut [Option<Option<usize>>],
+    ) -> Option<usize> {
+        if let Some(depth) = depths[variable_index] {
+            return depth;
+        }
+        depths[variable_index] = Some(None);
+
+        // While the symbol at a given step is being parsed, the stack holds the
+        // symbols for each of the production's preceding steps.
+        let mut result = 0;
+        for production in &syntax_grammar.variables[variable_index].productions {
+            for (i, step) in production.steps.iter().enumerate() {
+                let step_depth = if step.symbol.is_non_terminal() {
+                    variable_depth(syntax_grammar, step.symbol.index, depths)?
+                } else {
+                    1
+                };
+                result = result.max(i + step_depth);
+            }
+        }
+
+        depths[variable_index] = Some(Some(result));
+        Some(result)
+    }
+
+    if !syntax_grammar.extra_symbols.is_empty() {
+        return None;
+    }
+    if syntax_grammar.variables.is_empty() {
+        return Some(0);
+    }
+    let mut depths = vec![None; syntax_grammar.variables.len()];
+    variable_depth(syntax_grammar, 0, &mut depths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{Production, ProductionStep, SyntaxVariable, VariableType};
+    use crate::generate::rules::Symbol;
+
+    #[test]
+    fn test_max_stack_depth() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![production(&[
+                        Symbol::terminal(0),
+                        Symbol::non_terminal(1),
+                        Symbol::terminal(1),
+                    ])],
+                },
+                SyntaxVariable {
+                    name: "pair".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[Symbol::terminal(2)]),
+                        production(&[Symbol::terminal(2), Symbol::terminal(3)]),
+                    ],
+                },
+            ],
+            ..Default::default()
+        };
+
+        // The second token of a pair is pushed on top of the first token of the
+        // program and the first token of the pair.
+        assert_eq!(max_stack_depth(&syntax_grammar), Some(3));
+
+        // A pair can now contain another pair.
+        syntax_grammar.variables[1].productions[1] =
+            production(&[Symbol::terminal(2), Symbol::non_terminal(1)]);
+        assert_eq!(max_stack_depth(&syntax_grammar), None);
+    }
+}
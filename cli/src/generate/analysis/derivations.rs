@@ -0,0 +1,103 @@
+use crate::generate::grammars::{Production, SyntaxGrammar};
+
+/// Rank the productions of a grammar by the size of their smallest derivations:
+/// the fewest productions that must be applied, including the production
+/// itself, to derive a sequence of tokens from it. Each production is identified
+/// by its variable index and its index within that variable. Productions that
+/// can't derive any finite sequence of tokens are left out, and ties are broken
+/// by the productions' indices.
+pub fn shallowest_productions(syntax_grammar: &SyntaxGrammar) -> Vec<(usize, usize)> {
+    // Find the size of each variable's smallest derivation, shrinking the sizes
+    // until they reach a fixed point.
+    let production_cost = |production: &Production, variable_costs: &[Option<usize>]| {
+        production.steps.iter().try_fold(1, |cost: usize, step| {
+            if step.symbol.is_non_terminal() {
+                Some(cost.saturating_add(variable_costs[step.symbol.index]?))
+            } else {
+                Some(cost)
+            }
+        })
+    };
+    let mut variable_costs = vec![None; syntax_grammar.variables.len()];
+    let mut done = false;
+    while !done {
+        done = true;
+        for (i, variable) in syntax_grammar.variables.iter().enumerate() {
+            let cost = variable
+                .productions
+                .iter()
+                .filter_map(|production| production_cost(production, &variable_costs))
+                .min();
+            if cost.is_some() && (variable_costs[i].is_none() || cost < variable_costs[i]) {
+                variable_costs[i] = cost;
+                done = false;
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for (variable_index, variable) in syntax_grammar.variables.iter().enumerate() {
+        for (production_index, production) in variable.productions.iter().enumerate() {
+            if let Some(cost) = production_cost(production, &variable_costs) {
+                result.push((cost, variable_index, production_index));
+            }
+        }
+    }
+    result.sort_unstable();
+    result
+        .into_iter()
+        .map(|(_, variable_index, production_index)| (variable_index, production_index))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{ProductionStep, SyntaxVariable, VariableType};
+    use crate::generate::rules::Symbol;
+
+    #[test]
+    fn test_shallowest_productions() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let variable = |name: &str, productions| SyntaxVariable {
+            name: name.to_string(),
+            kind: VariableType::Named,
+            productions,
+        };
+        let (plus, open, close, number) = (
+            Symbol::terminal(0),
+            Symbol::terminal(1),
+            Symbol::terminal(2),
+            Symbol::terminal(3),
+        );
+        let (expression, group) = (Symbol::non_terminal(1), Symbol::non_terminal(2));
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                // program -> expression
+                variable("program", vec![production(&[expression])]),
+                // expression -> expression '+' expression | '(' expression ')' | number
+                variable(
+                    "expression",
+                    vec![
+                        production(&[expression, plus, expression]),
+                        production(&[open, expression, close]),
+                        production(&[number]),
+                    ],
+                ),
+                // group -> '(' group ')'
+                variable("group", vec![production(&[open, group, close])]),
+            ],
+            ..Default::default()
+        };
+
+        // The number is derived by a single production. The group can never be
+        // derived, because it has no production without a group.
+        assert_eq!(
+            shallowest_productions(&syntax_grammar),
+            [(1, 2), (0, 0), (1, 1), (1, 0)]
+        );
+    }
+}
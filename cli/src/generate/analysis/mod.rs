@@ -0,0 +1,47 @@
+mod depth;
+mod derivations;
+mod nullable;
+mod precedence;
+mod tokens;
+mod usage;
+mod validation;
+
+pub use self::depth::max_stack_depth;
+pub use self::derivations::shallowest_productions;
+pub use self::nullable::{nullable_productions, nullable_variables};
+pub use self::precedence::{precedence_hierarchy, PrecedenceGraph, PrecedenceLevel};
+pub use self::tokens::{
+    anonymous_literals, build_keyword_table, literal_keyword_collisions, max_finite_token_length,
+    token_pattern, KeywordTable, TokenPattern,
+};
+pub use self::usage::{
+    containing_rules, duplicate_productions, production_coverage, symbol_counts,
+    token_usage_counts, unused_externals, visible_rules, CoverageReport, SymbolCounts,
+};
+pub use self::validation::{
+    non_productive_variables, unreachable_variables, validate_cross_references,
+    validate_grammar_wellformed, CrossRefError, WellformednessError,
+};
+
+#[cfg(test)]
+use crate::generate::grammars::{LexicalGrammar, LexicalVariable, VariableType};
+#[cfg(test)]
+use crate::generate::nfa::Nfa;
+
+#[cfg(test)]
+fn build_lexical_grammar(names: &[&str]) -> LexicalGrammar {
+    LexicalGrammar {
+        nfa: Nfa::new(),
+        variables: names
+            .iter()
+            .map(|name| LexicalVariable {
+                name: name.to_string(),
+                kind: VariableType::Named,
+                implicit_precedence: 0,
+                start_state: 0,
+                case_insensitive: false,
+                lookahead: None,
+            })
+            .collect(),
+    }
+}
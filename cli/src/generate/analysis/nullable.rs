@@ -0,0 +1,87 @@
+use crate::generate::grammars::{Production, SyntaxGrammar};
+
+/// Find the productions that can match an empty sequence of tokens, because
+/// every one of their steps is a nullable non-terminal. Each production is
+/// reported as a pair of variable index and production index. Tokens are never
+/// considered nullable.
+pub fn nullable_productions(syntax_grammar: &SyntaxGrammar) -> Vec<(usize, usize)> {
+    let nullable_variables = nullable_variables(syntax_grammar);
+    let mut result = Vec::new();
+    for (variable_index, variable) in syntax_grammar.variables.iter().enumerate() {
+        for (production_index, production) in variable.productions.iter().enumerate() {
+            if is_nullable(production, &nullable_variables) {
+                result.push((variable_index, production_index));
+            }
+        }
+    }
+    result
+}
+
+/// Determine which variables can match an empty sequence of tokens, indexed by
+/// variable.
+pub fn nullable_variables(syntax_grammar: &SyntaxGrammar) -> Vec<bool> {
+    let mut result = vec![false; syntax_grammar.variables.len()];
+
+    // Propagate nullability until it reaches a fixed point.
+    let mut done = false;
+    while !done {
+        done = true;
+        for (i, variable) in syntax_grammar.variables.iter().enumerate() {
+            if !result[i]
+                && variable
+                    .productions
+                    .iter()
+                    .any(|production| is_nullable(production, &result))
+            {
+                result[i] = true;
+                done = false;
+            }
+        }
+    }
+    result
+}
+
+fn is_nullable(production: &Production, nullable_variables: &[bool]) -> bool {
+    production
+        .steps
+        .iter()
+        .all(|step| step.symbol.is_non_terminal() && nullable_variables[step.symbol.index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{ProductionStep, SyntaxVariable, VariableType};
+    use crate::generate::rules::Symbol;
+
+    #[test]
+    fn test_nullable_productions() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[Symbol::non_terminal(1), Symbol::non_terminal(1)]),
+                        production(&[Symbol::non_terminal(1), Symbol::terminal(0)]),
+                    ],
+                },
+                SyntaxVariable {
+                    name: "modifiers".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[]),
+                        production(&[Symbol::terminal(1), Symbol::non_terminal(1)]),
+                    ],
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(nullable_productions(&syntax_grammar), [(0, 0), (1, 0)]);
+    }
+}
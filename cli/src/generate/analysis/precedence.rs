@@ -0,0 +1,212 @@
+use crate::generate::grammars::{PrecedenceEntry, ProductionRef, SyntaxGrammar};
+use crate::generate::rules::{Associativity, Precedence};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The precedence levels of a grammar's productions, ordered from the loosest
+/// binding to the tightest. Each level lists the productions that are reduced
+/// with its precedence, along with their associativity. An edge `(a, b)` means
+/// that level `b` takes precedence over level `a`. Levels with integer
+/// precedences form a chain, while named levels are only ordered by the
+/// grammar's precedence orderings, so the levels form a partial order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrecedenceGraph {
+    pub levels: Vec<PrecedenceLevel>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrecedenceLevel {
+    pub precedence: Precedence,
+    pub productions: Vec<(ProductionRef, Option<Associativity>)>,
+}
+
+/// Build the graph of the precedence levels that the grammar's productions are
+/// reduced with. A production's precedence and associativity are those of its
+/// last step, as when the parser resolves a conflict over reducing it.
+/// Productions without precedence are left out.
+pub fn precedence_hierarchy(grammar: &SyntaxGrammar) -> PrecedenceGraph {
+    let mut productions_by_precedence = BTreeMap::<_, Vec<_>>::new();
+    for (variable_index, variable) in grammar.variables.iter().enumerate() {
+        for (production_index, production) in variable.productions.iter().enumerate() {
+            let Some(step) = production.steps.last() else {
+                continue;
+            };
+            if step.precedence != Precedence::None {
+                productions_by_precedence
+                    .entry(step.precedence.clone())
+                    .or_default()
+                    .push((
+                        ProductionRef {
+                            variable_index,
+                            production_index,
+                        },
+                        step.associativity,
+                    ));
+            }
+        }
+    }
+
+    let levels = productions_by_precedence
+        .into_iter()
+        .map(|(precedence, productions)| PrecedenceLevel {
+            precedence,
+            productions,
+        })
+        .collect::<Vec<_>>();
+    let level_index = |name: &str| {
+        levels
+            .iter()
+            .position(|level| matches!(&level.precedence, Precedence::Name(n) if n == name))
+    };
+
+    let mut edges = Vec::new();
+    for i in 1..levels.len() {
+        if let (Precedence::Integer(_), Precedence::Integer(_)) =
+            (&levels[i - 1].precedence, &levels[i].precedence)
+        {
+            edges.push((i - 1, i));
+        }
+    }
+    for ordering in &grammar.precedence_orderings {
+        let named_levels = ordering
+            .iter()
+            .filter_map(|entry| match entry {
+                PrecedenceEntry::Name(name) => level_index(name),
+                PrecedenceEntry::Symbol(_) => None,
+            })
+            .collect::<Vec<_>>();
+        for pair in named_levels.windows(2) {
+            let edge = (pair[1], pair[0]);
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+    }
+
+    PrecedenceGraph { levels, edges }
+}
+
+impl PrecedenceGraph {
+    /// Render the graph in the Graphviz DOT language, with an arrow from each
+    /// level to the levels that take precedence over it.
+    pub fn to_dot(&self, grammar: &SyntaxGrammar) -> String {
+        let mut result = "digraph precedence {\n".to_string();
+        for (i, level) in self.levels.iter().enumerate() {
+            let mut label = level.precedence.to_string().replace('\'', "");
+            for (production, associativity) in &level.productions {
+                write!(
+                    label,
+                    "\\n{} #{}",
+                    grammar.variables[production.variable_index].name, production.production_index
+                )
+                .unwrap();
+                match associativity {
+                    Some(Associativity::Left) => label += " (left)",
+                    Some(Associativity::Right) => label += " (right)",
+                    None => {}
+                }
+            }
+            writeln!(result, "  level{i} [label=\"{label}\"];").unwrap();
+        }
+        for (lower, higher) in &self.edges {
+            writeln!(result, "  level{lower} -> level{higher};").unwrap();
+        }
+        result += "}\n";
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{Production, ProductionStep, SyntaxVariable, VariableType};
+    use crate::generate::rules::Symbol;
+
+    #[test]
+    fn test_precedence_hierarchy() {
+        let production = |symbols: &[Symbol], precedence: i32, associativity| Production {
+            dynamic_precedence: 0,
+            steps: symbols
+                .iter()
+                .map(|symbol| {
+                    let step = ProductionStep::new(*symbol);
+                    if precedence == 0 {
+                        step
+                    } else {
+                        step.with_prec(Precedence::Integer(precedence), associativity)
+                    }
+                })
+                .collect(),
+        };
+        let (expression, plus, times, minus, number) = (
+            Symbol::non_terminal(0),
+            Symbol::terminal(0),
+            Symbol::terminal(1),
+            Symbol::terminal(2),
+            Symbol::terminal(3),
+        );
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "expression".to_string(),
+                kind: VariableType::Named,
+                productions: vec![
+                    production(
+                        &[expression, plus, expression],
+                        1,
+                        Some(Associativity::Left),
+                    ),
+                    production(
+                        &[expression, times, expression],
+                        2,
+                        Some(Associativity::Left),
+                    ),
+                    production(&[minus, expression], 3, None),
+                    production(&[number], 0, None),
+                ],
+            }],
+            ..Default::default()
+        };
+
+        let graph = precedence_hierarchy(&syntax_grammar);
+        assert_eq!(
+            graph
+                .levels
+                .iter()
+                .map(|level| (level.precedence.clone(), level.productions.clone()))
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+                .into_iter()
+                .map(|precedence| (
+                    Precedence::Integer(precedence),
+                    vec![(
+                        ProductionRef {
+                            variable_index: 0,
+                            production_index: precedence as usize - 1,
+                        },
+                        if precedence < 3 {
+                            Some(Associativity::Left)
+                        } else {
+                            None
+                        }
+                    )]
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(graph.edges, [(0, 1), (1, 2)]);
+        assert_eq!(
+            graph.to_dot(&syntax_grammar),
+            [
+                "digraph precedence {",
+                "  level0 [label=\"1\\nexpression #0 (left)\"];",
+                "  level1 [label=\"2\\nexpression #1 (left)\"];",
+                "  level2 [label=\"3\\nexpression #2\"];",
+                "  level0 -> level1;",
+                "  level1 -> level2;",
+                "}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use crate::generate::grammars::{LexicalGrammar, VariableType};
+use crate::generate::grammars::{Production, SyntaxGrammar};
+use crate::generate::nfa::NfaState;
+use crate::generate::nfa::{CharacterSet, Nfa, NfaCursor};
+use crate::generate::rules::Symbol;
+use crate::generate::tables::LexTable;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// The definition of a terminal in a [`LexicalGrammar`]. String literals are
+/// available verbatim, while other tokens are described by the NFA state at
+/// which the lexer starts matching them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenPattern {
+    Literal(String),
+    Nfa { start_state: u32 },
+}
+
+/// Get the definition of the given terminal, or `None` if the symbol is not
+/// a terminal in the lexical grammar.
+pub fn token_pattern(lexical_grammar: &LexicalGrammar, symbol: Symbol) -> Option<TokenPattern> {
+    if !symbol.is_terminal() {
+        return None;
+    }
+    let variable = lexical_grammar.variables.get(symbol.index)?;
+    Some(if variable.kind == VariableType::Anonymous {
+        TokenPattern::Literal(variable.name.clone())
+    } else {
+        TokenPattern::Nfa {
+            start_state: variable.start_state,
+        }
+    })
+}
+
+/// Find the anonymous string literals that match exactly the same text as some
+/// named keyword, such as a `"return"` literal alongside a `return_keyword` rule
+/// defined as `'return'`. The literal and the keyword produce different nodes,
+/// so this is usually a copy-paste mistake. Keywords can either be named tokens
+/// of their own, or, if their literal is used elsewhere, syntax rules whose only
+/// production is that literal. Only literals that are used by the syntax grammar
+/// outside of such a keyword rule are reported.
+pub fn literal_keyword_collisions(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> Vec<Symbol> {
+    let mut used_symbols = syntax_grammar
+        .extra_symbols
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>();
+    let mut keyword_rule_literals = HashSet::new();
+    for variable in &syntax_grammar.variables {
+        if let [Production { steps, .. }] = variable.productions.as_slice() {
+            if let [step] = steps.as_slice() {
+                if variable.kind == VariableType::Named
+                    && step.alias.is_none()
+                    && matches!(
+                        token_pattern(lexical_grammar, step.symbol),
+                        Some(TokenPattern::Literal(_))
+                    )
+                {
+                    keyword_rule_literals.insert(step.symbol);
+                    continue;
+                }
+            }
+        }
+        for production in &variable.productions {
+            used_symbols.extend(production.steps.iter().map(|step| step.symbol));
+        }
+    }
+
+    let keyword_start_states = (0..lexical_grammar.variables.len())
+        .filter_map(
+            |i| match token_pattern(lexical_grammar, Symbol::terminal(i)) {
+                Some(TokenPattern::Nfa { start_state }) => Some(start_state),
+                _ => None,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    (0..lexical_grammar.variables.len())
+        .map(Symbol::terminal)
+        .filter(|symbol| used_symbols.contains(symbol))
+        .filter(|symbol| {
+            let Some(TokenPattern::Literal(literal)) = token_pattern(lexical_grammar, *symbol)
+            else {
+                return false;
+            };
+            keyword_rule_literals.contains(symbol)
+                || keyword_start_states.iter().any(|start_state| {
+                    nfa_matches_only(&lexical_grammar.nfa, *start_state, &literal)
+                })
+        })
+        .collect()
+}
+
+// Determine whether the NFA starting at the given state matches the given
+// string and nothing else, ignoring any separators that precede it.
+fn nfa_matches_only(nfa: &Nfa, start_state: u32, string: &str) -> bool {
+    let mut cursor = NfaCursor::new(nfa, vec![start_state]);
+    for c in string.chars() {
+        if cursor.completions().next().is_some() {
+            return false;
+        }
+        let transitions = cursor
+            .transitions()
+            .into_iter()
+            .filter(|t| !t.is_separator)
+            .collect::<Vec<_>>();
+        match transitions.as_slice() {
+            [transition] if transition.characters == CharacterSet::from_char(c) => {
+                cursor.reset(transition.states.clone());
+            }
+            _ => return false,
+        }
+    }
+    cursor.completions().next().is_some() && cursor.transitions().iter().all(|t| t.is_separator)
+}
+
+/// Get each anonymous token in the lexical grammar, along with the literal
+/// string that it matches, in the order of the tokens' symbols.
+pub fn anonymous_literals(lexical_grammar: &LexicalGrammar) -> Vec<(Symbol, String)> {
+    lexical_grammar
+        .variables
+        .iter()
+        .enumerate()
+        .filter(|(_, variable)| variable.kind == VariableType::Anonymous)
+        .map(|(i, variable)| (Symbol::terminal(i), variable.name.clone()))
+        .collect()
+}
+
+/// Find the maximum number of characters that any single token can match, or
+/// `None` if some token can match arbitrarily long text, such as an identifier.
+/// The separators that can precede each token are not counted.
+pub fn max_finite_token_length(lexical_grammar: &LexicalGrammar) -> Option<usize> {
+    let mut lengths = HashMap::new();
+    let mut max_length = 0;
+    for variable in &lexical_grammar.variables {
+        let length = longest_match(&lexical_grammar.nfa, variable.start_state, 0, &mut lengths)?;
+        max_length = max_length.max(length.unwrap_or(0));
+    }
+    Some(max_length)
+}
+
+// Find the length of the longest path from the given NFA state to an accepting
+// state, without following separators. Returns `None` if the path is unbounded,
+// and `Some(None)` if no accepting state can be reached. The states on the
+// current path are stored in the map along with the number of characters
+// consumed before reaching them, so that cycles which consume characters can be
+// told apart from those which don't.
+fn longest_match(
+    nfa: &Nfa,
+    state_id: u32,
+    depth: usize,
+    lengths: &mut HashMap<u32, Result<Option<usize>, usize>>,
+) -> Option<Option<usize>> {
+    match lengths.get(&state_id) {
+        Some(Ok(length)) => return Some(*length),
+        Some(Err(start_depth)) if *start_depth == depth => return Some(None),
+        Some(Err(_)) => return None,
+        None => {}
+    }
+    lengths.insert(state_id, Err(depth));
+    let length = match &nfa.states[state_id as usize] {
+        NfaState::Accept { .. } => Some(0),
+        NfaState::Advance { is_sep: true, .. } => None,
+        NfaState::Advance { state_id, .. } => {
+            longest_match(nfa, *state_id, depth + 1, lengths)?.map(|length| length + 1)
+        }
+        NfaState::Split(left, right) => {
+            let left = longest_match(nfa, *left, depth, lengths)?;
+            let right = longest_match(nfa, *right, depth, lengths)?;
+            left.max(right)
+        }
+    };
+    lengths.insert(state_id, Ok(length));
+    Some(length)
+}
+
+/// The keywords of a grammar that has a word token, keyed by the strings that
+/// they match. After the lexer matches the word token, the matched string is
+/// looked up here to find the keyword it represents, if any. With case folding,
+/// the keys and the looked-up strings are converted to lowercase.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeywordTable {
+    pub case_fold: bool,
+    pub keywords: BTreeMap<String, Symbol>,
+}
+
+/// Build the keyword table for a grammar from its keyword lex table: the strings
+/// that the keyword lexer accepts, mapped to the keywords that it accepts them as.
+/// These are the keywords that the generator identified, whether they were
+/// written as string literals or as named tokens. All of the spellings of a
+/// case-insensitive keyword are listed, unless the table is case-folded. If two
+/// keywords fold to the same string, the one with the lower symbol is kept.
+pub fn build_keyword_table(keyword_lex_table: &LexTable, case_fold: bool) -> KeywordTable {
+    let mut result = KeywordTable {
+        case_fold,
+        keywords: BTreeMap::new(),
+    };
+    if !keyword_lex_table.states.is_empty() {
+        add_keywords(
+            keyword_lex_table,
+            0,
+            &mut String::new(),
+            &mut HashSet::new(),
+            &mut result,
+        );
+    }
+    result
+}
+
+// Add the keywords accepted in or after the given state of the keyword lex table,
+// given the characters that lead to the state. Separators are skipped, and so are
+// any cycles, which can't be part of a keyword.
+fn add_keywords(
+    keyword_lex_table: &LexTable,
+    state_id: usize,
+    prefix: &mut String,
+    states_on_path: &mut HashSet<usize>,
+    result: &mut KeywordTable,
+) {
+    let state = &keyword_lex_table.states[state_id];
+    if let Some(symbol) = state.accept_action {
+        let key = if result.case_fold {
+            prefix.to_lowercase()
+        } else {
+            prefix.clone()
+        };
+        let entry = result.keywords.entry(key).or_insert(symbol);
+        *entry = (*entry).min(symbol);
+    }
+
+    states_on_path.insert(state_id);
+    let mut visited = HashSet::new();
+    for (characters, action) in &state.advance_actions {
+        if !action.in_main_token || states_on_path.contains(&action.state) {
+            continue;
+        }
+        for c in characters.chars() {
+            let c = if result.case_fold {
+                c.to_lowercase().to_string()
+            } else {
+                c.to_string()
+            };
+            if visited.insert((c.clone(), action.state)) {
+                prefix.push_str(&c);
+                add_keywords(
+                    keyword_lex_table,
+                    action.state,
+                    prefix,
+                    states_on_path,
+                    result,
+                );
+                prefix.truncate(prefix.len() - c.len());
+            }
+        }
+    }
+    states_on_path.remove(&state_id);
+}
+
+impl KeywordTable {
+    /// Find the keyword that the given string, matched by the word token,
+    /// represents.
+    pub fn lookup(&self, word: &str) -> Option<Symbol> {
+        if self.case_fold {
+            self.keywords.get(&word.to_lowercase()).copied()
+        } else {
+            self.keywords.get(word).copied()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::build_tables::{build_test_tables, TestTables};
+    use crate::generate::grammars::{InputGrammar, Variable};
+    use crate::generate::prepare_grammar::prepare_grammar;
+    use crate::generate::rules::Rule;
+
+    #[test]
+    fn test_token_pattern() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "list".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::named("identifier"),
+                        Rule::repeat(Rule::seq(vec![
+                            Rule::string(","),
+                            Rule::named("identifier"),
+                        ])),
+                    ]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (_, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        let token = |name| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(
+            token_pattern(&lexical_grammar, token(",")),
+            Some(TokenPattern::Literal(",".to_string()))
+        );
+        let identifier = token("identifier");
+        assert_eq!(
+            token_pattern(&lexical_grammar, identifier),
+            Some(TokenPattern::Nfa {
+                start_state: lexical_grammar.variables[identifier.index].start_state
+            })
+        );
+        assert_eq!(
+            token_pattern(&lexical_grammar, Symbol::non_terminal(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_literal_keyword_collisions() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::seq(vec![Rule::string("return"), Rule::named("identifier")]),
+                        Rule::seq(vec![Rule::named("return_keyword"), Rule::string(";")]),
+                        Rule::seq(vec![Rule::string("returns"), Rule::string(";")]),
+                        Rule::seq(vec![Rule::string("yield"), Rule::named("yield_keyword")]),
+                    ]),
+                },
+                Variable {
+                    name: "yield_keyword".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("yield", ""),
+                },
+                Variable {
+                    name: "return_keyword".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("return"),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            extra_symbols: vec![Rule::pattern("\\s", "")],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        let token = |name| {
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == name && v.kind == VariableType::Anonymous)
+                .map(Symbol::terminal)
+                .unwrap()
+        };
+
+        assert_eq!(
+            literal_keyword_collisions(&syntax_grammar, &lexical_grammar),
+            [token("return"), token("yield")]
+        );
+    }
+
+    #[test]
+    fn test_anonymous_literals() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("return"),
+                        Rule::named("identifier"),
+                        Rule::string(";"),
+                    ]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (_, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        let token = |name: &str| {
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == name)
+                .map(Symbol::terminal)
+                .unwrap()
+        };
+
+        let mut expected = vec![
+            (token("return"), "return".to_string()),
+            (token(";"), ";".to_string()),
+        ];
+        expected.sort_unstable();
+        assert_eq!(anonymous_literals(&lexical_grammar), expected);
+    }
+
+    #[test]
+    fn test_max_finite_token_length() {
+        let mut grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::string(">>>="),
+                        Rule::string("=>"),
+                        Rule::string("+"),
+                        Rule::named("number"),
+                    ])),
+                },
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("0x[0-9a-f]|[0-9]", ""),
+                },
+            ],
+            extra_symbols: vec![Rule::pattern("\\s", "")],
+            ..Default::default()
+        };
+        let (_, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        assert_eq!(max_finite_token_length(&lexical_grammar), Some(4));
+
+        grammar.variables[1].rule = Rule::pattern("[0-9]+", "");
+        let (_, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        assert_eq!(max_finite_token_length(&lexical_grammar), None);
+    }
+
+    #[test]
+    fn test_build_keyword_table() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "query".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("select"),
+                        Rule::named("identifier"),
+                        Rule::named("from"),
+                        Rule::named("identifier"),
+                        Rule::string(";"),
+                    ]),
+                },
+                Variable {
+                    name: "from".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::case_insensitive_token(Rule::string("FROM")),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-zA-Z_]+", ""),
+                },
+            ],
+            word_token: Some("identifier".to_string()),
+            ..Default::default()
+        };
+        let TestTables {
+            lexical_grammar,
+            keyword_lex_table,
+            ..
+        } = build_test_tables(&grammar);
+        let terminal = |name: &str| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+
+        // The semicolon can't be matched by the word token, so it isn't a keyword.
+        // The named, case-insensitive token is.
+        let keyword_table = build_keyword_table(&keyword_lex_table, true);
+        assert_eq!(keyword_table.keywords.len(), 2);
+        assert_eq!(keyword_table.lookup("select"), Some(terminal("select")));
+        assert_eq!(keyword_table.lookup("SELECT"), Some(terminal("select")));
+        assert_eq!(keyword_table.lookup("From"), Some(terminal("from")));
+        assert_eq!(keyword_table.lookup("selected"), None);
+
+        // Without case folding, every spelling of the case-insensitive token is
+        // listed.
+        let keyword_table = build_keyword_table(&keyword_lex_table, false);
+        assert_eq!(keyword_table.keywords.len(), 1 + 16);
+        assert_eq!(keyword_table.lookup("select"), Some(terminal("select")));
+        assert_eq!(keyword_table.lookup("SELECT"), None);
+        assert_eq!(keyword_table.lookup("fRoM"), Some(terminal("from")));
+    }
+}
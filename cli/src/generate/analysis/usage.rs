@@ -0,0 +1,495 @@
+use crate::generate::grammars::Production;
+use crate::generate::grammars::VariableType;
+use crate::generate::grammars::{LexicalGrammar, SyntaxGrammar};
+use crate::generate::rules::Symbol;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SymbolCounts {
+    pub terminals: usize,
+    pub externals: usize,
+    pub non_terminals: usize,
+}
+
+/// Count the number of symbols of each kind in a grammar.
+pub fn symbol_counts(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> SymbolCounts {
+    SymbolCounts {
+        terminals: lexical_grammar.variables.len(),
+        externals: syntax_grammar.external_tokens.len(),
+        non_terminals: syntax_grammar.variables.len(),
+    }
+}
+
+/// The productions of a grammar that were never reduced while parsing a
+/// corpus, identified by variable index and production index.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub total_productions: usize,
+    pub unreduced: Vec<(u32, u32)>,
+}
+
+/// Determine which of the grammar's productions are not exercised by the given
+/// reductions, each of which is a pair of variable index and production index.
+pub fn production_coverage(
+    syntax_grammar: &SyntaxGrammar,
+    reductions: &[(u32, u32)],
+) -> CoverageReport {
+    let reduced = reductions.iter().copied().collect::<HashSet<_>>();
+    let mut result = CoverageReport::default();
+    for (variable_index, variable) in syntax_grammar.variables.iter().enumerate() {
+        for production_index in 0..variable.productions.len() {
+            let key = (variable_index as u32, production_index as u32);
+            if !reduced.contains(&key) {
+                result.unreduced.push(key);
+            }
+        }
+        result.total_productions += variable.productions.len();
+    }
+    result
+}
+
+/// Find the productions of each variable that are structurally identical. Each
+/// group of identical productions is reported as its variable index together
+/// with the indices of the productions in the group. `flatten_grammar` never
+/// creates such duplicates, but hand-built grammars and later transformations
+/// of a syntax grammar can.
+pub fn duplicate_productions(syntax_grammar: &SyntaxGrammar) -> Vec<(usize, Vec<usize>)> {
+    let mut result = Vec::new();
+    for (variable_index, variable) in syntax_grammar.variables.iter().enumerate() {
+        let mut groups = Vec::<(&Production, Vec<usize>)>::new();
+        for (i, production) in variable.productions.iter().enumerate() {
+            if let Some((_, indices)) = groups.iter_mut().find(|(p, _)| *p == production) {
+                indices.push(i);
+            } else {
+                groups.push((production, vec![i]));
+            }
+        }
+        result.extend(
+            groups
+                .into_iter()
+                .filter(|(_, indices)| indices.len() > 1)
+                .map(|(_, indices)| (variable_index, indices)),
+        );
+    }
+    result
+}
+
+/// Find the external tokens that are declared but never used: no production
+/// refers to them and they are not extras. An external token that corresponds
+/// to an internal token is used wherever that internal token is, because the
+/// productions refer to the internal token instead.
+pub fn unused_externals(syntax_grammar: &SyntaxGrammar) -> Vec<Symbol> {
+    let used_symbols = syntax_grammar
+        .variables
+        .iter()
+        .flat_map(|variable| &variable.productions)
+        .flat_map(|production| &production.steps)
+        .map(|step| step.symbol)
+        .chain(syntax_grammar.extra_symbols.iter().copied())
+        .collect::<HashSet<_>>();
+    syntax_grammar
+        .external_tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, token)| {
+            !used_symbols.contains(&Symbol::external(*i))
+                && !token
+                    .corresponding_internal_token
+                    .is_some_and(|symbol| used_symbols.contains(&symbol))
+        })
+        .map(|(i, _)| Symbol::external(i))
+        .collect()
+}
+
+/// Find the variables that can derive a sequence of symbols containing the given
+/// symbol, either because one of their productions refers to it directly, or
+/// because one refers to another variable that contains it.
+pub fn containing_rules(syntax_grammar: &SyntaxGrammar, symbol: Symbol) -> HashSet<Symbol> {
+    let mut result = HashSet::new();
+
+    // Propagate containment until it reaches a fixed point.
+    let mut done = false;
+    while !done {
+        done = true;
+        for (i, variable) in syntax_grammar.variables.iter().enumerate() {
+            let variable_symbol = Symbol::non_terminal(i);
+            if !result.contains(&variable_symbol)
+                && variable
+                    .productions
+                    .iter()
+                    .flat_map(|production| &production.steps)
+                    .any(|step| step.symbol == symbol || result.contains(&step.symbol))
+            {
+                result.insert(variable_symbol);
+                done = false;
+            }
+        }
+    }
+    result
+}
+
+/// Count the number of production steps that refer to each terminal. Terminals
+/// that no production refers to are not included.
+pub fn token_usage_counts(syntax_grammar: &SyntaxGrammar) -> HashMap<Symbol, usize> {
+    let mut result = HashMap::new();
+    for step in syntax_grammar
+        .variables
+        .iter()
+        .flat_map(|variable| &variable.productions)
+        .flat_map(|production| &production.steps)
+    {
+        if step.symbol.is_terminal() {
+            *result.entry(step.symbol).or_insert(0) += 1;
+        }
+    }
+    result
+}
+
+/// Find the non-terminals that appear as named nodes in the syntax tree. This
+/// excludes hidden rules, whose names start with an underscore, repeat helpers,
+/// and rules that are inlined wherever they occur.
+pub fn visible_rules(syntax_grammar: &SyntaxGrammar) -> Vec<Symbol> {
+    syntax_grammar
+        .variables
+        .iter()
+        .enumerate()
+        .map(|(i, variable)| (Symbol::non_terminal(i), variable))
+        .filter(|(symbol, variable)| {
+            variable.kind == VariableType::Named && !syntax_grammar.is_always_inlined(*symbol)
+        })
+        .map(|(symbol, _)| symbol)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::analysis::build_lexical_grammar;
+    use crate::generate::grammars::ProductionStep;
+    use crate::generate::grammars::{ExternalToken, SyntaxVariable};
+    use crate::generate::grammars::{InputGrammar, Variable};
+    use crate::generate::prepare_grammar::prepare_grammar;
+    use crate::generate::rules::Rule;
+
+    #[test]
+    fn test_symbol_counts() {
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: Vec::new(),
+                },
+                SyntaxVariable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    productions: Vec::new(),
+                },
+            ],
+            external_tokens: vec![ExternalToken {
+                name: "heredoc".to_string(),
+                kind: VariableType::Named,
+                corresponding_internal_token: None,
+                zero_width: false,
+                precedence: 0,
+            }],
+            ..Default::default()
+        };
+        let lexical_grammar = build_lexical_grammar(&["identifier", ";", "="]);
+
+        assert_eq!(
+            symbol_counts(&syntax_grammar, &lexical_grammar),
+            SymbolCounts {
+                terminals: 3,
+                externals: 1,
+                non_terminals: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_production_coverage() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![production(&[Symbol::non_terminal(1)])],
+                },
+                SyntaxVariable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[Symbol::terminal(0), Symbol::terminal(1)]),
+                        production(&[Symbol::terminal(1)]),
+                    ],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let report = production_coverage(&syntax_grammar, &[(1, 0), (0, 0), (1, 0)]);
+        assert_eq!(
+            report,
+            CoverageReport {
+                total_productions: 3,
+                unreduced: vec![(1, 1)]
+            }
+        );
+    }
+
+    #[test]
+    fn test_duplicate_productions() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![production(&[Symbol::non_terminal(1)])],
+                },
+                SyntaxVariable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[Symbol::terminal(0), Symbol::terminal(1)]),
+                        production(&[Symbol::terminal(1)]),
+                        production(&[Symbol::terminal(0), Symbol::terminal(1)]),
+                        Production {
+                            dynamic_precedence: 1,
+                            steps: vec![ProductionStep::new(Symbol::terminal(1))],
+                        },
+                    ],
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(duplicate_productions(&syntax_grammar), [(1, vec![0, 2])]);
+    }
+
+    #[test]
+    fn test_unused_externals() {
+        let external = |name: &str, corresponding_internal_token| ExternalToken {
+            name: name.to_string(),
+            kind: VariableType::Named,
+            corresponding_internal_token,
+            zero_width: false,
+            precedence: 0,
+        };
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                productions: vec![Production {
+                    dynamic_precedence: 0,
+                    steps: vec![
+                        ProductionStep::new(Symbol::external(0)),
+                        ProductionStep::new(Symbol::terminal(0)),
+                    ],
+                }],
+            }],
+            extra_symbols: vec![Symbol::external(1)],
+            external_tokens: vec![
+                external("heredoc_start", None),
+                external("comment", None),
+                external("string_content", None),
+                external("newline", Some(Symbol::terminal(0))),
+                external("error_sentinel", Some(Symbol::terminal(1))),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            unused_externals(&syntax_grammar),
+            [Symbol::external(2), Symbol::external(4)]
+        );
+    }
+
+    #[test]
+    fn test_containing_rules() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::named("statement"),
+                        Rule::named("label"),
+                    ])),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![Rule::named("call"), Rule::named("identifier")]),
+                },
+                Variable {
+                    name: "call".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::named("identifier"),
+                        Rule::string("("),
+                        Rule::string(")"),
+                    ]),
+                },
+                Variable {
+                    name: "label".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("identifier"), Rule::string(":")]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        let symbol = |name: &str| {
+            syntax_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == name)
+                .map(Symbol::non_terminal)
+                .unwrap()
+        };
+        let token = |name: &str| {
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == name)
+                .map(Symbol::terminal)
+                .unwrap()
+        };
+
+        // The auxiliary rule for the program's repetition contains everything
+        // that the program does.
+        let rules = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| symbol(name))
+                .chain(
+                    (0..syntax_grammar.variables.len())
+                        .filter(|i| syntax_grammar.variables[*i].is_auxiliary())
+                        .map(Symbol::non_terminal),
+                )
+                .collect::<HashSet<_>>()
+        };
+
+        // The parenthesis is used directly by calls, and indirectly by each of
+        // the rules that can contain a call, but not by labels.
+        assert_eq!(
+            containing_rules(&syntax_grammar, token("(")),
+            rules(&["call", "expression", "statement", "program"])
+        );
+        assert_eq!(
+            containing_rules(&syntax_grammar, token(":")),
+            rules(&["label", "program"])
+        );
+    }
+
+    #[test]
+    fn test_token_usage_counts() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![Rule::named("array"), Rule::named("arguments")]),
+                },
+                Variable {
+                    name: "array".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("["),
+                        Rule::named("identifier"),
+                        Rule::string(","),
+                        Rule::named("identifier"),
+                        Rule::string("]"),
+                    ]),
+                },
+                Variable {
+                    name: "arguments".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("("),
+                        Rule::named("identifier"),
+                        Rule::choice(vec![
+                            Rule::seq(vec![Rule::string(","), Rule::named("identifier")]),
+                            Rule::Blank,
+                        ]),
+                        Rule::string(")"),
+                    ]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, _, _) = prepare_grammar(&grammar).unwrap();
+        let token = |name: &str| {
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == name)
+                .map(Symbol::terminal)
+                .unwrap()
+        };
+
+        // The optional sequence in the arguments is expanded into two
+        // productions, so the comma is used once there and once in the array.
+        let counts = token_usage_counts(&syntax_grammar);
+        assert_eq!(counts[&token(",")], 2);
+        assert_eq!(counts[&token("(")], 2);
+        assert_eq!(counts[&token("[")], 1);
+        assert_eq!(counts[&token("identifier")], 5);
+    }
+
+    #[test]
+    fn test_visible_rules() {
+        let variable = |name: &str, kind| SyntaxVariable {
+            name: name.to_string(),
+            kind,
+            productions: vec![Production {
+                dynamic_precedence: 0,
+                steps: vec![ProductionStep::new(Symbol::terminal(0))],
+            }],
+        };
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                variable("program", VariableType::Named),
+                variable("_statement", VariableType::Hidden),
+                variable("expression", VariableType::Named),
+                variable("argument", VariableType::Named),
+                variable("program_repeat1", VariableType::Auxiliary),
+            ],
+            variables_to_inline: vec![(Symbol::non_terminal(3), None)],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            visible_rules(&syntax_grammar),
+            [Symbol::non_terminal(0), Symbol::non_terminal(2)]
+        );
+    }
+}
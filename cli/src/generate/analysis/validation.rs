@@ -0,0 +1,389 @@
+use crate::generate::grammars::{LexicalGrammar, SyntaxGrammar, VariableType};
+use crate::generate::rules::Symbol;
+use std::fmt;
+/// A reference from a syntax grammar production to a token that doesn't exist,
+/// or an external token whose name is ambiguous because a lexical token has the
+/// same name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CrossRefError {
+    DanglingTerminal {
+        variable: String,
+        symbol: Symbol,
+    },
+    DanglingExternal {
+        variable: String,
+        symbol: Symbol,
+    },
+    NameCollision {
+        external: String,
+        terminal: String,
+        terminal_kind: VariableType,
+    },
+}
+
+/// Check that every terminal referenced by the syntax grammar's productions
+/// exists in the lexical grammar, and that every external token that they
+/// reference has been declared. Also check that no external token shares its
+/// name with a lexical token, unless the external token corresponds to that
+/// lexical token.
+pub fn validate_cross_references(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> Result<(), Vec<CrossRefError>> {
+    let mut errors = Vec::new();
+    for variable in &syntax_grammar.variables {
+        for step in variable.productions.iter().flat_map(|p| &p.steps) {
+            let error = if step.symbol.is_terminal()
+                && step.symbol.index >= lexical_grammar.variables.len()
+            {
+                CrossRefError::DanglingTerminal {
+                    variable: variable.name.clone(),
+                    symbol: step.symbol,
+                }
+            } else if step.symbol.is_external()
+                && step.symbol.index >= syntax_grammar.external_tokens.len()
+            {
+                CrossRefError::DanglingExternal {
+                    variable: variable.name.clone(),
+                    symbol: step.symbol,
+                }
+            } else {
+                continue;
+            };
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+    }
+    for external_token in &syntax_grammar.external_tokens {
+        for (i, lexical_variable) in lexical_grammar.variables.iter().enumerate() {
+            if lexical_variable.name == external_token.name
+                && external_token.corresponding_internal_token != Some(Symbol::terminal(i))
+            {
+                errors.push(CrossRefError::NameCollision {
+                    external: external_token.name.clone(),
+                    terminal: lexical_variable.name.clone(),
+                    terminal_kind: lexical_variable.kind,
+                });
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+impl fmt::Display for CrossRefError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DanglingTerminal { variable, symbol } => write!(
+                f,
+                "Rule '{variable}' refers to nonexistent terminal {}",
+                symbol.index
+            ),
+            Self::DanglingExternal { variable, symbol } => write!(
+                f,
+                "Rule '{variable}' refers to undeclared external token {}",
+                symbol.index
+            ),
+            Self::NameCollision {
+                external,
+                terminal,
+                terminal_kind,
+            } => {
+                write!(
+                    f,
+                    "External token '{external}' in the grammar's externals has the same name as "
+                )?;
+                if *terminal_kind == VariableType::Anonymous {
+                    write!(f, "the string \"{terminal}\" in the grammar's rules")
+                } else {
+                    write!(f, "the token defined by rule '{terminal}'")
+                }
+            }
+        }
+    }
+}
+
+/// A syntax grammar variable that cannot be part of a successful parse, because
+/// the start rule never refers to it, directly or indirectly, or because none
+/// of its productions derive a finite sequence of tokens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WellformednessError {
+    Unreachable { variable: String },
+    NonProductive { variable: String },
+}
+
+/// Find the variables that cannot be reached from the start rule. Variables that
+/// are referenced by the grammar's extras are reachable, because extras can
+/// appear anywhere.
+pub fn unreachable_variables(syntax_grammar: &SyntaxGrammar) -> Vec<Symbol> {
+    let mut reachable = vec![false; syntax_grammar.variables.len()];
+    let mut stack = syntax_grammar
+        .extra_symbols
+        .iter()
+        .copied()
+        .filter(Symbol::is_non_terminal)
+        .collect::<Vec<_>>();
+    if !syntax_grammar.variables.is_empty() {
+        stack.push(Symbol::non_terminal(0));
+    }
+    while let Some(symbol) = stack.pop() {
+        if reachable[symbol.index] {
+            continue;
+        }
+        reachable[symbol.index] = true;
+        stack.extend(
+            syntax_grammar.variables[symbol.index]
+                .productions
+                .iter()
+                .flat_map(|production| &production.steps)
+                .map(|step| step.symbol)
+                .filter(|symbol| symbol.is_non_terminal() && !reachable[symbol.index]),
+        );
+    }
+    (0..syntax_grammar.variables.len())
+        .filter(|i| !reachable[*i])
+        .map(Symbol::non_terminal)
+        .collect()
+}
+
+/// Find the variables that cannot derive any finite sequence of tokens, because
+/// every one of their productions refers to a variable that can't either.
+pub fn non_productive_variables(syntax_grammar: &SyntaxGrammar) -> Vec<Symbol> {
+    let mut productive = vec![false; syntax_grammar.variables.len()];
+
+    // Propagate productivity until it reaches a fixed point.
+    let mut done = false;
+    while !done {
+        done = true;
+        for (i, variable) in syntax_grammar.variables.iter().enumerate() {
+            if !productive[i]
+                && variable.productions.iter().any(|production| {
+                    production
+                        .steps
+                        .iter()
+                        .all(|step| !step.symbol.is_non_terminal() || productive[step.symbol.index])
+                })
+            {
+                productive[i] = true;
+                done = false;
+            }
+        }
+    }
+    (0..syntax_grammar.variables.len())
+        .filter(|i| !productive[*i])
+        .map(Symbol::non_terminal)
+        .collect()
+}
+
+/// Check that every variable of the syntax grammar is both reachable from the
+/// start rule and productive, reporting all of the variables that are not.
+pub fn validate_grammar_wellformed(
+    syntax_grammar: &SyntaxGrammar,
+) -> Result<(), Vec<WellformednessError>> {
+    let name = |symbol: Symbol| syntax_grammar.variables[symbol.index].name.clone();
+    let errors = unreachable_variables(syntax_grammar)
+        .into_iter()
+        .map(|symbol| WellformednessError::Unreachable {
+            variable: name(symbol),
+        })
+        .chain(
+            non_productive_variables(syntax_grammar)
+                .into_iter()
+                .map(|symbol| WellformednessError::NonProductive {
+                    variable: name(symbol),
+                }),
+        )
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+impl fmt::Display for WellformednessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unreachable { variable } => {
+                write!(f, "Rule '{variable}' is not reachable from the start rule")
+            }
+            Self::NonProductive { variable } => {
+                write!(f, "Rule '{variable}' cannot match any finite input")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::analysis::build_lexical_grammar;
+    use crate::generate::grammars::{ExternalToken, Production, ProductionStep, SyntaxVariable};
+
+    #[test]
+    fn test_validate_cross_references() {
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                productions: vec![Production {
+                    dynamic_precedence: 0,
+                    steps: vec![
+                        ProductionStep::new(Symbol::terminal(0)),
+                        ProductionStep::new(Symbol::external(0)),
+                    ],
+                }],
+            }],
+            external_tokens: vec![ExternalToken {
+                name: "heredoc".to_string(),
+                kind: VariableType::Named,
+                corresponding_internal_token: None,
+                zero_width: false,
+                precedence: 0,
+            }],
+            ..Default::default()
+        };
+        let lexical_grammar = build_lexical_grammar(&["identifier"]);
+        assert_eq!(
+            validate_cross_references(&syntax_grammar, &lexical_grammar),
+            Ok(())
+        );
+
+        // A terminal index past the end of the lexical grammar
+        syntax_grammar.variables[0].productions[0].steps[0].symbol = Symbol::terminal(3);
+        assert_eq!(
+            validate_cross_references(&syntax_grammar, &lexical_grammar),
+            Err(vec![CrossRefError::DanglingTerminal {
+                variable: "program".to_string(),
+                symbol: Symbol::terminal(3)
+            }])
+        );
+
+        // An external token that was never declared
+        syntax_grammar.variables[0].productions[0].steps[0].symbol = Symbol::terminal(0);
+        syntax_grammar.external_tokens.clear();
+        let errors = validate_cross_references(&syntax_grammar, &lexical_grammar).unwrap_err();
+        assert_eq!(
+            errors,
+            [CrossRefError::DanglingExternal {
+                variable: "program".to_string(),
+                symbol: Symbol::external(0)
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "Rule 'program' refers to undeclared external token 0"
+        );
+    }
+
+    #[test]
+    fn test_validate_cross_references_with_colliding_names() {
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                productions: vec![Production {
+                    dynamic_precedence: 0,
+                    steps: vec![ProductionStep::new(Symbol::terminal(0))],
+                }],
+            }],
+            external_tokens: vec![ExternalToken {
+                name: "comment".to_string(),
+                kind: VariableType::Named,
+                corresponding_internal_token: None,
+                zero_width: false,
+                precedence: 0,
+            }],
+            ..Default::default()
+        };
+        let lexical_grammar = build_lexical_grammar(&["identifier", "comment"]);
+        let errors = validate_cross_references(&syntax_grammar, &lexical_grammar).unwrap_err();
+        assert_eq!(
+            errors,
+            [CrossRefError::NameCollision {
+                external: "comment".to_string(),
+                terminal: "comment".to_string(),
+                terminal_kind: VariableType::Named
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "External token 'comment' in the grammar's externals has the same name as the token defined by rule 'comment'"
+        );
+
+        // An external token can share the name of the internal token that it
+        // corresponds to.
+        syntax_grammar.external_tokens[0].corresponding_internal_token = Some(Symbol::terminal(1));
+        assert_eq!(
+            validate_cross_references(&syntax_grammar, &lexical_grammar),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_grammar_wellformed() {
+        let variable = |name: &str, productions: Vec<Vec<Symbol>>| SyntaxVariable {
+            name: name.to_string(),
+            kind: VariableType::Named,
+            productions: productions
+                .into_iter()
+                .map(|steps| Production {
+                    dynamic_precedence: 0,
+                    steps: steps.into_iter().map(ProductionStep::new).collect(),
+                })
+                .collect(),
+        };
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                // program -> statement | loop
+                variable(
+                    "program",
+                    vec![vec![Symbol::non_terminal(1)], vec![Symbol::non_terminal(2)]],
+                ),
+                // statement -> identifier
+                variable("statement", vec![vec![Symbol::terminal(0)]]),
+                // loop -> identifier loop
+                variable(
+                    "loop",
+                    vec![vec![Symbol::terminal(0), Symbol::non_terminal(2)]],
+                ),
+                // orphan -> identifier
+                variable("orphan", vec![vec![Symbol::terminal(0)]]),
+            ],
+            ..Default::default()
+        };
+        let errors = validate_grammar_wellformed(&syntax_grammar).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                WellformednessError::Unreachable {
+                    variable: "orphan".to_string()
+                },
+                WellformednessError::NonProductive {
+                    variable: "loop".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "Rule 'orphan' is not reachable from the start rule"
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "Rule 'loop' cannot match any finite input"
+        );
+
+        // Variables that are used as extras are reachable, and a production
+        // that ends the recursion makes the variable productive.
+        syntax_grammar.extra_symbols.push(Symbol::non_terminal(3));
+        syntax_grammar.variables[2].productions.push(Production {
+            dynamic_precedence: 0,
+            steps: vec![ProductionStep::new(Symbol::terminal(0))],
+        });
+        assert_eq!(validate_grammar_wellformed(&syntax_grammar), Ok(()));
+    }
+}
@@ -2,12 +2,12 @@ use super::coincident_tokens::CoincidentTokenIndex;
 use super::token_conflicts::TokenConflictMap;
 use crate::generate::dedup::split_state_id_groups;
 use crate::generate::grammars::{LexicalGrammar, SyntaxGrammar};
-use crate::generate::nfa::NfaCursor;
+use crate::generate::nfa::{CharacterSet, NfaCursor, NfaState};
 use crate::generate::rules::{Symbol, TokenSet};
 use crate::generate::tables::{AdvanceAction, LexState, LexTable, ParseStateId, ParseTable};
 use log::info;
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::mem;
 
 pub fn build_lex_table(
@@ -80,6 +80,58 @@ pub fn build_lex_table(
     (table, keyword_lex_table)
 }
 
+/// A partition of the characters used by a lexical grammar into classes, such
+/// that every token treats all of the characters within a class in the same
+/// way. The classes are ordered by their first character. Characters that no
+/// token uses are not in any class.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CharacterClasses {
+    pub classes: Vec<CharacterSet>,
+}
+
+impl CharacterClasses {
+    pub fn class_of(&self, c: char) -> Option<usize> {
+        self.classes.iter().position(|class| class.contains(c))
+    }
+}
+
+/// Compute the character classes of a lexical grammar by refining a partition
+/// of the characters with each of the character sets in its NFA. Every state of
+/// a lex table built from the grammar transitions on unions of these classes,
+/// so the lexer can examine a character's class instead of the character.
+pub fn compute_character_classes(lexical_grammar: &LexicalGrammar) -> CharacterClasses {
+    let mut classes = Vec::<CharacterSet>::new();
+    let mut visited_sets = HashSet::new();
+    for state in &lexical_grammar.nfa.states {
+        let NfaState::Advance { chars, .. } = state else {
+            continue;
+        };
+        if !visited_sets.insert(chars) {
+            continue;
+        }
+
+        let mut remaining_chars = chars.clone();
+        let mut refined_classes = Vec::with_capacity(classes.len() + 1);
+        for mut class in classes {
+            if class.does_intersect(&remaining_chars) {
+                refined_classes.push(class.remove_intersection(&mut remaining_chars));
+                if !class.is_empty() {
+                    refined_classes.push(class);
+                }
+            } else {
+                refined_classes.push(class);
+            }
+        }
+        if !remaining_chars.is_empty() {
+            refined_classes.push(remaining_chars);
+        }
+        classes = refined_classes;
+    }
+
+    classes.sort_unstable_by_key(|class| class.iter().next());
+    CharacterClasses { classes }
+}
+
 struct QueueEntry {
     state_id: usize,
     nfa_states: Vec<u32>,
@@ -220,6 +272,9 @@ impl<'a> LexTableBuilder<'a> {
 
         if let Some((complete_id, _)) = completion {
             self.table.states[state_id].accept_action = Some(Symbol::terminal(complete_id));
+            self.table.states[state_id]
+                .accept_lookahead
+                .clone_from(&self.lexical_grammar.variables[complete_id].lookahead);
         } else if self.cursor.state_ids.is_empty() {
             self.table.states[state_id].accept_action = Some(Symbol::end());
         }
@@ -260,6 +315,12 @@ fn merge_token_set(
     true
 }
 
+// Merge the states that are indistinguishable, by repeatedly splitting groups of
+// states whose transitions lead to different groups until the partition stops
+// changing. The result is the minimal DFA for each lexical entry point. States
+// with different accept actions always remain separate, so each state keeps
+// the token that it accepts, as chosen by the precedence rules when the state
+// was built.
 fn minimize_lex_table(table: &mut LexTable, parse_table: &mut ParseTable) {
     // Initially group the states by their accept action and their
     // valid lookahead characters.
@@ -268,6 +329,7 @@ fn minimize_lex_table(table: &mut LexTable, parse_table: &mut ParseTable) {
         let signature = (
             i == 0,
             state.accept_action,
+            state.accept_lookahead.clone(),
             state.eof_action.is_some(),
             state
                 .advance_actions
@@ -371,3 +433,130 @@ fn sort_states(table: &mut LexTable, parse_table: &mut ParseTable) {
         state.lex_state_id = new_ids_by_old_id[state.lex_state_id];
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+    use crate::generate::prepare_grammar::prepare_grammar;
+    use crate::generate::rules::Rule;
+    use crate::generate::tables::ParseState;
+
+    #[test]
+    fn test_compute_character_classes() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::named("operator"),
+                        Rule::named("arrow"),
+                        Rule::string(";"),
+                    ])),
+                },
+                Variable {
+                    name: "operator".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[+*/%^]", ""),
+                },
+                Variable {
+                    name: "arrow".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("->"),
+                },
+            ],
+            ..Default::default()
+        };
+        let (_, lexical_grammar, ..) = prepare_grammar(&grammar).unwrap();
+        let character_classes = compute_character_classes(&lexical_grammar);
+
+        // The operator characters are only ever matched together, so they share
+        // a class, while the characters of the arrow are matched individually.
+        let class_chars = |c| {
+            let class = character_classes.class_of(c).unwrap();
+            character_classes.classes[class].chars().collect::<String>()
+        };
+        assert_eq!(class_chars('+'), "%*+/^");
+        assert_eq!(class_chars('-'), "-");
+        assert_eq!(class_chars('>'), ">");
+        assert_eq!(class_chars(';'), ";");
+        assert_eq!(character_classes.classes.len(), 4);
+        assert_eq!(character_classes.class_of('a'), None);
+    }
+
+    #[test]
+    fn test_minimize_lex_table() {
+        let advance = |c, state| {
+            (
+                CharacterSet::from_char(c),
+                AdvanceAction {
+                    state,
+                    in_main_token: true,
+                },
+            )
+        };
+        let accept = |index| LexState {
+            accept_action: Some(Symbol::terminal(index)),
+            ..Default::default()
+        };
+        let mut table = LexTable {
+            states: vec![
+                LexState {
+                    advance_actions: vec![advance('a', 1), advance('b', 2), advance('c', 5)],
+                    ..Default::default()
+                },
+                LexState {
+                    advance_actions: vec![advance('x', 3)],
+                    ..Default::default()
+                },
+                LexState {
+                    advance_actions: vec![advance('x', 4)],
+                    ..Default::default()
+                },
+                accept(0),
+                accept(0),
+                LexState {
+                    advance_actions: vec![advance('x', 6)],
+                    ..Default::default()
+                },
+                accept(1),
+            ],
+        };
+        let mut parse_table = ParseTable {
+            states: [0, 1, 2, 5]
+                .into_iter()
+                .map(|lex_state_id| ParseState {
+                    lex_state_id,
+                    ..Default::default()
+                })
+                .collect(),
+            symbols: Vec::new(),
+            production_infos: Vec::new(),
+            max_aliased_production_length: 0,
+            external_lex_states: Vec::new(),
+            resolutions: Vec::new(),
+        };
+        minimize_lex_table(&mut table, &mut parse_table);
+
+        // The states reached by 'a' and 'b' accept the same token after the same
+        // characters, so they are merged, along with their accepting states. The
+        // state reached by 'c' leads to a different token, so it remains separate.
+        assert_eq!(table.states.len(), 5);
+        let lex_state_ids = parse_table
+            .states
+            .iter()
+            .map(|state| state.lex_state_id)
+            .collect::<Vec<_>>();
+        assert_eq!(lex_state_ids[0], 0);
+        assert_eq!(lex_state_ids[1], lex_state_ids[2]);
+        assert_ne!(lex_state_ids[1], lex_state_ids[3]);
+
+        let accepted_token = |state_id: usize| {
+            let next_state_id = table.states[state_id].advance_actions[0].1.state;
+            table.states[next_state_id].accept_action
+        };
+        assert_eq!(accepted_token(lex_state_ids[1]), Some(Symbol::terminal(0)));
+        assert_eq!(accepted_token(lex_state_ids[3]), Some(Symbol::terminal(1)));
+    }
+}
@@ -1,21 +1,24 @@
-use super::item::{ParseItem, ParseItemSet, ParseItemSetCore};
+use super::item::{
+    symbol_name, ParseActionDisplay, ParseItem, ParseItemSet, ParseItemSetCore, ParseItemSetDisplay,
+};
 use super::item_set_builder::ParseItemSetBuilder;
 use crate::generate::grammars::PrecedenceEntry;
 use crate::generate::grammars::{
-    InlinedProductionMap, LexicalGrammar, SyntaxGrammar, VariableType,
+    InlinedProductionMap, LexicalGrammar, ProductionRef, Resolution, SyntaxGrammar,
 };
 use crate::generate::node_types::VariableInfo;
 use crate::generate::rules::{Associativity, Precedence, Symbol, SymbolType, TokenSet};
 use crate::generate::tables::{
-    FieldLocation, GotoAction, ParseAction, ParseState, ParseStateId, ParseTable, ParseTableEntry,
-    ProductionInfo, ProductionInfoId,
+    ConflictResolution, FieldLocation, GotoAction, ParseAction, ParseState, ParseStateId,
+    ParseTable, ParseTableEntry, ProductionInfo, ProductionInfoId, ResolutionEvent,
+    ResolutionWinner,
 };
 use anyhow::{anyhow, Result};
-use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
-use std::fmt::Write;
+use serde::{Deserialize, Serialize};
+use std::cmp::{self, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{self, Write};
 use std::hash::BuildHasherDefault;
-use std::u32;
 
 use indexmap::{map::Entry, IndexMap};
 use rustc_hash::FxHasher;
@@ -42,6 +45,35 @@ struct ReductionInfo {
     has_non_assoc: bool,
 }
 
+/// A conflict that could not be resolved using precedence or associativity,
+/// and that has not been declared in the grammar's `conflicts`.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Conflict {
+    pub state_id: ParseStateId,
+    pub preceding_symbols: Vec<String>,
+    pub lookahead: String,
+    pub rules: Vec<String>,
+    /// The symbols of `rules`, which would be declared together in `conflicts`.
+    #[serde(skip)]
+    pub symbols: Vec<Symbol>,
+}
+
+/// The error returned when a grammar has unresolved conflicts. It is displayed
+/// as a description of the first conflict, but holds all of them.
+#[derive(Debug)]
+pub struct ConflictError {
+    pub conflicts: Vec<Conflict>,
+    message: String,
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
 struct ParseStateQueueEntry {
     state_id: ParseStateId,
     preceding_auxiliary_symbols: AuxiliarySymbolSequence,
@@ -50,6 +82,7 @@ struct ParseStateQueueEntry {
 struct ParseTableBuilder<'a> {
     item_set_builder: ParseItemSetBuilder<'a>,
     syntax_grammar: &'a SyntaxGrammar,
+    inlines: &'a InlinedProductionMap,
     lexical_grammar: &'a LexicalGrammar,
     variable_info: &'a [VariableInfo],
     core_ids_by_core: HashMap<ParseItemSetCore<'a>, usize>,
@@ -58,6 +91,7 @@ struct ParseTableBuilder<'a> {
     parse_state_queue: VecDeque<ParseStateQueueEntry>,
     non_terminal_extra_states: Vec<(Symbol, usize)>,
     actual_conflicts: HashSet<Vec<Symbol>>,
+    unresolved_conflicts: Vec<(Conflict, String)>,
     parse_table: ParseTable,
 }
 
@@ -81,35 +115,8 @@ impl<'a> ParseTableBuilder<'a> {
             ))),
         );
 
-        // Compute the possible item sets for non-terminal extras.
-        let mut non_terminal_extra_item_sets_by_first_terminal = BTreeMap::new();
-        for extra_non_terminal in self
-            .syntax_grammar
-            .extra_symbols
-            .iter()
-            .filter(|s| s.is_non_terminal())
-        {
-            let variable = &self.syntax_grammar.variables[extra_non_terminal.index];
-            for production in &variable.productions {
-                non_terminal_extra_item_sets_by_first_terminal
-                    .entry(production.first_symbol().unwrap())
-                    .or_insert_with(ParseItemSet::default)
-                    .insert(
-                        ParseItem {
-                            variable_index: extra_non_terminal.index as u32,
-                            production,
-                            step_index: 1,
-                            has_preceding_inherited_fields: false,
-                        },
-                        &std::iter::once(&Symbol::end_of_nonterminal_extra())
-                            .copied()
-                            .collect(),
-                    );
-            }
-        }
-
         // Add a state for each starting terminal of a non-terminal extra rule.
-        for (terminal, item_set) in non_terminal_extra_item_sets_by_first_terminal {
+        for (terminal, item_set) in non_terminal_extra_item_sets(self.syntax_grammar) {
             self.non_terminal_extra_states
                 .push((terminal, self.parse_table.states.len()));
             self.add_parse_state(&Vec::new(), &Vec::new(), item_set);
@@ -128,6 +135,16 @@ impl<'a> ParseTableBuilder<'a> {
             )?;
         }
 
+        if !self.unresolved_conflicts.is_empty() {
+            let message = self.unresolved_conflicts[0].1.clone();
+            let conflicts = self
+                .unresolved_conflicts
+                .into_iter()
+                .map(|(conflict, _)| conflict)
+                .collect();
+            return Err(ConflictError { conflicts, message }.into());
+        }
+
         if !self.actual_conflicts.is_empty() {
             println!("Warning: unnecessary conflicts");
             for conflict in &self.actual_conflicts {
@@ -204,33 +221,16 @@ impl<'a> ParseTableBuilder<'a> {
             // next symbol. Advance the item to its next step and insert the resulting
             // item into the successor item set.
             if let Some(next_symbol) = item.symbol() {
-                let mut successor = item.successor();
+                let successor = successor_item(item, self.syntax_grammar, self.variable_info);
                 if next_symbol.is_non_terminal() {
-                    let variable = &self.syntax_grammar.variables[next_symbol.index];
-
                     // Keep track of where auxiliary non-terminals (repeat symbols) are
                     // used within visible symbols. This information may be needed later
                     // for conflict resolution.
-                    if variable.is_auxiliary() {
+                    if self.syntax_grammar.variables[next_symbol.index].is_auxiliary() {
                         preceding_auxiliary_symbols
                             .push(self.get_auxiliary_node_info(item_set, next_symbol));
                     }
 
-                    // For most parse items, the symbols associated with the preceding children
-                    // don't matter: they have no effect on the REDUCE action that would be
-                    // performed at the end of the item. But the symbols *do* matter for
-                    // children that are hidden and have fields, because those fields are
-                    // "inherited" by the parent node.
-                    //
-                    // If this item has consumed a hidden child with fields, then the symbols
-                    // of its preceding children need to be taken into account when comparing
-                    // it with other items.
-                    if variable.is_hidden()
-                        && !self.variable_info[next_symbol.index].fields.is_empty()
-                    {
-                        successor.has_preceding_inherited_fields = true;
-                    }
-
                     non_terminal_successors
                         .entry(next_symbol)
                         .or_insert_with(ParseItemSet::default)
@@ -251,7 +251,7 @@ impl<'a> ParseTableBuilder<'a> {
                 } else {
                     ParseAction::Reduce {
                         symbol,
-                        child_count: item.step_index as usize,
+                        child_count: item.reduce_arity(),
                         dynamic_precedence: item.production.dynamic_precedence,
                         production_id: self.get_production_id(item),
                     }
@@ -263,7 +263,7 @@ impl<'a> ParseTableBuilder<'a> {
                     let table_entry = self.parse_table.states[state_id]
                         .terminal_entries
                         .entry(lookahead)
-                        .or_insert_with(ParseTableEntry::new);
+                        .or_default();
                     let reduction_info = reduction_infos.entry(lookahead).or_default();
 
                     // While inserting Reduce actions, eagerly resolve conflicts related
@@ -327,13 +327,10 @@ impl<'a> ParseTableBuilder<'a> {
                 }
             }
 
-            entry
-                .or_insert_with(ParseTableEntry::new)
-                .actions
-                .push(ParseAction::Shift {
-                    state: next_state_id,
-                    is_repetition: false,
-                });
+            entry.or_default().actions.push(ParseAction::Shift {
+                state: next_state_id,
+                is_repetition: false,
+            });
         }
 
         for (symbol, next_item_set) in non_terminal_successors {
@@ -353,7 +350,8 @@ impl<'a> ParseTableBuilder<'a> {
         // This will either
         // * choose one action over the others using precedence or associativity
         // * keep multiple actions if this conflict has been whitelisted in the grammar
-        // * fail, terminating the parser generation process
+        // * record an unresolved conflict, failing the parser generation process
+        //   once the rest of the table has been built
         for symbol in lookaheads_with_conflicts.iter() {
             self.handle_conflict(
                 item_set,
@@ -362,7 +360,7 @@ impl<'a> ParseTableBuilder<'a> {
                 &preceding_auxiliary_symbols,
                 symbol,
                 reduction_infos.get(&symbol).unwrap(),
-            )?;
+            );
         }
 
         // Finally, add actions for the grammar's `extra` symbols.
@@ -442,12 +440,7 @@ impl<'a> ParseTableBuilder<'a> {
         preceding_auxiliary_symbols: &[AuxiliarySymbolInfo],
         conflicting_lookahead: Symbol,
         reduction_info: &ReductionInfo,
-    ) -> Result<()> {
-        let entry = self.parse_table.states[state_id]
-            .terminal_entries
-            .get_mut(&conflicting_lookahead)
-            .unwrap();
-
+    ) {
         // Determine which items in the set conflict with each other, and the
         // precedences associated with SHIFT vs REDUCE actions. There won't
         // be multiple REDUCE actions with different precedences; that is
@@ -455,7 +448,7 @@ impl<'a> ParseTableBuilder<'a> {
         // REDUCE-REDUCE conflicts where all actions have the *same*
         // precedence, and there can still be SHIFT/REDUCE conflicts.
         let mut considered_associativity = false;
-        let mut shift_precedence = Vec::<(&Precedence, Symbol)>::new();
+        let mut shift_items = Vec::new();
         let mut conflicting_items = HashSet::new();
         for (item, lookaheads) in &item_set.entries {
             if let Some(step) = item.step() {
@@ -468,14 +461,7 @@ impl<'a> ParseTableBuilder<'a> {
                     if item.variable_index != u32::MAX {
                         conflicting_items.insert(item);
                     }
-
-                    let p = (
-                        item.precedence(),
-                        Symbol::non_terminal(item.variable_index as usize),
-                    );
-                    if let Err(i) = shift_precedence.binary_search(&p) {
-                        shift_precedence.insert(i, p);
-                    }
+                    shift_items.push(item);
                 }
             } else if lookaheads.contains(&conflicting_lookahead) && item.variable_index != u32::MAX
             {
@@ -483,6 +469,100 @@ impl<'a> ParseTableBuilder<'a> {
             }
         }
 
+        // Overrides for conflicts between specific productions take priority over
+        // precedence and associativity. The SHIFT action is only removed if every
+        // item that it advances loses.
+        let mut pruned_reduction_info = None;
+        let losing_productions = self.overridden_productions(&conflicting_items);
+        if !losing_productions.is_empty() {
+            let (losing_items, winning_items): (Vec<&ParseItem>, Vec<&ParseItem>) =
+                conflicting_items.iter().copied().partition(|item| {
+                    self.production_refs(item)
+                        .iter()
+                        .any(|production| losing_productions.contains(production))
+                });
+            if !winning_items.is_empty() {
+                let removes_shift = losing_items.iter().any(|item| !item.is_done())
+                    && winning_items.iter().all(|item| item.is_done());
+                let losing_reductions = losing_items
+                    .iter()
+                    .filter(|item| item.is_done())
+                    .map(|item| ParseAction::Reduce {
+                        symbol: Symbol::non_terminal(item.variable_index as usize),
+                        child_count: item.reduce_arity(),
+                        dynamic_precedence: item.production.dynamic_precedence,
+                        production_id: self.get_production_id(item),
+                    })
+                    .collect::<Vec<_>>();
+
+                let entry = self.parse_table.states[state_id]
+                    .terminal_entries
+                    .get_mut(&conflicting_lookahead)
+                    .unwrap();
+                let had_shift = matches!(entry.actions.last(), Some(ParseAction::Shift { .. }));
+                if removes_shift && had_shift {
+                    entry.actions.pop();
+                }
+                entry
+                    .actions
+                    .retain(|action| !losing_reductions.contains(action));
+                conflicting_items = winning_items.into_iter().collect();
+
+                // Only the remaining items take part in the precedence and
+                // associativity comparisons below.
+                shift_items.retain(|item| !losing_items.contains(item));
+                let mut reduction_info = ReductionInfo {
+                    precedence: reduction_info.precedence.clone(),
+                    ..Default::default()
+                };
+                for item in conflicting_items.iter().filter(|item| item.is_done()) {
+                    let symbol = Symbol::non_terminal(item.variable_index as usize);
+                    if let Err(i) = reduction_info.symbols.binary_search(&symbol) {
+                        reduction_info.symbols.insert(i, symbol);
+                    }
+                    match item.associativity() {
+                        Some(Associativity::Left) => reduction_info.has_left_assoc = true,
+                        Some(Associativity::Right) => reduction_info.has_right_assoc = true,
+                        None => reduction_info.has_non_assoc = true,
+                    }
+                }
+                pruned_reduction_info = Some(reduction_info);
+
+                if had_shift {
+                    let has_shift = matches!(entry.actions.last(), Some(ParseAction::Shift { .. }));
+                    self.parse_table.resolutions.push(ResolutionEvent {
+                        state_id,
+                        lookahead: conflicting_lookahead,
+                        resolution: ConflictResolution::Override,
+                        winner: if has_shift {
+                            ResolutionWinner::Shift
+                        } else {
+                            ResolutionWinner::Reduce
+                        },
+                    });
+                }
+                if entry.actions.len() == 1 {
+                    return;
+                }
+            }
+        }
+
+        let reduction_info = pruned_reduction_info.as_ref().unwrap_or(reduction_info);
+        let mut shift_precedence = Vec::<(&Precedence, Symbol)>::new();
+        for item in shift_items {
+            let p = (
+                item.precedence(),
+                Symbol::non_terminal(item.variable_index as usize),
+            );
+            if let Err(i) = shift_precedence.binary_search(&p) {
+                shift_precedence.insert(i, p);
+            }
+        }
+
+        let entry = self.parse_table.states[state_id]
+            .terminal_entries
+            .get_mut(&conflicting_lookahead)
+            .unwrap();
         if let ParseAction::Shift { is_repetition, .. } = entry.actions.last_mut().unwrap() {
             // If all of the items in the conflict have the same parent symbol,
             // and that parent symbols is auxiliary, then this is just the intentional
@@ -497,7 +577,7 @@ impl<'a> ParseTableBuilder<'a> {
                     .all(|item| item.variable_index == conflicting_variable_index)
             {
                 *is_repetition = true;
-                return Ok(());
+                return;
             }
 
             // If the SHIFT action has higher precedence, remove all the REDUCE actions.
@@ -517,13 +597,16 @@ impl<'a> ParseTableBuilder<'a> {
                 }
             }
 
+            let mut resolved = None;
             if shift_is_more && !shift_is_less {
                 entry.actions.drain(0..entry.actions.len() - 1);
+                resolved = Some((ConflictResolution::Precedence, ResolutionWinner::Shift));
             }
             // If the REDUCE actions have higher precedence, remove the SHIFT action.
             else if shift_is_less && !shift_is_more {
                 entry.actions.pop();
                 conflicting_items.retain(|item| item.is_done());
+                resolved = Some((ConflictResolution::Precedence, ResolutionWinner::Reduce));
             }
             // If the SHIFT and REDUCE actions have the same predence, consider
             // the REDUCE actions' associativity.
@@ -540,13 +623,26 @@ impl<'a> ParseTableBuilder<'a> {
                     (true, false, false) => {
                         entry.actions.pop();
                         conflicting_items.retain(|item| item.is_done());
+                        resolved =
+                            Some((ConflictResolution::Associativity, ResolutionWinner::Reduce));
                     }
                     (false, false, true) => {
                         entry.actions.drain(0..entry.actions.len() - 1);
+                        resolved =
+                            Some((ConflictResolution::Associativity, ResolutionWinner::Shift));
                     }
                     _ => {}
                 }
             }
+
+            if let Some((resolution, winner)) = resolved {
+                self.parse_table.resolutions.push(ResolutionEvent {
+                    state_id,
+                    lookahead: conflicting_lookahead,
+                    resolution,
+                    winner,
+                });
+            }
         }
 
         // If all of the actions but one have been eliminated, then there's no problem.
@@ -555,7 +651,7 @@ impl<'a> ParseTableBuilder<'a> {
             .get_mut(&conflicting_lookahead)
             .unwrap();
         if entry.actions.len() == 1 {
-            return Ok(());
+            return;
         }
 
         // Determine the set of parent symbols involved in this conflict.
@@ -591,7 +687,7 @@ impl<'a> ParseTableBuilder<'a> {
             .contains(&actual_conflict)
         {
             self.actual_conflicts.remove(&actual_conflict);
-            return Ok(());
+            return;
         }
 
         let mut msg = "Unresolved conflict for symbol sequence:\n\n".to_string();
@@ -762,7 +858,65 @@ impl<'a> ParseTableBuilder<'a> {
         }
         writeln!(&mut msg).unwrap();
 
-        Err(anyhow!(msg))
+        // Keep building the table so that the remaining conflicts can be reported
+        // as well.
+        let conflict = Conflict {
+            state_id,
+            preceding_symbols: preceding_symbols
+                .iter()
+                .map(|symbol| self.symbol_name(symbol))
+                .collect(),
+            lookahead: self.symbol_name(&conflicting_lookahead),
+            rules: actual_conflict
+                .iter()
+                .map(|symbol| self.symbol_name(symbol))
+                .collect(),
+            symbols: actual_conflict,
+        };
+        self.unresolved_conflicts.push((conflict, msg));
+    }
+
+    // Find the productions that lose a conflict override against another of the
+    // given items' productions.
+    fn overridden_productions(&self, items: &HashSet<&ParseItem>) -> Vec<ProductionRef> {
+        let productions = items
+            .iter()
+            .flat_map(|item| self.production_refs(item))
+            .collect::<HashSet<_>>();
+        self.syntax_grammar
+            .conflict_overrides
+            .iter()
+            .filter_map(|(first, second, resolution)| {
+                let (winner, loser) = match resolution {
+                    Resolution::PreferFirst => (first, second),
+                    Resolution::PreferSecond => (second, first),
+                };
+                (productions.contains(winner) && productions.contains(loser)).then_some(*loser)
+            })
+            .collect()
+    }
+
+    // Identify the grammar's productions that a non-augmented item's production
+    // stands for. A production that was created by inlining stands for each of
+    // the productions that it was built from.
+    fn production_refs(&self, item: &ParseItem) -> Vec<ProductionRef> {
+        let variable_index = item.variable_index as usize;
+        let Some(variable) = self.syntax_grammar.variables.get(variable_index) else {
+            return Vec::new();
+        };
+        if let Some(production_index) = variable
+            .productions
+            .iter()
+            .position(|production| std::ptr::eq(production, item.production))
+        {
+            return vec![ProductionRef {
+                variable_index,
+                production_index,
+            }];
+        }
+        self.inlines
+            .origins(item.production)
+            .map_or_else(Vec::new, <[ProductionRef]>::to_vec)
     }
 
     fn compare_precedence(
@@ -789,6 +943,9 @@ impl<'a> ParseTableBuilder<'a> {
             };
 
         match (left, right) {
+            // Error-recovery productions lose to everything else.
+            (l, r) if l.is_recovery() || r.is_recovery() => r.is_recovery().cmp(&l.is_recovery()),
+
             // Integer precedences can be compared to other integer precedences,
             // and to the default precedence, which is zero.
             (Precedence::Integer(l), Precedence::Integer(r)) if *l != 0 || *r != 0 => l.cmp(r),
@@ -908,95 +1065,2477 @@ impl<'a> ParseTableBuilder<'a> {
     }
 
     fn symbol_name(&self, symbol: &Symbol) -> String {
-        match symbol.kind {
-            SymbolType::End | SymbolType::EndOfNonTerminalExtra => "EOF".to_string(),
-            SymbolType::External => self.syntax_grammar.external_tokens[symbol.index]
-                .name
-                .clone(),
-            SymbolType::NonTerminal => self.syntax_grammar.variables[symbol.index].name.clone(),
-            SymbolType::Terminal => {
-                let variable = &self.lexical_grammar.variables[symbol.index];
-                if variable.kind == VariableType::Named {
-                    variable.name.clone()
+        symbol_name(symbol, self.syntax_grammar, self.lexical_grammar)
+    }
+}
+
+/// Advance the given item by one step, in the same way that the parse table
+/// builder does when computing a state's successors.
+fn successor_item<'a>(
+    item: &ParseItem<'a>,
+    syntax_grammar: &SyntaxGrammar,
+    variable_info: &[VariableInfo],
+) -> ParseItem<'a> {
+    let mut successor = item.successor();
+
+    // For most parse items, the symbols associated with the preceding children
+    // don't matter: they have no effect on the REDUCE action that would be
+    // performed at the end of the item. But the symbols *do* matter for
+    // children that are hidden and have fields, because those fields are
+    // "inherited" by the parent node.
+    //
+    // If this item has consumed a hidden child with fields, then the symbols
+    // of its preceding children need to be taken into account when comparing
+    // it with other items.
+    if let Some(next_symbol) = item.symbol() {
+        if next_symbol.is_non_terminal()
+            && syntax_grammar.variables[next_symbol.index].is_hidden()
+            && !variable_info[next_symbol.index].fields.is_empty()
+        {
+            successor.has_preceding_inherited_fields = true;
+        }
+    }
+    successor
+}
+
+/// Compute the possible item sets for non-terminal extras, keyed by the
+/// terminal that begins them.
+fn non_terminal_extra_item_sets(
+    syntax_grammar: &SyntaxGrammar,
+) -> BTreeMap<Symbol, ParseItemSet<'_>> {
+    let mut result = BTreeMap::new();
+    for extra_non_terminal in syntax_grammar
+        .extra_symbols
+        .iter()
+        .filter(|s| s.is_non_terminal())
+    {
+        let variable = &syntax_grammar.variables[extra_non_terminal.index];
+        for production in &variable.productions {
+            result
+                .entry(production.first_symbol().unwrap())
+                .or_insert_with(ParseItemSet::default)
+                .insert(
+                    ParseItem {
+                        variable_index: extra_non_terminal.index as u32,
+                        production,
+                        step_index: 1,
+                        has_preceding_inherited_fields: false,
+                    },
+                    &std::iter::once(&Symbol::end_of_nonterminal_extra())
+                        .copied()
+                        .collect(),
+                );
+        }
+    }
+    result
+}
+
+/// An iterator over the item sets of a grammar's parse states, which discovers
+/// the states lazily in the same order as [`build_parse_table`]. Each item set
+/// is yielded without its transitive closure. The error state is not included.
+pub struct ParseItemSetIter<'a, 'b> {
+    item_set_builder: &'b mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &'a SyntaxGrammar,
+    variable_info: &'a [VariableInfo],
+    visited_item_sets: HashSet<ParseItemSet<'a>>,
+    item_set_queue: VecDeque<ParseItemSet<'a>>,
+}
+
+pub fn states_iter<'a, 'b>(
+    item_set_builder: &'b mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &'a SyntaxGrammar,
+    variable_info: &'a [VariableInfo],
+) -> ParseItemSetIter<'a, 'b> {
+    let mut result = ParseItemSetIter {
+        item_set_builder,
+        syntax_grammar,
+        variable_info,
+        visited_item_sets: HashSet::new(),
+        item_set_queue: VecDeque::new(),
+    };
+    result.enqueue(ParseItemSet::with(std::iter::once((
+        ParseItem::start(),
+        std::iter::once(&Symbol::end()).copied().collect(),
+    ))));
+    for item_set in non_terminal_extra_item_sets(syntax_grammar).into_values() {
+        result.enqueue(item_set);
+    }
+    result
+}
+
+impl<'a, 'b> ParseItemSetIter<'a, 'b> {
+    fn enqueue(&mut self, item_set: ParseItemSet<'a>) {
+        if self.visited_item_sets.insert(item_set.clone()) {
+            self.item_set_queue.push_back(item_set);
+        }
+    }
+}
+
+impl<'a, 'b> Iterator for ParseItemSetIter<'a, 'b> {
+    type Item = ParseItemSet<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item_set = self.item_set_queue.pop_front()?;
+        let closure = self.item_set_builder.transitive_closure(&item_set);
+
+        let mut terminal_successors = BTreeMap::new();
+        let mut non_terminal_successors = BTreeMap::new();
+        for (item, lookaheads) in &closure.entries {
+            if let Some(next_symbol) = item.symbol() {
+                let successors = if next_symbol.is_non_terminal() {
+                    &mut non_terminal_successors
                 } else {
-                    format!("'{}'", &variable.name)
-                }
+                    &mut terminal_successors
+                };
+                successors
+                    .entry(next_symbol)
+                    .or_insert_with(ParseItemSet::default)
+                    .insert(
+                        successor_item(item, self.syntax_grammar, self.variable_info),
+                        lookaheads,
+                    );
             }
         }
+
+        for next_item_set in terminal_successors
+            .into_values()
+            .chain(non_terminal_successors.into_values())
+        {
+            self.enqueue(next_item_set);
+        }
+
+        Some(item_set)
     }
 }
 
-fn populate_following_tokens(
-    result: &mut [TokenSet],
-    grammar: &SyntaxGrammar,
-    inlines: &InlinedProductionMap,
-    builder: &ParseItemSetBuilder,
-) {
-    let productions = grammar
-        .variables
-        .iter()
-        .flat_map(|v| &v.productions)
-        .chain(&inlines.productions);
-    let all_tokens = (0..result.len())
-        .map(Symbol::terminal)
-        .collect::<TokenSet>();
-    for production in productions {
-        for i in 1..production.steps.len() {
-            let left_tokens = builder.last_set(&production.steps[i - 1].symbol);
-            let right_tokens = builder.first_set(&production.steps[i].symbol);
-            for left_token in left_tokens.iter() {
-                if left_token.is_terminal() {
-                    result[left_token.index].insert_all_terminals(right_tokens);
+/// Find the productions that can never be reduced, because in every parse state
+/// where they are complete, a competing action with higher precedence wins on
+/// each of their lookaheads. Productions are identified by their variable index
+/// and their index within that variable. Auxiliary variables and productions
+/// that were created by inlining are not considered.
+pub fn precedence_shadowed_productions<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &SyntaxGrammar,
+    states: &[ParseStateInfo<'a>],
+) -> Vec<(usize, usize)> {
+    let mut reduced_productions = HashSet::new();
+    let mut shadowed_productions = BTreeSet::new();
+    for (_, core) in states {
+        let item_set = item_set_builder.transitive_closure(core);
+        for (item, lookaheads) in &item_set.entries {
+            if !item.is_done() || item.is_augmented() {
+                continue;
+            }
+            let variable_index = item.variable_index as usize;
+            let variable = &syntax_grammar.variables[variable_index];
+            let Some(production_index) = variable
+                .productions
+                .iter()
+                .position(|production| std::ptr::eq(production, item.production))
+            else {
+                continue;
+            };
+            if variable.is_auxiliary() {
+                continue;
+            }
+
+            let symbol = Symbol::non_terminal(variable_index);
+            let compare_to_item = |other: &ParseItem| {
+                ParseTableBuilder::compare_precedence(
+                    syntax_grammar,
+                    other.precedence(),
+                    &[Symbol::non_terminal(other.variable_index as usize)],
+                    item.precedence(),
+                    &[symbol],
+                )
+            };
+
+            for lookahead in lookaheads.iter() {
+                // As in `handle_conflict`, a shift wins if it has higher precedence
+                // than the reduction according to some item, and lower precedence
+                // according to none.
+                let mut is_shifted = false;
+                let mut shift_is_more = false;
+                let mut shift_is_less = false;
+                let mut loses_to_reduction = false;
+                for (other, other_lookaheads) in &item_set.entries {
+                    if let Some(step) = other.step() {
+                        is_shifted |= step.symbol == lookahead;
+                        if other.step_index > 0
+                            && item_set_builder
+                                .first_set(&step.symbol)
+                                .contains(&lookahead)
+                        {
+                            match compare_to_item(other) {
+                                Ordering::Greater => shift_is_more = true,
+                                Ordering::Less => shift_is_less = true,
+                                Ordering::Equal => {}
+                            }
+                        }
+                    } else if !other.is_augmented()
+                        && other_lookaheads.contains(&lookahead)
+                        && compare_to_item(other) == Ordering::Greater
+                    {
+                        loses_to_reduction = true;
+                    }
+                }
+
+                let key = (variable_index, production_index);
+                if loses_to_reduction || (is_shifted && shift_is_more && !shift_is_less) {
+                    shadowed_productions.insert(key);
+                } else {
+                    reduced_productions.insert(key);
                 }
             }
         }
     }
-    for extra in &grammar.extra_symbols {
-        if extra.is_terminal() {
-            for entry in result.iter_mut() {
-                entry.insert(*extra);
+
+    shadowed_productions
+        .into_iter()
+        .filter(|key| !reduced_productions.contains(key))
+        .collect()
+}
+
+/// Find the productions that are only ever reduced as the result of winning a
+/// conflict: in every parse state where they are complete, each of their
+/// lookaheads can also be shifted, or can reduce a different item. If all of
+/// those conflicts were resolved the other way, the productions would never be
+/// reduced. Productions are identified as in [`precedence_shadowed_productions`],
+/// and auxiliary variables and inlined productions are not considered.
+pub fn conflict_dependent_productions<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &SyntaxGrammar,
+    states: &[ParseStateInfo<'a>],
+) -> Vec<(usize, usize)> {
+    let mut uncontested_productions = HashSet::new();
+    let mut contested_productions = BTreeSet::new();
+    for (_, core) in states {
+        let item_set = item_set_builder.transitive_closure(core);
+        for (item, lookaheads) in &item_set.entries {
+            if !item.is_done() || item.is_augmented() {
+                continue;
+            }
+            let variable_index = item.variable_index as usize;
+            let variable = &syntax_grammar.variables[variable_index];
+            let Some(production_index) = variable
+                .productions
+                .iter()
+                .position(|production| std::ptr::eq(production, item.production))
+            else {
+                continue;
+            };
+            if variable.is_auxiliary() {
+                continue;
+            }
+
+            let key = (variable_index, production_index);
+            for lookahead in lookaheads.iter() {
+                let is_contested = item_set.entries.iter().any(|(other, other_lookaheads)| {
+                    other.symbol() == Some(lookahead)
+                        || (other.is_done()
+                            && other != item
+                            && other_lookaheads.contains(&lookahead))
+                });
+                if is_contested {
+                    contested_productions.insert(key);
+                } else {
+                    uncontested_productions.insert(key);
+                }
             }
-            result[extra.index] = all_tokens.clone();
         }
     }
+
+    contested_productions
+        .into_iter()
+        .filter(|key| !uncontested_productions.contains(key))
+        .collect()
 }
 
-pub fn build_parse_table<'a>(
+/// Compute the goto table for the given parse states: for each state, the state
+/// that the parser moves to after reducing each non-terminal. The states must
+/// be the item set cores returned by [`build_parse_table`] or [`states_iter`],
+/// and the result is indexed in the same way.
+pub fn build_goto_table<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
     syntax_grammar: &'a SyntaxGrammar,
-    lexical_grammar: &'a LexicalGrammar,
-    inlines: &'a InlinedProductionMap,
     variable_info: &'a [VariableInfo],
-) -> Result<(ParseTable, Vec<TokenSet>, Vec<ParseStateInfo<'a>>)> {
-    let actual_conflicts = syntax_grammar.expected_conflicts.iter().cloned().collect();
-    let item_set_builder = ParseItemSetBuilder::new(syntax_grammar, lexical_grammar, inlines);
-    let mut following_tokens = vec![TokenSet::new(); lexical_grammar.variables.len()];
-    populate_following_tokens(
-        &mut following_tokens,
-        syntax_grammar,
-        inlines,
-        &item_set_builder,
-    );
+    states: &[ParseStateInfo<'a>],
+) -> Vec<HashMap<Symbol, ParseStateId>> {
+    let state_ids_by_item_set = states
+        .iter()
+        .enumerate()
+        .map(|(state_id, (_, item_set))| (item_set, state_id))
+        .collect::<HashMap<_, _>>();
 
-    let (table, item_sets) = ParseTableBuilder {
-        syntax_grammar,
-        lexical_grammar,
-        item_set_builder,
-        variable_info,
-        non_terminal_extra_states: Vec::new(),
-        actual_conflicts,
-        state_ids_by_item_set: IndexMap::default(),
-        core_ids_by_core: HashMap::new(),
-        parse_state_info_by_id: Vec::new(),
-        parse_state_queue: VecDeque::new(),
-        parse_table: ParseTable {
-            states: Vec::new(),
-            symbols: Vec::new(),
-            external_lex_states: Vec::new(),
-            production_infos: Vec::new(),
-            max_aliased_production_length: 1,
-        },
+    states
+        .iter()
+        .map(|(_, core)| {
+            let mut non_terminal_successors = BTreeMap::new();
+            for (item, lookaheads) in &item_set_builder.transitive_closure(core).entries {
+                if let Some(next_symbol) = item.symbol() {
+                    if next_symbol.is_non_terminal() {
+                        non_terminal_successors
+                            .entry(next_symbol)
+                            .or_insert_with(ParseItemSet::default)
+                            .insert(
+                                successor_item(item, syntax_grammar, variable_info),
+                                lookaheads,
+                            );
+                    }
+                }
+            }
+            non_terminal_successors
+                .into_iter()
+                .filter_map(|(symbol, item_set)| {
+                    Some((symbol, *state_ids_by_item_set.get(&item_set)?))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The move that the LR automaton makes from a parse state on a given symbol.
+/// For a non-terminal, `Shift` holds the state that the goto leads to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition<'a> {
+    Shift(ParseStateId),
+    Reduce(ParseItem<'a>),
+    Accept,
+}
+
+/// Find the transition from one parse state on one symbol, without building the
+/// rest of the table. The states must be the item set cores returned by
+/// [`build_parse_table`] or [`states_iter`]. Conflicts are not resolved: a
+/// shift is preferred over a reduction, and of several reductions, the first
+/// item in the state's closure is chosen. Extras are not considered.
+pub fn transition<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &'a SyntaxGrammar,
+    variable_info: &'a [VariableInfo],
+    states: &[ParseStateInfo<'a>],
+    state_id: ParseStateId,
+    symbol: Symbol,
+) -> Option<Transition<'a>> {
+    let closure = item_set_builder.transitive_closure(&states[state_id].1);
+    let mut successor = ParseItemSet::default();
+    let mut reduction = None;
+    for (item, lookaheads) in &closure.entries {
+        if item.symbol() == Some(symbol) {
+            successor.insert(
+                successor_item(item, syntax_grammar, variable_info),
+                lookaheads,
+            );
+        } else if item.is_done() && !symbol.is_non_terminal() && lookaheads.contains(&symbol) {
+            reduction.get_or_insert(*item);
+        }
     }
-    .build()?;
 
-    Ok((table, following_tokens, item_sets))
+    if !successor.entries.is_empty() {
+        states
+            .iter()
+            .position(|(_, item_set)| *item_set == successor)
+            .map(Transition::Shift)
+    } else {
+        reduction.map(|item| {
+            if item.is_augmented() {
+                Transition::Accept
+            } else {
+                Transition::Reduce(item)
+            }
+        })
+    }
+}
+
+/// Render the whole automaton as text that can be compared across versions of a
+/// grammar: each state's items, its actions, and its gotos. The parse table and
+/// states must be the ones returned by [`build_parse_table`], before the table
+/// is minimized. Within each state, every section is sorted, so the snapshot
+/// only changes when the automaton does.
+pub fn automaton_snapshot<'a>(
+    parse_table: &ParseTable,
+    states: &[ParseStateInfo<'a>],
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+    variable_info: &'a [VariableInfo],
+) -> String {
+    let goto_table = build_goto_table(item_set_builder, syntax_grammar, variable_info, states);
+    let mut result = String::new();
+    for (state_id, (_, item_set)) in states.iter().enumerate() {
+        writeln!(&mut result, "state {state_id}").unwrap();
+
+        writeln!(&mut result, "items:").unwrap();
+        let items = ParseItemSetDisplay(item_set, syntax_grammar, lexical_grammar).to_string();
+        let mut items = items.lines().collect::<Vec<_>>();
+        items.sort_unstable();
+        for item in items {
+            writeln!(&mut result, "  {item}").unwrap();
+        }
+
+        writeln!(&mut result, "actions:").unwrap();
+        let mut actions = parse_table.states[state_id]
+            .terminal_entries
+            .iter()
+            .map(|(symbol, entry)| {
+                let mut line = symbol_name(symbol, syntax_grammar, lexical_grammar);
+                for action in &entry.actions {
+                    write!(
+                        &mut line,
+                        " {}",
+                        ParseActionDisplay(action, syntax_grammar, lexical_grammar)
+                    )
+                    .unwrap();
+                }
+                line
+            })
+            .collect::<Vec<_>>();
+        actions.sort_unstable();
+        for action in actions {
+            writeln!(&mut result, "  {action}").unwrap();
+        }
+
+        writeln!(&mut result, "gotos:").unwrap();
+        let mut gotos = goto_table[state_id]
+            .iter()
+            .map(|(symbol, state_id)| {
+                format!(
+                    "{} {state_id}",
+                    symbol_name(symbol, syntax_grammar, lexical_grammar)
+                )
+            })
+            .collect::<Vec<_>>();
+        gotos.sort_unstable();
+        for goto in gotos {
+            writeln!(&mut result, "  {goto}").unwrap();
+        }
+        writeln!(&mut result).unwrap();
+    }
+    result
+}
+
+/// The version of the schema of [`AutomatonJson`]. This is incremented whenever
+/// the schema changes in a way that could break existing readers.
+pub const AUTOMATON_JSON_VERSION: u32 = 1;
+
+/// The LR automaton of a grammar, in the form that [`export_automaton_json`]
+/// serializes it. Symbols are identified by their names, as in conflict
+/// messages, so that the automaton can be compared with those generated by
+/// other tools.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutomatonJson {
+    pub version: u32,
+    pub states: Vec<AutomatonStateJson>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutomatonStateJson {
+    pub id: ParseStateId,
+    /// The state's core items, without its transitive closure.
+    pub items: Vec<AutomatonItemJson>,
+    pub actions: Vec<AutomatonActionsJson>,
+    pub gotos: Vec<AutomatonGotoJson>,
+    /// The lookaheads for which the state has more than one action, because
+    /// of a conflict that the grammar declared.
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutomatonItemJson {
+    /// The item's variable, or `None` for the augmented start item.
+    pub variable: Option<String>,
+    pub production: Vec<String>,
+    pub step_index: usize,
+    pub lookaheads: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutomatonActionsJson {
+    pub lookahead: String,
+    pub actions: Vec<AutomatonActionJson>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomatonActionJson {
+    Accept,
+    Shift { state: ParseStateId },
+    ShiftExtra,
+    Recover,
+    Reduce { symbol: String, child_count: usize },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutomatonGotoJson {
+    pub symbol: String,
+    pub state: ParseStateId,
+}
+
+/// Export the whole automaton as JSON, following the schema of [`AutomatonJson`]:
+/// each state's items, actions, gotos, and declared conflicts. As with
+/// [`automaton_snapshot`], the parse table and states must be the ones returned
+/// by [`build_parse_table`], before the table is minimized.
+pub fn export_automaton_json<'a>(
+    parse_table: &ParseTable,
+    states: &[ParseStateInfo<'a>],
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+    variable_info: &'a [VariableInfo],
+) -> serde_json::Value {
+    let name = |symbol: &Symbol| symbol_name(symbol, syntax_grammar, lexical_grammar);
+    let goto_table = build_goto_table(item_set_builder, syntax_grammar, variable_info, states);
+    let states = states
+        .iter()
+        .zip(goto_table)
+        .enumerate()
+        .map(|(id, ((_, item_set), gotos))| {
+            let parse_state = &parse_table.states[id];
+            let items = item_set
+                .entries
+                .iter()
+                .map(|(item, lookaheads)| AutomatonItemJson {
+                    variable: (!item.is_augmented()).then(|| {
+                        syntax_grammar.variables[item.variable_index as usize]
+                            .name
+                            .clone()
+                    }),
+                    production: item
+                        .production
+                        .steps
+                        .iter()
+                        .map(|s| name(&s.symbol))
+                        .collect(),
+                    step_index: item.step_index as usize,
+                    lookaheads: lookaheads.iter().map(|s| name(&s)).collect(),
+                })
+                .collect();
+            let actions = parse_state
+                .terminal_entries
+                .iter()
+                .map(|(symbol, entry)| AutomatonActionsJson {
+                    lookahead: name(symbol),
+                    actions: entry
+                        .actions
+                        .iter()
+                        .map(|action| match action {
+                            ParseAction::Accept => AutomatonActionJson::Accept,
+                            ParseAction::Shift { state, .. } => {
+                                AutomatonActionJson::Shift { state: *state }
+                            }
+                            ParseAction::ShiftExtra => AutomatonActionJson::ShiftExtra,
+                            ParseAction::Recover => AutomatonActionJson::Recover,
+                            ParseAction::Reduce {
+                                symbol,
+                                child_count,
+                                ..
+                            } => AutomatonActionJson::Reduce {
+                                symbol: name(symbol),
+                                child_count: *child_count,
+                            },
+                        })
+                        .collect(),
+                })
+                .collect();
+            let mut gotos = gotos.into_iter().collect::<Vec<_>>();
+            gotos.sort_unstable();
+            let conflicts = parse_state
+                .terminal_entries
+                .iter()
+                .filter(|(_, entry)| entry.actions.len() > 1)
+                .map(|(symbol, _)| name(symbol))
+                .collect();
+            AutomatonStateJson {
+                id,
+                items,
+                actions,
+                gotos: gotos
+                    .into_iter()
+                    .map(|(symbol, state)| AutomatonGotoJson {
+                        symbol: name(&symbol),
+                        state,
+                    })
+                    .collect(),
+                conflicts,
+            }
+        })
+        .collect();
+    serde_json::to_value(AutomatonJson {
+        version: AUTOMATON_JSON_VERSION,
+        states,
+    })
+    .unwrap()
+}
+
+/// Find the parse states in which some production of the given variable is
+/// complete, and can therefore be reduced. The states must be the item set
+/// cores returned by [`build_parse_table`] or [`states_iter`]. Inlined
+/// variables are never reduced, because their productions are expanded within
+/// the productions that use them.
+pub fn states_reducing<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    states: &[ParseStateInfo<'a>],
+    variable_index: usize,
+) -> Vec<ParseStateId> {
+    states
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, core))| {
+            item_set_builder
+                .transitive_closure(core)
+                .entries
+                .iter()
+                .any(|(item, _)| {
+                    item.is_done()
+                        && !item.is_augmented()
+                        && item.variable_index as usize == variable_index
+                })
+        })
+        .map(|(state_id, _)| state_id)
+        .collect()
+}
+
+/// Find the parse states in which a subtree of the given variable can begin:
+/// those whose transitive closure contains an item at the start of one of the
+/// variable's productions. These are the states in which parsing can resume at
+/// the boundary of such a subtree. The states must be the item set cores returned
+/// by [`build_parse_table`] or [`states_iter`].
+pub fn rule_entry_states<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    states: &[ParseStateInfo<'a>],
+    symbol: Symbol,
+) -> Vec<ParseStateId> {
+    states
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, core))| {
+            item_set_builder
+                .transitive_closure(core)
+                .entries
+                .iter()
+                .any(|(item, _)| {
+                    item.step_index == 0
+                        && !item.is_augmented()
+                        && item.variable_index as usize == symbol.index
+                })
+        })
+        .map(|(state_id, _)| state_id)
+        .collect()
+}
+
+/// Find the parse states that the parser can reach after recovering from an
+/// error. In the error state, the runtime either shifts the lookahead or, for a
+/// token with a recover action, returns to an earlier state in which the token is
+/// valid and shifts it there. So the entry points are the states that the error
+/// state shifts to, along with every state that the recovered tokens can be
+/// shifted into. The result contains these states and every state reachable
+/// from them by a shift or a goto, but not the error state itself.
+///
+/// The table must be one returned by [`build_tables`](super::build_tables),
+/// since the error state's actions are only added there. Without error recovery,
+/// no state is reachable.
+pub fn states_reachable_after_error(parse_table: &ParseTable) -> HashSet<ParseStateId> {
+    let Some(error_state) = parse_table.states.first() else {
+        return HashSet::new();
+    };
+
+    let mut stack = error_state.referenced_states().collect::<Vec<_>>();
+    let recovered_tokens = error_state
+        .terminal_entries
+        .iter()
+        .filter(|(_, entry)| entry.actions.contains(&ParseAction::Recover))
+        .map(|(symbol, _)| *symbol)
+        .collect::<HashSet<_>>();
+    for state in &parse_table.states[1..] {
+        for (symbol, entry) in &state.terminal_entries {
+            if recovered_tokens.contains(symbol) {
+                stack.extend(entry.actions.iter().filter_map(|action| match action {
+                    ParseAction::Shift { state, .. } => Some(*state),
+                    _ => None,
+                }));
+            }
+        }
+    }
+
+    let mut result = HashSet::new();
+    while let Some(state_id) = stack.pop() {
+        if state_id != 0 && result.insert(state_id) {
+            stack.extend(parse_table.states[state_id].referenced_states());
+        }
+    }
+    result
+}
+
+/// Find the reductions that can occur at the end of the input: for each parse
+/// state, the complete items whose lookaheads include the end of input. States
+/// with no such items are left out, and if no state reduces the start rule on
+/// the end of input, the grammar can never accept. The states must be the item
+/// set cores returned by [`build_parse_table`] or [`states_iter`]. The augmented
+/// start item is left out, because completing it accepts the input instead.
+pub fn eof_reductions<'a>(
+    item_set_builder: &mut ParseItemSetBuilder<'a>,
+    states: &[ParseStateInfo<'a>],
+) -> HashMap<ParseStateId, Vec<ParseItem<'a>>> {
+    let mut result = HashMap::new();
+    for (state_id, (_, core)) in states.iter().enumerate() {
+        let items = item_set_builder
+            .transitive_closure(core)
+            .entries
+            .iter()
+            .filter(|(item, lookaheads)| {
+                item.is_done() && !item.is_augmented() && lookaheads.contains(&Symbol::end())
+            })
+            .map(|(item, _)| *item)
+            .collect::<Vec<_>>();
+        if !items.is_empty() {
+            result.insert(state_id, items);
+        }
+    }
+    result
+}
+
+/// Find the items whose lookahead set contains every terminal in the grammar,
+/// which often indicates that a rule is more general than it needs to be. Each
+/// item is reported along with the id of its parse state. The states must be
+/// the item set cores returned by [`build_parse_table`] or [`states_iter`], so
+/// the items that are added by their transitive closures are not considered.
+pub fn universal_lookahead_items<'a>(
+    states: &[ParseStateInfo<'a>],
+    lexical_grammar: &LexicalGrammar,
+) -> Vec<(ParseStateId, ParseItem<'a>)> {
+    let mut result = Vec::new();
+    if lexical_grammar.variables.is_empty() {
+        return result;
+    }
+    for (state_id, (_, item_set)) in states.iter().enumerate() {
+        for (item, lookaheads) in &item_set.entries {
+            if (0..lexical_grammar.variables.len())
+                .all(|i| lookaheads.contains(&Symbol::terminal(i)))
+            {
+                result.push((state_id, *item));
+            }
+        }
+    }
+    result
+}
+
+/// Find the `n` parse states with the most items, as pairs of state id and item
+/// count, largest first. The states must be the item set cores returned by
+/// [`build_parse_table`] or [`states_iter`], so the counts don't include the
+/// items that are added by their transitive closures. Ties are broken by state
+/// id.
+pub fn largest_states(states: &[ParseStateInfo], n: usize) -> Vec<(usize, usize)> {
+    let mut result = states
+        .iter()
+        .enumerate()
+        .map(|(state_id, (_, item_set))| (state_id, item_set.entries.len()))
+        .collect::<Vec<_>>();
+    result.sort_unstable_by_key(|(state_id, item_count)| (cmp::Reverse(*item_count), *state_id));
+    result.truncate(n);
+    result
+}
+
+/// Find the groups of symbols that need to be added to the grammar's declared
+/// conflicts for the parse table to be built, sorted and without duplicates.
+/// Each group is one that an unresolved conflict would be declared with, and
+/// the table is rebuilt until no unresolved conflicts remain, since declaring
+/// a conflict can expose others. The grammar's declared conflicts are left as
+/// they were. Errors other than unresolved conflicts are returned as is.
+pub fn minimal_conflict_declarations(
+    syntax_grammar: &mut SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    inlines: &InlinedProductionMap,
+    variable_info: &[VariableInfo],
+) -> Result<Vec<Vec<Symbol>>> {
+    let declared_conflict_count = syntax_grammar.expected_conflicts.len();
+    let result = loop {
+        let error = match build_parse_table(syntax_grammar, lexical_grammar, inlines, variable_info)
+        {
+            Ok(_) => {
+                break Ok(syntax_grammar.expected_conflicts[declared_conflict_count..].to_vec())
+            }
+            Err(error) => error,
+        };
+        let Some(conflict_error) = error.downcast_ref::<ConflictError>() else {
+            break Err(error);
+        };
+        let mut new_conflicts = conflict_error
+            .conflicts
+            .iter()
+            .map(|conflict| conflict.symbols.clone())
+            .filter(|symbols| !syntax_grammar.expected_conflicts.contains(symbols))
+            .collect::<Vec<_>>();
+        if new_conflicts.is_empty() {
+            break Err(error);
+        }
+        new_conflicts.sort_unstable();
+        new_conflicts.dedup();
+        syntax_grammar.expected_conflicts.extend(new_conflicts);
+    };
+    syntax_grammar
+        .expected_conflicts
+        .truncate(declared_conflict_count);
+    result.map(|mut conflicts| {
+        conflicts.sort_unstable();
+        conflicts
+    })
+}
+
+fn populate_following_tokens(
+    result: &mut [TokenSet],
+    grammar: &SyntaxGrammar,
+    inlines: &InlinedProductionMap,
+    builder: &ParseItemSetBuilder,
+) {
+    let productions = grammar
+        .variables
+        .iter()
+        .flat_map(|v| &v.productions)
+        .chain(&inlines.productions);
+    let all_tokens = (0..result.len())
+        .map(Symbol::terminal)
+        .collect::<TokenSet>();
+    for production in productions {
+        for i in 1..production.steps.len() {
+            let left_tokens = builder.last_set(&production.steps[i - 1].symbol);
+            // A zero-width external token doesn't separate its neighbors, so the
+            // tokens after it can also follow the tokens before it.
+            let (right_tokens, _) = builder.sequence_first_set(&production.steps[i..]);
+            for left_token in left_tokens.iter() {
+                if left_token.is_terminal() {
+                    result[left_token.index].insert_all_terminals(&right_tokens);
+                }
+            }
+        }
+    }
+    for extra in &grammar.extra_symbols {
+        if extra.is_terminal() {
+            for entry in result.iter_mut() {
+                entry.insert(*extra);
+            }
+            result[extra.index] = all_tokens.clone();
+        }
+    }
+}
+
+pub fn build_parse_table<'a>(
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+    inlines: &'a InlinedProductionMap,
+    variable_info: &'a [VariableInfo],
+) -> Result<(ParseTable, Vec<TokenSet>, Vec<ParseStateInfo<'a>>)> {
+    let item_set_builder = ParseItemSetBuilder::new(syntax_grammar, lexical_grammar, inlines);
+    build_parse_table_with_item_set_builder(
+        syntax_grammar,
+        lexical_grammar,
+        inlines,
+        variable_info,
+        item_set_builder,
+    )
+}
+
+/// Build the parse table using an item set builder that has already been created
+/// for the same grammars, such as one with a different token set capacity.
+pub fn build_parse_table_with_item_set_builder<'a>(
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+    inlines: &'a InlinedProductionMap,
+    variable_info: &'a [VariableInfo],
+    item_set_builder: ParseItemSetBuilder<'a>,
+) -> Result<(ParseTable, Vec<TokenSet>, Vec<ParseStateInfo<'a>>)> {
+    let actual_conflicts = syntax_grammar.expected_conflicts.iter().cloned().collect();
+    let mut following_tokens = vec![TokenSet::new(); lexical_grammar.variables.len()];
+    populate_following_tokens(
+        &mut following_tokens,
+        syntax_grammar,
+        inlines,
+        &item_set_builder,
+    );
+
+    let (table, item_sets) = ParseTableBuilder {
+        syntax_grammar,
+        inlines,
+        lexical_grammar,
+        item_set_builder,
+        variable_info,
+        non_terminal_extra_states: Vec::new(),
+        actual_conflicts,
+        unresolved_conflicts: Vec::new(),
+        state_ids_by_item_set: IndexMap::default(),
+        core_ids_by_core: HashMap::new(),
+        parse_state_info_by_id: Vec::new(),
+        parse_state_queue: VecDeque::new(),
+        parse_table: ParseTable {
+            states: Vec::new(),
+            symbols: Vec::new(),
+            external_lex_states: Vec::new(),
+            production_infos: Vec::new(),
+            max_aliased_production_length: 1,
+            resolutions: Vec::new(),
+        },
+    }
+    .build()?;
+
+    Ok((table, following_tokens, item_sets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::prepare_grammar::mark_recovery_rule;
+    use crate::generate::prepare_grammar::prepare_grammar;
+    use crate::generate::rules::Rule;
+
+    #[test]
+    fn test_action_table() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::string("a"),
+            }],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, ..) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let action_table = parse_table.action_table().unwrap();
+        assert_eq!(action_table.len(), parse_table.states.len());
+
+        // The start state shifts the `a` token, and the resulting state reduces
+        // the `program` rule at the end of the input.
+        let ParseAction::Shift { state, .. } = action_table[1][&Symbol::terminal(0)] else {
+            panic!("expected a shift action");
+        };
+        assert_eq!(
+            action_table[state][&Symbol::end()],
+            ParseAction::Reduce {
+                symbol: Symbol::non_terminal(0),
+                child_count: 1,
+                dynamic_precedence: 0,
+                production_id: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_equivalent_action_states() {
+        use crate::generate::tables::equivalent_action_states;
+
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![Rule::named("first"), Rule::named("second")]),
+                },
+                Variable {
+                    name: "first".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("a"), Rule::named("pair")]),
+                },
+                Variable {
+                    name: "second".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("b"), Rule::named("pair")]),
+                },
+                Variable {
+                    name: "pair".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("word"), Rule::named("word")]),
+                },
+                Variable {
+                    name: "word".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, ..) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let action_table = parse_table.action_table().unwrap();
+
+        // After either keyword, the only action is to shift the pair's first
+        // word. The two states differ only in where the pair leads afterward.
+        let state_after = |keyword: &str| {
+            let keyword = Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == keyword)
+                    .unwrap(),
+            );
+            let ParseAction::Shift { state, .. } = action_table[1][&keyword] else {
+                panic!("expected a shift action");
+            };
+            state
+        };
+        let (a_state, b_state) = (state_after("a"), state_after("b"));
+        assert_ne!(a_state, b_state);
+
+        let groups = equivalent_action_states(&action_table);
+        assert_eq!(
+            groups.iter().map(Vec::len).sum::<usize>(),
+            action_table.len()
+        );
+        let group = groups
+            .iter()
+            .find(|group| group.contains(&a_state))
+            .unwrap();
+        assert!(group.contains(&b_state));
+        for group in &groups {
+            assert!(group
+                .iter()
+                .all(|state_id| action_table[*state_id] == action_table[group[0]]));
+        }
+    }
+
+    #[test]
+    fn test_recovery_costs() {
+        use crate::generate::tables::recovery_costs;
+
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::seq(vec![Rule::named("call"), Rule::string(";")]),
+                        Rule::string(";"),
+                    ]),
+                },
+                Variable {
+                    name: "call".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::named("identifier"),
+                        Rule::string("("),
+                        Rule::named("identifier"),
+                        Rule::string(")"),
+                    ]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, ..) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let terminal = |name: &str| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        let shift = |state_id: usize, symbol: Symbol| match parse_table.states[state_id]
+            .terminal_entries[&symbol]
+            .actions
+            .last()
+        {
+            Some(ParseAction::Shift { state, .. }) => *state,
+            _ => panic!("expected a shift action"),
+        };
+
+        // In `f(a;`, the closing parenthesis is missing. Inserting it is cheaper
+        // than discarding the partial call and resuming at the next statement.
+        let mut state_id = 1;
+        for name in ["identifier", "(", "identifier"] {
+            state_id = shift(state_id, terminal(name));
+        }
+        let semicolon = terminal(";");
+        assert!(!parse_table.states[state_id]
+            .terminal_entries
+            .contains_key(&semicolon));
+
+        let costs = recovery_costs(&parse_table.states, &syntax_grammar);
+        assert_eq!(costs[&(state_id, semicolon)], 610);
+
+        // After only `f`, there is no single token that can be inserted, so the
+        // cheapest recovery is to skip the identifier and start a new statement.
+        let state_id = shift(1, terminal("identifier"));
+        assert_eq!(costs[&(state_id, semicolon)], 600);
+    }
+
+    #[test]
+    fn test_extra_tokens_are_valid_between_statements() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("x"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "comment".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("#.*", ""),
+                },
+            ],
+            extra_symbols: vec![Rule::named("comment")],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, ..) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let terminal = |name: &str| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(syntax_grammar.extra_symbols, [terminal("comment")]);
+
+        // In every state where a statement can begin, including the states that
+        // follow a complete statement, a comment can be shifted as an extra.
+        let statement_start_states = parse_table
+            .states
+            .iter()
+            .filter(|state| state.terminal_entries.contains_key(&terminal("x")))
+            .collect::<Vec<_>>();
+        assert!(statement_start_states.len() > 1);
+        for state in statement_start_states {
+            assert_eq!(
+                state.terminal_entries[&terminal("comment")].actions,
+                [ParseAction::ShiftExtra]
+            );
+        }
+    }
+
+    #[test]
+    fn test_following_tokens_look_past_zero_width_externals() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "block".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::seq(vec![
+                    Rule::string(":"),
+                    Rule::named("_indent"),
+                    Rule::string("x"),
+                ]),
+            }],
+            external_tokens: vec![Rule::named("_indent")],
+            zero_width_externals: vec!["_indent".to_string()],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, following_tokens, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let terminal = |name: &str| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        assert!(syntax_grammar.external_tokens[0].zero_width);
+        assert!(following_tokens[terminal(":").index].contains(&terminal("x")));
+    }
+
+    #[test]
+    fn test_states_iter_matches_eager_construction() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("_statement")),
+                },
+                Variable {
+                    name: "_statement".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::seq(vec![
+                        Rule::field("value".to_string(), Rule::named("expression")),
+                        Rule::string(";"),
+                    ]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::prec_left(
+                            Precedence::Integer(1),
+                            Rule::seq(vec![
+                                Rule::named("expression"),
+                                Rule::string("+"),
+                                Rule::named("expression"),
+                            ]),
+                        ),
+                        Rule::string("x"),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, _, parse_state_info) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let item_sets =
+            states_iter(&mut item_set_builder, &syntax_grammar, &variable_info).collect::<Vec<_>>();
+
+        // The eager builder's first state is the error state.
+        let eager_item_sets = parse_state_info[1..]
+            .iter()
+            .map(|(_, item_set)| item_set.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(item_sets, eager_item_sets);
+    }
+
+    #[test]
+    fn test_resolution_log_records_associativity() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "expression".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![
+                    Rule::prec_left(
+                        Precedence::Integer(1),
+                        Rule::seq(vec![
+                            Rule::named("expression"),
+                            Rule::string("-"),
+                            Rule::named("expression"),
+                        ]),
+                    ),
+                    Rule::string("x"),
+                ]),
+            }],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let minus = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "-")
+                .unwrap(),
+        );
+        assert_eq!(
+            parse_table
+                .resolution_log()
+                .iter()
+                .map(|event| (event.lookahead, event.resolution, event.winner))
+                .collect::<Vec<_>>(),
+            [(
+                minus,
+                ConflictResolution::Associativity,
+                ResolutionWinner::Reduce
+            )]
+        );
+    }
+
+    #[test]
+    fn test_conflict_override_takes_priority_over_associativity() {
+        let binary = |operator: &'static str| {
+            Rule::prec_left(
+                Precedence::Integer(1),
+                Rule::seq(vec![
+                    Rule::named("expression"),
+                    Rule::string(operator),
+                    Rule::named("expression"),
+                ]),
+            )
+        };
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "expression".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![binary("+"), binary("*"), Rule::string("x")]),
+            }],
+            ..Default::default()
+        };
+        let (mut syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let token = |name: &str| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        let resolutions = |parse_table: &ParseTable| {
+            parse_table
+                .resolution_log()
+                .iter()
+                .map(|event| (event.lookahead, event.resolution, event.winner))
+                .collect::<Vec<_>>()
+        };
+
+        // By default, `x + x * x` is parsed as `(x + x) * x`, because the
+        // operators are left-associative with the same precedence.
+        let (parse_table, _, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let events = resolutions(&parse_table);
+        assert!(events.contains(&(
+            token("*"),
+            ConflictResolution::Associativity,
+            ResolutionWinner::Reduce
+        )));
+
+        // Prefer shifting the `*` over reducing the addition.
+        let addition = ProductionRef {
+            variable_index: 0,
+            production_index: 0,
+        };
+        let multiplication = ProductionRef {
+            variable_index: 0,
+            production_index: 1,
+        };
+        syntax_grammar.conflict_overrides =
+            vec![(multiplication, addition, Resolution::PreferFirst)];
+        let (parse_table, _, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let events = resolutions(&parse_table);
+        assert!(events.contains(&(
+            token("*"),
+            ConflictResolution::Override,
+            ResolutionWinner::Shift
+        )));
+        assert!(!events.contains(&(
+            token("*"),
+            ConflictResolution::Associativity,
+            ResolutionWinner::Reduce
+        )));
+    }
+
+    #[test]
+    fn test_conflict_override_between_inlined_rules() {
+        let binary = |operator: &'static str| Variable {
+            name: format!("_{operator}_expression"),
+            kind: VariableType::Hidden,
+            rule: Rule::prec_left(
+                Precedence::Integer(1),
+                Rule::seq(vec![
+                    Rule::named("expression"),
+                    Rule::string(operator),
+                    Rule::named("expression"),
+                ]),
+            ),
+        };
+        let grammar = |conflict_overrides: Vec<(&str, &str)>| InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::named("_+_expression"),
+                        Rule::named("_*_expression"),
+                        Rule::string("x"),
+                    ]),
+                },
+                binary("+"),
+                binary("*"),
+            ],
+            variables_to_inline: vec!["_+_expression".to_string(), "_*_expression".to_string()],
+            conflict_overrides: conflict_overrides
+                .into_iter()
+                .map(|(preferred, other)| (preferred.to_string(), other.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        let events = |grammar: &InputGrammar| {
+            let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+                prepare_grammar(grammar).unwrap();
+            let variable_info =
+                get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+            let (parse_table, _, _) =
+                build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info)
+                    .unwrap();
+            let star = Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == "*")
+                    .unwrap(),
+            );
+            parse_table
+                .resolution_log()
+                .iter()
+                .filter(|event| event.lookahead == star)
+                .map(|event| (event.resolution, event.winner))
+                .collect::<Vec<_>>()
+        };
+
+        // The inlined productions are resolved by associativity, unless the
+        // override applies to the rules that they were inlined from.
+        let default_events = events(&grammar(Vec::new()));
+        assert!(
+            default_events.contains(&(ConflictResolution::Associativity, ResolutionWinner::Reduce))
+        );
+        assert!(!default_events.contains(&(ConflictResolution::Override, ResolutionWinner::Shift)));
+
+        let overridden_events = events(&grammar(vec![("_*_expression", "_+_expression")]));
+        assert!(
+            overridden_events.contains(&(ConflictResolution::Override, ResolutionWinner::Shift))
+        );
+        assert!(!overridden_events
+            .contains(&(ConflictResolution::Associativity, ResolutionWinner::Reduce)));
+    }
+
+    #[test]
+    fn test_build_goto_table() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("-"), Rule::string("x")]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let goto_table = build_goto_table(
+            &mut item_set_builder,
+            &syntax_grammar,
+            &variable_info,
+            &states,
+        );
+
+        // From the start state, reducing an expression leads to the state that
+        // expects the semicolon.
+        let expression = Symbol::non_terminal(1);
+        let successor = goto_table[1][&expression];
+        let (item, _) = states[successor].1.entries.iter().next().unwrap();
+        assert_eq!(item.variable_index, 0);
+        assert_eq!(item.step_index, 1);
+
+        // The goto table matches the non-terminal entries of the parse table.
+        assert_eq!(goto_table.len(), parse_table.states.len());
+        for (gotos, state) in goto_table.iter().zip(&parse_table.states) {
+            assert_eq!(
+                *gotos,
+                state
+                    .nonterminal_entries
+                    .iter()
+                    .map(|(symbol, action)| match action {
+                        GotoAction::Goto(state_id) => (*symbol, *state_id),
+                        GotoAction::ShiftExtra => unreachable!(),
+                    })
+                    .collect::<HashMap<_, _>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_transition() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("-"), Rule::string("x")]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let token = |name: &str| {
+            let index = lexical_grammar
+                .variables
+                .iter()
+                .position(|variable| variable.name == name)
+                .unwrap();
+            Symbol::terminal(index)
+        };
+
+        // Step through the input `- x ;`, keeping a stack of states.
+        let mut stack = vec![1];
+        let mut steps = Vec::new();
+        let mut input = vec![token("-"), token("x"), token(";"), Symbol::end()].into_iter();
+        let mut lookahead = input.next().unwrap();
+        loop {
+            let state_id = *stack.last().unwrap();
+            match transition(
+                &mut item_set_builder,
+                &syntax_grammar,
+                &variable_info,
+                &states,
+                state_id,
+                lookahead,
+            ) {
+                Some(Transition::Shift(next_state_id)) => {
+                    steps.push("shift".to_string());
+                    stack.push(next_state_id);
+                    lookahead = input.next().unwrap();
+                }
+                Some(Transition::Reduce(item)) => {
+                    let variable = &syntax_grammar.variables[item.variable_index as usize];
+                    steps.push(format!("reduce {}", variable.name));
+                    stack.truncate(stack.len() - item.production.steps.len());
+                    let Some(Transition::Shift(next_state_id)) = transition(
+                        &mut item_set_builder,
+                        &syntax_grammar,
+                        &variable_info,
+                        &states,
+                        *stack.last().unwrap(),
+                        Symbol::non_terminal(item.variable_index as usize),
+                    ) else {
+                        panic!("expected a goto after reducing {}", variable.name);
+                    };
+                    stack.push(next_state_id);
+                }
+                Some(Transition::Accept) => break,
+                None => panic!("unexpected {lookahead:?} in state {state_id}"),
+            }
+        }
+        assert_eq!(
+            steps,
+            [
+                "shift",
+                "shift",
+                "reduce expression",
+                "shift",
+                "reduce program"
+            ]
+        );
+
+        // The start state has no transition on the semicolon.
+        assert_eq!(
+            transition(
+                &mut item_set_builder,
+                &syntax_grammar,
+                &variable_info,
+                &states,
+                1,
+                token(";"),
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_automaton_snapshot() {
+        let grammar = |expression_rule: Rule| InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: expression_rule,
+                },
+            ],
+            ..Default::default()
+        };
+        let expression_rule = || {
+            Rule::choice(vec![
+                Rule::seq(vec![Rule::string("-"), Rule::named("expression")]),
+                Rule::string("x"),
+            ])
+        };
+        let snapshot = |grammar: &InputGrammar| {
+            let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+                prepare_grammar(grammar).unwrap();
+            let variable_info =
+                get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+            let (parse_table, _, states) =
+                build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info)
+                    .unwrap();
+            let mut item_set_builder =
+                ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+            automaton_snapshot(
+                &parse_table,
+                &states,
+                &mut item_set_builder,
+                &syntax_grammar,
+                &lexical_grammar,
+                &variable_info,
+            )
+        };
+
+        let first = snapshot(&grammar(expression_rule()));
+        assert_eq!(first, snapshot(&grammar(expression_rule())));
+        assert!(first.contains("state 1\nitems:\n"));
+        assert!(first.contains("gotos:\n  expression "));
+
+        // Changing a rule changes the snapshot.
+        let changed_rule = Rule::seq(vec![Rule::string("x"), Rule::string("x")]);
+        assert_ne!(first, snapshot(&grammar(changed_rule)));
+    }
+
+    #[test]
+    fn test_export_automaton_json() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::seq(vec![Rule::string("-"), Rule::named("expression")]),
+                        Rule::string("x"),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let json = export_automaton_json(
+            &parse_table,
+            &states,
+            &mut item_set_builder,
+            &syntax_grammar,
+            &lexical_grammar,
+            &variable_info,
+        );
+
+        let automaton = serde_json::from_value::<AutomatonJson>(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&automaton).unwrap(), json);
+        assert_eq!(automaton.version, AUTOMATON_JSON_VERSION);
+        assert_eq!(automaton.states.len(), parse_table.states.len());
+
+        let start_state = &automaton.states[1];
+        assert_eq!(
+            start_state.items,
+            [AutomatonItemJson {
+                variable: None,
+                production: vec!["program".to_string()],
+                step_index: 0,
+                lookaheads: vec!["EOF".to_string()],
+            }]
+        );
+        assert!(start_state
+            .gotos
+            .iter()
+            .any(|goto| goto.symbol == "expression"));
+        assert!(start_state
+            .actions
+            .iter()
+            .any(|entry| entry.lookahead == "'x'"
+                && matches!(entry.actions[..], [AutomatonActionJson::Shift { .. }])));
+        assert!(automaton
+            .states
+            .iter()
+            .all(|state| state.conflicts.is_empty()));
+
+        let reduce = json["states"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|state| state["actions"].as_array().unwrap())
+            .flat_map(|entry| entry["actions"].as_array().unwrap())
+            .find(|action| action["type"] == "reduce")
+            .unwrap();
+        assert!(reduce["symbol"].is_string());
+        assert!(reduce["child_count"].is_u64());
+    }
+
+    #[test]
+    fn test_universal_lookahead_items() {
+        let grammar = |program_rule: Rule| InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: program_rule,
+                },
+                Variable {
+                    name: "word".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let universal_lookahead_variables = |grammar: &InputGrammar| {
+            let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+                prepare_grammar(grammar).unwrap();
+            let variable_info =
+                get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+            let (_, _, states) =
+                build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info)
+                    .unwrap();
+            universal_lookahead_items(&states, &lexical_grammar)
+                .into_iter()
+                .map(|(_, item)| {
+                    syntax_grammar.variables[item.variable_index as usize]
+                        .name
+                        .clone()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // A catch-all repetition of any token can be followed by any token.
+        let catch_all = Rule::repeat(Rule::choice(vec![
+            Rule::named("word"),
+            Rule::named("number"),
+        ]));
+        let names = universal_lookahead_variables(&grammar(catch_all));
+        assert!(!names.is_empty());
+        assert!(names.iter().all(|name| name == "program_repeat1"));
+
+        // When the repetition is delimited, the opening delimiter never follows
+        // any of its items.
+        let delimited = Rule::seq(vec![
+            Rule::string("("),
+            Rule::repeat(Rule::choice(vec![
+                Rule::named("word"),
+                Rule::named("number"),
+            ])),
+            Rule::string(")"),
+        ]);
+        assert!(universal_lookahead_variables(&grammar(delimited)).is_empty());
+    }
+
+    #[test]
+    fn test_largest_states() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::seq(vec![Rule::string("-"), Rule::string("x")]),
+                        Rule::seq(vec![Rule::string("-"), Rule::string("y")]),
+                        Rule::seq(vec![Rule::string("-"), Rule::string("z")]),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        // After the `-`, the three expression productions are all in progress.
+        let largest = largest_states(&states, 1);
+        assert_eq!(largest.len(), 1);
+        let (state_id, item_count) = largest[0];
+        assert_eq!(item_count, 3);
+        assert!(states[state_id]
+            .1
+            .entries
+            .iter()
+            .all(|(item, _)| item.variable_index == 1 && item.step_index == 1));
+        assert_eq!(
+            largest_states(&states, states.len() + 1).len(),
+            states.len()
+        );
+    }
+
+    #[test]
+    fn test_states_reducing() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                        Rule::seq(vec![
+                            Rule::string("("),
+                            Rule::named("expression"),
+                            Rule::string(")"),
+                        ]),
+                    ]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("-"), Rule::string("x")]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let reducing_states = states_reducing(&mut item_set_builder, &states, 1);
+
+        // The expression is reduced in a separate state in each context, because
+        // the items have different lookaheads.
+        assert_eq!(reducing_states.len(), 2);
+        for state_id in &reducing_states {
+            let (item, _) = states[*state_id].1.entries.iter().next().unwrap();
+            assert_eq!(item.variable_index, 1);
+            assert!(item.is_done());
+        }
+
+        // These are the states with actions that reduce the expression.
+        let expression = Symbol::non_terminal(1);
+        let expected = parse_table
+            .states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| {
+                state.terminal_entries.values().any(|entry| {
+                    entry.actions.iter().any(|action| {
+                        matches!(action, ParseAction::Reduce { symbol, .. } if *symbol == expression)
+                    })
+                })
+            })
+            .map(|(state_id, _)| state_id)
+            .collect::<Vec<_>>();
+        assert_eq!(reducing_states, expected);
+    }
+
+    #[test]
+    fn test_eof_reductions() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("-"), Rule::string("x")]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let reductions = eof_reductions(&mut item_set_builder, &states);
+
+        // Only the program is reduced at the end of the input, in the state after
+        // the semicolon. The expression is always followed by the semicolon.
+        assert_eq!(reductions.len(), 1);
+        let (state_id, items) = reductions.iter().next().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].variable_index, 0);
+        assert!(items[0].is_done());
+        assert!(matches!(
+            parse_table.states[*state_id].terminal_entries[&Symbol::end()].actions[..],
+            [ParseAction::Reduce { symbol, child_count: 2, .. }] if symbol == Symbol::non_terminal(0)
+        ));
+
+        // Reducing the program leads to the state that accepts the input, which
+        // is not itself a reduction.
+        let GotoAction::Goto(accept_state_id) =
+            parse_table.states[1].nonterminal_entries[&Symbol::non_terminal(0)]
+        else {
+            panic!("expected a goto action");
+        };
+        assert_eq!(
+            parse_table.states[accept_state_id].terminal_entries[&Symbol::end()].actions,
+            [ParseAction::Accept]
+        );
+        assert!(!reductions.contains_key(&accept_state_id));
+    }
+
+    #[test]
+    fn test_rule_entry_states() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::choice(vec![Rule::string("a"), Rule::named("block")]),
+                        Rule::string(";"),
+                    ]),
+                },
+                Variable {
+                    name: "block".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("{"),
+                        Rule::repeat(Rule::named("statement")),
+                        Rule::string("}"),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let statement = Symbol::non_terminal(
+            syntax_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "statement")
+                .unwrap(),
+        );
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let entry_states = rule_entry_states(&mut item_set_builder, &states, statement);
+
+        // A statement can begin at the start of the program, inside of a block,
+        // and after another statement. Those are exactly the states that have a
+        // goto on the statement.
+        assert!(entry_states.contains(&1));
+        let Some(ParseAction::Shift {
+            state: block_state_id,
+            ..
+        }) = parse_table.states[1].terminal_entries[&Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "{")
+                .unwrap(),
+        )]
+            .actions
+            .last()
+        else {
+            panic!("expected a shift action");
+        };
+        assert!(entry_states.contains(block_state_id));
+        assert_eq!(
+            entry_states,
+            (0..parse_table.states.len())
+                .filter(|state_id| parse_table.states[*state_id]
+                    .nonterminal_entries
+                    .contains_key(&statement))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_states_reachable_after_error() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("x"), Rule::string(";")]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let build = |recovery| {
+            crate::generate::build_tables::build_tables(
+                &syntax_grammar,
+                &lexical_grammar,
+                &default_aliases,
+                &variable_info,
+                &inlines,
+                None,
+                recovery,
+                false,
+            )
+            .unwrap()
+            .0
+        };
+
+        // After an error, the parser can resume at any statement, and parse the
+        // rest of it. But nothing leads back to the start state.
+        let parse_table = build(true);
+        let reachable = states_reachable_after_error(&parse_table);
+        let terminal = |name| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        let shift = |state_id: ParseStateId, symbol| match parse_table.states[state_id]
+            .terminal_entries[&symbol]
+            .actions
+            .last()
+        {
+            Some(ParseAction::Shift { state, .. }) => *state,
+            action => panic!("unexpected action {action:?}"),
+        };
+        let after_x = shift(1, terminal("x"));
+        assert!(reachable.contains(&after_x));
+        assert!(reachable.contains(&shift(after_x, terminal(";"))));
+        assert!(!reachable.contains(&0));
+        assert!(!reachable.contains(&1));
+        assert!(reachable.len() < parse_table.states.len() - 1);
+
+        // Without error recovery, the error state has no actions to resume with.
+        assert!(states_reachable_after_error(&build(false)).is_empty());
+    }
+
+    #[test]
+    fn test_inlined_rules_keep_their_precedence() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::named("_product"),
+                        Rule::prec_left(
+                            Precedence::Integer(1),
+                            Rule::seq(vec![
+                                Rule::named("expression"),
+                                Rule::string("+"),
+                                Rule::named("expression"),
+                            ]),
+                        ),
+                        Rule::string("x"),
+                    ]),
+                },
+                Variable {
+                    name: "_product".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::prec_left(
+                        Precedence::Integer(2),
+                        Rule::seq(vec![
+                            Rule::named("expression"),
+                            Rule::string("*"),
+                            Rule::named("expression"),
+                        ]),
+                    ),
+                },
+            ],
+            variables_to_inline: vec!["_product".to_string()],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        // After `x + x`, the inlined product binds more tightly than the sum, and
+        // after `x * x`, it is reduced before a sum.
+        let token = |name| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        let resolutions = parse_table
+            .resolution_log()
+            .iter()
+            .map(|event| (event.lookahead, event.resolution, event.winner))
+            .collect::<Vec<_>>();
+        assert!(resolutions.contains(&(
+            token("*"),
+            ConflictResolution::Precedence,
+            ResolutionWinner::Shift
+        )));
+        assert!(resolutions.contains(&(
+            token("+"),
+            ConflictResolution::Precedence,
+            ResolutionWinner::Reduce
+        )));
+    }
+
+    #[test]
+    fn test_inlined_rules_inherit_the_host_associativity() {
+        // The inlined product has a precedence but no associativity, so it takes
+        // the associativity of the host step that refers to it.
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::prec_left(Precedence::Integer(1), Rule::named("_product")),
+                        Rule::string("x"),
+                    ]),
+                },
+                Variable {
+                    name: "_product".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::prec(
+                        Precedence::Integer(2),
+                        Rule::seq(vec![
+                            Rule::named("expression"),
+                            Rule::string("*"),
+                            Rule::named("expression"),
+                        ]),
+                    ),
+                },
+            ],
+            variables_to_inline: vec!["_product".to_string()],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, _, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        // After `x * x`, the product is reduced before another `*`.
+        let star = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "*")
+                .unwrap(),
+        );
+        assert!(parse_table.resolution_log().iter().any(|event| {
+            event.lookahead == star
+                && event.resolution == ConflictResolution::Associativity
+                && event.winner == ResolutionWinner::Reduce
+        }));
+    }
+
+    #[test]
+    fn test_precedence_shadowed_productions() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![Rule::named("call"), Rule::named("identifier")]),
+                },
+                Variable {
+                    name: "call".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Integer(2), Rule::string("x")),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Integer(1), Rule::string("x")),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let identifier_index = syntax_grammar
+            .variables
+            .iter()
+            .position(|v| v.name == "identifier")
+            .unwrap();
+        assert_eq!(
+            precedence_shadowed_productions(&mut item_set_builder, &syntax_grammar, &states),
+            [(identifier_index, 0)]
+        );
+    }
+
+    #[test]
+    fn test_conflict_dependent_productions() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string("b")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::prec(Precedence::Integer(1), Rule::string("a")),
+                        Rule::seq(vec![Rule::string("a"), Rule::string("b")]),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, _, states) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        // After an `a`, the `b` could either be shifted as part of the longer
+        // expression, or follow the shorter one, so the shorter expression is only
+        // reduced because of its precedence. The other productions are reduced
+        // in states where nothing else can happen.
+        let mut item_set_builder =
+            ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        assert_eq!(
+            conflict_dependent_productions(&mut item_set_builder, &syntax_grammar, &states),
+            [(1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_minimal_conflict_declarations() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::named("expression"),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::named("sum"),
+                        Rule::named("variable"),
+                        Rule::named("type"),
+                    ]),
+                },
+                Variable {
+                    name: "sum".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::named("expression"),
+                        Rule::string("+"),
+                        Rule::named("expression"),
+                    ]),
+                },
+                Variable {
+                    name: "variable".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::named("identifier"),
+                },
+                Variable {
+                    name: "type".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::named("identifier"),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (mut syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let symbol = |name: &str| {
+            Symbol::non_terminal(
+                syntax_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        let sum = symbol("sum");
+        let variable = symbol("variable");
+        let type_ = symbol("type");
+
+        let declarations = minimal_conflict_declarations(
+            &mut syntax_grammar,
+            &lexical_grammar,
+            &inlines,
+            &variable_info,
+        )
+        .unwrap();
+        assert_eq!(declarations, [vec![sum], vec![variable, type_]]);
+        assert!(syntax_grammar.expected_conflicts.is_empty());
+        assert!(
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).is_err()
+        );
+
+        syntax_grammar.expected_conflicts = declarations;
+        assert!(
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_recovery_rules_lose_conflicts() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::named("statement"),
+                        Rule::seq(vec![Rule::named("recovery"), Rule::string(";")]),
+                    ])),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("x"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "recovery".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("x"),
+                },
+            ],
+            ..Default::default()
+        };
+        let (mut syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+
+        // Without marking, the two rules are in an unresolved conflict.
+        assert!(
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).is_err()
+        );
+
+        let recovery = Symbol::non_terminal(
+            syntax_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "recovery")
+                .unwrap(),
+        );
+        assert!(mark_recovery_rule(&mut syntax_grammar, Symbol::terminal(0)).is_err());
+        assert!(mark_recovery_rule(&mut syntax_grammar, Symbol::non_terminal(100)).is_err());
+        mark_recovery_rule(&mut syntax_grammar, recovery).unwrap();
+        let (parse_table, _, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+
+        let semicolon = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == ";")
+                .unwrap(),
+        );
+        assert_eq!(
+            parse_table
+                .resolution_log()
+                .iter()
+                .map(|event| (event.lookahead, event.resolution, event.winner))
+                .collect::<Vec<_>>(),
+            [(
+                semicolon,
+                ConflictResolution::Precedence,
+                ResolutionWinner::Shift
+            )]
+        );
+    }
 }
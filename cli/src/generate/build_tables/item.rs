@@ -1,10 +1,15 @@
-use crate::generate::grammars::{LexicalGrammar, Production, ProductionStep, SyntaxGrammar};
+use crate::generate::grammars::{
+    LexicalGrammar, Production, ProductionStep, SyntaxGrammar, VariableType,
+};
 use crate::generate::rules::{Associativity, Precedence, Symbol, SymbolType, TokenSet};
+use crate::generate::tables::ParseAction;
 use lazy_static::lazy_static;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::u32;
+use std::ops::{Deref, DerefMut};
+use std::{mem, slice, u32};
 
 lazy_static! {
     static ref START_PRODUCTION: Production = Production {
@@ -53,7 +58,16 @@ pub struct ParseItem<'a> {
 /// to a state in the final parse table.
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub struct ParseItemSet<'a> {
-    pub entries: Vec<(ParseItem<'a>, TokenSet)>,
+    pub entries: SmallItemSet<'a>,
+}
+
+/// The sorted entries of a [`ParseItemSet`]. Many parse states consist of a
+/// single item, so a lone entry is stored inline rather than in a separate
+/// allocation. Either way, the entries can be accessed as a slice.
+#[derive(Clone, Debug)]
+pub enum SmallItemSet<'a> {
+    Single((ParseItem<'a>, TokenSet)),
+    Multiple(Vec<(ParseItem<'a>, TokenSet)>),
 }
 
 /// A [`ParseItemSetCore`] is like a [`ParseItemSet`], but without the lookahead
@@ -81,6 +95,14 @@ pub struct ParseItemSetDisplay<'a>(
     pub &'a LexicalGrammar,
 );
 
+/// Renders a [`ParseAction`] for debugging. Reduce actions are annotated with
+/// the name of the rule being reduced and the number of children it consumes.
+pub struct ParseActionDisplay<'a>(
+    pub &'a ParseAction,
+    pub &'a SyntaxGrammar,
+    pub &'a LexicalGrammar,
+);
+
 impl<'a> ParseItem<'a> {
     pub fn start() -> Self {
         ParseItem {
@@ -121,6 +143,17 @@ impl<'a> ParseItem<'a> {
         self.step_index as usize == self.production.steps.len()
     }
 
+    /// The number of symbols that are popped from the stack when this item's
+    /// production is reduced. Items for inlined productions refer to the
+    /// expanded production in the [`InlinedProductionMap`], so this counts the
+    /// symbols that were substituted in place of the inlined variable.
+    ///
+    /// [`InlinedProductionMap`]: crate::generate::grammars::InlinedProductionMap
+    #[must_use]
+    pub fn reduce_arity(&self) -> usize {
+        self.production.steps.len()
+    }
+
     #[must_use]
     pub const fn is_augmented(&self) -> bool {
         self.variable_index == u32::MAX
@@ -146,6 +179,67 @@ impl<'a> ParseItem<'a> {
     }
 }
 
+impl<'a> SmallItemSet<'a> {
+    pub fn insert(&mut self, index: usize, entry: (ParseItem<'a>, TokenSet)) {
+        match self {
+            Self::Multiple(entries) if entries.is_empty() => *self = Self::Single(entry),
+            Self::Multiple(entries) => entries.insert(index, entry),
+            Self::Single(_) => {
+                let Self::Single(existing) = mem::take(self) else {
+                    unreachable!()
+                };
+                let mut entries = Vec::with_capacity(2);
+                entries.push(existing);
+                entries.insert(index, entry);
+                *self = Self::Multiple(entries);
+            }
+        }
+    }
+}
+
+impl<'a> Default for SmallItemSet<'a> {
+    fn default() -> Self {
+        Self::Multiple(Vec::new())
+    }
+}
+
+impl<'a> Deref for SmallItemSet<'a> {
+    type Target = [(ParseItem<'a>, TokenSet)];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Single(entry) => slice::from_ref(entry),
+            Self::Multiple(entries) => entries,
+        }
+    }
+}
+
+impl<'a> DerefMut for SmallItemSet<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::Single(entry) => slice::from_mut(entry),
+            Self::Multiple(entries) => entries,
+        }
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b SmallItemSet<'a> {
+    type Item = &'b (ParseItem<'a>, TokenSet);
+    type IntoIter = slice::Iter<'b, (ParseItem<'a>, TokenSet)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> PartialEq for SmallItemSet<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a> Eq for SmallItemSet<'a> {}
+
 impl<'a> ParseItemSet<'a> {
     pub fn with(elements: impl IntoIterator<Item = (ParseItem<'a>, TokenSet)>) -> Self {
         let mut result = Self::default();
@@ -168,11 +262,169 @@ impl<'a> ParseItemSet<'a> {
         }
     }
 
+    /// Find, for each item, the lookahead tokens that no other item in the set
+    /// has. When one of these tokens is seen, that item is the only one that
+    /// it can select.
+    pub fn distinguishing_lookaheads(&self) -> HashMap<ParseItem<'a>, TokenSet> {
+        let mut item_counts = HashMap::<Symbol, usize>::new();
+        for (_, lookaheads) in &self.entries {
+            for token in lookaheads.iter() {
+                *item_counts.entry(token).or_default() += 1;
+            }
+        }
+        self.entries
+            .iter()
+            .map(|(item, lookaheads)| {
+                let unique_lookaheads = lookaheads
+                    .iter()
+                    .filter(|token| item_counts[token] == 1)
+                    .collect();
+                (*item, unique_lookaheads)
+            })
+            .collect()
+    }
+
     pub fn core(&self) -> ParseItemSetCore<'a> {
         ParseItemSetCore {
             entries: self.entries.iter().map(|e| e.0).collect(),
         }
     }
+
+    /// Determine how many tokens of lookahead are needed in order to choose
+    /// between the actions in this item set, which should be a transitive
+    /// closure. This returns:
+    ///   * `0` if the state has no reduce-reduce or shift-reduce conflicts at all
+    ///   * `1` if the lookahead sets of the completed items distinguish them from
+    ///     each other and from every possible shift
+    ///   * `2` if one token is not enough, so the state's conflicts can only be
+    ///     resolved using precedence, associativity, or declared conflicts
+    pub fn required_lookahead_level(&self) -> usize {
+        let mut shift_tokens = TokenSet::new();
+        let mut reduce_count = 0;
+        for (item, _) in &self.entries {
+            match item.symbol() {
+                Some(symbol) if !symbol.is_non_terminal() => shift_tokens.insert(symbol),
+                Some(_) => {}
+                None => reduce_count += 1,
+            }
+        }
+
+        if reduce_count == 0 || (reduce_count == 1 && shift_tokens.is_empty()) {
+            return 0;
+        }
+
+        let mut seen_tokens = shift_tokens;
+        for (item, lookaheads) in &self.entries {
+            if item.is_done() {
+                if lookaheads.iter().any(|token| seen_tokens.contains(&token)) {
+                    return 2;
+                }
+                seen_tokens.insert_all(lookaheads);
+            }
+        }
+        1
+    }
+}
+
+/// Get the name of a symbol, as it should appear in error messages. Anonymous
+/// tokens are quoted, and symbols whose indices are out of range for the given
+/// grammars are rendered as `<invalid symbol N>` instead of causing a panic.
+pub fn symbol_name(
+    symbol: &Symbol,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> String {
+    symbol_name_with(symbol, syntax_grammar, lexical_grammar, &|_| None)
+}
+
+/// Like [`symbol_name`], but consult the given `namer` first. Whenever it
+/// returns a name, that name is used verbatim. Otherwise the symbol is named
+/// from the grammars as usual.
+pub fn symbol_name_with(
+    symbol: &Symbol,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    namer: &dyn Fn(Symbol) -> Option<String>,
+) -> String {
+    if let Some(name) = namer(*symbol) {
+        return name;
+    }
+    match symbol.kind {
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => "EOF".to_string(),
+        SymbolType::Terminal => match lexical_grammar.variables.get(symbol.index) {
+            Some(variable) if variable.kind == VariableType::Named => variable.name.clone(),
+            Some(variable) => format!("'{}'", &variable.name),
+            None => invalid_symbol_name(symbol),
+        },
+        SymbolType::External | SymbolType::NonTerminal => {
+            raw_symbol_name(symbol, syntax_grammar, lexical_grammar)
+                .map_or_else(|| invalid_symbol_name(symbol), str::to_string)
+        }
+    }
+}
+
+/// Format an item in dotted-rule notation, as in `A → α • β`. An item whose
+/// production is not one of its variable's own productions was produced by
+/// inlining, so its expanded steps are followed by an `(inlined)` marker.
+pub fn format_item(
+    item: &ParseItem,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> String {
+    let mut result = ParseItemDisplay(item, syntax_grammar, lexical_grammar).to_string();
+    let is_inlined = !item.is_augmented()
+        && syntax_grammar
+            .variables
+            .get(item.variable_index as usize)
+            .is_some_and(|variable| {
+                !variable
+                    .productions
+                    .iter()
+                    .any(|production| std::ptr::eq(production, item.production))
+            });
+    if is_inlined {
+        result += " (inlined)";
+    }
+    result
+}
+
+fn raw_symbol_name<'a>(
+    symbol: &Symbol,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+) -> Option<&'a str> {
+    match symbol.kind {
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => Some("EOF"),
+        SymbolType::Terminal => lexical_grammar
+            .variables
+            .get(symbol.index)
+            .map(|v| v.name.as_str()),
+        SymbolType::External => syntax_grammar
+            .external_tokens
+            .get(symbol.index)
+            .map(|t| t.name.as_str()),
+        SymbolType::NonTerminal => syntax_grammar
+            .variables
+            .get(symbol.index)
+            .map(|v| v.name.as_str()),
+    }
+}
+
+fn invalid_symbol_name(symbol: &Symbol) -> String {
+    format!("<invalid symbol {}>", symbol.index)
+}
+
+fn write_symbol_name(
+    f: &mut fmt::Formatter,
+    symbol: &Symbol,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> fmt::Result {
+    if let Some(name) = raw_symbol_name(symbol, syntax_grammar, lexical_grammar) {
+        write!(f, "{name}")
+    } else {
+        write!(f, "{}", invalid_symbol_name(symbol))
+    }
 }
 
 impl<'a> fmt::Display for ParseItemDisplay<'a> {
@@ -180,11 +432,13 @@ impl<'a> fmt::Display for ParseItemDisplay<'a> {
         if self.0.is_augmented() {
             write!(f, "START →")?;
         } else {
-            write!(
+            write_symbol_name(
                 f,
-                "{} →",
-                &self.1.variables[self.0.variable_index as usize].name
+                &Symbol::non_terminal(self.0.variable_index as usize),
+                self.1,
+                self.2,
             )?;
+            write!(f, " →")?;
         }
 
         for (i, step) in self.0.production.steps.iter().enumerate() {
@@ -202,17 +456,7 @@ impl<'a> fmt::Display for ParseItemDisplay<'a> {
             }
 
             write!(f, " ")?;
-            if step.symbol.is_terminal() {
-                if let Some(variable) = self.2.variables.get(step.symbol.index) {
-                    write!(f, "{}", &variable.name)?;
-                } else {
-                    write!(f, "terminal-{}", step.symbol.index)?;
-                }
-            } else if step.symbol.is_external() {
-                write!(f, "{}", &self.1.external_tokens[step.symbol.index].name)?;
-            } else {
-                write!(f, "{}", &self.1.variables[step.symbol.index].name)?;
-            }
+            write_symbol_name(f, &step.symbol, self.1, self.2)?;
 
             if let Some(alias) = &step.alias {
                 write!(f, "@{}", alias.value)?;
@@ -246,17 +490,7 @@ impl<'a> fmt::Display for TokenSetDisplay<'a> {
                 write!(f, ", ")?;
             }
 
-            if symbol.is_terminal() {
-                if let Some(variable) = self.2.variables.get(symbol.index) {
-                    write!(f, "{}", &variable.name)?;
-                } else {
-                    write!(f, "terminal-{}", symbol.index)?;
-                }
-            } else if symbol.is_external() {
-                write!(f, "{}", &self.1.external_tokens[symbol.index].name)?;
-            } else {
-                write!(f, "{}", &self.1.variables[symbol.index].name)?;
-            }
+            write_symbol_name(f, &symbol, self.1, self.2)?;
         }
         write!(f, "]")?;
         Ok(())
@@ -277,6 +511,43 @@ impl<'a> fmt::Display for ParseItemSetDisplay<'a> {
     }
 }
 
+impl<'a> fmt::Display for ParseActionDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.0 {
+            ParseAction::Accept => write!(f, "ACCEPT_INPUT()"),
+            ParseAction::Recover => write!(f, "RECOVER()"),
+            ParseAction::ShiftExtra => write!(f, "SHIFT_EXTRA()"),
+            ParseAction::Shift {
+                state,
+                is_repetition,
+            } => {
+                if *is_repetition {
+                    write!(f, "SHIFT_REPEAT({state})")
+                } else {
+                    write!(f, "SHIFT({state})")
+                }
+            }
+            ParseAction::Reduce {
+                symbol,
+                child_count,
+                dynamic_precedence,
+                production_id,
+            } => {
+                write!(f, "REDUCE({}, {child_count}", symbol.index)?;
+                if *dynamic_precedence != 0 {
+                    write!(f, ", .dynamic_precedence = {dynamic_precedence}")?;
+                }
+                if *production_id != 0 {
+                    write!(f, ", .production_id = {production_id}")?;
+                }
+                write!(f, ") /* ")?;
+                write_symbol_name(f, symbol, self.1, self.2)?;
+                write!(f, ", {child_count} children */")
+            }
+        }
+    }
+}
+
 impl<'a> Hash for ParseItem<'a> {
     fn hash<H: Hasher>(&self, hasher: &mut H) {
         hasher.write_u32(self.variable_index);
@@ -409,3 +680,359 @@ impl<'a> Hash for ParseItemSetCore<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{LexicalVariable, SyntaxVariable};
+
+    #[test]
+    fn test_distinguishing_lookaheads() {
+        // expression -> number
+        let number_production = Production {
+            dynamic_precedence: 0,
+            steps: vec![ProductionStep::new(Symbol::terminal(1))],
+        };
+        // expression -> '-' number
+        let negation_production = Production {
+            dynamic_precedence: 0,
+            steps: vec![
+                ProductionStep::new(Symbol::terminal(2)),
+                ProductionStep::new(Symbol::terminal(1)),
+            ],
+        };
+        let number_item = ParseItem {
+            variable_index: 0,
+            step_index: 1,
+            production: &number_production,
+            has_preceding_inherited_fields: false,
+        };
+        let negation_item = ParseItem {
+            production: &negation_production,
+            step_index: 2,
+            ..number_item
+        };
+        let lookaheads = |symbols: &[Symbol]| symbols.iter().copied().collect::<TokenSet>();
+
+        // The end of input can follow either item, so it distinguishes neither.
+        let state = ParseItemSet::with([
+            (
+                number_item,
+                lookaheads(&[Symbol::end(), Symbol::terminal(3)]),
+            ),
+            (
+                negation_item,
+                lookaheads(&[Symbol::end(), Symbol::terminal(4), Symbol::terminal(5)]),
+            ),
+        ]);
+        let result = state.distinguishing_lookaheads();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[&number_item], lookaheads(&[Symbol::terminal(3)]));
+        assert_eq!(
+            result[&negation_item],
+            lookaheads(&[Symbol::terminal(4), Symbol::terminal(5)])
+        );
+    }
+
+    #[test]
+    fn test_required_lookahead_level() {
+        // expression -> number
+        let number_production = Production {
+            dynamic_precedence: 0,
+            steps: vec![ProductionStep::new(Symbol::terminal(1))],
+        };
+        // expression -> expression '+' expression
+        let sum_production = Production {
+            dynamic_precedence: 0,
+            steps: vec![
+                ProductionStep::new(Symbol::non_terminal(0)),
+                ProductionStep::new(Symbol::terminal(0)),
+                ProductionStep::new(Symbol::non_terminal(0)),
+            ],
+        };
+        let item = |production, step_index| ParseItem {
+            variable_index: 0,
+            step_index,
+            production,
+            has_preceding_inherited_fields: false,
+        };
+        let lookaheads = |symbols: &[Symbol]| symbols.iter().copied().collect::<TokenSet>();
+
+        // A lone completed item can be reduced without looking ahead.
+        let state = ParseItemSet::with([(
+            item(&number_production, 1),
+            lookaheads(&[Symbol::end(), Symbol::terminal(0)]),
+        )]);
+        assert_eq!(state.required_lookahead_level(), 0);
+
+        // A completed item alongside a shift needs one token to decide.
+        let state = ParseItemSet::with([
+            (item(&number_production, 1), lookaheads(&[Symbol::end()])),
+            (item(&sum_production, 1), lookaheads(&[Symbol::end()])),
+        ]);
+        assert_eq!(state.required_lookahead_level(), 1);
+
+        // If the reduction is also valid before the shifted token, one token
+        // is not enough.
+        let state = ParseItemSet::with([
+            (
+                item(&sum_production, 3),
+                lookaheads(&[Symbol::end(), Symbol::terminal(0)]),
+            ),
+            (item(&sum_production, 1), lookaheads(&[Symbol::end()])),
+        ]);
+        assert_eq!(state.required_lookahead_level(), 2);
+    }
+
+    #[test]
+    fn test_reduce_arity_of_inlined_production() {
+        use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+        use crate::generate::prepare_grammar::prepare_grammar;
+        use crate::generate::rules::Rule;
+
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("a"),
+                        Rule::named("_pair"),
+                        Rule::string("d"),
+                    ]),
+                },
+                Variable {
+                    name: "_pair".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::seq(vec![Rule::string("b"), Rule::string("c")]),
+                },
+            ],
+            variables_to_inline: vec!["_pair".to_string()],
+            ..Default::default()
+        };
+        let (syntax_grammar, _, inlines, _) = prepare_grammar(&grammar).unwrap();
+        let production = &syntax_grammar.variables[0].productions[0];
+        let inlined_production = inlines
+            .inlined_productions(production, 1)
+            .unwrap()
+            .next()
+            .unwrap();
+        fn done(production: &Production) -> ParseItem<'_> {
+            ParseItem {
+                variable_index: 0,
+                step_index: production.steps.len() as u32,
+                production,
+                has_preceding_inherited_fields: false,
+            }
+        }
+
+        assert_eq!(done(production).reduce_arity(), 3);
+        assert_eq!(done(inlined_production).reduce_arity(), 4);
+    }
+
+    #[test]
+    fn test_format_item() {
+        use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+        use crate::generate::prepare_grammar::prepare_grammar;
+        use crate::generate::rules::Rule;
+
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("a"),
+                        Rule::named("_pair"),
+                        Rule::string("d"),
+                    ]),
+                },
+                Variable {
+                    name: "_pair".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::seq(vec![Rule::string("b"), Rule::string("c")]),
+                },
+            ],
+            variables_to_inline: vec!["_pair".to_string()],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, _) = prepare_grammar(&grammar).unwrap();
+        let production = &syntax_grammar.variables[0].productions[0];
+        let inlined_production = inlines
+            .inlined_productions(production, 1)
+            .unwrap()
+            .next()
+            .unwrap();
+        let item = |production| ParseItem {
+            variable_index: 0,
+            step_index: 1,
+            production,
+            has_preceding_inherited_fields: false,
+        };
+
+        assert_eq!(
+            format_item(&item(production), &syntax_grammar, &lexical_grammar),
+            "program → a • _pair d"
+        );
+        assert_eq!(
+            format_item(&item(inlined_production), &syntax_grammar, &lexical_grammar),
+            "program → a • b c d (inlined)"
+        );
+    }
+
+    #[test]
+    fn test_single_item_sets_are_stored_inline() {
+        let production = Production {
+            dynamic_precedence: 0,
+            steps: vec![
+                ProductionStep::new(Symbol::terminal(0)),
+                ProductionStep::new(Symbol::terminal(1)),
+            ],
+        };
+        let item = |step_index| ParseItem {
+            variable_index: 0,
+            step_index,
+            production: &production,
+            has_preceding_inherited_fields: false,
+        };
+        let lookaheads = [Symbol::end()].into_iter().collect::<TokenSet>();
+
+        let mut item_set = ParseItemSet::default();
+        assert!(item_set.entries.is_empty());
+
+        item_set.insert(item(1), &lookaheads);
+        assert!(matches!(item_set.entries, SmallItemSet::Single(_)));
+
+        item_set.insert(item(1), &[Symbol::terminal(1)].into_iter().collect());
+        assert!(matches!(item_set.entries, SmallItemSet::Single(_)));
+
+        item_set.insert(item(0), &lookaheads);
+        assert!(matches!(item_set.entries, SmallItemSet::Multiple(_)));
+        assert_eq!(
+            item_set
+                .entries
+                .iter()
+                .map(|(item, _)| item.step_index)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            item_set,
+            ParseItemSet::with([
+                (
+                    item(1),
+                    [Symbol::end(), Symbol::terminal(1)].into_iter().collect()
+                ),
+                (item(0), lookaheads.clone()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_symbol_name_with_custom_namer() {
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "_expression_repeat1".to_string(),
+                kind: VariableType::Auxiliary,
+                productions: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let lexical_grammar = LexicalGrammar {
+            variables: vec![LexicalVariable {
+                name: "identifier".to_string(),
+                kind: VariableType::Named,
+                implicit_precedence: 0,
+                start_state: 0,
+                case_insensitive: false,
+                lookahead: None,
+            }],
+            ..Default::default()
+        };
+        let namer = |symbol: Symbol| {
+            (symbol == Symbol::non_terminal(0)).then(|| "repeat($.expression)".to_string())
+        };
+
+        assert_eq!(
+            symbol_name_with(
+                &Symbol::non_terminal(0),
+                &syntax_grammar,
+                &lexical_grammar,
+                &namer
+            ),
+            "repeat($.expression)"
+        );
+        assert_eq!(
+            symbol_name_with(
+                &Symbol::terminal(0),
+                &syntax_grammar,
+                &lexical_grammar,
+                &namer
+            ),
+            "identifier"
+        );
+        assert_eq!(
+            symbol_name(&Symbol::non_terminal(0), &syntax_grammar, &lexical_grammar),
+            "_expression_repeat1"
+        );
+    }
+
+    #[test]
+    fn test_symbol_name_with_invalid_symbol() {
+        let syntax_grammar = SyntaxGrammar::default();
+        let lexical_grammar = LexicalGrammar::default();
+
+        assert_eq!(
+            symbol_name(&Symbol::non_terminal(5), &syntax_grammar, &lexical_grammar),
+            "<invalid symbol 5>"
+        );
+
+        let production = Production {
+            dynamic_precedence: 0,
+            steps: vec![ProductionStep::new(Symbol::non_terminal(5))],
+        };
+        let item = ParseItem {
+            variable_index: 3,
+            step_index: 0,
+            production: &production,
+            has_preceding_inherited_fields: false,
+        };
+        assert_eq!(
+            ParseItemDisplay(&item, &syntax_grammar, &lexical_grammar).to_string(),
+            "<invalid symbol 3> → • <invalid symbol 5>"
+        );
+    }
+
+    #[test]
+    fn test_parse_action_display_names_reduced_rule() {
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "binary_expression".to_string(),
+                kind: VariableType::Named,
+                productions: Vec::new(),
+            }],
+            ..Default::default()
+        };
+        let lexical_grammar = LexicalGrammar::default();
+
+        let action = ParseAction::Reduce {
+            symbol: Symbol::non_terminal(0),
+            child_count: 3,
+            dynamic_precedence: 0,
+            production_id: 2,
+        };
+        assert_eq!(
+            ParseActionDisplay(&action, &syntax_grammar, &lexical_grammar).to_string(),
+            "REDUCE(0, 3, .production_id = 2) /* binary_expression, 3 children */"
+        );
+
+        let action = ParseAction::Shift {
+            state: 4,
+            is_repetition: false,
+        };
+        assert_eq!(
+            ParseActionDisplay(&action, &syntax_grammar, &lexical_grammar).to_string(),
+            "SHIFT(4)"
+        );
+    }
+}
@@ -1,8 +1,14 @@
-use super::item::{ParseItem, ParseItemDisplay, ParseItemSet, TokenSetDisplay};
-use crate::generate::grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar};
+use super::item::{
+    symbol_name, ParseItem, ParseItemDisplay, ParseItemSet, ParseItemSetDisplay, TokenSetDisplay,
+};
+use crate::generate::analysis::nullable_variables;
+use crate::generate::grammars::{
+    InlinedProductionMap, LexicalGrammar, ProductionStep, SyntaxGrammar,
+};
 use crate::generate::rules::{Symbol, SymbolType, TokenSet};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
-use std::fmt;
+use std::fmt::{self, Write};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct TransitiveClosureAddition<'a> {
@@ -10,10 +16,39 @@ struct TransitiveClosureAddition<'a> {
     info: FollowSetInfo,
 }
 
+/// The tokens that can follow a non-terminal where it occurs at the beginning of
+/// another non-terminal's expansion.
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct FollowSetInfo {
-    lookaheads: TokenSet,
-    propagates_lookaheads: bool,
+pub struct FollowSetInfo {
+    /// The tokens that can always follow the non-terminal in the expansion.
+    pub lookaheads: TokenSet,
+    /// Whether the non-terminal can occur at the end of the expansion, so that
+    /// the lookaheads of the expanded non-terminal can follow it too.
+    pub propagates_lookaheads: bool,
+}
+
+/// Computes the [`FollowSetInfo`] of each non-terminal that can begin a given
+/// non-terminal, one step at a time. Each step processes one entry of a work
+/// list, adding to the follow set info of its non-terminal, and, if that info
+/// changed, adding entries for the non-terminals that begin its productions.
+pub struct FollowSetPropagator<'a, 'b> {
+    builder: &'b ParseItemSetBuilder<'a>,
+    entries_to_process: Vec<(usize, Cow<'b, TokenSet>, bool)>,
+    follow_set_info_by_non_terminal: HashMap<usize, FollowSetInfo>,
+}
+
+/// A single step of a [`FollowSetPropagator`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PropagationEvent<'b> {
+    /// The index of the non-terminal whose follow set info was updated.
+    pub variable_index: usize,
+    /// The tokens that were added to the non-terminal's lookaheads, unless
+    /// the step propagates the lookaheads of the expanded non-terminal instead.
+    pub lookaheads: Cow<'b, TokenSet>,
+    pub propagates_lookaheads: bool,
+    /// Whether the step changed the non-terminal's follow set info. If not, no
+    /// further entries were added to the work list.
+    pub changed: bool,
 }
 
 pub struct ParseItemSetBuilder<'a> {
@@ -23,6 +58,80 @@ pub struct ParseItemSetBuilder<'a> {
     last_sets: HashMap<Symbol, TokenSet>,
     inlines: &'a InlinedProductionMap,
     transitive_closure_additions: Vec<Vec<TransitiveClosureAddition<'a>>>,
+    token_set_capacity: usize,
+}
+
+/// A copy of the data that a [`ParseItemSetBuilder`] derives from its grammars,
+/// without the grammars themselves. This can be used to roll the builder back
+/// to an earlier state after an experimental modification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuilderSnapshot<'a> {
+    first_sets: HashMap<Symbol, TokenSet>,
+    last_sets: HashMap<Symbol, TokenSet>,
+    transitive_closure_additions: Vec<Vec<TransitiveClosureAddition<'a>>>,
+}
+
+/// Computes FIRST sets on demand, caching each one, for tools that only need
+/// the FIRST sets of a few symbols. A [`ParseItemSetBuilder`] computes all of
+/// them eagerly, along with the other data that is needed to build a parse
+/// table.
+pub struct LazyBuilder<'a> {
+    syntax_grammar: &'a SyntaxGrammar,
+    terminal_count: usize,
+    first_sets: HashMap<Symbol, TokenSet>,
+}
+
+impl<'a> LazyBuilder<'a> {
+    pub fn new(syntax_grammar: &'a SyntaxGrammar, lexical_grammar: &LexicalGrammar) -> Self {
+        Self {
+            syntax_grammar,
+            terminal_count: lexical_grammar.variables.len(),
+            first_sets: HashMap::new(),
+        }
+    }
+
+    pub fn first_set_lazy(&mut self, symbol: Symbol) -> &TokenSet {
+        self.first_sets.entry(symbol).or_insert_with(|| {
+            let mut first_set = TokenSet::with_capacity(self.terminal_count);
+            add_first_set(
+                self.syntax_grammar,
+                symbol,
+                &mut first_set,
+                &mut Vec::new(),
+                &mut HashSet::new(),
+            );
+            first_set
+        })
+    }
+}
+
+// Add the terminals that can begin the given symbol to its FIRST set. The stack
+// and the set of visited non-terminals are passed in so that their allocations
+// can be reused.
+fn add_first_set(
+    syntax_grammar: &SyntaxGrammar,
+    symbol: Symbol,
+    first_set: &mut TokenSet,
+    symbols_to_process: &mut Vec<Symbol>,
+    processed_non_terminals: &mut HashSet<Symbol>,
+) {
+    processed_non_terminals.clear();
+    symbols_to_process.clear();
+    symbols_to_process.push(symbol);
+    while let Some(current_symbol) = symbols_to_process.pop() {
+        if current_symbol.is_terminal() || current_symbol.is_external() {
+            first_set.insert(current_symbol);
+        } else if processed_non_terminals.insert(current_symbol) {
+            for production in &syntax_grammar.variables[current_symbol.index].productions {
+                for step in &production.steps {
+                    symbols_to_process.push(step.symbol);
+                    if !syntax_grammar.is_zero_width(step.symbol) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
 }
 
 fn find_or_push<T: Eq>(vector: &mut Vec<T>, value: T) {
@@ -31,11 +140,88 @@ fn find_or_push<T: Eq>(vector: &mut Vec<T>, value: T) {
     }
 }
 
+impl<'a, 'b> FollowSetPropagator<'a, 'b> {
+    /// Process the next entry of the work list, or return `None` if the
+    /// propagation is complete.
+    pub fn step(&mut self) -> Option<PropagationEvent<'b>> {
+        let (variable_index, lookaheads, propagates_lookaheads) = self.entries_to_process.pop()?;
+        let existing_info = self
+            .follow_set_info_by_non_terminal
+            .entry(variable_index)
+            .or_insert_with(|| FollowSetInfo {
+                lookaheads: TokenSet::new(),
+                propagates_lookaheads: false,
+            });
+
+        let did_add_follow_set_info;
+        if propagates_lookaheads {
+            did_add_follow_set_info = !existing_info.propagates_lookaheads;
+            existing_info.propagates_lookaheads = true;
+        } else {
+            did_add_follow_set_info = existing_info.lookaheads.insert_all(&lookaheads);
+        }
+
+        if did_add_follow_set_info {
+            let builder = self.builder;
+            for production in &builder.syntax_grammar.variables[variable_index].productions {
+                if let Some(symbol) = production.first_symbol() {
+                    if symbol.is_non_terminal() {
+                        let (following_tokens, reaches_end) =
+                            builder.sequence_first_set(&production.steps[1..]);
+                        if !following_tokens.is_empty() {
+                            self.entries_to_process
+                                .push((symbol.index, following_tokens, false));
+                        }
+                        if reaches_end {
+                            self.entries_to_process.push((
+                                symbol.index,
+                                lookaheads.clone(),
+                                propagates_lookaheads,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(PropagationEvent {
+            variable_index,
+            lookaheads,
+            propagates_lookaheads,
+            changed: did_add_follow_set_info,
+        })
+    }
+
+    /// Get the follow set info that has been computed so far for the given
+    /// non-terminal, if it has been reached.
+    pub fn follow_set_info(&self, variable_index: usize) -> Option<&FollowSetInfo> {
+        self.follow_set_info_by_non_terminal.get(&variable_index)
+    }
+
+    /// Check if there are no more entries to process.
+    pub fn is_done(&self) -> bool {
+        self.entries_to_process.is_empty()
+    }
+}
+
 impl<'a> ParseItemSetBuilder<'a> {
     pub fn new(
         syntax_grammar: &'a SyntaxGrammar,
         lexical_grammar: &'a LexicalGrammar,
         inlines: &'a InlinedProductionMap,
+    ) -> Self {
+        let token_set_capacity = lexical_grammar.variables.len();
+        Self::with_token_set_capacity(syntax_grammar, lexical_grammar, inlines, token_set_capacity)
+    }
+
+    /// Create a builder whose FIRST and LAST sets for non-terminals are allocated
+    /// with room for the given number of terminals. [`Self::new`] allocates room
+    /// for all of the lexical grammar's terminals.
+    pub fn with_token_set_capacity(
+        syntax_grammar: &'a SyntaxGrammar,
+        lexical_grammar: &'a LexicalGrammar,
+        inlines: &'a InlinedProductionMap,
+        token_set_capacity: usize,
     ) -> Self {
         let mut result = Self {
             syntax_grammar,
@@ -44,6 +230,7 @@ impl<'a> ParseItemSetBuilder<'a> {
             last_sets: HashMap::new(),
             inlines,
             transitive_closure_additions: vec![Vec::new(); syntax_grammar.variables.len()],
+            token_set_capacity,
         };
 
         // For each grammar symbol, populate the FIRST and LAST sets: the set of
@@ -68,168 +255,270 @@ impl<'a> ParseItemSetBuilder<'a> {
             result.last_sets.insert(symbol, set);
         }
 
-        // The FIRST set of a non-terminal `i` is the union of the following sets:
-        // * the set of all terminals that appear at the beginnings of i's productions
-        // * the FIRST sets of all the non-terminals that appear at the beginnings
-        //   of i's productions
-        //
-        // Rather than computing these sets using recursion, we use an explicit stack
-        // called `symbols_to_process`.
         let mut symbols_to_process = Vec::new();
         let mut processed_non_terminals = HashSet::new();
         for i in 0..syntax_grammar.variables.len() {
-            let symbol = Symbol::non_terminal(i);
+            result.compute_first_and_last_sets(
+                i,
+                &mut symbols_to_process,
+                &mut processed_non_terminals,
+            );
+        }
+        for i in 0..syntax_grammar.variables.len() {
+            result.compute_transitive_closure_additions(i);
+        }
+        result
+    }
 
-            let first_set = result
-                .first_sets
-                .entry(symbol)
-                .or_insert_with(TokenSet::new);
-            processed_non_terminals.clear();
-            symbols_to_process.clear();
-            symbols_to_process.push(symbol);
-            while let Some(current_symbol) = symbols_to_process.pop() {
-                if current_symbol.is_terminal() || current_symbol.is_external() {
-                    first_set.insert(current_symbol);
-                } else if processed_non_terminals.insert(current_symbol) {
-                    for production in &syntax_grammar.variables[current_symbol.index].productions {
-                        if let Some(step) = production.steps.first() {
-                            symbols_to_process.push(step.symbol);
-                        }
-                    }
+    /// Replace the builder's grammars with new versions, recomputing only the
+    /// data for the variables that are affected by the change, and return those
+    /// variables. A variable is affected if its productions changed, or if it can
+    /// derive a variable that is affected. The data for the other variables is
+    /// reused. Variables are matched by name, and if the new grammar doesn't
+    /// number its symbols in the same way as the old one, or inlines different
+    /// variables, everything is recomputed, as in [`ParseItemSetBuilder::new`].
+    pub fn reload(
+        &mut self,
+        syntax_grammar: &'a SyntaxGrammar,
+        lexical_grammar: &'a LexicalGrammar,
+        inlines: &'a InlinedProductionMap,
+    ) -> Vec<Symbol> {
+        let old_syntax_grammar = self.syntax_grammar;
+        let has_same_symbols = old_syntax_grammar
+            .variables
+            .iter()
+            .map(|v| &v.name)
+            .eq(syntax_grammar.variables.iter().map(|v| &v.name))
+            && self
+                .lexical_grammar
+                .variables
+                .iter()
+                .map(|v| &v.name)
+                .eq(lexical_grammar.variables.iter().map(|v| &v.name))
+            && old_syntax_grammar.external_tokens == syntax_grammar.external_tokens
+            && old_syntax_grammar.variables_to_inline == syntax_grammar.variables_to_inline;
+        if !has_same_symbols {
+            *self = Self::new(syntax_grammar, lexical_grammar, inlines);
+            return (0..syntax_grammar.variables.len())
+                .map(Symbol::non_terminal)
+                .collect();
+        }
+
+        // Propagate the changes to the variables that can derive the changed ones
+        // until they reach a fixed point.
+        let mut is_affected = old_syntax_grammar
+            .variables
+            .iter()
+            .zip(&syntax_grammar.variables)
+            .map(|(old, new)| old.productions != new.productions)
+            .collect::<Vec<_>>();
+        let mut done = false;
+        while !done {
+            done = true;
+            for (i, variable) in syntax_grammar.variables.iter().enumerate() {
+                if !is_affected[i]
+                    && variable
+                        .productions
+                        .iter()
+                        .flat_map(|production| &production.steps)
+                        .any(|step| step.symbol.is_non_terminal() && is_affected[step.symbol.index])
+                {
+                    is_affected[i] = true;
+                    done = false;
                 }
             }
+        }
 
-            // The LAST set is defined in a similar way to the FIRST set.
-            let last_set = result.last_sets.entry(symbol).or_insert_with(TokenSet::new);
-            processed_non_terminals.clear();
-            symbols_to_process.clear();
-            symbols_to_process.push(symbol);
-            while let Some(current_symbol) = symbols_to_process.pop() {
-                if current_symbol.is_terminal() || current_symbol.is_external() {
-                    last_set.insert(current_symbol);
-                } else if processed_non_terminals.insert(current_symbol) {
-                    for production in &syntax_grammar.variables[current_symbol.index].productions {
-                        if let Some(step) = production.steps.last() {
-                            symbols_to_process.push(step.symbol);
-                        }
-                    }
-                }
+        self.syntax_grammar = syntax_grammar;
+        self.lexical_grammar = lexical_grammar;
+        self.inlines = inlines;
+
+        let mut symbols_to_process = Vec::new();
+        let mut processed_non_terminals = HashSet::new();
+        for (i, is_affected) in is_affected.iter().enumerate() {
+            if *is_affected {
+                self.compute_first_and_last_sets(
+                    i,
+                    &mut symbols_to_process,
+                    &mut processed_non_terminals,
+                );
             }
         }
 
-        // To compute an item set's transitive closure, we find each item in the set
-        // whose next symbol is a non-terminal, and we add new items to the set for
-        // each of that symbols' productions. These productions might themselves begin
-        // with non-terminals, so the process continues recursively. In this process,
-        // the total set of entries that get added depends only on two things:
-        //   * the set of non-terminal symbols that occur at each item's current position
-        //   * the set of terminals that occurs after each of these non-terminal symbols
-        //
-        // So we can avoid a lot of duplicated recursive work by precomputing, for each
-        // non-terminal symbol `i`, a final list of *additions* that must be made to an
-        // item set when `i` occurs as the next symbol in one if its core items. The
-        // structure of an *addition* is as follows:
-        //   * `item` - the new item that must be added as part of the expansion of `i`
-        //   * `lookaheads` - lookahead tokens that can always come after that item in
-        //      the expansion of `i`
-        //   * `propagates_lookaheads` - a boolean indicating whether or not `item` can
-        //      occur at the *end* of the expansion of `i`, so that i's own current
-        //      lookahead tokens can occur after `item`.
-        //
-        // Again, rather than computing these additions recursively, we use an explicit
-        // stack called `entries_to_process`.
-        for i in 0..syntax_grammar.variables.len() {
-            let empty_lookaheads = TokenSet::new();
-            let mut entries_to_process = vec![(i, &empty_lookaheads, true)];
-
-            // First, build up a map whose keys are all of the non-terminals that can
-            // appear at the beginning of non-terminal `i`, and whose values store
-            // information about the tokens that can follow each non-terminal.
-            let mut follow_set_info_by_non_terminal = HashMap::new();
-            while let Some(entry) = entries_to_process.pop() {
-                let (variable_index, lookaheads, propagates_lookaheads) = entry;
-                let existing_info = follow_set_info_by_non_terminal
-                    .entry(variable_index)
-                    .or_insert_with(|| FollowSetInfo {
-                        lookaheads: TokenSet::new(),
-                        propagates_lookaheads: false,
-                    });
-
-                let did_add_follow_set_info;
-                if propagates_lookaheads {
-                    did_add_follow_set_info = !existing_info.propagates_lookaheads;
-                    existing_info.propagates_lookaheads = true;
-                } else {
-                    did_add_follow_set_info = existing_info.lookaheads.insert_all(lookaheads);
+        // The reused closure additions refer to the old grammar's productions, so
+        // point them at the same productions in the new grammar. Additions that
+        // were created by inlining are recomputed, because the new grammar has its
+        // own inlined productions.
+        for (i, is_affected) in is_affected.iter().enumerate() {
+            if !is_affected {
+                let additions = self.transitive_closure_additions[i]
+                    .iter()
+                    .map(|addition| {
+                        let variable_index = addition.item.variable_index as usize;
+                        let production_index = old_syntax_grammar.variables[variable_index]
+                            .productions
+                            .iter()
+                            .position(|p| std::ptr::eq(p, addition.item.production))?;
+                        let mut addition = addition.clone();
+                        addition.item.production =
+                            &syntax_grammar.variables[variable_index].productions[production_index];
+                        Some(addition)
+                    })
+                    .collect::<Option<Vec<_>>>();
+                if let Some(additions) = additions {
+                    self.transitive_closure_additions[i] = additions;
+                    continue;
                 }
+            }
+            self.compute_transitive_closure_additions(i);
+        }
 
-                if did_add_follow_set_info {
-                    for production in &syntax_grammar.variables[variable_index].productions {
-                        if let Some(symbol) = production.first_symbol() {
-                            if symbol.is_non_terminal() {
-                                if production.steps.len() == 1 {
-                                    entries_to_process.push((
-                                        symbol.index,
-                                        lookaheads,
-                                        propagates_lookaheads,
-                                    ));
-                                } else {
-                                    entries_to_process.push((
-                                        symbol.index,
-                                        &result.first_sets[&production.steps[1].symbol],
-                                        false,
-                                    ));
-                                }
-                            }
+        (0..syntax_grammar.variables.len())
+            .filter(|i| is_affected[*i])
+            .map(Symbol::non_terminal)
+            .collect()
+    }
+
+    // The FIRST set of a non-terminal `i` is the union of the following sets:
+    // * the set of all terminals that appear at the beginnings of i's productions
+    // * the FIRST sets of all the non-terminals that appear at the beginnings
+    //   of i's productions
+    //
+    // Zero-width external tokens don't consume any input, so when a production
+    // begins with one, the symbol after it also contributes to the FIRST set.
+    //
+    // Rather than computing these sets using recursion, we use an explicit stack
+    // called `symbols_to_process`.
+    //
+    // These sets can contain any terminal, so they are allocated with room for
+    // all of them up front, unless the builder was given a smaller capacity.
+    fn compute_first_and_last_sets(
+        &mut self,
+        variable_index: usize,
+        symbols_to_process: &mut Vec<Symbol>,
+        processed_non_terminals: &mut HashSet<Symbol>,
+    ) {
+        let syntax_grammar = self.syntax_grammar;
+        let symbol = Symbol::non_terminal(variable_index);
+
+        let mut first_set = TokenSet::with_capacity(self.token_set_capacity);
+        add_first_set(
+            syntax_grammar,
+            symbol,
+            &mut first_set,
+            symbols_to_process,
+            processed_non_terminals,
+        );
+        self.first_sets.insert(symbol, first_set);
+
+        // The LAST set is defined in a similar way to the FIRST set.
+        let mut last_set = TokenSet::with_capacity(self.token_set_capacity);
+        processed_non_terminals.clear();
+        symbols_to_process.clear();
+        symbols_to_process.push(symbol);
+        while let Some(current_symbol) = symbols_to_process.pop() {
+            if current_symbol.is_terminal() || current_symbol.is_external() {
+                last_set.insert(current_symbol);
+            } else if processed_non_terminals.insert(current_symbol) {
+                for production in &syntax_grammar.variables[current_symbol.index].productions {
+                    for step in production.steps.iter().rev() {
+                        symbols_to_process.push(step.symbol);
+                        if !syntax_grammar.is_zero_width(step.symbol) {
+                            break;
                         }
                     }
                 }
             }
+        }
+        self.last_sets.insert(symbol, last_set);
+    }
 
-            // Store all of those non-terminals' productions, along with their associated
-            // lookahead info, as *additions* associated with non-terminal `i`.
-            let additions_for_non_terminal = &mut result.transitive_closure_additions[i];
-            for (variable_index, follow_set_info) in follow_set_info_by_non_terminal {
-                let variable = &syntax_grammar.variables[variable_index];
-                let non_terminal = Symbol::non_terminal(variable_index);
-                let variable_index = variable_index as u32;
-                if syntax_grammar.variables_to_inline.contains(&non_terminal) {
-                    continue;
-                }
-                for production in &variable.productions {
-                    let item = ParseItem {
-                        variable_index,
-                        production,
-                        step_index: 0,
-                        has_preceding_inherited_fields: false,
-                    };
+    // To compute an item set's transitive closure, we find each item in the set
+    // whose next symbol is a non-terminal, and we add new items to the set for
+    // each of that symbols' productions. These productions might themselves begin
+    // with non-terminals, so the process continues recursively. In this process,
+    // the total set of entries that get added depends only on two things:
+    //   * the set of non-terminal symbols that occur at each item's current position
+    //   * the set of terminals that occurs after each of these non-terminal symbols
+    //
+    // So we can avoid a lot of duplicated recursive work by precomputing, for each
+    // non-terminal symbol `i`, a final list of *additions* that must be made to an
+    // item set when `i` occurs as the next symbol in one if its core items. The
+    // structure of an *addition* is as follows:
+    //   * `item` - the new item that must be added as part of the expansion of `i`
+    //   * `lookaheads` - lookahead tokens that can always come after that item in
+    //      the expansion of `i`
+    //   * `propagates_lookaheads` - a boolean indicating whether or not `item` can
+    //      occur at the *end* of the expansion of `i`, so that i's own current
+    //      lookahead tokens can occur after `item`.
+    //
+    // Again, rather than computing these additions recursively, we use an explicit
+    // stack, in a `FollowSetPropagator`.
+    fn compute_transitive_closure_additions(&mut self, i: usize) {
+        let syntax_grammar = self.syntax_grammar;
+        let inlines = self.inlines;
 
-                    if let Some(inlined_productions) =
-                        inlines.inlined_productions(item.production, item.step_index)
-                    {
-                        for production in inlined_productions {
-                            find_or_push(
-                                additions_for_non_terminal,
-                                TransitiveClosureAddition {
-                                    item: item.substitute_production(production),
-                                    info: follow_set_info.clone(),
-                                },
-                            );
-                        }
-                    } else {
+        // First, build up a map whose keys are all of the non-terminals that can
+        // appear at the beginning of non-terminal `i`, and whose values store
+        // information about the tokens that can follow each non-terminal.
+        let mut propagator = self.follow_set_propagator(i);
+        while propagator.step().is_some() {}
+        let follow_set_info_by_non_terminal = propagator.follow_set_info_by_non_terminal;
+
+        // Store all of those non-terminals' productions, along with their associated
+        // lookahead info, as *additions* associated with non-terminal `i`.
+        let mut additions_for_non_terminal = Vec::new();
+        for (variable_index, follow_set_info) in follow_set_info_by_non_terminal {
+            let variable = &syntax_grammar.variables[variable_index];
+            let non_terminal = Symbol::non_terminal(variable_index);
+            let variable_index = variable_index as u32;
+            if syntax_grammar.is_always_inlined(non_terminal) {
+                continue;
+            }
+            for production in &variable.productions {
+                let item = ParseItem {
+                    variable_index,
+                    production,
+                    step_index: 0,
+                    has_preceding_inherited_fields: false,
+                };
+
+                if let Some(inlined_productions) =
+                    inlines.inlined_productions(item.production, item.step_index)
+                {
+                    for production in inlined_productions {
                         find_or_push(
-                            additions_for_non_terminal,
+                            &mut additions_for_non_terminal,
                             TransitiveClosureAddition {
-                                item,
+                                item: item.substitute_production(production),
                                 info: follow_set_info.clone(),
                             },
                         );
                     }
+                } else {
+                    find_or_push(
+                        &mut additions_for_non_terminal,
+                        TransitiveClosureAddition {
+                            item,
+                            info: follow_set_info.clone(),
+                        },
+                    );
                 }
             }
         }
+        self.transitive_closure_additions[i] = additions_for_non_terminal;
+    }
 
-        result
+    /// Start propagating follow set info through the non-terminals that can begin
+    /// the given non-terminal. The builder does this for every non-terminal when
+    /// it is created, to compute the items that are added to an item set's
+    /// transitive closure.
+    pub fn follow_set_propagator(&self, variable_index: usize) -> FollowSetPropagator<'a, '_> {
+        FollowSetPropagator {
+            builder: self,
+            entries_to_process: vec![(variable_index, Cow::Owned(TokenSet::new()), true)],
+            follow_set_info_by_non_terminal: HashMap::new(),
+        }
     }
 
     pub fn transitive_closure(&mut self, item_set: &ParseItemSet<'a>) -> ParseItemSet<'a> {
@@ -253,6 +542,20 @@ impl<'a> ParseItemSetBuilder<'a> {
         result
     }
 
+    pub fn checkpoint(&self) -> BuilderSnapshot<'a> {
+        BuilderSnapshot {
+            first_sets: self.first_sets.clone(),
+            last_sets: self.last_sets.clone(),
+            transitive_closure_additions: self.transitive_closure_additions.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: BuilderSnapshot<'a>) {
+        self.first_sets = snapshot.first_sets;
+        self.last_sets = snapshot.last_sets;
+        self.transitive_closure_additions = snapshot.transitive_closure_additions;
+    }
+
     pub fn first_set(&self, symbol: &Symbol) -> &TokenSet {
         &self.first_sets[symbol]
     }
@@ -261,26 +564,405 @@ impl<'a> ParseItemSetBuilder<'a> {
         &self.last_sets[symbol]
     }
 
+    /// Get the set of tokens that can end the given symbol. Unlike the LAST set,
+    /// which only skips over zero-width external tokens at the end of each
+    /// production, this also skips over non-terminals that can match nothing, so
+    /// it includes the tokens that end a production's earlier steps.
+    pub fn ending_tokens(&self, symbol: Symbol) -> TokenSet {
+        self.ending_tokens_with(symbol, &nullable_variables(self.syntax_grammar))
+    }
+
+    // Compute the ending tokens of the given symbol, using the nullability of
+    // each variable, which is passed in so that it's only computed once when
+    // finding the ending tokens of many symbols.
+    fn ending_tokens_with(&self, symbol: Symbol, nullable_variables: &[bool]) -> TokenSet {
+        let mut result = TokenSet::new();
+        let mut symbols_to_process = vec![symbol];
+        let mut processed_non_terminals = HashSet::new();
+        while let Some(current_symbol) = symbols_to_process.pop() {
+            if current_symbol.is_terminal() || current_symbol.is_external() {
+                result.insert(current_symbol);
+            } else if processed_non_terminals.insert(current_symbol) {
+                for production in &self.syntax_grammar.variables[current_symbol.index].productions {
+                    for step in production.steps.iter().rev() {
+                        symbols_to_process.push(step.symbol);
+                        let is_nullable =
+                            step.symbol.is_non_terminal() && nullable_variables[step.symbol.index];
+                        if !is_nullable && !self.syntax_grammar.is_zero_width(step.symbol) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Get the set of tokens that could legally appear next after the given item:
+    /// the FIRST set of its next symbol or, if the item is done, its lookaheads.
+    pub fn valid_next_tokens(&self, item: &ParseItem, lookaheads: &TokenSet) -> TokenSet {
+        item.step().map_or_else(
+            || lookaheads.clone(),
+            |step| self.first_sets[&step.symbol].clone(),
+        )
+    }
+
+    /// Get the names of the tokens that are valid in the given parse state, for
+    /// error messages of the form "expected X, found Y". This includes the tokens
+    /// that can be shifted and the lookaheads of the items that can be reduced,
+    /// but not extras, which are valid everywhere. The names are sorted and
+    /// deduplicated.
+    pub fn expected_token_names(&mut self, state: &ParseItemSet<'a>) -> Vec<String> {
+        let mut tokens = TokenSet::new();
+        for (item, lookaheads) in &self.transitive_closure(state).entries {
+            if !item.is_augmented() {
+                tokens.insert_all(&self.valid_next_tokens(item, lookaheads));
+            }
+        }
+        let mut result = tokens
+            .iter()
+            .filter(|token| !self.syntax_grammar.extra_symbols.contains(token))
+            .map(|token| symbol_name(&token, self.syntax_grammar, self.lexical_grammar))
+            .collect::<Vec<_>>();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /// Get the global FOLLOW set of a non-terminal: the tokens that can follow it
+    /// anywhere in the grammar, including the end of input for the start rule.
+    pub fn follow_set(&self, symbol: Symbol) -> TokenSet {
+        let mut follow_sets = vec![TokenSet::new(); self.syntax_grammar.variables.len()];
+        follow_sets[0].insert(Symbol::end());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, variable) in self.syntax_grammar.variables.iter().enumerate() {
+                for production in &variable.productions {
+                    for (j, step) in production.steps.iter().enumerate() {
+                        if !step.symbol.is_non_terminal() {
+                            continue;
+                        }
+                        let (following_tokens, reaches_end) =
+                            self.sequence_first_set(&production.steps[j + 1..]);
+                        let mut following_tokens = following_tokens.into_owned();
+                        if reaches_end {
+                            following_tokens.insert_all(&follow_sets[i]);
+                        }
+                        changed |= follow_sets[step.symbol.index].insert_all(&following_tokens);
+                    }
+                }
+            }
+        }
+        follow_sets.swap_remove(symbol.index)
+    }
+
+    /// Get the lookaheads of the given item in the closure of the given state.
+    /// These are the tokens that can follow the item's variable in the context
+    /// of that state, which is a subset of the variable's [`Self::follow_set`].
+    pub fn contextual_follow(
+        &mut self,
+        state: &ParseItemSet<'a>,
+        item: &ParseItem<'a>,
+    ) -> Option<TokenSet> {
+        self.transitive_closure(state)
+            .entries
+            .iter()
+            .find(|(entry, _)| entry == item)
+            .map(|(_, lookaheads)| lookaheads.clone())
+    }
+
+    /// Find the productions that can begin with one of the given tokens, for example
+    /// the keywords that start statements. Each production is identified by its
+    /// variable index and its index within that variable.
+    pub fn statement_starter_productions(&self, starters: &TokenSet) -> Vec<(usize, usize)> {
+        let mut result = Vec::new();
+        for (variable_index, variable) in self.syntax_grammar.variables.iter().enumerate() {
+            for (production_index, production) in variable.productions.iter().enumerate() {
+                if let Some(symbol) = production.first_symbol() {
+                    if self.first_sets[&symbol]
+                        .iter()
+                        .any(|token| starters.contains(&token))
+                    {
+                        result.push((variable_index, production_index));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Find pairs of non-terminals whose languages appear to overlap, because they can
+    /// both derive some common string of tokens. This is approximate: pairs are first
+    /// filtered by whether their FIRST sets and their [`Self::ending_tokens`]
+    /// intersect, and then compared on the token sequences they can derive up to
+    /// a small length. Pairs where one variable derives the other through a chain
+    /// of single-step productions are skipped, since their overlap is intended.
+    pub fn mutually_ambiguous_rules(&self) -> Vec<(Symbol, Symbol)> {
+        const MAX_SEQUENCE_LENGTH: usize = 3;
+
+        let variable_count = self.syntax_grammar.variables.len();
+        let mut derived_sequences = vec![HashSet::<Vec<Symbol>>::new(); variable_count];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, variable) in self.syntax_grammar.variables.iter().enumerate() {
+                for production in &variable.productions {
+                    let mut prefixes = vec![Vec::new()];
+                    for step in &production.steps {
+                        let step_sequences = if step.symbol.is_non_terminal() {
+                            derived_sequences[step.symbol.index]
+                                .iter()
+                                .cloned()
+                                .collect::<Vec<_>>()
+                        } else {
+                            vec![vec![step.symbol]]
+                        };
+                        let mut next_prefixes = Vec::new();
+                        for prefix in &prefixes {
+                            for sequence in &step_sequences {
+                                if prefix.len() + sequence.len() <= MAX_SEQUENCE_LENGTH {
+                                    let mut next_prefix = prefix.clone();
+                                    next_prefix.extend_from_slice(sequence);
+                                    next_prefixes.push(next_prefix);
+                                }
+                            }
+                        }
+                        prefixes = next_prefixes;
+                        if prefixes.is_empty() {
+                            break;
+                        }
+                    }
+                    for prefix in prefixes {
+                        changed |= derived_sequences[i].insert(prefix);
+                    }
+                }
+            }
+        }
+
+        let mut unit_derivations = vec![HashSet::new(); variable_count];
+        for (i, reachable) in unit_derivations.iter_mut().enumerate() {
+            let mut stack = vec![i];
+            while let Some(j) = stack.pop() {
+                for production in &self.syntax_grammar.variables[j].productions {
+                    if let [step] = production.steps.as_slice() {
+                        if step.symbol.is_non_terminal() && reachable.insert(step.symbol.index) {
+                            stack.push(step.symbol.index);
+                        }
+                    }
+                }
+            }
+        }
+
+        let nullable_variables = nullable_variables(self.syntax_grammar);
+        let ending_tokens = (0..variable_count)
+            .map(|i| self.ending_tokens_with(Symbol::non_terminal(i), &nullable_variables))
+            .collect::<Vec<_>>();
+
+        let mut result = Vec::new();
+        for i in 0..variable_count {
+            let symbol_i = Symbol::non_terminal(i);
+            for j in (i + 1)..variable_count {
+                if unit_derivations[i].contains(&j) || unit_derivations[j].contains(&i) {
+                    continue;
+                }
+                let symbol_j = Symbol::non_terminal(j);
+                let first_set_j = &self.first_sets[&symbol_j];
+                if !self.first_sets[&symbol_i]
+                    .iter()
+                    .any(|token| first_set_j.contains(&token))
+                {
+                    continue;
+                }
+                if !ending_tokens[i]
+                    .iter()
+                    .any(|token| ending_tokens[j].contains(&token))
+                {
+                    continue;
+                }
+                if derived_sequences[i]
+                    .iter()
+                    .any(|sequence| !sequence.is_empty() && derived_sequences[j].contains(sequence))
+                {
+                    result.push((symbol_i, symbol_j));
+                }
+            }
+        }
+        result
+    }
+
+    /// Compute the set of tokens that can begin the given sequence of steps, looking
+    /// past any zero-width tokens. Also returns whether the sequence consists
+    /// entirely of zero-width tokens, so that whatever follows it can also appear
+    /// at its beginning.
+    pub fn sequence_first_set(&self, steps: &[ProductionStep]) -> (Cow<'_, TokenSet>, bool) {
+        let Some(first_step) = steps.first() else {
+            return (Cow::Owned(TokenSet::new()), true);
+        };
+        let first_set = &self.first_sets[&first_step.symbol];
+        if !self.syntax_grammar.is_zero_width(first_step.symbol) {
+            return (Cow::Borrowed(first_set), false);
+        }
+
+        let mut result = first_set.clone();
+        for step in &steps[1..] {
+            result.insert_all(&self.first_sets[&step.symbol]);
+            if !self.syntax_grammar.is_zero_width(step.symbol) {
+                return (Cow::Owned(result), false);
+            }
+        }
+        (Cow::Owned(result), true)
+    }
+
+    /// Recompute the lookaheads of the items in a closed item set after the
+    /// lookaheads of its core items have changed, without re-deriving its items.
+    /// The derived items' lookaheads are rebuilt from the closure additions of
+    /// the core items, following their `propagates_lookaheads` flags.
+    ///
+    /// Apart from the augmented start item, items at the beginning of their
+    /// productions are assumed to be derived from the set's other items, so this
+    /// must not be used for the item sets of non-terminal extras.
+    pub fn repropagate_lookaheads(&self, set: &mut ParseItemSet<'a>) {
+        let mut core_entries = Vec::new();
+        for (item, lookaheads) in set.entries.iter_mut() {
+            if item.step_index > 0 || item.is_augmented() {
+                core_entries.push((*item, lookaheads.clone()));
+            } else {
+                *lookaheads = TokenSet::new();
+            }
+        }
+        for (item, lookaheads) in core_entries {
+            self.add_closure_additions(set, item, &lookaheads);
+        }
+    }
+
     fn add_item(&self, set: &mut ParseItemSet<'a>, item: ParseItem<'a>, lookaheads: &TokenSet) {
+        self.add_closure_additions(set, item, lookaheads);
+        set.insert(item, lookaheads);
+    }
+
+    /// Describe, step by step, how the transitive closure of the given core item
+    /// set is constructed: which non-terminal of each core item is expanded, which
+    /// items that adds, and which lookaheads those items receive, either from the
+    /// grammar itself or propagated from the core item. The trace ends with the
+    /// complete closure.
+    pub fn trace_closure(&self, core: &ParseItemSet<'a>) -> String {
+        let (syntax_grammar, lexical_grammar) = (self.syntax_grammar, self.lexical_grammar);
+
+        let mut result = String::new();
+        let mut closure = ParseItemSet::default();
+        for (core_item, lookaheads) in &core.entries {
+            writeln!(
+                &mut result,
+                "core item: {}\t{}",
+                ParseItemDisplay(core_item, syntax_grammar, lexical_grammar),
+                TokenSetDisplay(lookaheads, syntax_grammar, lexical_grammar)
+            )
+            .unwrap();
+
+            let items = self
+                .inlines
+                .inlined_productions(core_item.production, core_item.step_index)
+                .map_or_else(
+                    || vec![*core_item],
+                    |productions| {
+                        productions
+                            .map(|production| core_item.substitute_production(production))
+                            .collect()
+                    },
+                );
+            for item in items {
+                if item != *core_item {
+                    writeln!(
+                        &mut result,
+                        "  inlined as: {}",
+                        ParseItemDisplay(&item, syntax_grammar, lexical_grammar)
+                    )
+                    .unwrap();
+                }
+                if let Some(step) = item.step().filter(|step| step.symbol.is_non_terminal()) {
+                    let following_tokens = self.following_tokens(&item, lookaheads);
+                    writeln!(
+                        &mut result,
+                        "  expanding `{}`, which can be followed by {}",
+                        syntax_grammar.variables[step.symbol.index].name,
+                        TokenSetDisplay(&following_tokens, syntax_grammar, lexical_grammar)
+                    )
+                    .unwrap();
+                    for addition in &self.transitive_closure_additions[step.symbol.index] {
+                        write!(
+                            &mut result,
+                            "    added: {}\t{}",
+                            ParseItemDisplay(&addition.item, syntax_grammar, lexical_grammar),
+                            TokenSetDisplay(
+                                &addition.info.lookaheads,
+                                syntax_grammar,
+                                lexical_grammar
+                            )
+                        )
+                        .unwrap();
+                        if addition.info.propagates_lookaheads {
+                            write!(
+                                &mut result,
+                                " + propagated {}",
+                                TokenSetDisplay(&following_tokens, syntax_grammar, lexical_grammar)
+                            )
+                            .unwrap();
+                        }
+                        result.push('\n');
+                    }
+                }
+                self.add_item(&mut closure, item, lookaheads);
+            }
+        }
+
+        writeln!(&mut result, "closure:").unwrap();
+        write!(
+            &mut result,
+            "{}",
+            ParseItemSetDisplay(&closure, syntax_grammar, lexical_grammar)
+        )
+        .unwrap();
+        result
+    }
+
+    // Determine which tokens can follow the given item's next symbol.
+    fn following_tokens<'b>(
+        &'b self,
+        item: &ParseItem,
+        lookaheads: &'b TokenSet,
+    ) -> Cow<'b, TokenSet> {
+        let following_steps = &item.production.steps[item.step_index as usize + 1..];
+        if following_steps.is_empty() {
+            Cow::Borrowed(lookaheads)
+        } else {
+            let (mut following_tokens, reaches_end) = self.sequence_first_set(following_steps);
+            if reaches_end {
+                following_tokens.to_mut().insert_all(lookaheads);
+            }
+            following_tokens
+        }
+    }
+
+    fn add_closure_additions(
+        &self,
+        set: &mut ParseItemSet<'a>,
+        item: ParseItem<'a>,
+        lookaheads: &TokenSet,
+    ) {
         if let Some(step) = item.step() {
             if step.symbol.is_non_terminal() {
-                let next_step = item.successor().step();
-
-                // Determine which tokens can follow this non-terminal.
-                let following_tokens = next_step.map_or(lookaheads, |next_step| {
-                    self.first_sets.get(&next_step.symbol).unwrap()
-                });
+                let following_tokens = self.following_tokens(&item, lookaheads);
 
                 // Use the pre-computed *additions* to expand the non-terminal.
                 for addition in &self.transitive_closure_additions[step.symbol.index] {
                     let lookaheads = set.insert(addition.item, &addition.info.lookaheads);
                     if addition.info.propagates_lookaheads {
-                        lookaheads.insert_all(following_tokens);
+                        lookaheads.insert_all(&following_tokens);
                     }
                 }
             }
         }
-        set.insert(item, lookaheads);
     }
 }
 
@@ -338,3 +1020,645 @@ impl<'a> fmt::Debug for ParseItemSetBuilder<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{
+        ExternalToken, LexicalVariable, Production, SyntaxVariable, VariableType,
+    };
+
+    #[test]
+    fn test_restore_after_transitive_closure() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // program -> expression
+            vec![vec![Symbol::non_terminal(1)]],
+            // expression -> expression '+' expression | number
+            vec![
+                vec![
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                ],
+                vec![Symbol::terminal(1)],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["+", "number"]);
+        let inlines = InlinedProductionMap::default();
+        let mut builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let core = ParseItemSet::with([(
+            ParseItem::start(),
+            [Symbol::end()].into_iter().collect::<TokenSet>(),
+        )]);
+
+        let snapshot = builder.checkpoint();
+        let closure = builder.transitive_closure(&core);
+        builder.restore(snapshot.clone());
+
+        assert_eq!(builder.checkpoint(), snapshot);
+        assert_eq!(builder.transitive_closure(&core), closure);
+    }
+
+    #[test]
+    fn test_follow_set_propagator() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // program -> expression
+            vec![vec![Symbol::non_terminal(1)]],
+            // expression -> expression '+' expression | number
+            vec![
+                vec![
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                ],
+                vec![Symbol::terminal(1)],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["+", "number"]);
+        let inlines = InlinedProductionMap::default();
+        let builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let mut propagator = builder.follow_set_propagator(0);
+        let mut events = Vec::new();
+        while let Some(event) = propagator.step() {
+            events.push((
+                event.variable_index,
+                event.lookaheads.iter().collect::<Vec<_>>(),
+                event.propagates_lookaheads,
+                event.changed,
+            ));
+        }
+        assert!(propagator.is_done());
+
+        // The program propagates its own lookaheads to the expression that it
+        // begins with. Where the expression begins a larger expression, it is
+        // followed by '+'. Once that has been added, adding it again changes
+        // nothing, so the propagation stops.
+        assert_eq!(
+            events,
+            [
+                (0, vec![], true, true),
+                (1, vec![], true, true),
+                (1, vec![Symbol::terminal(0)], false, true),
+                (1, vec![Symbol::terminal(0)], false, false),
+            ]
+        );
+        assert_eq!(
+            propagator.follow_set_info(1),
+            Some(&FollowSetInfo {
+                lookaheads: [Symbol::terminal(0)].into_iter().collect(),
+                propagates_lookaheads: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_reload() {
+        let grammar = |expression_productions: Vec<Vec<Symbol>>| {
+            build_syntax_grammar(vec![
+                // program -> statement
+                vec![vec![Symbol::non_terminal(1)]],
+                // statement -> expression ';' | block
+                vec![
+                    vec![Symbol::non_terminal(2), Symbol::terminal(2)],
+                    vec![Symbol::non_terminal(3)],
+                ],
+                // expression -> ...
+                expression_productions,
+                // block -> '{' '}'
+                vec![vec![Symbol::terminal(3), Symbol::terminal(4)]],
+            ])
+        };
+        // expression -> number
+        let old_grammar = grammar(vec![vec![Symbol::terminal(0)]]);
+        // expression -> number | identifier
+        let new_grammar = grammar(vec![vec![Symbol::terminal(0)], vec![Symbol::terminal(1)]]);
+        let lexical_grammar = build_lexical_grammar(&["number", "identifier", ";", "{", "}"]);
+        let inlines = InlinedProductionMap::default();
+
+        // Only the edited rule and the rules that contain it are recomputed.
+        let mut builder = ParseItemSetBuilder::new(&old_grammar, &lexical_grammar, &inlines);
+        assert_eq!(
+            builder.reload(&new_grammar, &lexical_grammar, &inlines),
+            [
+                Symbol::non_terminal(0),
+                Symbol::non_terminal(1),
+                Symbol::non_terminal(2)
+            ]
+        );
+
+        // The result is the same as building from scratch.
+        let mut expected_builder =
+            ParseItemSetBuilder::new(&new_grammar, &lexical_grammar, &inlines);
+        for i in 0..new_grammar.variables.len() {
+            let symbol = Symbol::non_terminal(i);
+            assert_eq!(
+                builder.first_set(&symbol),
+                expected_builder.first_set(&symbol)
+            );
+            assert_eq!(
+                builder.last_set(&symbol),
+                expected_builder.last_set(&symbol)
+            );
+        }
+        let core = ParseItemSet::with([(
+            ParseItem::start(),
+            [Symbol::end()].into_iter().collect::<TokenSet>(),
+        )]);
+        let closure = builder.transitive_closure(&core);
+        assert_eq!(closure, expected_builder.transitive_closure(&core));
+        for (item, _) in &closure.entries {
+            if !item.is_augmented() {
+                let variable = &new_grammar.variables[item.variable_index as usize];
+                assert!(variable
+                    .productions
+                    .iter()
+                    .any(|production| std::ptr::eq(production, item.production)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_repropagate_lookaheads() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // statement -> 'return' expression | expression ';'
+            vec![
+                vec![Symbol::terminal(2), Symbol::non_terminal(1)],
+                vec![Symbol::non_terminal(1), Symbol::terminal(3)],
+            ],
+            // expression -> expression '+' expression | number
+            vec![
+                vec![
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                ],
+                vec![Symbol::terminal(1)],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["+", "number", "return", ";"]);
+        let inlines = InlinedProductionMap::default();
+        let mut builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let core = |lookaheads: &[Symbol]| {
+            ParseItemSet::with([(
+                ParseItem {
+                    variable_index: 0,
+                    production: &syntax_grammar.variables[0].productions[0],
+                    step_index: 1,
+                    has_preceding_inherited_fields: false,
+                },
+                lookaheads.iter().copied().collect(),
+            )])
+        };
+
+        let mut item_set = builder.transitive_closure(&core(&[Symbol::end()]));
+        let expected = builder.transitive_closure(&core(&[Symbol::terminal(3)]));
+        assert_ne!(item_set, expected);
+
+        for (item, lookaheads) in item_set.entries.iter_mut() {
+            if item.step_index > 0 {
+                *lookaheads = [Symbol::terminal(3)].into_iter().collect();
+            }
+        }
+        builder.repropagate_lookaheads(&mut item_set);
+        assert_eq!(item_set, expected);
+    }
+
+    #[test]
+    fn test_trace_closure() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // statement -> 'return' expression | expression ';'
+            vec![
+                vec![Symbol::terminal(2), Symbol::non_terminal(1)],
+                vec![Symbol::non_terminal(1), Symbol::terminal(3)],
+            ],
+            // expression -> expression '+' expression | number
+            vec![
+                vec![
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                ],
+                vec![Symbol::terminal(1)],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["+", "number", "return", ";"]);
+        let inlines = InlinedProductionMap::default();
+        let mut builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let core = ParseItemSet::with([(
+            ParseItem {
+                variable_index: 0,
+                production: &syntax_grammar.variables[0].productions[0],
+                step_index: 1,
+                has_preceding_inherited_fields: false,
+            },
+            [Symbol::end()].into_iter().collect(),
+        )]);
+        let trace = builder.trace_closure(&core);
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "core item: rule0 → return • rule1\t[EOF]");
+        assert_eq!(
+            lines[1],
+            "  expanding `rule1`, which can be followed by [EOF]"
+        );
+        assert!(lines.contains(&"    added: rule1 → • number\t[+] + propagated [EOF]"));
+        assert!(trace.ends_with(&format!(
+            "closure:\n{}",
+            ParseItemSetDisplay(
+                &builder.transitive_closure(&core),
+                &syntax_grammar,
+                &lexical_grammar
+            )
+        )));
+    }
+
+    #[test]
+    fn test_valid_next_tokens() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // statement -> expression ';'
+            vec![vec![Symbol::non_terminal(1), Symbol::terminal(2)]],
+            // expression -> expression '+' expression | number
+            vec![
+                vec![
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                ],
+                vec![Symbol::terminal(1)],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["+", "number", ";"]);
+        let inlines = InlinedProductionMap::default();
+        let builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let production = &syntax_grammar.variables[0].productions[0];
+        let item = |step_index| ParseItem {
+            variable_index: 0,
+            production,
+            step_index,
+            has_preceding_inherited_fields: false,
+        };
+        let lookaheads = [Symbol::end()].into_iter().collect::<TokenSet>();
+
+        assert_eq!(
+            builder.valid_next_tokens(&item(0), &lookaheads),
+            [Symbol::terminal(1)].into_iter().collect()
+        );
+        assert_eq!(
+            builder.valid_next_tokens(&item(1), &lookaheads),
+            [Symbol::terminal(2)].into_iter().collect()
+        );
+        assert_eq!(builder.valid_next_tokens(&item(2), &lookaheads), lookaheads);
+    }
+
+    #[test]
+    fn test_ending_tokens() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // program -> statement
+            vec![vec![Symbol::non_terminal(1)]],
+            // statement -> expression terminator
+            vec![vec![Symbol::non_terminal(2), Symbol::non_terminal(3)]],
+            // expression -> number | '(' expression ')'
+            vec![
+                vec![Symbol::terminal(0)],
+                vec![
+                    Symbol::terminal(1),
+                    Symbol::non_terminal(2),
+                    Symbol::terminal(2),
+                ],
+            ],
+            // terminator -> ';' | <empty>
+            vec![vec![Symbol::terminal(3)], vec![]],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["number", "(", ")", ";"]);
+        let inlines = InlinedProductionMap::default();
+        let builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        // The LAST set stops at the optional terminator, but a statement can also
+        // end with the end of its expression.
+        let statement = Symbol::non_terminal(1);
+        assert_eq!(
+            builder.last_set(&statement).iter().collect::<Vec<_>>(),
+            [Symbol::terminal(3)]
+        );
+        assert_eq!(
+            builder.ending_tokens(statement).iter().collect::<Vec<_>>(),
+            [
+                Symbol::terminal(0),
+                Symbol::terminal(2),
+                Symbol::terminal(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mutually_ambiguous_rules() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // program -> variable_reference | type_reference | number_literal
+            vec![
+                vec![Symbol::non_terminal(1)],
+                vec![Symbol::non_terminal(2)],
+                vec![Symbol::non_terminal(3)],
+            ],
+            // variable_reference -> identifier | '$' identifier
+            vec![
+                vec![Symbol::terminal(0)],
+                vec![Symbol::terminal(2), Symbol::terminal(0)],
+            ],
+            // type_reference -> identifier
+            vec![vec![Symbol::terminal(0)]],
+            // number_literal -> number
+            vec![vec![Symbol::terminal(1)]],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["identifier", "number", "$"]);
+        let inlines = InlinedProductionMap::default();
+        let builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        // Both references can consist of a bare identifier. The program derives
+        // each of its children directly, so it is not reported.
+        assert_eq!(
+            builder.mutually_ambiguous_rules(),
+            [(Symbol::non_terminal(1), Symbol::non_terminal(2))]
+        );
+    }
+
+    #[test]
+    fn test_first_set_lazy() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // statement -> expression ';'
+            vec![vec![Symbol::non_terminal(1), Symbol::terminal(2)]],
+            // expression -> expression '+' expression | number | '(' statement ')'
+            vec![
+                vec![
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                ],
+                vec![Symbol::terminal(1)],
+                vec![
+                    Symbol::terminal(3),
+                    Symbol::non_terminal(0),
+                    Symbol::terminal(4),
+                ],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["+", "number", ";", "(", ")"]);
+        let inlines = InlinedProductionMap::default();
+        let eager_builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+        let mut lazy_builder = LazyBuilder::new(&syntax_grammar, &lexical_grammar);
+
+        let symbols = (0..2)
+            .map(Symbol::non_terminal)
+            .chain((0..5).map(Symbol::terminal))
+            .collect::<Vec<_>>();
+        for symbol in symbols {
+            assert_eq!(
+                lazy_builder
+                    .first_set_lazy(symbol)
+                    .iter()
+                    .collect::<Vec<_>>(),
+                eager_builder.first_set(&symbol).iter().collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(
+            lazy_builder
+                .first_set_lazy(Symbol::non_terminal(0))
+                .iter()
+                .collect::<Vec<_>>(),
+            [Symbol::terminal(1), Symbol::terminal(3)]
+        );
+    }
+
+    #[test]
+    fn test_contextual_follow() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // program -> '(' expression ')' | expression ';'
+            vec![
+                vec![
+                    Symbol::terminal(0),
+                    Symbol::non_terminal(1),
+                    Symbol::terminal(1),
+                ],
+                vec![Symbol::non_terminal(1), Symbol::terminal(2)],
+            ],
+            // expression -> 'x'
+            vec![vec![Symbol::terminal(3)]],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["(", ")", ";", "x"]);
+        let inlines = InlinedProductionMap::default();
+        let mut builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let follow_set = builder.follow_set(Symbol::non_terminal(1));
+        assert_eq!(
+            follow_set,
+            [Symbol::terminal(1), Symbol::terminal(2)]
+                .into_iter()
+                .collect()
+        );
+
+        // Inside of the parentheses, the expression can only be followed by the
+        // closing parenthesis.
+        let state = ParseItemSet::with([(
+            ParseItem {
+                variable_index: 0,
+                production: &syntax_grammar.variables[0].productions[0],
+                step_index: 1,
+                has_preceding_inherited_fields: false,
+            },
+            [Symbol::end()].into_iter().collect::<TokenSet>(),
+        )]);
+        let item = ParseItem {
+            variable_index: 1,
+            production: &syntax_grammar.variables[1].productions[0],
+            step_index: 0,
+            has_preceding_inherited_fields: false,
+        };
+        let contextual_follow = builder.contextual_follow(&state, &item).unwrap();
+        assert_eq!(
+            contextual_follow,
+            [Symbol::terminal(1)].into_iter().collect()
+        );
+        assert!(contextual_follow
+            .iter()
+            .all(|token| follow_set.contains(&token)));
+        assert_ne!(contextual_follow, follow_set);
+    }
+
+    #[test]
+    fn test_statement_starter_productions() {
+        let syntax_grammar = build_syntax_grammar(vec![
+            // program -> statement
+            vec![vec![Symbol::non_terminal(1)]],
+            // statement -> 'if' expression | 'return' expression | expression ';'
+            vec![
+                vec![Symbol::terminal(0), Symbol::non_terminal(2)],
+                vec![Symbol::terminal(1), Symbol::non_terminal(2)],
+                vec![Symbol::non_terminal(2), Symbol::terminal(2)],
+            ],
+            // expression -> identifier | '(' expression ')'
+            vec![
+                vec![Symbol::terminal(3)],
+                vec![
+                    Symbol::terminal(4),
+                    Symbol::non_terminal(2),
+                    Symbol::terminal(5),
+                ],
+            ],
+        ]);
+        let lexical_grammar = build_lexical_grammar(&["if", "return", ";", "identifier", "(", ")"]);
+        let inlines = InlinedProductionMap::default();
+        let builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let keywords = [Symbol::terminal(0), Symbol::terminal(1)]
+            .into_iter()
+            .collect::<TokenSet>();
+        assert_eq!(
+            builder.statement_starter_productions(&keywords),
+            [(0, 0), (1, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_zero_width_external_tokens() {
+        let mut syntax_grammar = build_syntax_grammar(vec![
+            // program -> block
+            vec![vec![Symbol::non_terminal(1)]],
+            // block -> _indent statement _dedent ';'
+            vec![vec![
+                Symbol::external(0),
+                Symbol::non_terminal(2),
+                Symbol::external(1),
+                Symbol::terminal(1),
+            ]],
+            // statement -> identifier
+            vec![vec![Symbol::terminal(0)]],
+        ]);
+        syntax_grammar.external_tokens = ["_indent", "_dedent"]
+            .iter()
+            .map(|name| ExternalToken {
+                name: name.to_string(),
+                kind: VariableType::Hidden,
+                corresponding_internal_token: None,
+                zero_width: true,
+                precedence: 0,
+            })
+            .collect();
+        let lexical_grammar = build_lexical_grammar(&["identifier", ";"]);
+        let inlines = InlinedProductionMap::default();
+        let mut builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        // The FIRST sets include the tokens after the indentation marker.
+        assert_eq!(
+            *builder.first_set(&Symbol::non_terminal(0)),
+            [Symbol::external(0), Symbol::terminal(0)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            *builder.last_set(&Symbol::non_terminal(1)),
+            [Symbol::terminal(1)].into_iter().collect()
+        );
+
+        // The statement can be followed by either the dedent or the semicolon.
+        let item_set = builder.transitive_closure(&ParseItemSet::with([(
+            ParseItem {
+                variable_index: 1,
+                production: &syntax_grammar.variables[1].productions[0],
+                step_index: 1,
+                has_preceding_inherited_fields: false,
+            },
+            [Symbol::end()].into_iter().collect::<TokenSet>(),
+        )]));
+        let (_, statement_lookaheads) = item_set
+            .entries
+            .iter()
+            .find(|(item, _)| item.variable_index == 2)
+            .unwrap();
+        assert_eq!(
+            *statement_lookaheads,
+            [Symbol::external(1), Symbol::terminal(1)]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_expected_token_names() {
+        let mut syntax_grammar = build_syntax_grammar(vec![
+            // block -> '{' statement '}'
+            vec![vec![
+                Symbol::terminal(0),
+                Symbol::non_terminal(1),
+                Symbol::terminal(3),
+            ]],
+            // statement -> 'x' ';' | 'x'
+            vec![
+                vec![Symbol::terminal(1), Symbol::terminal(2)],
+                vec![Symbol::terminal(1)],
+            ],
+        ]);
+        syntax_grammar.extra_symbols = vec![Symbol::terminal(4)];
+        let lexical_grammar = build_lexical_grammar(&["{", "x", ";", "}", "comment"]);
+        let inlines = InlinedProductionMap::default();
+        let mut builder = ParseItemSetBuilder::new(&syntax_grammar, &lexical_grammar, &inlines);
+
+        let lookaheads = [Symbol::terminal(3), Symbol::terminal(4)]
+            .into_iter()
+            .collect::<TokenSet>();
+        let state = ParseItemSet::with(syntax_grammar.variables[1].productions.iter().map(
+            |production| {
+                (
+                    ParseItem {
+                        variable_index: 1,
+                        production,
+                        step_index: 1,
+                        has_preceding_inherited_fields: false,
+                    },
+                    lookaheads.clone(),
+                )
+            },
+        ));
+        assert_eq!(builder.expected_token_names(&state), ["';'", "'}'"]);
+    }
+
+    fn build_syntax_grammar(variables: Vec<Vec<Vec<Symbol>>>) -> SyntaxGrammar {
+        SyntaxGrammar {
+            variables: variables
+                .into_iter()
+                .enumerate()
+                .map(|(i, productions)| SyntaxVariable {
+                    name: format!("rule{i}"),
+                    kind: VariableType::Named,
+                    productions: productions
+                        .into_iter()
+                        .map(|symbols| Production {
+                            dynamic_precedence: 0,
+                            steps: symbols.into_iter().map(ProductionStep::new).collect(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn build_lexical_grammar(names: &[&str]) -> LexicalGrammar {
+        LexicalGrammar {
+            nfa: Default::default(),
+            variables: names
+                .iter()
+                .map(|name| LexicalVariable {
+                    name: name.to_string(),
+                    kind: VariableType::Anonymous,
+                    implicit_precedence: 0,
+                    start_state: 0,
+                    case_insensitive: false,
+                    lookahead: None,
+                })
+                .collect(),
+        }
+    }
+}
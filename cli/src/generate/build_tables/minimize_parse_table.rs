@@ -193,6 +193,7 @@ impl<'a> Minimizer<'a> {
         }
 
         self.parse_table.states = new_states;
+        self.renumber_resolutions(|state_id| Some(group_ids_by_state_id[state_id]));
     }
 
     fn states_conflict(
@@ -452,6 +453,9 @@ impl<'a> Minimizer<'a> {
             }
             original_state_id += 1;
         }
+        self.renumber_resolutions(|state_id| {
+            state_usage_map[state_id].then(|| state_replacement_map[state_id])
+        });
     }
 
     fn reorder_states_by_descending_size(&mut self) {
@@ -485,5 +489,25 @@ impl<'a> Minimizer<'a> {
                 state
             })
             .collect();
+        self.renumber_resolutions(|state_id| Some(new_ids_by_old_id[state_id]));
+    }
+
+    /// Update the state ids in the resolution log after the states have been
+    /// renumbered, dropping the events for states that were removed, and the
+    /// duplicates that arise when states are merged.
+    fn renumber_resolutions(
+        &mut self,
+        new_state_id: impl Fn(ParseStateId) -> Option<ParseStateId>,
+    ) {
+        let mut resolutions = Vec::with_capacity(self.parse_table.resolutions.len());
+        for mut event in mem::take(&mut self.parse_table.resolutions) {
+            if let Some(state_id) = new_state_id(event.state_id) {
+                event.state_id = state_id;
+                if !resolutions.contains(&event) {
+                    resolutions.push(event);
+                }
+            }
+        }
+        self.parse_table.resolutions = resolutions;
     }
 }
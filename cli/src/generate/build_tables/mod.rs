@@ -1,14 +1,15 @@
 pub mod build_lex_table;
 pub mod build_parse_table;
 mod coincident_tokens;
-mod item;
-mod item_set_builder;
+pub mod item;
+pub mod item_set_builder;
 mod minimize_parse_table;
-mod token_conflicts;
+pub mod token_conflicts;
 
 use self::build_lex_table::build_lex_table;
 use self::build_parse_table::{build_parse_table, ParseStateInfo};
 use self::coincident_tokens::CoincidentTokenIndex;
+use self::item::symbol_name;
 use self::minimize_parse_table::minimize_parse_table;
 use self::token_conflicts::TokenConflictMap;
 use crate::generate::grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar};
@@ -16,10 +17,11 @@ use crate::generate::nfa::NfaCursor;
 use crate::generate::node_types::VariableInfo;
 use crate::generate::rules::{AliasMap, Symbol, SymbolType, TokenSet};
 use crate::generate::tables::{LexTable, ParseAction, ParseTable, ParseTableEntry};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::info;
 use std::collections::{BTreeSet, HashMap};
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_tables(
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
@@ -27,11 +29,21 @@ pub fn build_tables(
     variable_info: &[VariableInfo],
     inlines: &InlinedProductionMap,
     report_symbol_name: Option<&str>,
+    recovery: bool,
+    strict_token_order: bool,
 ) -> Result<(ParseTable, LexTable, LexTable, Option<Symbol>)> {
     let (mut parse_table, following_tokens, parse_state_info) =
         build_parse_table(syntax_grammar, lexical_grammar, inlines, variable_info)?;
     let token_conflict_map = TokenConflictMap::new(lexical_grammar, following_tokens);
     let coincident_token_index = CoincidentTokenIndex::new(&parse_table, lexical_grammar);
+    if strict_token_order {
+        check_order_dependent_tokens(
+            syntax_grammar,
+            lexical_grammar,
+            &token_conflict_map,
+            &coincident_token_index,
+        )?;
+    }
     let keywords = identify_keywords(
         lexical_grammar,
         &parse_table,
@@ -39,14 +51,18 @@ pub fn build_tables(
         &token_conflict_map,
         &coincident_token_index,
     );
-    populate_error_state(
-        &mut parse_table,
-        syntax_grammar,
-        lexical_grammar,
-        &coincident_token_index,
-        &token_conflict_map,
-        &keywords,
-    );
+    // Without error recovery, the error state has no actions, so the runtime can
+    // only recover from an error by skipping tokens.
+    if recovery {
+        populate_error_state(
+            &mut parse_table,
+            syntax_grammar,
+            lexical_grammar,
+            &coincident_token_index,
+            &token_conflict_map,
+            &keywords,
+        );
+    }
     populate_used_symbols(&mut parse_table, syntax_grammar, lexical_grammar);
     minimize_parse_table(
         &mut parse_table,
@@ -84,6 +100,37 @@ pub fn build_tables(
     ))
 }
 
+// Conflicts between parse actions are never resolved silently: without
+// precedence, associativity, or a declared conflict, they are reported as
+// errors. But when two tokens match the same string in the same parse state,
+// the lexer falls back to preferring the token that is defined first. With
+// `strict_token_order`, that fallback is reported as an error too. This only
+// concerns tokens; it doesn't change how parse conflicts are resolved.
+fn check_order_dependent_tokens(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    token_conflict_map: &TokenConflictMap,
+    coincident_token_index: &CoincidentTokenIndex,
+) -> Result<()> {
+    let n = lexical_grammar.variables.len();
+    for i in 0..n {
+        for j in i + 1..n {
+            let (preferred, other) = (Symbol::terminal(i), Symbol::terminal(j));
+            if token_conflict_map.is_preferred_by_order(i, j)
+                && coincident_token_index.contains(preferred, other)
+            {
+                return Err(anyhow!(
+                    "Tokens {} and {} can match the same string in the same state, and {} is only preferred because it is defined first. Use precedence to choose between them.",
+                    symbol_name(&preferred, syntax_grammar, lexical_grammar),
+                    symbol_name(&other, syntax_grammar, lexical_grammar),
+                    symbol_name(&preferred, syntax_grammar, lexical_grammar),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
 fn populate_error_state(
     parse_table: &mut ParseTable,
     syntax_grammar: &SyntaxGrammar,
@@ -462,6 +509,21 @@ fn report_state_info<'a>(
                 "\nitems:\n{}",
                 self::item::ParseItemSetDisplay(item_set, syntax_grammar, lexical_grammar,),
             );
+            eprintln!("actions:");
+            for (symbol, entry) in &parse_table.states[state_index].terminal_entries {
+                eprint!("{}\t", symbol_name(symbol, syntax_grammar, lexical_grammar));
+                for (i, action) in entry.actions.iter().enumerate() {
+                    if i > 0 {
+                        eprint!(" ");
+                    }
+                    eprint!(
+                        "{}",
+                        self::item::ParseActionDisplay(action, syntax_grammar, lexical_grammar)
+                    );
+                }
+                eprintln!();
+            }
+            eprintln!();
         }
     }
 }
@@ -475,3 +537,511 @@ fn all_chars_are_alphabetical(cursor: &NfaCursor) -> bool {
         }
     })
 }
+
+/// The grammars and tables that result from preparing a grammar and building
+/// its tables in the default way: with error recovery, and without a conflict
+/// report or strict token checks.
+#[cfg(test)]
+pub struct TestTables {
+    pub syntax_grammar: SyntaxGrammar,
+    pub lexical_grammar: LexicalGrammar,
+    pub simple_aliases: AliasMap,
+    pub parse_table: ParseTable,
+    pub main_lex_table: LexTable,
+    pub keyword_lex_table: LexTable,
+    pub keyword_capture_token: Option<Symbol>,
+}
+
+#[cfg(test)]
+pub fn build_test_tables(grammar: &crate::generate::grammars::InputGrammar) -> TestTables {
+    let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
+        crate::generate::prepare_grammar::prepare_grammar(grammar).unwrap();
+    let variable_info = crate::generate::node_types::get_variable_info(
+        &syntax_grammar,
+        &lexical_grammar,
+        &simple_aliases,
+    )
+    .unwrap();
+    let (parse_table, main_lex_table, keyword_lex_table, keyword_capture_token) = build_tables(
+        &syntax_grammar,
+        &lexical_grammar,
+        &simple_aliases,
+        &variable_info,
+        &inlines,
+        None,
+        true,
+        false,
+    )
+    .unwrap();
+    TestTables {
+        syntax_grammar,
+        lexical_grammar,
+        simple_aliases,
+        parse_table,
+        main_lex_table,
+        keyword_lex_table,
+        keyword_capture_token,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::prepare_grammar::prepare_grammar;
+    use crate::generate::rules::{Precedence, Rule};
+    use crate::generate::tables::GotoAction;
+
+    #[test]
+    fn test_valid_external_tokens() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::seq(vec![Rule::string("cat"), Rule::named("heredoc")]),
+                        Rule::named("word"),
+                    ]),
+                },
+                Variable {
+                    name: "word".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            external_tokens: vec![Rule::named("heredoc")],
+            ..Default::default()
+        };
+        let TestTables {
+            lexical_grammar,
+            parse_table,
+            ..
+        } = build_test_tables(&grammar);
+
+        // The heredoc is only valid after the `cat` keyword.
+        let cat = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "cat")
+                .unwrap(),
+        );
+        let action_table = parse_table.action_table().unwrap();
+        let ParseAction::Shift { state, .. } = action_table[1][&cat] else {
+            panic!("expected a shift action");
+        };
+        assert!(parse_table.valid_external_tokens(1).is_empty());
+        assert_eq!(
+            *parse_table.valid_external_tokens(state),
+            [Symbol::external(0)].into_iter().collect()
+        );
+        for state_id in 0..parse_table.states.len() {
+            assert_eq!(
+                parse_table.valid_external_tokens(state_id).is_empty(),
+                !parse_table.states[state_id]
+                    .terminal_entries
+                    .contains_key(&Symbol::external(0))
+            );
+        }
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_tokens_preferred_by_order() {
+        let grammar = |identifier_precedence| InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![Rule::named("int"), Rule::named("identifier")]),
+                },
+                Variable {
+                    name: "int".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::token(Rule::prec(
+                        Precedence::Integer(identifier_precedence),
+                        Rule::pattern("[a-z0-9]+", ""),
+                    )),
+                },
+            ],
+            ..Default::default()
+        };
+        let build = |grammar: &InputGrammar, strict| {
+            let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+                prepare_grammar(grammar).unwrap();
+            let variable_info =
+                get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+            build_tables(
+                &syntax_grammar,
+                &lexical_grammar,
+                &default_aliases,
+                &variable_info,
+                &inlines,
+                None,
+                true,
+                strict,
+            )
+            .map(|_| ())
+        };
+
+        // Both tokens match a string of digits at the start of the program, and
+        // without precedence, `int` wins because it is defined first.
+        let ambiguous_grammar = grammar(0);
+        build(&ambiguous_grammar, false).unwrap();
+        assert_eq!(
+            build(&ambiguous_grammar, true).unwrap_err().to_string(),
+            "Tokens int and identifier can match the same string in the same state, and int is only preferred because it is defined first. Use precedence to choose between them.",
+        );
+
+        // With precedence, the choice no longer depends on the order of the rules.
+        build(&grammar(-1), true).unwrap();
+    }
+
+    #[test]
+    fn test_external_token_valid_states() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::named("string"),
+                        Rule::named("word"),
+                    ])),
+                },
+                Variable {
+                    name: "string".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("\""),
+                        Rule::repeat(Rule::named("string_content")),
+                        Rule::string("\""),
+                    ]),
+                },
+                Variable {
+                    name: "word".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            external_tokens: vec![Rule::named("string_content")],
+            ..Default::default()
+        };
+        let TestTables {
+            lexical_grammar,
+            parse_table,
+            ..
+        } = build_test_tables(&grammar);
+
+        // Apart from the error recovery state, which allows every token, the
+        // string content is only valid inside of a string, where words are not.
+        let quote = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "\"")
+                .unwrap(),
+        );
+        let word = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "word")
+                .unwrap(),
+        );
+        let valid_states = parse_table.external_token_valid_states(Symbol::external(0));
+        assert_eq!(valid_states[0], 0);
+        for state_id in &valid_states[1..] {
+            assert!(!parse_table.states[*state_id]
+                .terminal_entries
+                .contains_key(&word));
+        }
+
+        let Some(ParseAction::Shift { state, .. }) = parse_table.states[1].terminal_entries[&quote]
+            .actions
+            .last()
+        else {
+            panic!("expected a shift action");
+        };
+        assert!(!valid_states.contains(&1));
+        assert!(valid_states.contains(state));
+    }
+
+    #[test]
+    fn test_subtree_reuse_map() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("word"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "word".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let TestTables {
+            syntax_grammar,
+            parse_table,
+            ..
+        } = build_test_tables(&grammar);
+
+        let statement = Symbol::non_terminal(
+            syntax_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "statement")
+                .unwrap(),
+        );
+        let reuse_map = parse_table.build_subtree_reuse_map();
+        let states = &reuse_map[&statement];
+
+        // A statement can be reused at the start of the program, and every
+        // listed state has a goto on the statement.
+        assert!(states.contains(&1));
+        for state_id in states {
+            assert!(matches!(
+                parse_table.states[*state_id].nonterminal_entries[&statement],
+                GotoAction::Goto(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_ordered_external_tokens() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![
+                    Rule::named("string_start"),
+                    Rule::named("heredoc_start"),
+                ]),
+            }],
+            external_tokens: vec![
+                Rule::named("string_start"),
+                Rule::prec(Precedence::Integer(1), Rule::named("heredoc_start")),
+            ],
+            ..Default::default()
+        };
+        let TestTables {
+            syntax_grammar,
+            parse_table,
+            ..
+        } = build_test_tables(&grammar);
+
+        // Both tokens are valid at the start of the program. The heredoc start is
+        // declared later, but is tried first because of its precedence.
+        assert_eq!(syntax_grammar.external_tokens[1].name, "heredoc_start");
+        assert_eq!(
+            parse_table.ordered_external_tokens(1, &syntax_grammar),
+            [Symbol::external(1), Symbol::external(0)]
+        );
+    }
+
+    #[test]
+    fn test_resolution_log_refers_to_minimized_states() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "expression".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![
+                    Rule::prec_left(
+                        Precedence::Integer(1),
+                        Rule::seq(vec![
+                            Rule::named("expression"),
+                            Rule::string("+"),
+                            Rule::named("expression"),
+                        ]),
+                    ),
+                    Rule::prec_left(
+                        Precedence::Integer(2),
+                        Rule::seq(vec![
+                            Rule::named("expression"),
+                            Rule::string("*"),
+                            Rule::named("expression"),
+                        ]),
+                    ),
+                    Rule::string("x"),
+                ]),
+            }],
+            ..Default::default()
+        };
+        let TestTables { parse_table, .. } = build_test_tables(&grammar);
+
+        // Every event names a state of the minimized table that has an entry for
+        // the event's lookahead, and no event is listed twice.
+        let events = parse_table.resolution_log();
+        assert!(!events.is_empty());
+        for (i, event) in events.iter().enumerate() {
+            assert!(parse_table.states[event.state_id]
+                .terminal_entries
+                .contains_key(&event.lookahead));
+            assert!(!events[..i].contains(event));
+        }
+    }
+
+    #[test]
+    fn test_viable_prefix() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("("),
+                        Rule::string("x"),
+                        Rule::string(")"),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let TestTables {
+            lexical_grammar,
+            parse_table,
+            ..
+        } = build_test_tables(&grammar);
+
+        let tokens = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| {
+                    Symbol::terminal(
+                        lexical_grammar
+                            .variables
+                            .iter()
+                            .position(|v| v.name == *name)
+                            .unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let dfa = parse_table.viable_prefix_states();
+        assert_eq!(dfa.viable_prefix(&tokens(&["(", "x", ")", ";"])).length, 4);
+        assert_eq!(dfa.viable_prefix(&tokens(&["(", "x"])).length, 2);
+        assert_eq!(dfa.viable_prefix(&tokens(&["(", "x", "x", ";"])).length, 2);
+
+        // The error is detected after `expression` has been reduced.
+        let prefix = dfa.viable_prefix(&tokens(&["(", "x", ")", ")"]));
+        assert_eq!(prefix.length, 3);
+        assert!(parse_table.states[prefix.state_id]
+            .terminal_entries
+            .contains_key(&tokens(&[";"])[0]));
+    }
+
+    #[test]
+    fn test_ambiguity_metrics() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "expression".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![
+                    Rule::seq(vec![
+                        Rule::named("expression"),
+                        Rule::string("+"),
+                        Rule::named("expression"),
+                    ]),
+                    Rule::string("x"),
+                ]),
+            }],
+            expected_conflicts: vec![vec!["expression".to_string()]],
+            ..Default::default()
+        };
+        let TestTables { parse_table, .. } = build_test_tables(&grammar);
+
+        // After `expression + expression`, the parser can either reduce or shift
+        // another `+`.
+        let metrics = parse_table.ambiguity_metrics();
+        assert_eq!(metrics.conflicting_state_count, 1);
+        assert_eq!(
+            metrics.conflicts_per_state,
+            1.0 / parse_table.states.len() as f64
+        );
+        assert_eq!(
+            metrics.most_conflicted_rule,
+            Some((Symbol::non_terminal(0), 1))
+        );
+    }
+
+    #[test]
+    fn test_build_tables_without_recovery() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::choice(vec![Rule::string("x"), Rule::string("y")]),
+                        Rule::string(";"),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let entry_count = |recovery| {
+            let (parse_table, ..) = build_tables(
+                &syntax_grammar,
+                &lexical_grammar,
+                &default_aliases,
+                &variable_info,
+                &inlines,
+                None,
+                recovery,
+                false,
+            )
+            .unwrap();
+            let error_state_entry_count = parse_table.states[0].terminal_entries.len()
+                + parse_table.states[0].nonterminal_entries.len();
+            let total_entry_count = parse_table
+                .states
+                .iter()
+                .map(|state| state.terminal_entries.len() + state.nonterminal_entries.len())
+                .sum::<usize>();
+            (error_state_entry_count, total_entry_count)
+        };
+
+        // The error state can recover on every token, and the end of input.
+        let (error_state_entry_count, total_entry_count) = entry_count(true);
+        assert_eq!(error_state_entry_count, lexical_grammar.variables.len() + 1);
+
+        // Without recovery, the error state is empty, and the other states are
+        // unchanged.
+        let (error_state_entry_count_without_recovery, total_entry_count_without_recovery) =
+            entry_count(false);
+        assert_eq!(error_state_entry_count_without_recovery, 0);
+        assert_eq!(
+            total_entry_count_without_recovery,
+            total_entry_count - error_state_entry_count
+        );
+    }
+}
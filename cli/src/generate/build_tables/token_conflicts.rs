@@ -1,7 +1,8 @@
 use crate::generate::build_tables::item::TokenSetDisplay;
 use crate::generate::grammars::{LexicalGrammar, SyntaxGrammar};
 use crate::generate::nfa::{CharacterSet, NfaCursor, NfaTransition};
-use crate::generate::rules::TokenSet;
+use crate::generate::rules::{Symbol, TokenSet};
+use crate::generate::tables::{ParseStateId, ParseTable};
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt;
@@ -19,12 +20,22 @@ struct TokenConflictStatus {
 pub struct TokenConflictMap<'a> {
     n: usize,
     status_matrix: Vec<TokenConflictStatus>,
+    order_dependent_pairs: HashSet<(usize, usize)>,
     following_tokens: Vec<TokenSet>,
     starting_chars_by_index: Vec<CharacterSet>,
     following_chars_by_index: Vec<CharacterSet>,
     grammar: &'a LexicalGrammar,
 }
 
+/// A pair of tokens that are both valid in some parse state and that match some
+/// of the same strings, so that the lexer must choose between them. The tokens
+/// are ordered by index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LexAmbiguity {
+    pub state_id: ParseStateId,
+    pub tokens: (Symbol, Symbol),
+}
+
 impl<'a> TokenConflictMap<'a> {
     /// Create a token conflict map based on a lexical grammar, which describes the structure
     /// each token, and a `following_token` map, which indicates which tokens may be appear
@@ -39,17 +50,23 @@ impl<'a> TokenConflictMap<'a> {
 
         let n = grammar.variables.len();
         let mut status_matrix = vec![TokenConflictStatus::default(); n * n];
+        let mut order_dependent_pairs = HashSet::new();
         for i in 0..grammar.variables.len() {
             for j in 0..i {
-                let status = compute_conflict_status(&mut cursor, grammar, &following_chars, i, j);
-                status_matrix[matrix_index(n, i, j)] = status.0;
-                status_matrix[matrix_index(n, j, i)] = status.1;
+                let (status_i, status_j, is_order_dependent) =
+                    compute_conflict_status(&mut cursor, grammar, &following_chars, i, j);
+                status_matrix[matrix_index(n, i, j)] = status_i;
+                status_matrix[matrix_index(n, j, i)] = status_j;
+                if is_order_dependent {
+                    order_dependent_pairs.insert((j, i));
+                }
             }
         }
 
         TokenConflictMap {
             n,
             status_matrix,
+            order_dependent_pairs,
             following_tokens,
             starting_chars_by_index: starting_chars,
             following_chars_by_index: following_chars,
@@ -76,6 +93,13 @@ impl<'a> TokenConflictMap<'a> {
         self.status_matrix[matrix_index(self.n, i, j)].matches_same_string
     }
 
+    /// Do tokens `i` and `j` match some of the same strings, where the token that
+    /// is preferred is chosen only because it is defined earlier in the grammar,
+    /// and not because of precedence?
+    pub fn is_preferred_by_order(&self, i: usize, j: usize) -> bool {
+        self.order_dependent_pairs.contains(&(i.min(j), i.max(j)))
+    }
+
     pub fn does_conflict(&self, i: usize, j: usize) -> bool {
         let entry = &self.status_matrix[matrix_index(self.n, i, j)];
         entry.does_match_valid_continuation
@@ -103,6 +127,15 @@ impl<'a> TokenConflictMap<'a> {
             || status.does_match_continuation
     }
 
+    /// Can terminal `a` be immediately followed by terminal `b` in some valid parse?
+    /// This is an over-approximation: it may be true for tokens that never actually
+    /// appear next to each other, but never false for tokens that do. The tokens
+    /// that follow external tokens aren't tracked, so any pair that involves
+    /// something other than internal terminals is assumed to be adjacent.
+    pub fn can_be_adjacent(&self, a: Symbol, b: Symbol) -> bool {
+        !a.is_terminal() || !b.is_terminal() || self.following_tokens[a.index].contains(&b)
+    }
+
     pub fn prefer_token(grammar: &LexicalGrammar, left: (i32, usize), right: (i32, usize)) -> bool {
         match left.0.cmp(&right.0) {
             Ordering::Less => false,
@@ -144,6 +177,56 @@ impl<'a> TokenConflictMap<'a> {
     }
 }
 
+/// Find the parse states in which two valid lookahead tokens match some of the
+/// same strings. Unlike parse conflicts, these are resolved silently by the
+/// lexer, which picks the token that is preferred by precedence or length.
+pub fn lexical_ambiguities(
+    parse_table: &ParseTable,
+    token_conflict_map: &TokenConflictMap,
+) -> Vec<LexAmbiguity> {
+    let mut result = Vec::new();
+    for (state_id, state) in parse_table.states.iter().enumerate() {
+        let mut tokens = state
+            .terminal_entries
+            .keys()
+            .filter(|symbol| symbol.is_terminal())
+            .copied()
+            .collect::<Vec<_>>();
+        tokens.sort_unstable();
+        for (i, left) in tokens.iter().enumerate() {
+            for right in &tokens[i + 1..] {
+                if token_conflict_map.does_match_same_string(left.index, right.index)
+                    || token_conflict_map.does_match_same_string(right.index, left.index)
+                {
+                    result.push(LexAmbiguity {
+                        state_id,
+                        tokens: (*left, *right),
+                    });
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Find the tokens that the lexer can never produce when some other token is
+/// also valid, because every string that they match is also matched by that
+/// other token, which is preferred by precedence. Such a token is dead unless
+/// it is valid in some parse state where none of the tokens that shadow it are.
+pub fn shadowed_tokens(token_conflict_map: &TokenConflictMap) -> Vec<Symbol> {
+    (0..token_conflict_map.n)
+        .filter(|i| {
+            (0..token_conflict_map.n).any(|j| {
+                *i != j
+                    && !token_conflict_map.does_match_different_string(*i, j)
+                    && !token_conflict_map.does_match_same_string(*i, j)
+                    && token_conflict_map.does_match_same_string(j, *i)
+            })
+        })
+        .map(Symbol::terminal)
+        .collect()
+}
+
 impl<'a> fmt::Debug for TokenConflictMap<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "TokenConflictMap {{")?;
@@ -241,7 +324,7 @@ fn compute_conflict_status(
     following_chars: &[CharacterSet],
     i: usize,
     j: usize,
-) -> (TokenConflictStatus, TokenConflictStatus) {
+) -> (TokenConflictStatus, TokenConflictStatus, bool) {
     let mut visited_state_sets = HashSet::new();
     let mut state_set_queue = vec![vec![
         grammar.variables[i].start_state,
@@ -250,6 +333,7 @@ fn compute_conflict_status(
     let mut result = (
         TokenConflictStatus::default(),
         TokenConflictStatus::default(),
+        false,
     );
 
     while let Some(state_set) = state_set_queue.pop() {
@@ -308,6 +392,12 @@ fn compute_conflict_status(
                 } else {
                     result.1.matches_same_string = true;
                 }
+                if prev_precedence == precedence
+                    && grammar.variables[prev_id].implicit_precedence
+                        == grammar.variables[id].implicit_precedence
+                {
+                    result.2 = true;
+                }
             } else {
                 completion = Some((id, precedence));
             }
@@ -372,9 +462,13 @@ fn compute_conflict_status(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::generate::grammars::{Variable, VariableType};
-    use crate::generate::prepare_grammar::{expand_tokens, ExtractedLexicalGrammar};
-    use crate::generate::rules::{Precedence, Rule, Symbol};
+    use crate::generate::build_tables::build_parse_table::build_parse_table;
+    use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::prepare_grammar::{
+        expand_tokens, prepare_grammar, ExtractedLexicalGrammar,
+    };
+    use crate::generate::rules::{Precedence, Rule};
 
     #[test]
     fn test_starting_characters() {
@@ -516,6 +610,137 @@ mod tests {
         assert!(!token_map.does_match_shorter_or_longer(var("x"), var("anything")));
     }
 
+    #[test]
+    fn test_can_be_adjacent() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("call")),
+                },
+                Variable {
+                    name: "call".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("f"),
+                        Rule::string("("),
+                        Rule::choice(vec![Rule::string("x"), Rule::Blank]),
+                        Rule::string(")"),
+                    ]),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (_, following_tokens, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let token_map = TokenConflictMap::new(&lexical_grammar, following_tokens);
+
+        let token = |name| Symbol::terminal(index_of_var(&lexical_grammar, name));
+        assert!(token_map.can_be_adjacent(token("("), token(")")));
+        assert!(token_map.can_be_adjacent(token(")"), token("f")));
+        assert!(!token_map.can_be_adjacent(token(")"), token("(")));
+        assert!(token_map.can_be_adjacent(token(")"), Symbol::external(0)));
+        assert!(token_map.can_be_adjacent(Symbol::external(0), token("(")));
+    }
+
+    #[test]
+    fn test_lexical_ambiguities() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::named("int"),
+                        Rule::named("identifier"),
+                        Rule::seq(vec![Rule::string("-"), Rule::named("int")]),
+                    ]),
+                },
+                Variable {
+                    name: "int".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z0-9]+", ""),
+                },
+            ],
+            ..Default::default()
+        };
+        let (syntax_grammar, lexical_grammar, inlines, default_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+        let (parse_table, following_tokens, _) =
+            build_parse_table(&syntax_grammar, &lexical_grammar, &inlines, &variable_info).unwrap();
+        let token_map = TokenConflictMap::new(&lexical_grammar, following_tokens);
+
+        // Both tokens match a string of digits, but they are only both valid at
+        // the start of the program, not after a minus sign.
+        let token = |name| Symbol::terminal(index_of_var(&lexical_grammar, name));
+        let (int, identifier) = (token("int"), token("identifier"));
+        assert_eq!(
+            lexical_ambiguities(&parse_table, &token_map),
+            [LexAmbiguity {
+                state_id: 1,
+                tokens: (int.min(identifier), int.max(identifier)),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_shadowed_tokens() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: Vec::new(),
+            variables: vec![
+                Variable {
+                    name: "if".to_string(),
+                    kind: VariableType::Anonymous,
+                    rule: Rule::prec(Precedence::Integer(-1), Rule::string("if")),
+                },
+                Variable {
+                    name: "while".to_string(),
+                    kind: VariableType::Anonymous,
+                    rule: Rule::string("while"),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "digit".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]", ""),
+                },
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+            ],
+        })
+        .unwrap();
+        let following_tokens = vec![TokenSet::new(); grammar.variables.len()];
+        let token_map = TokenConflictMap::new(&grammar, following_tokens);
+
+        // The `if` keyword has a lower precedence than identifiers, so it is never
+        // produced where an identifier would also be valid. The `while` keyword
+        // is preferred over identifiers, and single digits are preferred over
+        // longer numbers because they come first.
+        assert_eq!(
+            shadowed_tokens(&token_map),
+            [Symbol::terminal(index_of_var(&grammar, "if"))]
+        );
+    }
+
     fn index_of_var(grammar: &LexicalGrammar, name: &str) -> usize {
         grammar
             .variables
@@ -176,6 +176,14 @@ token.immediate = function(value) {
   };
 }
 
+token.case_insensitive = function(value) {
+  checkArguments(arguments, arguments.length, token.case_insensitive, 'token.case_insensitive', '', 'literal');
+  return {
+    type: "CASE_INSENSITIVE_TOKEN",
+    content: normalize(value)
+  };
+}
+
 function normalize(value) {
   if (typeof value == "undefined")
     throw new Error("Undefined symbol");
@@ -230,8 +238,11 @@ function grammar(baseGrammar, options) {
       rules: {},
       extras: [normalize(/\s/)],
       conflicts: [],
+      conflict_overrides: [],
       externals: [],
+      zero_width_externals: [],
       inline: [],
+      inline_after: [],
       supertypes: [],
       precedences: [],
     };
@@ -344,6 +355,44 @@ function grammar(baseGrammar, options) {
     });
   }
 
+  let conflict_overrides = baseGrammar.conflict_overrides || [];
+  if (options.conflict_overrides) {
+    if (typeof options.conflict_overrides !== "function") {
+      throw new Error("Grammar's 'conflict_overrides' property must be a function.");
+    }
+
+    const baseOverrideRules = conflict_overrides.map(pair => pair.map(sym));
+    const overrideRules = options.conflict_overrides.call(ruleBuilder, ruleBuilder, baseOverrideRules);
+
+    if (!Array.isArray(overrideRules)) {
+      throw new Error("Grammar's conflict_overrides must be an array of pairs of rules.");
+    }
+
+    conflict_overrides = overrideRules.map(pair => {
+      if (!Array.isArray(pair) || pair.length !== 2) {
+        throw new Error("Grammar's conflict_overrides must be an array of pairs of rules.");
+      }
+
+      return pair.map(symbol => normalize(symbol).name);
+    });
+  }
+
+  let zero_width_externals = baseGrammar.zero_width_externals || [];
+  if (options.zero_width_externals) {
+    if (typeof options.zero_width_externals !== "function") {
+      throw new Error("Grammar's 'zero_width_externals' property must be a function.");
+    }
+
+    const baseZeroWidthRules = zero_width_externals.map(sym);
+    const zeroWidthRules = options.zero_width_externals.call(ruleBuilder, ruleBuilder, baseZeroWidthRules);
+
+    if (!Array.isArray(zeroWidthRules)) {
+      throw new Error("Grammar's zero_width_externals must be an array of rules.");
+    }
+
+    zero_width_externals = zeroWidthRules.map(symbol => normalize(symbol).name);
+  }
+
   let inline = baseGrammar.inline;
   if (options.inline) {
     if (typeof options.inline !== "function") {
@@ -370,6 +419,28 @@ function grammar(baseGrammar, options) {
     }).map(symbol => symbol.name);
   }
 
+  let inline_after = baseGrammar.inline_after || [];
+  if (options.inline_after) {
+    if (typeof options.inline_after !== "function") {
+      throw new Error("Grammar's 'inline_after' property must be a function.");
+    }
+
+    const baseInlineAfterRules = inline_after.map(([name, predecessor]) => [sym(name), predecessor]);
+    const inlineAfterRules = options.inline_after.call(ruleBuilder, ruleBuilder, baseInlineAfterRules);
+
+    if (!Array.isArray(inlineAfterRules)) {
+      throw new Error("Grammar's inline_after must be an array of pairs of a rule and the symbol it follows.");
+    }
+
+    inline_after = inlineAfterRules.map(pair => {
+      if (!Array.isArray(pair) || pair.length !== 2) {
+        throw new Error("Grammar's inline_after must be an array of pairs of a rule and the symbol it follows.");
+      }
+
+      return [normalize(pair[0]).name, normalize(pair[1])];
+    });
+  }
+
   let supertypes = baseGrammar.supertypes;
   if (options.supertypes) {
     if (typeof options.supertypes !== "function") {
@@ -407,7 +478,7 @@ function grammar(baseGrammar, options) {
     throw new Error("Grammar must have at least one rule.");
   }
 
-  return { grammar: { name, word, rules, extras, conflicts, precedences, externals, inline, supertypes } };
+  return { grammar: { name, word, rules, extras, conflicts, conflict_overrides, precedences, externals, zero_width_externals, inline, inline_after, supertypes } };
 }
 
 function checkArguments(args, ruleCount, caller, callerName, suffix = '', argType = 'rule') {
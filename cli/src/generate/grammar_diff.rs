@@ -0,0 +1,344 @@
+use super::grammars::{LexicalGrammar, Production, SyntaxGrammar, VariableType};
+use super::rules::{Alias, Associativity, Precedence, Symbol, SymbolType};
+
+// Two productions are considered to be versions of the same production when
+// their longest common subsequence of steps is at least this fraction of the
+// length of the longer one.
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// The differences between two versions of a grammar, at the level of their
+/// productions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GrammarDiff {
+    pub added_variables: Vec<String>,
+    pub removed_variables: Vec<String>,
+    pub changed_variables: Vec<VariableDiff>,
+}
+
+/// The differences between the productions of a variable that exists in both
+/// versions of a grammar. Added productions are identified by their index in
+/// the new grammar, and removed productions by their index in the old grammar.
+/// Modified productions are pairs of an old and a new index.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VariableDiff {
+    pub name: String,
+    pub added_productions: Vec<usize>,
+    pub removed_productions: Vec<usize>,
+    pub modified_productions: Vec<(usize, usize)>,
+}
+
+// A production step with its symbol identified by name, so that steps can be
+// compared between grammars whose symbols are numbered differently.
+#[derive(PartialEq, Eq)]
+struct StepKey<'a> {
+    kind: SymbolType,
+    name: &'a str,
+    variable_type: Option<VariableType>,
+    precedence: &'a Precedence,
+    associativity: Option<Associativity>,
+    alias: Option<&'a Alias>,
+    field_name: Option<&'a str>,
+}
+
+impl GrammarDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_variables.is_empty()
+            && self.removed_variables.is_empty()
+            && self.changed_variables.is_empty()
+    }
+}
+
+/// Compare two versions of a grammar. Variables are matched by name, and so are
+/// the symbols within their productions, so the diff is not affected by symbols
+/// being numbered differently in the two versions. Within a variable, identical
+/// productions are matched first, and the remaining productions are paired up
+/// as modifications when they are sufficiently similar.
+pub fn diff_grammars(
+    old_syntax_grammar: &SyntaxGrammar,
+    old_lexical_grammar: &LexicalGrammar,
+    new_syntax_grammar: &SyntaxGrammar,
+    new_lexical_grammar: &LexicalGrammar,
+) -> GrammarDiff {
+    let mut result = GrammarDiff::default();
+    for old_variable in &old_syntax_grammar.variables {
+        let Some(new_variable) = new_syntax_grammar
+            .variables
+            .iter()
+            .find(|v| v.name == old_variable.name)
+        else {
+            result.removed_variables.push(old_variable.name.clone());
+            continue;
+        };
+
+        let old_productions = old_variable
+            .productions
+            .iter()
+            .map(|p| production_key(p, old_syntax_grammar, old_lexical_grammar))
+            .collect::<Vec<_>>();
+        let new_productions = new_variable
+            .productions
+            .iter()
+            .map(|p| production_key(p, new_syntax_grammar, new_lexical_grammar))
+            .collect::<Vec<_>>();
+        let variable_diff =
+            diff_productions(&old_variable.name, &old_productions, &new_productions);
+        if !variable_diff.added_productions.is_empty()
+            || !variable_diff.removed_productions.is_empty()
+            || !variable_diff.modified_productions.is_empty()
+        {
+            result.changed_variables.push(variable_diff);
+        }
+    }
+
+    for new_variable in &new_syntax_grammar.variables {
+        if !old_syntax_grammar
+            .variables
+            .iter()
+            .any(|v| v.name == new_variable.name)
+        {
+            result.added_variables.push(new_variable.name.clone());
+        }
+    }
+    result
+}
+
+fn diff_productions(
+    name: &str,
+    old_productions: &[(Vec<StepKey>, i32)],
+    new_productions: &[(Vec<StepKey>, i32)],
+) -> VariableDiff {
+    let mut unmatched_old = (0..old_productions.len()).collect::<Vec<_>>();
+    let mut unmatched_new = Vec::new();
+    for (new_index, new_production) in new_productions.iter().enumerate() {
+        if let Some(i) = unmatched_old
+            .iter()
+            .position(|old_index| old_productions[*old_index] == *new_production)
+        {
+            unmatched_old.remove(i);
+        } else {
+            unmatched_new.push(new_index);
+        }
+    }
+
+    // Pair up the most similar of the remaining productions first.
+    let mut candidates = Vec::new();
+    for old_index in &unmatched_old {
+        for new_index in &unmatched_new {
+            let similarity = similarity(
+                &old_productions[*old_index].0,
+                &new_productions[*new_index].0,
+            );
+            if similarity >= MIN_SIMILARITY {
+                candidates.push((similarity, *old_index, *new_index));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut result = VariableDiff {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    for (_, old_index, new_index) in candidates {
+        if unmatched_old.contains(&old_index) && unmatched_new.contains(&new_index) {
+            unmatched_old.retain(|i| *i != old_index);
+            unmatched_new.retain(|i| *i != new_index);
+            result.modified_productions.push((old_index, new_index));
+        }
+    }
+    result.modified_productions.sort_unstable();
+    result.removed_productions = unmatched_old;
+    result.added_productions = unmatched_new;
+    result
+}
+
+// The length of the longest common subsequence of the two step sequences,
+// relative to the length of the longer sequence.
+fn similarity(a: &[StepKey], b: &[StepKey]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() {
+        for j in 0..b.len() {
+            lengths[i + 1][j + 1] = if a[i] == b[j] {
+                lengths[i][j] + 1
+            } else {
+                lengths[i][j + 1].max(lengths[i + 1][j])
+            };
+        }
+    }
+    f64::from(lengths[a.len()][b.len()]) / a.len().max(b.len()) as f64
+}
+
+fn production_key<'a>(
+    production: &'a Production,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+) -> (Vec<StepKey<'a>>, i32) {
+    let steps = production
+        .steps
+        .iter()
+        .map(|step| StepKey {
+            kind: step.symbol.kind,
+            name: symbol_name(step.symbol, syntax_grammar, lexical_grammar),
+            variable_type: symbol_variable_type(step.symbol, syntax_grammar, lexical_grammar),
+            precedence: &step.precedence,
+            associativity: step.associativity,
+            alias: step.alias.as_ref(),
+            field_name: step.field_name.as_deref(),
+        })
+        .collect();
+    (steps, production.dynamic_precedence)
+}
+
+fn symbol_name<'a>(
+    symbol: Symbol,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+) -> &'a str {
+    match symbol.kind {
+        SymbolType::NonTerminal => &syntax_grammar.variables[symbol.index].name,
+        SymbolType::External => &syntax_grammar.external_tokens[symbol.index].name,
+        SymbolType::Terminal => &lexical_grammar.variables[symbol.index].name,
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => "",
+    }
+}
+
+// Whether the symbol is named, anonymous or hidden, so that replacing a string
+// with a named token of the same name counts as a change.
+fn symbol_variable_type(
+    symbol: Symbol,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> Option<VariableType> {
+    match symbol.kind {
+        SymbolType::NonTerminal => Some(syntax_grammar.variables[symbol.index].kind),
+        SymbolType::External => Some(syntax_grammar.external_tokens[symbol.index].kind),
+        SymbolType::Terminal => Some(lexical_grammar.variables[symbol.index].kind),
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+    use crate::generate::prepare_grammar::prepare_grammar;
+    use crate::generate::rules::Rule;
+
+    #[test]
+    fn test_diff_grammars_with_added_production() {
+        let statement = |alternatives: Vec<Rule>| Variable {
+            name: "statement".to_string(),
+            kind: VariableType::Named,
+            rule: Rule::choice(alternatives),
+        };
+        let expression = Variable {
+            name: "expression".to_string(),
+            kind: VariableType::Named,
+            rule: Rule::seq(vec![
+                Rule::string("("),
+                Rule::string("x"),
+                Rule::string(")"),
+            ]),
+        };
+        let old_grammar = InputGrammar {
+            variables: vec![
+                statement(vec![
+                    Rule::seq(vec![Rule::string("return"), Rule::named("expression")]),
+                    Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                ]),
+                expression.clone(),
+            ],
+            ..Default::default()
+        };
+        // The new version adds a production that refers to a new rule, declared
+        // before the existing ones so that their symbols are numbered differently.
+        let new_grammar = InputGrammar {
+            variables: vec![
+                statement(vec![
+                    Rule::seq(vec![Rule::string("return"), Rule::named("expression")]),
+                    Rule::seq(vec![Rule::named("expression"), Rule::string(";")]),
+                    Rule::seq(vec![Rule::named("label"), Rule::named("expression")]),
+                ]),
+                Variable {
+                    name: "label".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("x"), Rule::string(":")]),
+                },
+                expression,
+            ],
+            ..Default::default()
+        };
+
+        let (old_syntax_grammar, old_lexical_grammar, ..) = prepare_grammar(&old_grammar).unwrap();
+        let (new_syntax_grammar, new_lexical_grammar, ..) = prepare_grammar(&new_grammar).unwrap();
+        assert_eq!(
+            diff_grammars(
+                &old_syntax_grammar,
+                &old_lexical_grammar,
+                &new_syntax_grammar,
+                &new_lexical_grammar
+            ),
+            GrammarDiff {
+                added_variables: vec!["label".to_string()],
+                removed_variables: Vec::new(),
+                changed_variables: vec![VariableDiff {
+                    name: "statement".to_string(),
+                    added_productions: vec![2],
+                    removed_productions: Vec::new(),
+                    modified_productions: Vec::new(),
+                }],
+            }
+        );
+
+        assert!(diff_grammars(
+            &old_syntax_grammar,
+            &old_lexical_grammar,
+            &old_syntax_grammar,
+            &old_lexical_grammar
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_diff_grammars_with_string_replaced_by_named_token() {
+        let grammar = |rule| InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![rule, Rule::string(";")]),
+                },
+                Variable {
+                    name: "x".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("x"),
+                },
+            ],
+            ..Default::default()
+        };
+        let (old_syntax_grammar, old_lexical_grammar, ..) =
+            prepare_grammar(&grammar(Rule::string("x"))).unwrap();
+        let (new_syntax_grammar, new_lexical_grammar, ..) =
+            prepare_grammar(&grammar(Rule::named("x"))).unwrap();
+
+        // Both tokens are named `x`, but only one of them is a named node.
+        assert_eq!(
+            diff_grammars(
+                &old_syntax_grammar,
+                &old_lexical_grammar,
+                &new_syntax_grammar,
+                &new_lexical_grammar
+            )
+            .changed_variables,
+            [VariableDiff {
+                name: "statement".to_string(),
+                added_productions: Vec::new(),
+                removed_productions: Vec::new(),
+                modified_productions: vec![(0, 0)],
+            }]
+        );
+    }
+}
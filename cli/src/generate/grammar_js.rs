@@ -0,0 +1,1287 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
+
+// The punctuation recognized by the tokenizer, with longer tokens first so that
+// they take priority over their prefixes.
+const PUNCTUATION: &[&str] = &[
+    "...", "=>", "(", ")", "[", "]", "{", "}", ",", ":", ";", ".", "=", "-", "+",
+];
+
+const GRAMMAR_PROPERTIES: &[&str] = &[
+    "name",
+    "rules",
+    "extras",
+    "externals",
+    "conflicts",
+    "conflict_overrides",
+    "zero_width_externals",
+    "inline",
+    "inline_after",
+    "supertypes",
+    "word",
+    "precedences",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Identifier(String),
+    String(String),
+    Regex { source: String, flags: String },
+    Number(i32),
+    Punctuation(&'static str),
+    Eof,
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Builtin {
+    Alias,
+    Blank,
+    Choice,
+    Field,
+    Grammar,
+    Optional,
+    Prec,
+    PrecLeft,
+    PrecRight,
+    PrecDynamic,
+    Repeat,
+    Repeat1,
+    Seq,
+    Sym,
+    Token,
+    ImmediateToken,
+    CaseInsensitiveToken,
+}
+
+/// A function defined in the grammar file. The body is evaluated each time the
+/// function is called, starting from the token at index `body`.
+#[derive(Clone, Debug)]
+struct Function {
+    params: Vec<String>,
+    body: usize,
+    scope: HashMap<String, Value>,
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Undefined,
+    Number(i32),
+    String(String),
+    Rule(JsonValue),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+    Function(Function),
+    Builtin(Builtin),
+    RuleBuilder,
+    // A reference to a rule that doesn't exist, which is only valid as the
+    // name of an alias.
+    UndefinedSymbol(String),
+    Grammar(JsonValue),
+}
+
+type Scope = HashMap<String, Value>;
+
+struct Interpreter {
+    tokens: Vec<Token>,
+    position: usize,
+    globals: Scope,
+    rule_names: Option<HashSet<String>>,
+}
+
+/// Evaluate the source of a `grammar.js` file without a JavaScript runtime,
+/// producing the same grammar JSON as running the file with `dsl.js`.
+///
+/// Only the common subset of the DSL is supported: the rule functions, along
+/// with top-level constants and helper functions whose bodies are single
+/// expressions. Any other construct causes an error.
+pub fn evaluate_grammar_js(source: &str) -> Result<String> {
+    let mut interpreter = Interpreter {
+        tokens: tokenize(source)?,
+        position: 0,
+        globals: Scope::new(),
+        rule_names: None,
+    };
+    let grammar = interpreter.run()?;
+    Ok(serde_json::to_string_pretty(&grammar)? + "\n")
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            loop {
+                match chars.get(i) {
+                    None => return Err(anyhow!("Unterminated comment on line {line}")),
+                    Some('*') if chars.get(i + 1) == Some(&'/') => break,
+                    Some('\n') => line += 1,
+                    Some(_) => {}
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+
+        let kind = if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+                return Err(anyhow!("Unsupported non-integer number on line {line}"));
+            }
+            let digits = chars[start..i].iter().collect::<String>();
+            TokenKind::Number(
+                digits
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid number {digits} on line {line}"))?,
+            )
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            TokenKind::Identifier(chars[start..i].iter().collect())
+        } else if c == '\'' || c == '"' || c == '`' {
+            i += 1;
+            let mut value = String::new();
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') if c != '`' => {
+                        return Err(anyhow!("Unterminated string on line {line}"))
+                    }
+                    None => return Err(anyhow!("Unterminated template literal on line {line}")),
+                    Some(&d) if d == c => break,
+                    Some('$') if c == '`' && chars.get(i + 1) == Some(&'{') => {
+                        return Err(anyhow!(
+                            "Unsupported template literal substitution on line {line}"
+                        ))
+                    }
+                    Some('\\') => {
+                        i += 1;
+                        let (escaped, length) = unescape(&chars[i..])
+                            .ok_or_else(|| anyhow!("Invalid escape sequence on line {line}"))?;
+                        if chars[i] == '\n' {
+                            line += 1;
+                        }
+                        value.extend(escaped);
+                        i += length;
+                        continue;
+                    }
+                    Some(&d) => {
+                        if d == '\n' {
+                            line += 1;
+                        }
+                        value.push(d);
+                    }
+                }
+                i += 1;
+            }
+            i += 1;
+            TokenKind::String(value)
+        } else if c == '/' {
+            i += 1;
+            let mut source = String::new();
+            let mut in_class = false;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => {
+                        return Err(anyhow!("Unterminated regex on line {line}"));
+                    }
+                    Some('/') if !in_class => break,
+                    Some('\\') => {
+                        source.push('\\');
+                        i += 1;
+                        if let Some(&d) = chars.get(i) {
+                            source.push(d);
+                        }
+                    }
+                    Some(&d) => {
+                        match d {
+                            '[' => in_class = true,
+                            ']' => in_class = false,
+                            _ => {}
+                        }
+                        source.push(d);
+                    }
+                }
+                i += 1;
+            }
+            i += 1;
+            let start = i;
+            while i < chars.len() && chars[i].is_alphabetic() {
+                i += 1;
+            }
+            TokenKind::Regex {
+                source,
+                flags: chars[start..i].iter().collect(),
+            }
+        } else if let Some(punctuation) = PUNCTUATION.iter().find(|p| {
+            p.chars()
+                .enumerate()
+                .all(|(j, p)| chars.get(i + j) == Some(&p))
+        }) {
+            i += punctuation.len();
+            TokenKind::Punctuation(punctuation)
+        } else {
+            return Err(anyhow!("Unexpected character '{c}' on line {line}"));
+        };
+        tokens.push(Token { kind, line });
+    }
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+    });
+    Ok(tokens)
+}
+
+// Interpret the escape sequence at the start of the given characters, which
+// follow a backslash. Returns the escaped character, if any, and the number of
+// characters consumed.
+fn unescape(chars: &[char]) -> Option<(Option<char>, usize)> {
+    let hex = |digits: &[char]| {
+        u32::from_str_radix(&digits.iter().collect::<String>(), 16)
+            .ok()
+            .and_then(char::from_u32)
+    };
+    Some(match chars.first()? {
+        'n' => (Some('\n'), 1),
+        'r' => (Some('\r'), 1),
+        't' => (Some('\t'), 1),
+        'b' => (Some('\u{8}'), 1),
+        'f' => (Some('\u{c}'), 1),
+        'v' => (Some('\u{b}'), 1),
+        '0' => (Some('\0'), 1),
+        '\n' => (None, 1),
+        'x' => (Some(hex(chars.get(1..3)?)?), 3),
+        'u' if chars.get(1) == Some(&'{') => {
+            let end = chars.iter().position(|c| *c == '}')?;
+            (Some(hex(&chars[2..end])?), end + 1)
+        }
+        'u' => (Some(hex(chars.get(1..5)?)?), 5),
+        c => (Some(*c), 1),
+    })
+}
+
+impl Interpreter {
+    fn run(&mut self) -> Result<JsonValue> {
+        let mut exports = None;
+        loop {
+            match self.peek().clone() {
+                TokenKind::Eof => break,
+                TokenKind::Punctuation(";") => self.position += 1,
+                TokenKind::String(_) => {
+                    // A directive such as 'use strict'.
+                    self.position += 1;
+                }
+                TokenKind::Identifier(keyword) if is_declaration_keyword(&keyword) => {
+                    self.position += 1;
+                    let name = self.expect_identifier()?;
+                    self.expect("=")?;
+                    let value = self.eval_expression(&Scope::new())?;
+                    self.globals.insert(name, value);
+                }
+                TokenKind::Identifier(keyword) if keyword == "function" => {
+                    self.position += 1;
+                    let name = self.expect_identifier()?;
+                    let params = self.parse_params()?;
+                    let body = self.position;
+                    self.skip_function_body()?;
+                    self.globals.insert(
+                        name,
+                        Value::Function(Function {
+                            params,
+                            body,
+                            scope: Scope::new(),
+                        }),
+                    );
+                }
+                TokenKind::Identifier(keyword) if keyword == "module" => {
+                    self.position += 1;
+                    self.expect(".")?;
+                    if self.expect_identifier()? != "exports" {
+                        return Err(self.error("Expected `module.exports`"));
+                    }
+                    self.expect("=")?;
+                    exports = Some(self.eval_expression(&Scope::new())?);
+                }
+                TokenKind::Identifier(keyword) if keyword == "export" => {
+                    self.position += 1;
+                    if self.expect_identifier()? != "default" {
+                        return Err(self.error("Expected `export default`"));
+                    }
+                    exports = Some(self.eval_expression(&Scope::new())?);
+                }
+                kind => {
+                    return Err(self.error(&format!(
+                        "Unsupported statement beginning with {}",
+                        describe(&kind)
+                    )))
+                }
+            }
+        }
+
+        match exports {
+            Some(Value::Grammar(grammar)) => Ok(grammar),
+            _ => Err(anyhow!(
+                "The grammar file must export the result of calling `grammar`"
+            )),
+        }
+    }
+
+    fn eval_expression(&mut self, scope: &Scope) -> Result<Value> {
+        if let Some(params) = self.try_parse_arrow_params() {
+            let body = self.position;
+            self.skip_function_body()?;
+            return Ok(Value::Function(Function {
+                params,
+                body,
+                scope: scope.clone(),
+            }));
+        }
+
+        let mut value = self.eval_postfix(scope)?;
+        loop {
+            let is_addition = if self.eat("+") {
+                true
+            } else if self.eat("-") {
+                false
+            } else {
+                break;
+            };
+            let right = self.eval_postfix(scope)?;
+            value = match (value, right, is_addition) {
+                (Value::Number(l), Value::Number(r), true) => l.checked_add(r).map(Value::Number),
+                (Value::Number(l), Value::Number(r), false) => l.checked_sub(r).map(Value::Number),
+                (Value::String(l), Value::String(r), true) => Some(Value::String(l + &r)),
+                _ => return Err(self.error("Unsupported operands for arithmetic")),
+            }
+            .ok_or_else(|| self.error("Arithmetic overflow"))?;
+        }
+        Ok(value)
+    }
+
+    fn eval_postfix(&mut self, scope: &Scope) -> Result<Value> {
+        let mut value = self.eval_primary(scope)?;
+        loop {
+            if self.eat(".") {
+                let name = self.expect_identifier()?;
+                value = self.get_property(value, &name)?;
+            } else if self.eat("[") {
+                let key = self.eval_expression(scope)?;
+                self.expect("]")?;
+                value = match (value, key) {
+                    (Value::Array(elements), Value::Number(index)) => usize::try_from(index)
+                        .ok()
+                        .and_then(|index| elements.get(index).cloned())
+                        .unwrap_or(Value::Undefined),
+                    (value, Value::String(name)) => self.get_property(value, &name)?,
+                    _ => return Err(self.error("Unsupported index expression")),
+                };
+            } else if *self.peek() == TokenKind::Punctuation("(") {
+                let args = self.eval_list(scope, "(", ")")?;
+                value = self.call(value, args)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn eval_primary(&mut self, scope: &Scope) -> Result<Value> {
+        let kind = self.peek().clone();
+        self.position += 1;
+        match kind {
+            TokenKind::Number(n) => Ok(Value::Number(n)),
+            TokenKind::String(s) => Ok(Value::String(s)),
+            TokenKind::Regex { source, flags } => Ok(Value::Rule(if flags.is_empty() {
+                json!({ "type": "PATTERN", "value": source })
+            } else {
+                json!({ "type": "PATTERN", "value": source, "flags": flags })
+            })),
+            TokenKind::Punctuation("-") => match self.eval_postfix(scope)? {
+                Value::Number(n) => Ok(Value::Number(-n)),
+                _ => Err(self.error("Unsupported operand for negation")),
+            },
+            TokenKind::Punctuation("(") => {
+                let value = self.eval_expression(scope)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            TokenKind::Punctuation("[") => {
+                self.position -= 1;
+                Ok(Value::Array(self.eval_list(scope, "[", "]")?))
+            }
+            TokenKind::Punctuation("{") => {
+                let mut entries = Vec::new();
+                while !self.eat("}") {
+                    let key = match self.peek().clone() {
+                        TokenKind::Identifier(key) | TokenKind::String(key) => key,
+                        TokenKind::Number(key) => key.to_string(),
+                        kind => {
+                            return Err(
+                                self.error(&format!("Unsupported object key {}", describe(&kind)))
+                            )
+                        }
+                    };
+                    self.position += 1;
+                    let value = if self.eat(":") {
+                        self.eval_expression(scope)?
+                    } else if matches!(self.peek(), TokenKind::Punctuation("," | "}")) {
+                        self.lookup(scope, &key)?
+                    } else {
+                        return Err(self.error("Unsupported object property syntax"));
+                    };
+                    entries.retain(|(k, _)| *k != key);
+                    entries.push((key, value));
+                    if !self.eat(",") {
+                        self.expect("}")?;
+                        break;
+                    }
+                }
+                Ok(Value::Object(entries))
+            }
+            TokenKind::Identifier(name) => match name.as_str() {
+                "undefined" | "null" => Ok(Value::Undefined),
+                _ => self.lookup(scope, &name),
+            },
+            kind => {
+                self.position -= 1;
+                Err(self.error(&format!("Unexpected {}", describe(&kind))))
+            }
+        }
+    }
+
+    // Evaluate a comma-separated list of expressions between the given delimiters.
+    fn eval_list(&mut self, scope: &Scope, open: &str, close: &str) -> Result<Vec<Value>> {
+        self.expect(open)?;
+        let mut result = Vec::new();
+        while !self.eat(close) {
+            if *self.peek() == TokenKind::Punctuation("...") {
+                return Err(self.error("Unsupported spread syntax"));
+            }
+            result.push(self.eval_expression(scope)?);
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    fn lookup(&self, scope: &Scope, name: &str) -> Result<Value> {
+        if let Some(value) = scope.get(name).or_else(|| self.globals.get(name)) {
+            return Ok(value.clone());
+        }
+        let builtin = match name {
+            "alias" => Builtin::Alias,
+            "blank" => Builtin::Blank,
+            "choice" => Builtin::Choice,
+            "field" => Builtin::Field,
+            "grammar" => Builtin::Grammar,
+            "optional" => Builtin::Optional,
+            "prec" => Builtin::Prec,
+            "repeat" => Builtin::Repeat,
+            "repeat1" => Builtin::Repeat1,
+            "seq" => Builtin::Seq,
+            "sym" => Builtin::Sym,
+            "token" => Builtin::Token,
+            _ => return Err(self.error(&format!("Unsupported or undefined identifier `{name}`"))),
+        };
+        Ok(Value::Builtin(builtin))
+    }
+
+    fn get_property(&self, value: Value, name: &str) -> Result<Value> {
+        match (value, name) {
+            (Value::RuleBuilder, _) => {
+                if self
+                    .rule_names
+                    .as_ref()
+                    .is_some_and(|names| !names.contains(name))
+                {
+                    return Ok(Value::UndefinedSymbol(name.to_string()));
+                }
+                Ok(Value::Rule(json!({ "type": "SYMBOL", "name": name })))
+            }
+            (Value::Object(entries), _) => Ok(entries
+                .into_iter()
+                .find(|(key, _)| key == name)
+                .map_or(Value::Undefined, |(_, value)| value)),
+            (Value::Rule(rule), _) => Ok(rule.get(name).map_or(Value::Undefined, |value| {
+                value.as_str().map_or_else(
+                    || Value::Rule(value.clone()),
+                    |s| Value::String(s.to_string()),
+                )
+            })),
+            (Value::Builtin(Builtin::Prec), "left") => Ok(Value::Builtin(Builtin::PrecLeft)),
+            (Value::Builtin(Builtin::Prec), "right") => Ok(Value::Builtin(Builtin::PrecRight)),
+            (Value::Builtin(Builtin::Prec), "dynamic") => Ok(Value::Builtin(Builtin::PrecDynamic)),
+            (Value::Builtin(Builtin::Token), "immediate") => {
+                Ok(Value::Builtin(Builtin::ImmediateToken))
+            }
+            (Value::Builtin(Builtin::Token), "case_insensitive") => {
+                Ok(Value::Builtin(Builtin::CaseInsensitiveToken))
+            }
+            _ => Err(self.error(&format!("Unsupported property `{name}`"))),
+        }
+    }
+
+    fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value> {
+        let function = match callee {
+            Value::Builtin(builtin) => return self.call_builtin(builtin, args),
+            Value::Function(function) => function,
+            _ => return Err(self.error("Called a value that is not a function")),
+        };
+
+        let mut scope = function.scope;
+        let mut args = args.into_iter();
+        for param in function.params {
+            scope.insert(param, args.next().unwrap_or(Value::Undefined));
+        }
+
+        let saved_position = self.position;
+        self.position = function.body;
+        let result = if self.eat("{") {
+            if *self.peek() != TokenKind::Identifier("return".to_string()) {
+                return Err(self.error(
+                    "Only function bodies consisting of a single `return` statement are supported",
+                ));
+            }
+            self.position += 1;
+            let result = self.eval_expression(&scope)?;
+            self.eat(";");
+            self.expect("}")?;
+            result
+        } else {
+            self.eval_expression(&scope)?
+        };
+        self.position = saved_position;
+        Ok(result)
+    }
+
+    fn call_builtin(&mut self, builtin: Builtin, args: Vec<Value>) -> Result<Value> {
+        let name = match builtin {
+            Builtin::Alias => "alias",
+            Builtin::Blank => "blank",
+            Builtin::Choice => "choice",
+            Builtin::Field => "field",
+            Builtin::Grammar => "grammar",
+            Builtin::Optional => "optional",
+            Builtin::Prec => "prec",
+            Builtin::PrecLeft => "prec.left",
+            Builtin::PrecRight => "prec.right",
+            Builtin::PrecDynamic => "prec.dynamic",
+            Builtin::Repeat => "repeat",
+            Builtin::Repeat1 => "repeat1",
+            Builtin::Seq => "seq",
+            Builtin::Sym => "sym",
+            Builtin::Token => "token",
+            Builtin::ImmediateToken => "token.immediate",
+            Builtin::CaseInsensitiveToken => "token.case_insensitive",
+        };
+        let single_rule = |this: &Self, args: Vec<Value>| {
+            if args.len() > 1 {
+                return Err(this.error(&format!(
+                    "The `{name}` function only takes one rule argument. \
+                     You passed in multiple rules. Did you mean to call `seq`?"
+                )));
+            }
+            this.normalize(args.into_iter().next().unwrap_or(Value::Undefined))
+        };
+        let wrap = |rule_type: &str, content: JsonValue| {
+            Value::Rule(json!({ "type": rule_type, "content": content }))
+        };
+
+        Ok(match builtin {
+            Builtin::Alias => {
+                let mut args = args.into_iter();
+                let content = self.normalize(args.next().unwrap_or(Value::Undefined))?;
+                let (named, value) = match args.next() {
+                    Some(Value::String(value)) => (false, value),
+                    Some(Value::UndefinedSymbol(value)) => (true, value),
+                    Some(Value::Rule(rule)) if rule["type"] == "SYMBOL" => {
+                        (true, rule["name"].as_str().unwrap_or_default().to_string())
+                    }
+                    _ => return Err(self.error("Invalid alias value")),
+                };
+                Value::Rule(json!({
+                    "type": "ALIAS",
+                    "content": content,
+                    "named": named,
+                    "value": value,
+                }))
+            }
+            Builtin::Blank => Value::Rule(json!({ "type": "BLANK" })),
+            Builtin::Choice | Builtin::Seq => {
+                let members = args
+                    .into_iter()
+                    .map(|arg| self.normalize(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let rule_type = if builtin == Builtin::Seq {
+                    "SEQ"
+                } else {
+                    "CHOICE"
+                };
+                Value::Rule(json!({ "type": rule_type, "members": members }))
+            }
+            Builtin::Field => {
+                let mut args = args.into_iter();
+                let Some(Value::String(field_name)) = args.next() else {
+                    return Err(self.error("The first argument to `field` must be a string"));
+                };
+                let content = self.normalize(args.next().unwrap_or(Value::Undefined))?;
+                Value::Rule(json!({ "type": "FIELD", "name": field_name, "content": content }))
+            }
+            Builtin::Optional => {
+                let content = single_rule(self, args)?;
+                Value::Rule(json!({
+                    "type": "CHOICE",
+                    "members": [content, { "type": "BLANK" }],
+                }))
+            }
+            Builtin::Prec | Builtin::PrecLeft | Builtin::PrecRight | Builtin::PrecDynamic => {
+                let mut args = args.into_iter();
+                let (precedence, content) = match (args.next(), args.next()) {
+                    (Some(content), None)
+                        if matches!(builtin, Builtin::PrecLeft | Builtin::PrecRight) =>
+                    {
+                        (Value::Number(0), content)
+                    }
+                    (Some(precedence), Some(content)) => (precedence, content),
+                    _ => return Err(self.error("Missing precedence value")),
+                };
+                let precedence = match precedence {
+                    Value::Number(n) => json!(n),
+                    Value::String(s) if builtin != Builtin::PrecDynamic => json!(s),
+                    _ => return Err(self.error(&format!("Invalid precedence for `{name}`"))),
+                };
+                let rule_type = match builtin {
+                    Builtin::PrecLeft => "PREC_LEFT",
+                    Builtin::PrecRight => "PREC_RIGHT",
+                    Builtin::PrecDynamic => "PREC_DYNAMIC",
+                    _ => "PREC",
+                };
+                Value::Rule(json!({
+                    "type": rule_type,
+                    "value": precedence,
+                    "content": self.normalize(content)?,
+                }))
+            }
+            Builtin::Repeat => wrap("REPEAT", single_rule(self, args)?),
+            Builtin::Repeat1 => wrap("REPEAT1", single_rule(self, args)?),
+            Builtin::Token => wrap("TOKEN", single_rule(self, args)?),
+            Builtin::ImmediateToken => wrap("IMMEDIATE_TOKEN", single_rule(self, args)?),
+            Builtin::CaseInsensitiveToken => {
+                wrap("CASE_INSENSITIVE_TOKEN", single_rule(self, args)?)
+            }
+            Builtin::Sym => match args.into_iter().next() {
+                Some(Value::String(name)) => Value::Rule(json!({ "type": "SYMBOL", "name": name })),
+                _ => return Err(self.error("The argument to `sym` must be a string")),
+            },
+            Builtin::Grammar => match <[Value; 1]>::try_from(args) {
+                Ok([Value::Object(options)]) => Value::Grammar(self.build_grammar(options)?),
+                Ok(_) => return Err(self.error("The argument to `grammar` must be an object")),
+                Err(_) => return Err(self.error("Extending a base grammar is not supported")),
+            },
+        })
+    }
+
+    fn build_grammar(&mut self, options: Vec<(String, Value)>) -> Result<JsonValue> {
+        if let Some((key, _)) = options
+            .iter()
+            .find(|(key, _)| !GRAMMAR_PROPERTIES.contains(&key.as_str()))
+        {
+            return Err(self.error(&format!("Unsupported grammar property '{key}'")));
+        }
+        let option = |key: &str| {
+            options
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.clone())
+        };
+
+        let name = match option("name") {
+            Some(Value::String(name))
+                if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+            {
+                name
+            }
+            Some(Value::String(_)) => {
+                return Err(self.error(
+                    "Grammar's 'name' property must not start with a digit and cannot contain non-word characters.",
+                ))
+            }
+            _ => return Err(self.error("Grammar's 'name' property must be a string.")),
+        };
+
+        let mut externals = Vec::new();
+        if let Some(value) = option("externals") {
+            self.rule_names = None;
+            for external in self.call_grammar_function(value, "externals")? {
+                externals.push(self.normalize(external)?);
+            }
+        }
+
+        let Some(Value::Object(rule_fns)) = option("rules") else {
+            return Err(self.error("Grammar's 'rules' property must be an object."));
+        };
+        if rule_fns.is_empty() {
+            return Err(self.error("Grammar must have at least one rule."));
+        }
+        self.rule_names = Some(
+            rule_fns
+                .iter()
+                .map(|(name, _)| name.clone())
+                .chain(
+                    externals
+                        .iter()
+                        .filter_map(|e| e["name"].as_str().map(str::to_string)),
+                )
+                .collect(),
+        );
+
+        let mut rules = Map::new();
+        for (rule_name, rule_fn) in rule_fns {
+            if !matches!(rule_fn, Value::Function(_)) {
+                return Err(self.error(&format!(
+                    "Grammar rules must all be functions. '{rule_name}' rule is not."
+                )));
+            }
+            let rule = self.call(rule_fn, vec![Value::RuleBuilder, Value::Undefined])?;
+            rules.insert(rule_name, self.normalize(rule)?);
+        }
+
+        let extras = match option("extras") {
+            Some(value) => self
+                .call_grammar_function(value, "extras")?
+                .into_iter()
+                .map(|extra| self.normalize(extra))
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![json!({ "type": "PATTERN", "value": "\\s" })],
+        };
+
+        let word = match option("word") {
+            Some(value) => {
+                let word = self.call_grammar_function_once(value, "word")?;
+                match self.symbol_name(word) {
+                    Ok(name) => Some(name),
+                    Err(_) => {
+                        return Err(self.error("Grammar's 'word' property must be a named rule."))
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let mut conflicts = Vec::new();
+        if let Some(value) = option("conflicts") {
+            for conflict in self.call_grammar_function(value, "conflicts")? {
+                let Value::Array(conflict) = conflict else {
+                    return Err(
+                        self.error("Grammar's conflicts must be an array of arrays of rules.")
+                    );
+                };
+                conflicts.push(
+                    conflict
+                        .into_iter()
+                        .map(|symbol| self.symbol_name(symbol))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+            }
+        }
+
+        let mut conflict_overrides = Vec::new();
+        if let Some(value) = option("conflict_overrides") {
+            for pair in self.call_grammar_function(value, "conflict_overrides")? {
+                let pair = match pair {
+                    Value::Array(pair) if pair.len() == 2 => pair,
+                    _ => {
+                        return Err(self.error(
+                            "Grammar's conflict_overrides must be an array of pairs of rules.",
+                        ))
+                    }
+                };
+                conflict_overrides.push(
+                    pair.into_iter()
+                        .map(|symbol| self.symbol_name(symbol))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+            }
+        }
+
+        let zero_width_externals = match option("zero_width_externals") {
+            Some(value) => self
+                .call_grammar_function(value, "zero_width_externals")?
+                .into_iter()
+                .map(|symbol| self.symbol_name(symbol))
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        let mut inline = Vec::new();
+        if let Some(value) = option("inline") {
+            for symbol in self.call_grammar_function(value, "inline")? {
+                if let Value::UndefinedSymbol(name) = symbol {
+                    println!("Warning: inline rule '{name}' is not defined.");
+                    continue;
+                }
+                let name = self.symbol_name(symbol)?;
+                if inline.contains(&name) {
+                    println!("Warning: duplicate inline rule '{name}'");
+                } else {
+                    inline.push(name);
+                }
+            }
+        }
+
+        let mut inline_after = Vec::new();
+        if let Some(value) = option("inline_after") {
+            for pair in self.call_grammar_function(value, "inline_after")? {
+                let pair = match pair {
+                    Value::Array(pair) if pair.len() == 2 => pair,
+                    _ => {
+                        return Err(self.error(
+                            "Grammar's inline_after must be an array of pairs of a rule and the symbol it follows.",
+                        ))
+                    }
+                };
+                let [rule, predecessor] = <[Value; 2]>::try_from(pair).unwrap();
+                inline_after.push(json!([
+                    self.symbol_name(rule)?,
+                    self.normalize(predecessor)?
+                ]));
+            }
+        }
+
+        let supertypes = match option("supertypes") {
+            Some(value) => self
+                .call_grammar_function(value, "supertypes")?
+                .into_iter()
+                .map(|symbol| self.symbol_name(symbol))
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
+        let mut precedences = Vec::new();
+        if let Some(value) = option("precedences") {
+            for list in self.call_grammar_function(value, "precedences")? {
+                let Value::Array(list) = list else {
+                    return Err(
+                        self.error("Grammar's precedences must be an array of arrays of rules.")
+                    );
+                };
+                precedences.push(
+                    list.into_iter()
+                        .map(|entry| self.normalize(entry))
+                        .collect::<Result<Vec<_>>>()?,
+                );
+            }
+        }
+
+        self.rule_names = None;
+
+        let mut result = Map::new();
+        result.insert("name".to_string(), json!(name));
+        if let Some(word) = word {
+            result.insert("word".to_string(), json!(word));
+        }
+        result.insert("rules".to_string(), JsonValue::Object(rules));
+        result.insert("extras".to_string(), json!(extras));
+        result.insert("conflicts".to_string(), json!(conflicts));
+        result.insert("conflict_overrides".to_string(), json!(conflict_overrides));
+        result.insert("precedences".to_string(), json!(precedences));
+        result.insert("externals".to_string(), json!(externals));
+        result.insert(
+            "zero_width_externals".to_string(),
+            json!(zero_width_externals),
+        );
+        result.insert("inline".to_string(), json!(inline));
+        result.insert("inline_after".to_string(), json!(inline_after));
+        result.insert("supertypes".to_string(), json!(supertypes));
+        Ok(JsonValue::Object(result))
+    }
+
+    // Call one of the functions in the grammar object, such as `extras`, which
+    // must return an array.
+    fn call_grammar_function(&mut self, value: Value, property: &str) -> Result<Vec<Value>> {
+        match self.call_grammar_function_once(value, property)? {
+            Value::Array(elements) => Ok(elements),
+            _ => Err(self.error(&format!(
+                "Grammar's '{property}' function must return an array."
+            ))),
+        }
+    }
+
+    fn call_grammar_function_once(&mut self, value: Value, property: &str) -> Result<Value> {
+        if !matches!(value, Value::Function(_)) {
+            return Err(self.error(&format!(
+                "Grammar's '{property}' property must be a function."
+            )));
+        }
+        self.call(value, vec![Value::RuleBuilder, Value::Array(Vec::new())])
+    }
+
+    fn normalize(&self, value: Value) -> Result<JsonValue> {
+        match value {
+            Value::String(value) => Ok(json!({ "type": "STRING", "value": value })),
+            Value::Rule(rule) => Ok(rule),
+            Value::Undefined => Err(self.error("Undefined symbol")),
+            Value::UndefinedSymbol(name) => Err(self.error(&format!("Undefined symbol '{name}'"))),
+            _ => Err(self.error("Invalid rule")),
+        }
+    }
+
+    fn symbol_name(&self, value: Value) -> Result<String> {
+        match value {
+            Value::Rule(rule) if rule["type"] == "SYMBOL" => {
+                Ok(rule["name"].as_str().unwrap_or_default().to_string())
+            }
+            Value::UndefinedSymbol(name) => Err(self.error(&format!("Undefined symbol '{name}'"))),
+            _ => Err(self.error("Expected a symbol")),
+        }
+    }
+
+    // If the upcoming tokens are the parameters of an arrow function, consume
+    // them along with the arrow and return the parameter names.
+    fn try_parse_arrow_params(&mut self) -> Option<Vec<String>> {
+        let start = self.position;
+        if let TokenKind::Identifier(name) = self.peek().clone() {
+            self.position += 1;
+            if self.eat("=>") {
+                return Some(vec![name]);
+            }
+        } else if *self.peek() == TokenKind::Punctuation("(") {
+            if let Ok(params) = self.parse_params() {
+                if self.eat("=>") {
+                    return Some(params);
+                }
+            }
+        }
+        self.position = start;
+        None
+    }
+
+    fn parse_params(&mut self) -> Result<Vec<String>> {
+        self.expect("(")?;
+        let mut params = Vec::new();
+        while !self.eat(")") {
+            params.push(self.expect_identifier()?);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    // Skip over a function body without evaluating it. The body is either a
+    // block or an expression that ends at an unmatched delimiter.
+    fn skip_function_body(&mut self) -> Result<()> {
+        let is_block = *self.peek() == TokenKind::Punctuation("{");
+        let mut depth = 0_usize;
+        loop {
+            match self.peek() {
+                TokenKind::Eof => return Err(self.error("Unexpected end of file")),
+                TokenKind::Punctuation("(" | "[" | "{") => depth += 1,
+                TokenKind::Punctuation(")" | "]" | "}") if depth == 0 => return Ok(()),
+                TokenKind::Punctuation(")" | "]" | "}") => {
+                    depth -= 1;
+                    if depth == 0 && is_block {
+                        self.position += 1;
+                        return Ok(());
+                    }
+                }
+                TokenKind::Punctuation("," | ";") if depth == 0 => return Ok(()),
+                _ => {}
+            }
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> &TokenKind {
+        &self.tokens[self.position].kind
+    }
+
+    fn eat(&mut self, punctuation: &str) -> bool {
+        if matches!(self.peek(), TokenKind::Punctuation(p) if *p == punctuation) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punctuation: &str) -> Result<()> {
+        if self.eat(punctuation) {
+            Ok(())
+        } else {
+            Err(self.error(&format!(
+                "Expected '{punctuation}' but found {}",
+                describe(self.peek())
+            )))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String> {
+        if let TokenKind::Identifier(name) = self.peek().clone() {
+            self.position += 1;
+            Ok(name)
+        } else {
+            Err(self.error(&format!(
+                "Expected an identifier but found {}",
+                describe(self.peek())
+            )))
+        }
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        let line = self.tokens[self.position.min(self.tokens.len() - 1)].line;
+        anyhow!("{message} on line {line}")
+    }
+}
+
+fn is_declaration_keyword(word: &str) -> bool {
+    matches!(word, "const" | "let" | "var")
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Identifier(name) => format!("`{name}`"),
+        TokenKind::String(_) => "a string".to_string(),
+        TokenKind::Regex { .. } => "a regex".to_string(),
+        TokenKind::Number(n) => format!("the number {n}"),
+        TokenKind::Punctuation(p) => format!("'{p}'"),
+        TokenKind::Eof => "the end of the file".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::generate_parser_for_grammar;
+
+    #[test]
+    fn test_evaluate_grammar_js() {
+        let grammar_json = evaluate_grammar_js(
+            r#"
+            const PREC = { sum: 1, product: 2 };
+
+            function commaSep1(rule) {
+              return seq(rule, repeat(seq(',', rule)));
+            }
+
+            module.exports = grammar({
+              name: 'calc',
+
+              extras: $ => [/\s/, $.comment],
+
+              word: $ => $.identifier,
+
+              rules: {
+                program: $ => commaSep1($._expression),
+
+                _expression: $ => choice(
+                  $.sum,
+                  $.product,
+                  $.call,
+                  $.identifier,
+                  $.number,
+                ),
+
+                sum: $ => prec.left(PREC.sum, seq(
+                  field('left', $._expression),
+                  '+',
+                  field('right', $._expression),
+                )),
+
+                product: $ => prec.left(PREC.product, seq(
+                  field('left', $._expression),
+                  '*',
+                  field('right', $._expression),
+                )),
+
+                call: $ => prec(PREC.product + 1, seq(
+                  alias($.identifier, $.function_name),
+                  token.immediate('('),
+                  optional(commaSep1($._expression)),
+                  ')',
+                )),
+
+                identifier: $ => /[a-z_]+/,
+
+                number: $ => token(/\d+/),
+
+                comment: $ => token(seq('#', /.*/)),
+              }
+            });
+            "#,
+        )
+        .unwrap();
+
+        let grammar = serde_json::from_str::<JsonValue>(&grammar_json).unwrap();
+        assert_eq!(grammar["name"], "calc");
+        assert_eq!(grammar["word"], "identifier");
+        assert_eq!(
+            grammar["rules"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            [
+                "program",
+                "_expression",
+                "sum",
+                "product",
+                "call",
+                "identifier",
+                "number",
+                "comment"
+            ]
+        );
+        assert_eq!(
+            grammar["rules"]["sum"],
+            json!({
+                "type": "PREC_LEFT",
+                "value": 1,
+                "content": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "left",
+                            "content": { "type": "SYMBOL", "name": "_expression" },
+                        },
+                        { "type": "STRING", "value": "+" },
+                        {
+                            "type": "FIELD",
+                            "name": "right",
+                            "content": { "type": "SYMBOL", "name": "_expression" },
+                        },
+                    ],
+                },
+            })
+        );
+        assert_eq!(grammar["rules"]["call"]["value"], 3);
+        assert_eq!(
+            grammar["extras"],
+            json!([
+                { "type": "PATTERN", "value": "\\s" },
+                { "type": "SYMBOL", "name": "comment" },
+            ])
+        );
+
+        let (name, c_code) = generate_parser_for_grammar(&grammar_json).unwrap();
+        assert_eq!(name, "calc");
+        assert!(c_code.contains("tree_sitter_calc"));
+    }
+
+    #[test]
+    fn test_evaluate_grammar_js_with_extensions() {
+        let grammar_json = evaluate_grammar_js(
+            r#"
+            module.exports = grammar({
+              name: 'query',
+
+              externals: $ => [$.indent],
+
+              conflict_overrides: $ => [[$.select, $.call]],
+
+              zero_width_externals: $ => [$.indent],
+
+              inline_after: $ => [[$.name, '(']],
+
+              rules: {
+                program: $ => seq($.indent, choice($.select, $.call)),
+                select: $ => seq(token.case_insensitive('select'), $.name),
+                call: $ => seq($.name, '(', ')'),
+                name: $ => /[a-z]+/,
+              }
+            });
+            "#,
+        )
+        .unwrap();
+
+        let grammar = serde_json::from_str::<JsonValue>(&grammar_json).unwrap();
+        assert_eq!(grammar["conflict_overrides"], json!([["select", "call"]]));
+        assert_eq!(grammar["zero_width_externals"], json!(["indent"]));
+        assert_eq!(
+            grammar["inline_after"],
+            json!([["name", { "type": "STRING", "value": "(" }]])
+        );
+        assert_eq!(
+            grammar["rules"]["select"]["members"][0],
+            json!({
+                "type": "CASE_INSENSITIVE_TOKEN",
+                "content": { "type": "STRING", "value": "select" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_evaluate_grammar_js_with_unsupported_constructs() {
+        let error = |source| evaluate_grammar_js(source).unwrap_err().to_string();
+
+        assert_eq!(
+            error("module.exports = grammar({ name: 'a', rules: { a: $ => $.b } });"),
+            "Undefined symbol 'b' on line 1"
+        );
+        assert_eq!(
+            error(
+                "const base = require('./base');\n\
+                 module.exports = grammar(base, { name: 'a', rules: {} });"
+            ),
+            "Unsupported or undefined identifier `require` on line 1"
+        );
+        assert_eq!(
+            error(
+                "module.exports = grammar({\n\
+                   name: 'a',\n\
+                   rules: { a: $ => choice(...['x', 'y'].map(s => s)) },\n\
+                 });"
+            ),
+            "Unsupported spread syntax on line 3"
+        );
+        assert_eq!(
+            error("module.exports = grammar({ name: 'a', rules: { a: $ => `x${1}` } });"),
+            "Unsupported template literal substitution on line 1"
+        );
+        assert_eq!(
+            error(
+                "module.exports = grammar({\n\
+                   name: 'a',\n\
+                   rules: { a: $ => prec(2147483647 + 1, 'a') },\n\
+                 });"
+            ),
+            "Arithmetic overflow on line 3"
+        );
+    }
+}
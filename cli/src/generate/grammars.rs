@@ -1,4 +1,4 @@
-use super::nfa::Nfa;
+use super::nfa::{CharacterSet, Nfa};
 use super::rules::{Alias, Associativity, Precedence, Rule, Symbol};
 use std::collections::HashMap;
 use std::fmt;
@@ -35,8 +35,17 @@ pub struct InputGrammar {
     pub precedence_orderings: Vec<Vec<PrecedenceEntry>>,
     pub external_tokens: Vec<Rule>,
     pub variables_to_inline: Vec<String>,
+    /// Rules to inline only where they directly follow the given symbol or
+    /// token, rather than everywhere.
+    pub variables_to_inline_after: Vec<(String, Rule)>,
     pub supertype_symbols: Vec<String>,
     pub word_token: Option<String>,
+    /// Pairs of rules, where conflicts between the two rules' productions are
+    /// resolved in favor of the first rule.
+    pub conflict_overrides: Vec<(String, String)>,
+    /// The names of external tokens that the external scanner produces without
+    /// consuming any input.
+    pub zero_width_externals: Vec<String>,
 }
 
 // Extracted lexical grammar
@@ -47,6 +56,11 @@ pub struct LexicalVariable {
     pub kind: VariableType,
     pub implicit_precedence: i32,
     pub start_state: u32,
+    /// Whether the token's letters were expanded to match either case.
+    pub case_insensitive: bool,
+    /// The characters that must follow the token for it to be accepted, from a
+    /// lookahead assertion at the end of its pattern.
+    pub lookahead: Option<CharacterSet>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -76,6 +90,9 @@ pub struct Production {
 pub struct InlinedProductionMap {
     pub productions: Vec<Production>,
     pub production_map: HashMap<(*const Production, u32), Vec<usize>>,
+    /// For each inlined production, the grammar's productions that it was built
+    /// from: the one whose steps were inlined into, and each inlined one.
+    pub origins: Vec<Vec<ProductionRef>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -90,21 +107,47 @@ pub struct ExternalToken {
     pub name: String,
     pub kind: VariableType,
     pub corresponding_internal_token: Option<Symbol>,
+    /// Whether the external scanner produces this token without consuming any
+    /// input, as with indentation markers.
+    pub zero_width: bool,
+    /// The precedence given to this token in the grammar's list of externals. When
+    /// several external tokens are valid in the same state, those with higher
+    /// precedence are listed first.
+    pub precedence: i32,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct SyntaxGrammar {
     pub variables: Vec<SyntaxVariable>,
     pub extra_symbols: Vec<Symbol>,
     pub expected_conflicts: Vec<Vec<Symbol>>,
     pub external_tokens: Vec<ExternalToken>,
     pub supertype_symbols: Vec<Symbol>,
-    pub variables_to_inline: Vec<Symbol>,
+    /// The variables to inline. A variable paired with a symbol is only inlined
+    /// where it directly follows that symbol in a production.
+    pub variables_to_inline: Vec<(Symbol, Option<Symbol>)>,
     pub word_token: Option<Symbol>,
     pub precedence_orderings: Vec<Vec<PrecedenceEntry>>,
+    /// Resolutions for conflicts between specific pairs of productions, which
+    /// take priority over precedence and associativity.
+    pub conflict_overrides: Vec<(ProductionRef, ProductionRef, Resolution)>,
+}
+
+/// A production of a syntax grammar, identified by the index of its variable
+/// and its index within that variable's productions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProductionRef {
+    pub variable_index: usize,
+    pub production_index: usize,
+}
+
+/// Which of the two productions in a conflict override should win.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    PreferFirst,
+    PreferSecond,
 }
 
-#[cfg(test)]
 impl ProductionStep {
     pub const fn new(symbol: Symbol) -> Self {
         Self {
@@ -115,7 +158,10 @@ impl ProductionStep {
             field_name: None,
         }
     }
+}
 
+#[cfg(test)]
+impl ProductionStep {
     pub fn with_prec(self, precedence: Precedence, associativity: Option<Associativity>) -> Self {
         Self {
             symbol: self.symbol,
@@ -231,6 +277,43 @@ impl SyntaxVariable {
     }
 }
 
+impl SyntaxGrammar {
+    /// Check if the given symbol refers to one of the auxiliary variables that
+    /// were introduced when desugaring `repeat` rules. These variables never
+    /// appear as named nodes in the syntax tree.
+    pub fn is_repeat_helper(&self, symbol: Symbol) -> bool {
+        symbol.is_non_terminal()
+            && self
+                .variables
+                .get(symbol.index)
+                .is_some_and(SyntaxVariable::is_auxiliary)
+    }
+
+    /// Check if the given symbol refers to an external token that doesn't consume
+    /// any input, so the tokens that follow it can appear at the same position.
+    pub fn is_zero_width(&self, symbol: Symbol) -> bool {
+        symbol.is_external()
+            && self
+                .external_tokens
+                .get(symbol.index)
+                .is_some_and(|token| token.zero_width)
+    }
+
+    /// Check if the given symbol is inlined wherever it occurs, so that it never
+    /// becomes a node of its own.
+    pub fn is_always_inlined(&self, symbol: Symbol) -> bool {
+        self.variables_to_inline.contains(&(symbol, None))
+    }
+
+    /// Check if the given symbol is inlined where it follows the given symbol,
+    /// or where it begins a production if there is no preceding symbol.
+    pub fn is_inlined_after(&self, symbol: Symbol, predecessor: Option<Symbol>) -> bool {
+        self.variables_to_inline
+            .iter()
+            .any(|(s, context)| *s == symbol && (context.is_none() || *context == predecessor))
+    }
+}
+
 impl InlinedProductionMap {
     pub fn inlined_productions<'a>(
         &'a self,
@@ -246,6 +329,16 @@ impl InlinedProductionMap {
                     .map(move |index| &self.productions[index])
             })
     }
+
+    /// The grammar's productions that the given production was built from, if
+    /// it was created by inlining.
+    pub fn origins(&self, production: &Production) -> Option<&[ProductionRef]> {
+        let index = self
+            .productions
+            .iter()
+            .position(|p| std::ptr::eq(p, production))?;
+        Some(&self.origins[index])
+    }
 }
 
 impl fmt::Display for PrecedenceEntry {
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::{env, fs};
@@ -8,26 +8,33 @@ use lazy_static::lazy_static;
 use regex::{Regex, RegexBuilder};
 use semver::Version;
 
+use analysis::{validate_grammar_wellformed, WellformednessError};
+use build_tables::build_parse_table::{Conflict, ConflictError};
 use build_tables::build_tables;
 use grammar_files::path_in_ignore;
 use grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar};
 use parse_grammar::parse_grammar;
-use prepare_grammar::prepare_grammar;
-use render::render_c_code;
-use rules::AliasMap;
+use prepare_grammar::{coalesce_optionals, prepare_grammar, process_inlines};
+use render::{check_abi_version, render_c_code, render_c_code_to};
+use rules::{AliasMap, Symbol};
+use tables::{LexTable, ParseTable};
 
-mod build_tables;
+pub mod analysis;
+pub mod build_tables;
 mod char_tree;
 mod dedup;
+pub mod grammar_diff;
 mod grammar_files;
-mod grammars;
+pub mod grammar_js;
+pub mod grammars;
 mod nfa;
-mod node_types;
+pub mod node_types;
 pub mod parse_grammar;
-mod prepare_grammar;
+pub mod prepare_grammar;
 mod render;
-mod rules;
-mod tables;
+pub mod rules;
+pub mod table_backend;
+pub mod tables;
 
 lazy_static! {
     static ref JSON_COMMENT_REGEX: Regex = RegexBuilder::new("^\\s*//.*")
@@ -37,12 +44,51 @@ lazy_static! {
 }
 
 struct GeneratedParser {
-    c_code: String,
-    node_types_json: String,
+    name: String,
+    parse_table: ParseTable,
+    main_lex_table: LexTable,
+    keyword_lex_table: LexTable,
+    keyword_capture_token: Option<Symbol>,
+    syntax_grammar: SyntaxGrammar,
+    lexical_grammar: LexicalGrammar,
+    simple_aliases: AliasMap,
+    abi_version: usize,
+}
+
+impl GeneratedParser {
+    fn render_c_code(self) -> String {
+        render_c_code(
+            &self.name,
+            self.parse_table,
+            self.main_lex_table,
+            self.keyword_lex_table,
+            self.keyword_capture_token,
+            self.syntax_grammar,
+            self.lexical_grammar,
+            self.simple_aliases,
+            self.abi_version,
+        )
+    }
+
+    fn render_c_code_to(self, writer: impl Write) -> std::io::Result<()> {
+        render_c_code_to(
+            writer,
+            &self.name,
+            self.parse_table,
+            self.main_lex_table,
+            self.keyword_lex_table,
+            self.keyword_capture_token,
+            self.syntax_grammar,
+            self.lexical_grammar,
+            self.simple_aliases,
+            self.abi_version,
+        )
+    }
 }
 
 pub const ALLOC_HEADER: &str = include_str!("./templates/alloc.h");
 
+#[allow(clippy::too_many_arguments)]
 pub fn generate_parser_in_directory(
     repo_path: &Path,
     grammar_path: Option<&str>,
@@ -50,7 +96,10 @@ pub fn generate_parser_in_directory(
     generate_bindings: bool,
     report_symbol_name: Option<&str>,
     js_runtime: Option<&str>,
-) -> Result<()> {
+    conflicts_json_path: Option<&Path>,
+    recovery: bool,
+    strict_token_order: bool,
+) -> Result<Vec<WellformednessError>> {
     let mut repo_path = repo_path.to_owned();
     let mut grammar_path = grammar_path;
 
@@ -109,10 +158,7 @@ pub fn generate_parser_in_directory(
     let language_name = input_grammar.name;
 
     // Generate the parser and related files.
-    let GeneratedParser {
-        c_code,
-        node_types_json,
-    } = generate_parser_for_grammar_with_opts(
+    let result = generate_parser_for_grammar_with_opts(
         &language_name,
         syntax_grammar,
         lexical_grammar,
@@ -120,9 +166,36 @@ pub fn generate_parser_in_directory(
         simple_aliases,
         abi_version,
         report_symbol_name,
-    )?;
+        recovery,
+        strict_token_order,
+    );
 
-    write_file(&src_path.join("parser.c"), c_code)?;
+    // Write out the unresolved conflicts, if requested, before reporting any error.
+    // Other errors stop the generator before all of the conflicts are known, so
+    // the file is only written if generation succeeded or failed on conflicts.
+    if let Some(path) = conflicts_json_path {
+        let conflicts = match &result {
+            Ok(_) => Some(&[] as &[Conflict]),
+            Err(error) => error
+                .downcast_ref::<ConflictError>()
+                .map(|error| error.conflicts.as_slice()),
+        };
+        if let Some(conflicts) = conflicts {
+            fs::write(path, serde_json::to_string_pretty(conflicts)?)
+                .with_context(|| format!("Failed to write conflicts to {path:?}"))?;
+        }
+    }
+    let (parser, node_types_json) = result?;
+    let warnings = check_wellformedness(&parser.syntax_grammar)?;
+
+    // Stream the C code to the file, since it can be very large.
+    let parser_path = src_path.join("parser.c");
+    let write_error = || "Failed to write \"parser.c\"";
+    let mut parser_file = BufWriter::new(fs::File::create(&parser_path).with_context(write_error)?);
+    parser
+        .render_c_code_to(&mut parser_file)
+        .with_context(write_error)?;
+    parser_file.flush().with_context(write_error)?;
     write_file(&src_path.join("node-types.json"), node_types_json)?;
     write_file(&header_path.join("alloc.h"), ALLOC_HEADER)?;
     write_file(&header_path.join("array.h"), tree_sitter::ARRAY_HEADER)?;
@@ -132,10 +205,19 @@ pub fn generate_parser_in_directory(
         grammar_files::generate_grammar_files(&repo_path, &language_name, generate_bindings)?;
     }
 
-    Ok(())
+    Ok(warnings)
 }
 
 pub fn generate_parser_for_grammar(grammar_json: &str) -> Result<(String, String)> {
+    generate_parser_for_grammar_with_recovery(grammar_json, true)
+}
+
+/// Generate a parser for the given grammar, omitting the error recovery actions
+/// from its parse table if `recovery` is false.
+pub fn generate_parser_for_grammar_with_recovery(
+    grammar_json: &str,
+    recovery: bool,
+) -> Result<(String, String)> {
     let grammar_json = JSON_COMMENT_REGEX.replace_all(grammar_json, "\n");
     let input_grammar = parse_grammar(&grammar_json)?;
     let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
@@ -148,10 +230,38 @@ pub fn generate_parser_for_grammar(grammar_json: &str) -> Result<(String, String
         simple_aliases,
         tree_sitter::LANGUAGE_VERSION,
         None,
+        recovery,
+        false,
     )?;
-    Ok((input_grammar.name, parser.c_code))
+    check_wellformedness(&parser.0.syntax_grammar)?;
+    Ok((input_grammar.name, parser.0.render_c_code()))
+}
+
+/// Check that every rule in the grammar can match some finite input. Rules that
+/// are never used are allowed, as they may only be meant for other grammars that
+/// extend this one, so they are returned as warnings instead.
+///
+/// This runs after the parse table is built, because a rule that can't match
+/// any input often causes conflicts too, and those say more about the problem.
+fn check_wellformedness(syntax_grammar: &SyntaxGrammar) -> Result<Vec<WellformednessError>> {
+    let Err(errors) = validate_grammar_wellformed(syntax_grammar) else {
+        return Ok(Vec::new());
+    };
+    let (unreachable, non_productive) = errors
+        .into_iter()
+        .partition::<Vec<_>, _>(|error| matches!(error, WellformednessError::Unreachable { .. }));
+    if non_productive.is_empty() {
+        Ok(unreachable)
+    } else {
+        Err(anyhow!(non_productive
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")))
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn generate_parser_for_grammar_with_opts(
     name: &str,
     syntax_grammar: SyntaxGrammar,
@@ -160,7 +270,10 @@ fn generate_parser_for_grammar_with_opts(
     simple_aliases: AliasMap,
     abi_version: usize,
     report_symbol_name: Option<&str>,
-) -> Result<GeneratedParser> {
+    recovery: bool,
+    strict_token_order: bool,
+) -> Result<(GeneratedParser, String)> {
+    check_abi_version(abi_version, &syntax_grammar)?;
     let variable_info =
         node_types::get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases)?;
     let node_types_json = node_types::generate_node_types_json(
@@ -169,29 +282,81 @@ fn generate_parser_for_grammar_with_opts(
         &simple_aliases,
         &variable_info,
     );
-    let (parse_table, main_lex_table, keyword_lex_table, keyword_capture_token) = build_tables(
+    let mut syntax_grammar = syntax_grammar;
+    let mut tables = build_tables(
         &syntax_grammar,
         &lexical_grammar,
         &simple_aliases,
         &variable_info,
         inlines,
         report_symbol_name,
+        recovery,
+        strict_token_order,
     )?;
-    let c_code = render_c_code(
-        name,
-        parse_table,
-        main_lex_table,
-        keyword_lex_table,
-        keyword_capture_token,
-        syntax_grammar,
+
+    // Coalescing chains of optional steps always leaves fewer productions, but
+    // not always fewer parse states, so it is only kept when the table shrinks.
+    if let Some((coalesced_grammar, coalesced_tables)) = build_coalesced_tables(
+        &syntax_grammar,
+        &lexical_grammar,
+        &simple_aliases,
+        recovery,
+        strict_token_order,
+    ) {
+        if coalesced_tables.0.states.len() < tables.0.states.len() {
+            syntax_grammar = coalesced_grammar;
+            tables = coalesced_tables;
+        }
+    }
+
+    let (parse_table, main_lex_table, keyword_lex_table, keyword_capture_token) = tables;
+    Ok((
+        GeneratedParser {
+            name: name.to_string(),
+            parse_table,
+            main_lex_table,
+            keyword_lex_table,
+            keyword_capture_token,
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            abi_version,
+        },
+        serde_json::to_string_pretty(&node_types_json).unwrap(),
+    ))
+}
+
+type Tables = (ParseTable, LexTable, LexTable, Option<Symbol>);
+
+// Build the tables for a copy of the grammar in which chains of optional steps
+// are coalesced. Returns `None` if there are no such chains, or if the tables
+// can't be built for the coalesced grammar.
+fn build_coalesced_tables(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    simple_aliases: &AliasMap,
+    recovery: bool,
+    strict_token_order: bool,
+) -> Option<(SyntaxGrammar, Tables)> {
+    let mut coalesced_grammar = syntax_grammar.clone();
+    if !coalesce_optionals(&mut coalesced_grammar) {
+        return None;
+    }
+    let inlines = process_inlines(&coalesced_grammar, lexical_grammar).ok()?;
+    let variable_info =
+        node_types::get_variable_info(&coalesced_grammar, lexical_grammar, simple_aliases).ok()?;
+    let tables = build_tables(
+        &coalesced_grammar,
         lexical_grammar,
         simple_aliases,
-        abi_version,
-    );
-    Ok(GeneratedParser {
-        c_code,
-        node_types_json: serde_json::to_string_pretty(&node_types_json).unwrap(),
-    })
+        &variable_info,
+        &inlines,
+        None,
+        recovery,
+        strict_token_order,
+    )
+    .ok()?;
+    Some((coalesced_grammar, tables))
 }
 
 pub fn load_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result<String> {
@@ -215,12 +380,26 @@ fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result
 
     let js_runtime = js_runtime.unwrap_or("node");
 
-    let mut node_process = Command::new(js_runtime)
-        .env("TREE_SITTER_GRAMMAR_PATH", grammar_path)
+    let mut node_process = match Command::new(js_runtime)
+        .env("TREE_SITTER_GRAMMAR_PATH", &grammar_path)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
-        .with_context(|| format!("Failed to run `{js_runtime}`"))?;
+    {
+        Ok(node_process) => node_process,
+        // Without a JavaScript runtime, fall back to evaluating the grammar
+        // file directly, which supports the common subset of the DSL.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let source = fs::read_to_string(&grammar_path)
+                .with_context(|| format!("Failed to read grammar file {grammar_path:?}"))?;
+            return grammar_js::evaluate_grammar_js(&source).with_context(|| {
+                format!(
+                    "Failed to run `{js_runtime}`, and could not evaluate the grammar without it"
+                )
+            });
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to run `{js_runtime}`")),
+    };
 
     let mut node_stdin = node_process
         .stdin
@@ -361,6 +361,24 @@ pub fn get_variable_info(
     Ok(result)
 }
 
+/// Generate the contents of `node-types.json` for the given grammar, as a JSON
+/// value. This computes the grammar's variable info, which
+/// [`generate_node_types_json`] takes as an argument.
+pub fn generate_node_types(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    default_aliases: &AliasMap,
+) -> Result<serde_json::Value> {
+    let variable_info = get_variable_info(syntax_grammar, lexical_grammar, default_aliases)?;
+    let node_types_json = generate_node_types_json(
+        syntax_grammar,
+        lexical_grammar,
+        default_aliases,
+        &variable_info,
+    );
+    Ok(serde_json::to_value(node_types_json)?)
+}
+
 pub fn generate_node_types_json(
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
@@ -487,7 +505,7 @@ pub fn generate_node_types_json(
             };
             subtype_map.push((supertype, subtypes.clone()));
             node_type_json.subtypes = Some(subtypes);
-        } else if !syntax_grammar.variables_to_inline.contains(&symbol) {
+        } else if !syntax_grammar.is_always_inlined(symbol) {
             // If a rule is aliased under multiple names, then its information
             // contributes to multiple entries in the final JSON.
             for alias in aliases_by_symbol
@@ -684,7 +702,7 @@ fn variable_type_for_child_type(
         ChildType::Normal(symbol) => {
             if syntax_grammar.supertype_symbols.contains(symbol) {
                 VariableType::Named
-            } else if syntax_grammar.variables_to_inline.contains(symbol) {
+            } else if syntax_grammar.is_always_inlined(*symbol) {
                 VariableType::Hidden
             } else {
                 match symbol.kind {
@@ -810,6 +828,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generate_node_types() {
+        let (syntax_grammar, lexical_grammar, _, default_aliases) =
+            prepare_grammar(&InputGrammar {
+                variables: vec![
+                    Variable {
+                        name: "assignment".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::seq(vec![
+                            Rule::field("left".to_string(), Rule::named("identifier")),
+                            Rule::string("="),
+                            Rule::named("identifier"),
+                        ]),
+                    },
+                    Variable {
+                        name: "identifier".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::pattern("[a-z]+", ""),
+                    },
+                ],
+                ..Default::default()
+            })
+            .unwrap();
+        let node_types =
+            generate_node_types(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
+
+        assert_eq!(
+            node_types[0],
+            serde_json::json!({
+                "type": "assignment",
+                "named": true,
+                "fields": {
+                    "left": {
+                        "multiple": false,
+                        "required": true,
+                        "types": [{ "type": "identifier", "named": true }],
+                    },
+                },
+                "children": {
+                    "multiple": false,
+                    "required": true,
+                    "types": [{ "type": "identifier", "named": true }],
+                },
+            })
+        );
+    }
+
     #[test]
     fn test_node_types_simple_extras() {
         let node_types = get_node_types(&InputGrammar {
@@ -1797,6 +1862,8 @@ mod tests {
                 kind: VariableType::Named,
                 implicit_precedence: 0,
                 start_state: 0,
+                case_insensitive: false,
+                lookahead: None,
             });
         }
         lexical_grammar
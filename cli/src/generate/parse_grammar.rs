@@ -63,6 +63,9 @@ enum RuleJSON {
     IMMEDIATE_TOKEN {
         content: Box<RuleJSON>,
     },
+    CASE_INSENSITIVE_TOKEN {
+        content: Box<RuleJSON>,
+    },
 }
 
 #[derive(Deserialize)]
@@ -87,8 +90,14 @@ pub(crate) struct GrammarJSON {
     #[serde(default)]
     inline: Vec<String>,
     #[serde(default)]
+    inline_after: Vec<(String, RuleJSON)>,
+    #[serde(default)]
     supertypes: Vec<String>,
     word: Option<String>,
+    #[serde(default)]
+    conflict_overrides: Vec<(String, String)>,
+    #[serde(default)]
+    zero_width_externals: Vec<String>,
 }
 
 pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
@@ -144,6 +153,13 @@ pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
         expected_conflicts: grammar_json.conflicts,
         supertype_symbols: grammar_json.supertypes,
         variables_to_inline: grammar_json.inline,
+        variables_to_inline_after: grammar_json
+            .inline_after
+            .into_iter()
+            .map(|(name, predecessor)| (name, parse_rule(predecessor)))
+            .collect(),
+        conflict_overrides: grammar_json.conflict_overrides,
+        zero_width_externals: grammar_json.zero_width_externals,
         precedence_orderings,
         variables,
         extra_symbols,
@@ -198,6 +214,9 @@ fn parse_rule(json: RuleJSON) -> Rule {
         }
         RuleJSON::TOKEN { content } => Rule::token(parse_rule(*content)),
         RuleJSON::IMMEDIATE_TOKEN { content } => Rule::immediate_token(parse_rule(*content)),
+        RuleJSON::CASE_INSENSITIVE_TOKEN { content } => {
+            Rule::case_insensitive_token(parse_rule(*content))
+        }
     }
 }
 
@@ -253,4 +272,63 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_parse_grammar_with_conflict_overrides() {
+        let grammar = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {
+                "expression": {"type": "STRING", "value": "x"}
+            },
+            "conflict_overrides": [["multiplication", "addition"]]
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            grammar.conflict_overrides,
+            [("multiplication".to_string(), "addition".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_grammar_with_inline_after() {
+        let grammar = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {
+                "call": {"type": "STRING", "value": "x"}
+            },
+            "inline_after": [["_arguments", {"type": "STRING", "value": "("}]]
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            grammar.variables_to_inline_after,
+            [("_arguments".to_string(), Rule::string("("))]
+        );
+    }
+
+    #[test]
+    fn test_parse_grammar_with_case_insensitive_tokens() {
+        let grammar = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {
+                "select": {
+                    "type": "CASE_INSENSITIVE_TOKEN",
+                    "content": {"type": "STRING", "value": "SELECT"}
+                }
+            }
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            grammar.variables[0].rule,
+            Rule::case_insensitive_token(Rule::String("SELECT".to_string()))
+        );
+    }
 }
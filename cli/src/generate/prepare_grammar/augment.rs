@@ -0,0 +1,75 @@
+use crate::generate::grammars::{
+    Production, ProductionStep, SyntaxGrammar, SyntaxVariable, VariableType,
+};
+use crate::generate::rules::Symbol;
+
+/// Add an augmented start variable `S' -> S`, where `S` is the grammar's start
+/// rule, and return its symbol. The new variable is appended, so that the start
+/// rule keeps its index. The accepting state is the one in which this variable's
+/// production is complete and the lookahead is the end of input.
+///
+/// The parse table builder does not need this: its initial item, created by
+/// `ParseItem::start`, stands for the same production without adding a variable
+/// to the grammar. This is for analyses that expect the augmentation to be
+/// explicit.
+///
+/// Returns `None`, leaving the grammar untouched, if it has no variables.
+pub fn augment(grammar: &mut SyntaxGrammar) -> Option<Symbol> {
+    let name = format!("{}_augmented", grammar.variables.first()?.name);
+    let symbol = Symbol::non_terminal(grammar.variables.len());
+    grammar.variables.push(SyntaxVariable {
+        name,
+        kind: VariableType::Auxiliary,
+        productions: vec![Production {
+            dynamic_precedence: 0,
+            steps: vec![ProductionStep::new(Symbol::non_terminal(0))],
+        }],
+    });
+    Some(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_augment() {
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::non_terminal(1))],
+                    }],
+                },
+                SyntaxVariable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::terminal(0))],
+                    }],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let symbol = augment(&mut syntax_grammar).unwrap();
+        assert_eq!(symbol, Symbol::non_terminal(2));
+        let variable = &syntax_grammar.variables[symbol.index];
+        assert_eq!(variable.name, "program_augmented");
+        assert_eq!(
+            variable.productions,
+            [Production {
+                dynamic_precedence: 0,
+                steps: vec![ProductionStep::new(Symbol::non_terminal(0))]
+            }]
+        );
+
+        let mut empty_grammar = SyntaxGrammar::default();
+        assert_eq!(augment(&mut empty_grammar), None);
+        assert!(empty_grammar.variables.is_empty());
+    }
+}
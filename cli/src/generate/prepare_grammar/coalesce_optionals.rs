@@ -0,0 +1,260 @@
+use crate::generate::grammars::{
+    Production, ProductionStep, SyntaxGrammar, SyntaxVariable, VariableType,
+};
+use crate::generate::rules::{Precedence, Symbol};
+
+/// Find the chains of optional steps that were expanded into a production for
+/// every combination of the steps being present or absent, and replace each such
+/// set of productions with a smaller one. For each step in the chain, a new
+/// hidden variable matches that step followed by any combination of the steps
+/// after it: it has a production for the step alone, and one for the step
+/// followed by each of the later steps' variables. The production refers to one
+/// of these variables in place of the chain, or leaves the chain out. The
+/// language is unchanged, but the `2^n` productions for `n` optional steps become
+/// `n + 1` productions, along with `n(n + 1) / 2` productions for the new
+/// variables.
+///
+/// Only chains of at least two steps without precedence are replaced, within
+/// productions that have the same dynamic precedence. Returns whether any chain
+/// was replaced.
+pub fn coalesce_optionals(grammar: &mut SyntaxGrammar) -> bool {
+    let mut changed = false;
+    let mut variable_index = 0;
+    while variable_index < grammar.variables.len() {
+        let mut helper_count = 0;
+        while let Some((index, start, len)) =
+            find_optional_chain(&grammar.variables[variable_index].productions)
+        {
+            changed = true;
+            let production = grammar.variables[variable_index].productions[index].clone();
+
+            // Add one helper for each step of the chain, starting with the last,
+            // so that each can refer to the ones after it.
+            let mut helpers = Vec::with_capacity(len);
+            for step in production.steps[start..start + len].iter().rev() {
+                let mut productions = vec![Production {
+                    dynamic_precedence: 0,
+                    steps: vec![step.clone()],
+                }];
+                for later_helper in helpers.iter().rev() {
+                    productions.push(Production {
+                        dynamic_precedence: 0,
+                        steps: vec![step.clone(), ProductionStep::new(*later_helper)],
+                    });
+                }
+                let symbol = Symbol::non_terminal(grammar.variables.len());
+                helper_count += 1;
+                grammar.variables.push(SyntaxVariable {
+                    name: format!(
+                        "_{}_optional{helper_count}",
+                        grammar.variables[variable_index].name
+                    ),
+                    kind: VariableType::Hidden,
+                    productions,
+                });
+                helpers.push(symbol);
+            }
+
+            let mut replacements = Vec::with_capacity(len + 1);
+            for helper in helpers.into_iter().rev() {
+                let mut steps = production.steps.clone();
+                steps.splice(start..start + len, [ProductionStep::new(helper)]);
+                replacements.push(Production {
+                    steps,
+                    dynamic_precedence: production.dynamic_precedence,
+                });
+            }
+            let mut steps = production.steps.clone();
+            steps.drain(start..start + len);
+            replacements.push(Production {
+                steps,
+                dynamic_precedence: production.dynamic_precedence,
+            });
+
+            let productions = &mut grammar.variables[variable_index].productions;
+            productions.retain(|p| {
+                p.dynamic_precedence != production.dynamic_precedence
+                    || !is_optional_variant(&production, start, len, p)
+            });
+            let index = index.min(productions.len());
+            productions.splice(index..index, replacements);
+        }
+        variable_index += 1;
+    }
+    changed
+}
+
+// Find a production and a range of at least two of its steps such that the
+// variable has a production for every way of leaving out some of those steps.
+// Returns the production's index, and the start and length of the range.
+fn find_optional_chain(productions: &[Production]) -> Option<(usize, usize, usize)> {
+    for (index, production) in productions.iter().enumerate() {
+        for start in 0..production.steps.len() {
+            let max_len = production.steps[start..]
+                .iter()
+                .take_while(|step| {
+                    step.precedence == Precedence::None && step.associativity.is_none()
+                })
+                .count();
+            for len in (2..=max_len.min(8)).rev() {
+                // With repeated steps, a variant could leave out different steps.
+                let chain = &production.steps[start..start + len];
+                if chain
+                    .iter()
+                    .enumerate()
+                    .any(|(i, step)| chain[..i].contains(step))
+                {
+                    continue;
+                }
+                let variant_count = productions
+                    .iter()
+                    .filter(|p| {
+                        p.dynamic_precedence == production.dynamic_precedence
+                            && is_optional_variant(production, start, len, p)
+                    })
+                    .count();
+                if variant_count == 1 << len {
+                    return Some((index, start, len));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Check if the given production is the same as the full one, except that some
+// of the steps in the given range may be left out.
+fn is_optional_variant(
+    full: &Production,
+    start: usize,
+    len: usize,
+    production: &Production,
+) -> bool {
+    let end = start + len;
+    let suffix_len = full.steps.len() - end;
+    if production.steps.len() < start + suffix_len
+        || production.steps.len() > full.steps.len()
+        || production.steps[..start] != full.steps[..start]
+        || production.steps[production.steps.len() - suffix_len..] != full.steps[end..]
+    {
+        return false;
+    }
+
+    // The remaining steps must be a subsequence of the chain.
+    let mut chain = full.steps[start..end].iter();
+    production.steps[start..production.steps.len() - suffix_len]
+        .iter()
+        .all(|step| chain.any(|s| s == step))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::build_tables::build_tables;
+    use crate::generate::grammars::{InputGrammar, LexicalGrammar, Variable};
+    use crate::generate::node_types::get_variable_info;
+    use crate::generate::prepare_grammar::{prepare_grammar, process_inlines};
+    use crate::generate::rules::{AliasMap, Rule};
+    use crate::generate::tables::ParseTable;
+
+    #[test]
+    fn test_coalesce_optionals() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable::named("program", Rule::repeat(Rule::named("declaration"))),
+                Variable::named(
+                    "declaration",
+                    Rule::seq(vec![
+                        Rule::string("let"),
+                        Rule::choice(vec![Rule::string("pub"), Rule::Blank]),
+                        Rule::choice(vec![Rule::string("mut"), Rule::Blank]),
+                        Rule::choice(vec![Rule::string("ref"), Rule::Blank]),
+                        Rule::string("x"),
+                    ]),
+                ),
+            ],
+            ..Default::default()
+        };
+        let (mut syntax_grammar, lexical_grammar, _, simple_aliases) =
+            prepare_grammar(&grammar).unwrap();
+        let original_variable_count = syntax_grammar.variables.len();
+        let original_table = build_parse_table(&syntax_grammar, &lexical_grammar, &simple_aliases);
+
+        assert!(coalesce_optionals(&mut syntax_grammar));
+        let helper_names = syntax_grammar.variables[original_variable_count..]
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            helper_names,
+            [
+                "_declaration_optional1",
+                "_declaration_optional2",
+                "_declaration_optional3",
+            ]
+        );
+
+        // Each prefix of the chain no longer needs its own set of parse states.
+        let parse_table = build_parse_table(&syntax_grammar, &lexical_grammar, &simple_aliases);
+        assert_eq!(original_table.states.len(), 16);
+        assert_eq!(parse_table.states.len(), 13);
+
+        // The grammar still accepts every combination of the optional keywords,
+        // in order and at most once.
+        let dfa = parse_table.viable_prefix_states();
+        let token = |name| {
+            Symbol::terminal(
+                lexical_grammar
+                    .variables
+                    .iter()
+                    .position(|v| v.name == name)
+                    .unwrap(),
+            )
+        };
+        let optional_keywords = ["pub", "mut", "ref"];
+        for mask in 0..1 << optional_keywords.len() {
+            let mut tokens = vec![token("let")];
+            for (i, keyword) in optional_keywords.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    tokens.push(token(keyword));
+                }
+            }
+            tokens.push(token("x"));
+            tokens.push(token("let"));
+            assert_eq!(
+                dfa.viable_prefix(&tokens).length,
+                tokens.len(),
+                "declaration with keywords {mask:03b} is rejected"
+            );
+        }
+        for names in [["let", "mut", "pub", "x"], ["let", "pub", "pub", "x"]] {
+            let tokens = names.map(token);
+            assert_eq!(dfa.viable_prefix(&tokens).length, 2, "{names:?}");
+        }
+
+        // There are no chains left to replace.
+        assert!(!coalesce_optionals(&mut syntax_grammar));
+    }
+
+    fn build_parse_table(
+        syntax_grammar: &SyntaxGrammar,
+        lexical_grammar: &LexicalGrammar,
+        simple_aliases: &AliasMap,
+    ) -> ParseTable {
+        let inlines = process_inlines(syntax_grammar, lexical_grammar).unwrap();
+        let variable_info =
+            get_variable_info(syntax_grammar, lexical_grammar, simple_aliases).unwrap();
+        build_tables(
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            &variable_info,
+            &inlines,
+            None,
+            true,
+            false,
+        )
+        .unwrap()
+        .0
+    }
+}
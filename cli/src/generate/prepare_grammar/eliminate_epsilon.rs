@@ -0,0 +1,311 @@
+use crate::generate::analysis::nullable_variables;
+use crate::generate::grammars::{Production, SyntaxGrammar};
+use crate::generate::rules::Symbol;
+
+/// Remove the empty productions from the grammar without changing the language
+/// that it matches. Wherever a production refers to a nullable variable, a copy
+/// of the production without that step is added, so that the empty production
+/// is no longer needed. The start rule keeps an empty production if it could
+/// match an empty sequence of tokens before.
+///
+/// Variables that could only match an empty sequence are removed, along with
+/// every reference to them, and the remaining variables are renumbered. Because
+/// production indices change, the grammar's conflict overrides are cleared.
+/// Where a copy leaves out a production's last step, the new last step takes
+/// that step's precedence and associativity unless it has its own, so that the
+/// copy is reduced in the same way.
+pub fn eliminate_epsilon(grammar: &mut SyntaxGrammar) {
+    let nullable_variables = nullable_variables(grammar);
+    for (variable_index, variable) in grammar.variables.iter_mut().enumerate() {
+        let mut productions = Vec::<Production>::new();
+        for production in &variable.productions {
+            // Each nullable step can either be kept or left out.
+            let mut variants = vec![Vec::new()];
+            for (step_index, step) in production.steps.iter().enumerate() {
+                let is_optional =
+                    step.symbol.is_non_terminal() && nullable_variables[step.symbol.index];
+                for i in 0..variants.len() {
+                    if is_optional {
+                        variants.push(variants[i].clone());
+                    }
+                    variants[i].push(step_index);
+                }
+            }
+
+            for step_indices in variants {
+                if step_indices.is_empty() && variable_index != 0 {
+                    continue;
+                }
+                let mut steps = step_indices
+                    .iter()
+                    .map(|i| production.steps[*i].clone())
+                    .collect::<Vec<_>>();
+                if let (Some(last_step), Some(original_last_step)) =
+                    (steps.last_mut(), production.steps.last())
+                {
+                    if last_step.precedence.is_none() {
+                        last_step.precedence = original_last_step.precedence.clone();
+                    }
+                    if last_step.associativity.is_none() {
+                        last_step.associativity = original_last_step.associativity;
+                    }
+                }
+                let production = Production {
+                    steps,
+                    dynamic_precedence: production.dynamic_precedence,
+                };
+                if !productions.contains(&production) {
+                    productions.push(production);
+                }
+            }
+        }
+        variable.productions = productions;
+    }
+
+    // Variables that only matched an empty sequence now have no productions.
+    // Remove the productions that refer to them, until none are left.
+    let mut removed = vec![false; grammar.variables.len()];
+    let mut done = false;
+    while !done {
+        done = true;
+        for (i, variable) in grammar.variables.iter_mut().enumerate() {
+            variable.productions.retain(|production| {
+                !production
+                    .steps
+                    .iter()
+                    .any(|step| step.symbol.is_non_terminal() && removed[step.symbol.index])
+            });
+            if i != 0 && !removed[i] && variable.productions.is_empty() {
+                removed[i] = true;
+                done = false;
+            }
+        }
+    }
+    remove_variables(grammar, &removed);
+}
+
+// Remove the given variables from the grammar, renumbering the others, and drop
+// any references to the removed ones outside of the productions.
+fn remove_variables(grammar: &mut SyntaxGrammar, removed: &[bool]) {
+    let mut new_indices = Vec::with_capacity(removed.len());
+    let mut next_index = 0;
+    for is_removed in removed {
+        new_indices.push(next_index);
+        if !is_removed {
+            next_index += 1;
+        }
+    }
+    let map = |symbol: Symbol| {
+        if !symbol.is_non_terminal() {
+            Some(symbol)
+        } else if removed[symbol.index] {
+            None
+        } else {
+            Some(Symbol::non_terminal(new_indices[symbol.index]))
+        }
+    };
+
+    let mut index = 0;
+    grammar.variables.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+    for variable in &mut grammar.variables {
+        for production in &mut variable.productions {
+            for step in &mut production.steps {
+                step.symbol = map(step.symbol).unwrap();
+            }
+        }
+    }
+    grammar.extra_symbols = grammar
+        .extra_symbols
+        .iter()
+        .filter_map(|s| map(*s))
+        .collect();
+    grammar.supertype_symbols = grammar
+        .supertype_symbols
+        .iter()
+        .filter_map(|s| map(*s))
+        .collect();
+    grammar.expected_conflicts = grammar
+        .expected_conflicts
+        .iter()
+        .map(|conflict| conflict.iter().filter_map(|s| map(*s)).collect::<Vec<_>>())
+        .filter(|conflict| conflict.len() > 1)
+        .collect();
+    grammar.variables_to_inline = grammar
+        .variables_to_inline
+        .iter()
+        .filter_map(|(symbol, context)| match context {
+            Some(context) => Some((map(*symbol)?, Some(map(*context)?))),
+            None => Some((map(*symbol)?, None)),
+        })
+        .collect();
+    grammar.conflict_overrides.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::analysis::nullable_productions;
+    use crate::generate::grammars::{ProductionStep, SyntaxVariable, VariableType};
+    use crate::generate::rules::{Associativity, Precedence};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_eliminate_epsilon() {
+        let production = |steps: Vec<Symbol>| Production {
+            dynamic_precedence: 0,
+            steps: steps.into_iter().map(ProductionStep::new).collect(),
+        };
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                // program -> wrapper | wrapper ';'
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(vec![Symbol::non_terminal(1)]),
+                        production(vec![Symbol::non_terminal(1), Symbol::terminal(1)]),
+                    ],
+                },
+                // wrapper -> list
+                SyntaxVariable {
+                    name: "wrapper".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![production(vec![Symbol::non_terminal(2)])],
+                },
+                // list -> ε | 'x' list
+                SyntaxVariable {
+                    name: "list".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(vec![]),
+                        production(vec![Symbol::terminal(0), Symbol::non_terminal(2)]),
+                    ],
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Enumerate the sequences of tokens, up to the given length, that the
+        // grammar matches, by expanding the leftmost variable of each sentence.
+        let language = |grammar: &SyntaxGrammar, max_length: usize| {
+            let mut result = HashSet::new();
+            let mut stack = vec![vec![Symbol::non_terminal(0)]];
+            while let Some(sentence) = stack.pop() {
+                if sentence.len() > max_length + 1 {
+                    continue;
+                }
+                let Some(i) = sentence.iter().position(Symbol::is_non_terminal) else {
+                    if sentence.len() <= max_length {
+                        result.insert(sentence);
+                    }
+                    continue;
+                };
+                for production in &grammar.variables[sentence[i].index].productions {
+                    let mut next_sentence = sentence[..i].to_vec();
+                    next_sentence.extend(production.steps.iter().map(|step| step.symbol));
+                    next_sentence.extend_from_slice(&sentence[i + 1..]);
+                    stack.push(next_sentence);
+                }
+            }
+            result
+        };
+
+        let original_language = language(&syntax_grammar, 4);
+        eliminate_epsilon(&mut syntax_grammar);
+        assert_eq!(language(&syntax_grammar, 4), original_language);
+        assert!(original_language.contains(&Vec::new()));
+
+        // Only the start rule still has an empty production.
+        assert_eq!(nullable_productions(&syntax_grammar), [(0, 1)]);
+        assert_eq!(
+            syntax_grammar.variables[2].productions,
+            [
+                production(vec![Symbol::terminal(0), Symbol::non_terminal(2)]),
+                production(vec![Symbol::terminal(0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eliminate_epsilon_removes_empty_variables() {
+        let production = |steps: Vec<ProductionStep>| Production {
+            dynamic_precedence: 0,
+            steps,
+        };
+        let step = ProductionStep::new;
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                // program -> e 'x' | 'x' e suffix
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(vec![
+                            step(Symbol::non_terminal(1)),
+                            step(Symbol::terminal(0)),
+                        ]),
+                        production(vec![
+                            step(Symbol::terminal(0)),
+                            step(Symbol::non_terminal(1)),
+                            step(Symbol::non_terminal(2))
+                                .with_prec(Precedence::Integer(2), Some(Associativity::Left)),
+                        ]),
+                    ],
+                },
+                // e -> ε
+                SyntaxVariable {
+                    name: "e".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![production(vec![])],
+                },
+                // suffix -> ε | 'y'
+                SyntaxVariable {
+                    name: "suffix".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(vec![]),
+                        production(vec![step(Symbol::terminal(1))]),
+                    ],
+                },
+            ],
+            extra_symbols: vec![Symbol::non_terminal(1)],
+            supertype_symbols: vec![Symbol::non_terminal(2)],
+            ..Default::default()
+        };
+
+        eliminate_epsilon(&mut syntax_grammar);
+        assert_eq!(
+            syntax_grammar
+                .variables
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            ["program", "suffix"]
+        );
+        assert!(syntax_grammar.extra_symbols.is_empty());
+        assert_eq!(syntax_grammar.supertype_symbols, [Symbol::non_terminal(1)]);
+
+        // Where the last step is left out, its precedence moves to the new last
+        // step.
+        assert_eq!(
+            syntax_grammar.variables[0].productions,
+            [
+                production(vec![step(Symbol::terminal(0))]),
+                production(vec![
+                    step(Symbol::terminal(0)),
+                    step(Symbol::non_terminal(1))
+                        .with_prec(Precedence::Integer(2), Some(Associativity::Left)),
+                ]),
+                production(vec![step(Symbol::terminal(0))
+                    .with_prec(Precedence::Integer(2), Some(Associativity::Left))]),
+            ]
+        );
+        assert_eq!(
+            syntax_grammar.variables[1].productions,
+            [production(vec![step(Symbol::terminal(1))])]
+        );
+    }
+}
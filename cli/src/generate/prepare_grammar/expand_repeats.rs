@@ -115,7 +115,7 @@ pub(super) fn expand_repeats(mut grammar: ExtractedSyntaxGrammar) -> ExtractedSy
         if expanded_top_level_repetition {
             grammar
                 .variables_to_inline
-                .retain(|symbol| *symbol != Symbol::non_terminal(i));
+                .retain(|(symbol, _)| *symbol != Symbol::non_terminal(i));
         }
     }
 
@@ -30,9 +30,20 @@ const UNICODE_PROPERTY_ALIASES_JSON: &str = include_str!("./unicode-property-ali
 struct NfaBuilder {
     nfa: Nfa,
     is_sep: bool,
+    is_case_insensitive: bool,
+    allows_lookahead: bool,
+    lookahead: Option<CharacterSet>,
     precedence_stack: Vec<i32>,
 }
 
+const fn inverse_char(c: char) -> char {
+    match c {
+        'a'..='z' => (c as u8 - b'a' + b'A') as char,
+        'A'..='Z' => (c as u8 - b'A' + b'a') as char,
+        c => c,
+    }
+}
+
 fn get_implicit_precedence(rule: &Rule) -> i32 {
     match rule {
         Rule::String(_) => 2,
@@ -47,6 +58,35 @@ fn get_implicit_precedence(rule: &Rule) -> i32 {
     }
 }
 
+// Whether the whole rule is case-insensitive, looking through any metadata
+// wrapped around it, as in `token(prec(1, token.case_insensitive(...)))`.
+fn is_case_insensitive(rule: &Rule) -> bool {
+    match rule {
+        Rule::Metadata { rule, params } => params.is_case_insensitive || is_case_insensitive(rule),
+        _ => false,
+    }
+}
+
+fn unwrap_metadata(rule: &Rule) -> &Rule {
+    match rule {
+        Rule::Metadata { rule, .. } => unwrap_metadata(rule),
+        _ => rule,
+    }
+}
+
+// Split a lookahead assertion, `(?=...)` or `(?!...)`, off of the end of a pattern,
+// returning the rest of the pattern, the asserted pattern, and whether the
+// assertion is negative.
+fn split_lookahead_assertion(pattern: &str) -> Option<(&str, &str, bool)> {
+    let start = pattern.rfind("(?=").max(pattern.rfind("(?!"))?;
+    let assertion = pattern[start + 3..].strip_suffix(')')?;
+    Some((
+        &pattern[..start],
+        assertion,
+        &pattern[start..start + 3] == "(?!",
+    ))
+}
+
 const fn get_completion_precedence(rule: &Rule) -> i32 {
     if let Rule::Metadata { params, .. } = rule {
         if let Precedence::Integer(p) = params.precedence {
@@ -60,6 +100,9 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> Result<LexicalGram
     let mut builder = NfaBuilder {
         nfa: Nfa::new(),
         is_sep: true,
+        is_case_insensitive: false,
+        allows_lookahead: false,
+        lookahead: None,
         precedence_stack: vec![0],
     };
 
@@ -78,6 +121,7 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> Result<LexicalGram
         };
 
         builder.is_sep = false;
+        builder.allows_lookahead = matches!(unwrap_metadata(&variable.rule), Rule::Pattern(..));
         builder.nfa.states.push(NfaState::Accept {
             variable_index: i,
             precedence: get_completion_precedence(&variable.rule),
@@ -87,6 +131,7 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> Result<LexicalGram
             .expand_rule(&variable.rule, last_state_id)
             .with_context(|| format!("Error processing rule {}", variable.name))?;
 
+        builder.allows_lookahead = false;
         if !is_immediate_token {
             builder.is_sep = true;
             let last_state_id = builder.nfa.last_state_id();
@@ -98,6 +143,8 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> Result<LexicalGram
             kind: variable.kind,
             implicit_precedence: get_implicit_precedence(&variable.rule),
             start_state: builder.nfa.last_state_id(),
+            case_insensitive: is_case_insensitive(&variable.rule),
+            lookahead: builder.lookahead.take(),
         });
     }
 
@@ -111,12 +158,28 @@ impl NfaBuilder {
     fn expand_rule(&mut self, rule: &Rule, mut next_state_id: u32) -> Result<bool> {
         match rule {
             Rule::Pattern(s, f) => {
+                let case_insensitive = f.contains('i') || self.is_case_insensitive;
+                let s = if let Some((s, assertion, is_negative)) = split_lookahead_assertion(s) {
+                    self.expand_lookahead_assertion(assertion, is_negative, case_insensitive)?;
+                    s
+                } else {
+                    s
+                };
                 let ast = parse::Parser::new().parse(s)?;
-                self.expand_regex(&ast, next_state_id, f.contains('i'))
+                if self.lookahead.is_some() && matches!(ast, Ast::Alternation(_)) {
+                    return Err(anyhow!(
+                        "Regex error: Lookahead assertions cannot follow an alternation"
+                    ));
+                }
+                self.expand_regex(&ast, next_state_id, case_insensitive)
             }
             Rule::String(s) => {
                 for c in s.chars().rev() {
-                    self.push_advance(CharacterSet::empty().add_char(c), next_state_id);
+                    let mut chars = CharacterSet::empty().add_char(c);
+                    if self.is_case_insensitive {
+                        chars = chars.add_char(inverse_char(c));
+                    }
+                    self.push_advance(chars, next_state_id);
                     next_state_id = self.nfa.last_state_id();
                 }
                 Ok(!s.is_empty())
@@ -169,7 +232,10 @@ impl NfaBuilder {
                 } else {
                     false
                 };
+                let was_case_insensitive = self.is_case_insensitive;
+                self.is_case_insensitive |= params.is_case_insensitive;
                 let result = self.expand_rule(rule, next_state_id);
+                self.is_case_insensitive = was_case_insensitive;
                 if has_precedence {
                     self.precedence_stack.pop();
                 }
@@ -186,14 +252,6 @@ impl NfaBuilder {
         mut next_state_id: u32,
         case_insensitive: bool,
     ) -> Result<bool> {
-        const fn inverse_char(c: char) -> char {
-            match c {
-                'a'..='z' => (c as u8 - b'a' + b'A') as char,
-                'A'..='Z' => (c as u8 - b'A' + b'a') as char,
-                c => c,
-            }
-        }
-
         fn with_inverse_char(mut chars: CharacterSet) -> CharacterSet {
             for char in chars.clone().chars() {
                 let inverted = inverse_char(char);
@@ -326,6 +384,36 @@ impl NfaBuilder {
         }
     }
 
+    // Record the characters that a lookahead assertion allows to follow the token.
+    // The assertion's pattern is expanded like any other, into a throwaway NFA
+    // state, which must match a single character.
+    fn expand_lookahead_assertion(
+        &mut self,
+        assertion: &str,
+        is_negative: bool,
+        case_insensitive: bool,
+    ) -> Result<()> {
+        if !self.allows_lookahead {
+            return Err(anyhow!(
+                "Regex error: Lookahead assertions are only supported at the end of a token's pattern"
+            ));
+        }
+        let ast = parse::Parser::new().parse(assertion)?;
+        let state_count = self.nfa.states.len();
+        self.expand_regex(&ast, self.nfa.last_state_id(), case_insensitive)?;
+        let chars = match self.nfa.states.get(state_count..) {
+            Some([NfaState::Advance { chars, .. }]) => chars.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Regex error: Lookahead assertions must match a single character"
+                ))
+            }
+        };
+        self.nfa.states.truncate(state_count);
+        self.lookahead = Some(if is_negative { chars.negate() } else { chars });
+        Ok(())
+    }
+
     fn translate_class_set(&self, class_set: &ClassSet) -> Result<CharacterSet> {
         match &class_set {
             ClassSet::Item(item) => self.expand_character_class(item),
@@ -803,6 +891,17 @@ mod tests {
                 separators: vec![],
                 examples: vec![("abΨ12٣٣, ok", Some((0, "abΨ12٣٣")))],
             },
+            // unicode identifier properties
+            Row {
+                rules: vec![Rule::pattern(r"\p{XID_Start}\p{XID_Continue}*", "")],
+                separators: vec![],
+                examples: vec![
+                    ("été_2!", Some((0, "été_2"))),
+                    ("Ωμέγα", Some((0, "Ωμέγα"))),
+                    ("¿qué", None),
+                    ("_x", None),
+                ],
+            },
             // unicode character escapes
             Row {
                 rules: vec![
@@ -932,4 +1031,107 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_case_insensitive_tokens() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![
+                Variable::anonymous(
+                    "SELECT",
+                    Rule::case_insensitive_token(Rule::string("SELECT")),
+                ),
+                Variable::anonymous("FROM", Rule::string("FROM")),
+            ],
+        })
+        .unwrap();
+
+        assert!(grammar.variables[0].case_insensitive);
+        assert!(!grammar.variables[1].case_insensitive);
+        assert_eq!(simulate_nfa(&grammar, "SELECT"), Some((0, "SELECT")));
+        assert_eq!(simulate_nfa(&grammar, "select"), Some((0, "select")));
+        assert_eq!(simulate_nfa(&grammar, "Select"), Some((0, "Select")));
+        assert_eq!(simulate_nfa(&grammar, "FROM"), Some((1, "FROM")));
+        assert_eq!(simulate_nfa(&grammar, "from"), None);
+    }
+
+    #[test]
+    fn test_nested_case_insensitive_tokens() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![
+                Variable::anonymous(
+                    "LIMIT",
+                    Rule::prec(
+                        Precedence::Integer(1),
+                        Rule::case_insensitive_token(Rule::string("LIMIT")),
+                    ),
+                ),
+                Variable::named(
+                    "order_by_desc",
+                    Rule::token(Rule::seq(vec![
+                        Rule::case_insensitive_token(Rule::string("ORDER BY")),
+                        Rule::string(" DESC"),
+                    ])),
+                ),
+            ],
+        })
+        .unwrap();
+
+        assert!(grammar.variables[0].case_insensitive);
+        assert!(!grammar.variables[1].case_insensitive);
+        assert_eq!(simulate_nfa(&grammar, "limit"), Some((0, "limit")));
+        assert_eq!(
+            simulate_nfa(&grammar, "Order By DESC"),
+            Some((1, "Order By DESC"))
+        );
+        assert_eq!(simulate_nfa(&grammar, "order by desc"), None);
+    }
+
+    #[test]
+    fn test_lookahead_assertions() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![
+                Variable::anonymous("/", Rule::pattern("/(?![/*])", "")),
+                Variable::named("identifier", Rule::pattern("[a-z]+(?=\\()", "")),
+            ],
+        })
+        .unwrap();
+
+        // The asserted characters are not part of the token.
+        assert_eq!(simulate_nfa(&grammar, "/"), Some((0, "/")));
+        assert_eq!(simulate_nfa(&grammar, "abc("), Some((1, "abc")));
+        let division = grammar.variables[0].lookahead.as_ref().unwrap();
+        assert!(!division.contains('/'));
+        assert!(!division.contains('*'));
+        assert!(division.contains(' '));
+        assert_eq!(
+            grammar.variables[1].lookahead,
+            Some(CharacterSet::from_char('('))
+        );
+
+        let error = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![Variable::anonymous("/", Rule::pattern("/(?!//)", ""))],
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Regex error: Lookahead assertions must match a single character"
+        );
+
+        let error = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![Variable::anonymous(
+                "/",
+                Rule::seq(vec![Rule::string("a"), Rule::pattern("/(?!/)", "")]),
+            )],
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Regex error: Lookahead assertions are only supported at the end of a token's pattern"
+        );
+    }
 }
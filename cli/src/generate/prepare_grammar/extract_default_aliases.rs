@@ -41,7 +41,7 @@ pub(super) fn extract_default_aliases(
                 };
 
                 // Default aliases don't work for inlined variables.
-                if syntax_grammar.variables_to_inline.contains(&step.symbol) {
+                if syntax_grammar.is_always_inlined(step.symbol) {
                     continue;
                 }
 
@@ -218,24 +218,32 @@ mod tests {
                     kind: VariableType::Anonymous,
                     implicit_precedence: 0,
                     start_state: 0,
+                    case_insensitive: false,
+                    lookahead: None,
                 },
                 LexicalVariable {
                     name: "t1".to_string(),
                     kind: VariableType::Anonymous,
                     implicit_precedence: 0,
                     start_state: 0,
+                    case_insensitive: false,
+                    lookahead: None,
                 },
                 LexicalVariable {
                     name: "t2".to_string(),
                     kind: VariableType::Anonymous,
                     implicit_precedence: 0,
                     start_state: 0,
+                    case_insensitive: false,
+                    lookahead: None,
                 },
                 LexicalVariable {
                     name: "t3".to_string(),
                     kind: VariableType::Anonymous,
                     implicit_precedence: 0,
                     start_state: 0,
+                    case_insensitive: false,
+                    lookahead: None,
                 },
             ],
         };
@@ -299,4 +307,58 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_extract_default_alias_for_conditionally_inlined_variable() {
+        let mut syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                SyntaxVariable {
+                    name: "v1".to_owned(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::terminal(0)),
+                            ProductionStep::new(Symbol::non_terminal(1)).with_alias("a1", true),
+                        ],
+                    }],
+                },
+                SyntaxVariable {
+                    name: "v2".to_owned(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::terminal(0))],
+                    }],
+                },
+            ],
+            // `v2` is only inlined after a token that never precedes it, so it
+            // is still a node wherever it occurs.
+            variables_to_inline: vec![(Symbol::non_terminal(1), Some(Symbol::terminal(1)))],
+            ..Default::default()
+        };
+        let lexical_grammar = LexicalGrammar {
+            nfa: Nfa::new(),
+            variables: ["t0", "t1"]
+                .into_iter()
+                .map(|name| LexicalVariable {
+                    name: name.to_string(),
+                    kind: VariableType::Anonymous,
+                    implicit_precedence: 0,
+                    start_state: 0,
+                    case_insensitive: false,
+                    lookahead: None,
+                })
+                .collect(),
+        };
+
+        let default_aliases = extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
+        assert_eq!(
+            default_aliases.get(&Symbol::non_terminal(1)),
+            Some(&Alias {
+                value: "a1".to_string(),
+                is_named: true,
+            })
+        );
+    }
 }
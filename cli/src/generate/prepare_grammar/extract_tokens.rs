@@ -1,6 +1,6 @@
 use super::{ExtractedLexicalGrammar, ExtractedSyntaxGrammar, InternedGrammar};
 use crate::generate::grammars::{ExternalToken, Variable, VariableType};
-use crate::generate::rules::{MetadataParams, Rule, Symbol, SymbolType};
+use crate::generate::rules::{MetadataParams, Precedence, Rule, Symbol, SymbolType};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 use std::mem;
@@ -32,6 +32,23 @@ pub(super) fn extract_tokens(
         });
     }
 
+    for symbol in &grammar.zero_width_externals {
+        let is_external = grammar
+            .external_tokens
+            .iter()
+            .any(|external_token| external_token_symbol(&external_token.rule) == Some(*symbol));
+        if !is_external {
+            let name = if symbol.is_external() {
+                &grammar.external_tokens[symbol.index].name
+            } else {
+                &grammar.variables[symbol.index].name
+            };
+            return Err(anyhow!(
+                "Rule '{name}' cannot be zero-width, because it is not an external token"
+            ));
+        }
+    }
+
     // If a variable's entire rule was extracted as a token and that token didn't
     // appear within any other rule, then remove that variable from the syntax
     // grammar, giving its name to the token in the lexical grammar. Any symbols
@@ -83,11 +100,22 @@ pub(super) fn extract_tokens(
         .map(|symbol| symbol_replacer.replace_symbol(symbol))
         .collect();
 
-    let variables_to_inline = grammar
-        .variables_to_inline
-        .into_iter()
-        .map(|symbol| symbol_replacer.replace_symbol(symbol))
-        .collect();
+    let mut variables_to_inline = Vec::with_capacity(grammar.variables_to_inline.len());
+    for (symbol, predecessor) in grammar.variables_to_inline {
+        let predecessor = match predecessor {
+            None => None,
+            Some(Rule::Symbol(predecessor)) => Some(symbol_replacer.replace_symbol(predecessor)),
+            Some(rule) => match lexical_variables.iter().position(|v| v.rule == rule) {
+                Some(index) => Some(Symbol::terminal(index)),
+                None => {
+                    return Err(anyhow!(
+                        "Rules can only be inlined after a symbol or a token that is used in the grammar"
+                    ))
+                }
+            },
+        };
+        variables_to_inline.push((symbol_replacer.replace_symbol(symbol), predecessor));
+    }
 
     let mut separators = Vec::new();
     let mut extra_symbols = Vec::new();
@@ -101,9 +129,35 @@ pub(super) fn extract_tokens(
         }
     }
 
+    let mut conflict_overrides = Vec::with_capacity(grammar.conflict_overrides.len());
+    for (preferred, other) in grammar.conflict_overrides {
+        let (preferred, other) = (
+            symbol_replacer.replace_symbol(preferred),
+            symbol_replacer.replace_symbol(other),
+        );
+        if let Some(token) = [preferred, other]
+            .into_iter()
+            .find(|s| !s.is_non_terminal())
+        {
+            let name = if token.is_external() {
+                &grammar.external_tokens[token.index].name
+            } else {
+                &lexical_variables[token.index].name
+            };
+            return Err(anyhow!(
+                "Token '{name}' cannot be used in a conflict override, which only applies to rules"
+            ));
+        }
+        conflict_overrides.push((preferred, other));
+    }
+
     let mut external_tokens = Vec::new();
     for external_token in grammar.external_tokens {
-        let rule = symbol_replacer.replace_symbols_in_rule(&external_token.rule);
+        let zero_width = external_token_symbol(&external_token.rule)
+            .is_some_and(|symbol| grammar.zero_width_externals.contains(&symbol));
+        let (rule, precedence) = external_token_precedence(
+            symbol_replacer.replace_symbols_in_rule(&external_token.rule),
+        );
         if let Rule::Symbol(symbol) = rule {
             if symbol.is_non_terminal() {
                 return Err(anyhow!(
@@ -117,12 +171,16 @@ pub(super) fn extract_tokens(
                     name: external_token.name,
                     kind: external_token.kind,
                     corresponding_internal_token: None,
+                    zero_width,
+                    precedence,
                 });
             } else {
                 external_tokens.push(ExternalToken {
                     name: lexical_variables[symbol.index].name.clone(),
                     kind: external_token.kind,
                     corresponding_internal_token: Some(symbol),
+                    zero_width,
+                    precedence,
                 });
             }
         } else {
@@ -132,6 +190,12 @@ pub(super) fn extract_tokens(
         }
     }
 
+    let zero_width_externals = grammar
+        .zero_width_externals
+        .iter()
+        .map(|symbol| symbol_replacer.replace_symbol(*symbol))
+        .collect();
+
     let mut word_token = None;
     if let Some(token) = grammar.word_token {
         let token = symbol_replacer.replace_symbol(token);
@@ -154,6 +218,8 @@ pub(super) fn extract_tokens(
             external_tokens,
             word_token,
             precedence_orderings: grammar.precedence_orderings,
+            conflict_overrides,
+            zero_width_externals,
         },
         ExtractedLexicalGrammar {
             variables: lexical_variables,
@@ -162,6 +228,38 @@ pub(super) fn extract_tokens(
     ))
 }
 
+// The symbol named in the grammar's list of externals, looking through any
+// metadata wrapped around it.
+fn external_token_symbol(rule: &Rule) -> Option<Symbol> {
+    match rule {
+        Rule::Symbol(symbol) => Some(*symbol),
+        Rule::Metadata { rule, .. } => external_token_symbol(rule),
+        _ => None,
+    }
+}
+
+/// Unwrap an external token that is wrapped in `prec`, returning the inner rule
+/// and its precedence. Any other metadata is left in place, so that it is
+/// reported rather than silently dropped.
+fn external_token_precedence(rule: Rule) -> (Rule, i32) {
+    if let Rule::Metadata {
+        params,
+        rule: inner,
+    } = &rule
+    {
+        if let Precedence::Integer(precedence) = params.precedence {
+            let prec_only = MetadataParams {
+                precedence: params.precedence.clone(),
+                ..Default::default()
+            };
+            if *params == prec_only {
+                return ((**inner).clone(), precedence);
+            }
+        }
+    }
+    (rule, 0)
+}
+
 struct TokenExtractor {
     current_variable_name: String,
     current_variable_token_count: usize,
@@ -447,21 +545,86 @@ mod test {
                     name: "external_0".to_string(),
                     kind: VariableType::Named,
                     corresponding_internal_token: None,
+                    zero_width: false,
+                    precedence: 0,
                 },
                 ExternalToken {
                     name: "a".to_string(),
                     kind: VariableType::Anonymous,
                     corresponding_internal_token: Some(Symbol::terminal(0)),
+                    zero_width: false,
+                    precedence: 0,
                 },
                 ExternalToken {
                     name: "rule_2".to_string(),
                     kind: VariableType::Named,
                     corresponding_internal_token: Some(Symbol::terminal(2)),
+                    zero_width: false,
+                    precedence: 0,
                 },
             ]
         );
     }
 
+    #[test]
+    fn test_extract_zero_width_externals() {
+        let mut grammar = build_grammar(vec![Variable::named(
+            "rule_0",
+            Rule::seq(vec![Rule::external(0), Rule::external(1)]),
+        )]);
+        grammar.external_tokens = vec![
+            Variable::named("_indent", Rule::external(0)),
+            Variable::named("_newline", Rule::external(1)),
+        ];
+        grammar.zero_width_externals = vec![Symbol::external(0)];
+
+        let (syntax_grammar, _) = extract_tokens(grammar).unwrap();
+        assert!(syntax_grammar.external_tokens[0].zero_width);
+        assert!(!syntax_grammar.external_tokens[1].zero_width);
+    }
+
+    #[test]
+    fn test_error_on_zero_width_rule_that_is_not_external() {
+        let mut grammar = build_grammar(vec![
+            Variable::named("rule_0", Rule::non_terminal(1)),
+            Variable::named("rule_1", Rule::string("a")),
+        ]);
+        grammar.zero_width_externals = vec![Symbol::non_terminal(1)];
+
+        match extract_tokens(grammar) {
+            Err(e) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Rule 'rule_1' cannot be zero-width, because it is not an external token"
+                );
+            }
+            _ => {
+                panic!("Expected an error but got no error");
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_on_external_with_metadata_other_than_precedence() {
+        let mut grammar = build_grammar(vec![Variable::named("rule_0", Rule::external(0))]);
+        grammar.external_tokens = vec![Variable::named(
+            "external_0",
+            Rule::prec_dynamic(1, Rule::external(0)),
+        )];
+
+        match extract_tokens(grammar) {
+            Err(e) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Non-symbol rules cannot be used as external tokens"
+                );
+            }
+            _ => {
+                panic!("Expected an error but got no error");
+            }
+        }
+    }
+
     #[test]
     fn test_error_on_external_with_same_name_as_non_terminal() {
         let mut grammar = build_grammar(vec![
@@ -1,6 +1,6 @@
 use super::ExtractedSyntaxGrammar;
 use crate::generate::grammars::{
-    Production, ProductionStep, SyntaxGrammar, SyntaxVariable, Variable,
+    Production, ProductionRef, ProductionStep, Resolution, SyntaxGrammar, SyntaxVariable, Variable,
 };
 use crate::generate::rules::{Alias, Associativity, Precedence, Rule, Symbol};
 use anyhow::{anyhow, Result};
@@ -204,6 +204,23 @@ unless they are used only as the grammar's start rule.
             }
         }
     }
+
+    // An override between two rules applies to every pair of their productions.
+    let production_refs = |symbol: Symbol| {
+        (0..variables[symbol.index].productions.len()).map(move |production_index| ProductionRef {
+            variable_index: symbol.index,
+            production_index,
+        })
+    };
+    let mut conflict_overrides = Vec::new();
+    for (preferred, other) in grammar.conflict_overrides {
+        for preferred in production_refs(preferred) {
+            for other in production_refs(other) {
+                conflict_overrides.push((preferred, other, Resolution::PreferFirst));
+            }
+        }
+    }
+
     Ok(SyntaxGrammar {
         extra_symbols: grammar.extra_symbols,
         expected_conflicts: grammar.expected_conflicts,
@@ -212,6 +229,7 @@ unless they are used only as the grammar's start rule.
         external_tokens: grammar.external_tokens,
         supertype_symbols: grammar.supertype_symbols,
         word_token: grammar.word_token,
+        conflict_overrides,
         variables,
     })
 }
@@ -22,8 +22,8 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
     let mut external_tokens = Vec::with_capacity(grammar.external_tokens.len());
     for external_token in &grammar.external_tokens {
         let rule = interner.intern_rule(external_token)?;
-        let (name, kind) = if let Rule::NamedSymbol(name) = external_token {
-            (name.clone(), variable_type_for_name(name))
+        let (name, kind) = if let Some(name) = external_token_name(external_token) {
+            (name.to_string(), variable_type_for_name(name))
         } else {
             (String::new(), VariableType::Anonymous)
         };
@@ -57,12 +57,37 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
         expected_conflicts.push(interned_conflict);
     }
 
+    let mut conflict_overrides = Vec::with_capacity(grammar.conflict_overrides.len());
+    for (preferred, other) in &grammar.conflict_overrides {
+        let intern = |name: &String| {
+            interner
+                .intern_name(name)
+                .ok_or_else(|| anyhow!("Undefined symbol `{name}`"))
+        };
+        conflict_overrides.push((intern(preferred)?, intern(other)?));
+    }
+
+    let mut zero_width_externals = Vec::with_capacity(grammar.zero_width_externals.len());
+    for name in &grammar.zero_width_externals {
+        zero_width_externals.push(
+            interner
+                .intern_name(name)
+                .ok_or_else(|| anyhow!("Undefined symbol `{name}`"))?,
+        );
+    }
+
     let mut variables_to_inline = Vec::new();
     for name in &grammar.variables_to_inline {
         if let Some(symbol) = interner.intern_name(name) {
-            variables_to_inline.push(symbol);
+            variables_to_inline.push((symbol, None));
         }
     }
+    for (name, predecessor) in &grammar.variables_to_inline_after {
+        let symbol = interner
+            .intern_name(name)
+            .ok_or_else(|| anyhow!("Undefined symbol `{name}`"))?;
+        variables_to_inline.push((symbol, Some(interner.intern_rule(predecessor)?)));
+    }
 
     let mut word_token = None;
     if let Some(name) = grammar.word_token.as_ref() {
@@ -88,6 +113,8 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
         supertype_symbols,
         word_token,
         precedence_orderings: grammar.precedence_orderings.clone(),
+        conflict_overrides,
+        zero_width_externals,
     })
 }
 
@@ -135,7 +162,7 @@ impl<'a> Interner<'a> {
         }
 
         for (i, external_token) in self.grammar.external_tokens.iter().enumerate() {
-            if let Rule::NamedSymbol(name) = external_token {
+            if let Some(name) = external_token_name(external_token) {
                 if name == symbol {
                     return Some(Symbol::external(i));
                 }
@@ -146,6 +173,16 @@ impl<'a> Interner<'a> {
     }
 }
 
+/// Get the name of an external token, which may be wrapped in `prec` to give it a
+/// priority over the other external tokens.
+fn external_token_name(rule: &Rule) -> Option<&str> {
+    match rule {
+        Rule::NamedSymbol(name) => Some(name),
+        Rule::Metadata { rule, .. } => external_token_name(rule),
+        _ => None,
+    }
+}
+
 fn variable_type_for_name(name: &str) -> VariableType {
     if name.starts_with('_') {
         VariableType::Hidden
@@ -0,0 +1,21 @@
+use crate::generate::grammars::SyntaxGrammar;
+use crate::generate::rules::{Precedence, Symbol};
+use anyhow::{anyhow, Result};
+
+/// Mark the given variable as an error-recovery rule by assigning the recovery
+/// precedence to every step of its productions, so that it never wins a
+/// conflict against a real rule. Returns an error if the symbol is not one of
+/// the grammar's variables.
+pub fn mark_recovery_rule(grammar: &mut SyntaxGrammar, symbol: Symbol) -> Result<()> {
+    let variable = grammar
+        .variables
+        .get_mut(symbol.index)
+        .filter(|_| symbol.is_non_terminal())
+        .ok_or_else(|| anyhow!("Only grammar rules can be marked as recovery rules"))?;
+    for production in &mut variable.productions {
+        for step in &mut production.steps {
+            step.precedence = Precedence::Recovery;
+        }
+    }
+    Ok(())
+}
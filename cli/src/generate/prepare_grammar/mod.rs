@@ -1,19 +1,34 @@
+mod augment;
+mod coalesce_optionals;
+mod eliminate_epsilon;
 mod expand_repeats;
 mod expand_tokens;
 mod extract_default_aliases;
 mod extract_tokens;
 mod flatten_grammar;
 mod intern_symbols;
+mod mark_recovery_rule;
+mod normalize_grammar;
 mod process_inlines;
+mod right_factor;
+mod to_cnf;
 
+pub use self::augment::augment;
+pub use self::coalesce_optionals::coalesce_optionals;
+pub use self::eliminate_epsilon::eliminate_epsilon;
 pub use self::expand_tokens::expand_tokens;
+pub use self::mark_recovery_rule::mark_recovery_rule;
+pub use self::normalize_grammar::normalize;
+pub use self::process_inlines::process_inlines;
+pub use self::right_factor::right_factor;
+pub use self::to_cnf::to_cnf;
 
 use self::expand_repeats::expand_repeats;
 use self::extract_default_aliases::extract_default_aliases;
 use self::extract_tokens::extract_tokens;
 use self::flatten_grammar::flatten_grammar;
 use self::intern_symbols::intern_symbols;
-use self::process_inlines::process_inlines;
+use super::analysis::{validate_cross_references, CrossRefError};
 use super::grammars::{
     ExternalToken, InlinedProductionMap, InputGrammar, LexicalGrammar, PrecedenceEntry,
     SyntaxGrammar, Variable,
@@ -32,9 +47,13 @@ pub struct IntermediateGrammar<T, U> {
     expected_conflicts: Vec<Vec<Symbol>>,
     precedence_orderings: Vec<Vec<PrecedenceEntry>>,
     external_tokens: Vec<U>,
-    variables_to_inline: Vec<Symbol>,
+    /// The variables to inline, each with the symbol that it must follow in
+    /// order to be inlined, if any.
+    variables_to_inline: Vec<(Symbol, Option<T>)>,
     supertype_symbols: Vec<Symbol>,
     word_token: Option<Symbol>,
+    conflict_overrides: Vec<(Symbol, Symbol)>,
+    zero_width_externals: Vec<Symbol>,
 }
 
 pub type InternedGrammar = IntermediateGrammar<Rule, Variable>;
@@ -58,6 +77,8 @@ impl<T, U> Default for IntermediateGrammar<T, U> {
             variables_to_inline: Vec::default(),
             supertype_symbols: Vec::default(),
             word_token: Option::default(),
+            conflict_overrides: Vec::default(),
+            zero_width_externals: Vec::default(),
         }
     }
 }
@@ -79,11 +100,22 @@ pub fn prepare_grammar(
     let syntax_grammar = expand_repeats(syntax_grammar);
     let mut syntax_grammar = flatten_grammar(syntax_grammar)?;
     let lexical_grammar = expand_tokens(lexical_grammar)?;
+    validate_cross_references(&syntax_grammar, &lexical_grammar).map_err(join_errors)?;
     let default_aliases = extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
     let inlines = process_inlines(&syntax_grammar, &lexical_grammar)?;
     Ok((syntax_grammar, lexical_grammar, inlines, default_aliases))
 }
 
+// Combine the errors reported by validate_cross_references into a single
+// error, with one line per problem.
+fn join_errors(errors: Vec<CrossRefError>) -> anyhow::Error {
+    anyhow!(errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
 /// Check that all of the named precedences used in the grammar are declared
 /// within the `precedences` lists, and also that there are no conflicting
 /// precedence orderings declared in those lists.
@@ -162,6 +194,7 @@ fn validate_precedences(grammar: &InputGrammar) -> Result<()> {
 mod tests {
     use super::*;
     use crate::generate::grammars::VariableType;
+    use crate::generate::rules::Associativity;
 
     #[test]
     fn test_validate_precedences_with_undeclared_precedence() {
@@ -246,4 +279,187 @@ mod tests {
             "Conflicting orderings for precedences 'a' and 'b'",
         );
     }
+
+    #[test]
+    fn test_prepare_grammar_with_colliding_token_names() {
+        let grammar = InputGrammar {
+            variables: vec![Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::seq(vec![Rule::named("comment"), Rule::string("comment")]),
+            }],
+            external_tokens: vec![Rule::named("comment")],
+            ..Default::default()
+        };
+
+        // Symbols in a prepared grammar are always in range, so a name collision is
+        // the cross-reference problem that an input grammar can cause.
+        match prepare_grammar(&grammar) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "External token 'comment' in the grammar's externals has the same name as the string \"comment\" in the grammar's rules"
+            ),
+            _ => panic!("Expected an error but got no error"),
+        }
+    }
+
+    #[test]
+    fn test_prepare_grammar_with_inline_after() {
+        let mut grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::choice(vec![
+                        Rule::named("call"),
+                        Rule::named("_arguments"),
+                    ])),
+                },
+                Variable {
+                    name: "call".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::named("name"),
+                        Rule::string("("),
+                        Rule::named("_arguments"),
+                        Rule::string(")"),
+                    ]),
+                },
+                Variable {
+                    name: "_arguments".to_string(),
+                    kind: VariableType::Hidden,
+                    rule: Rule::seq(vec![
+                        Rule::named("name"),
+                        Rule::string(","),
+                        Rule::named("name"),
+                    ]),
+                },
+                Variable {
+                    name: "name".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+            ],
+            variables_to_inline_after: vec![("_arguments".to_string(), Rule::string("("))],
+            ..Default::default()
+        };
+
+        let (syntax_grammar, lexical_grammar, inlines, _) = prepare_grammar(&grammar).unwrap();
+        let arguments = Symbol::non_terminal(2);
+        let open_paren = Symbol::terminal(
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == "(")
+                .unwrap(),
+        );
+        assert_eq!(syntax_grammar.variables[2].name, "_arguments");
+        assert_eq!(
+            syntax_grammar.variables_to_inline,
+            [(arguments, Some(open_paren))]
+        );
+
+        // The rule is only inlined where it follows the parenthesis.
+        let mut inlined_steps = Vec::new();
+        for variable in &syntax_grammar.variables {
+            for production in &variable.productions {
+                for (i, step) in production.steps.iter().enumerate() {
+                    if step.symbol == arguments {
+                        let is_inlined =
+                            inlines.inlined_productions(production, i as u32).is_some();
+                        inlined_steps.push((variable.name.as_str(), is_inlined));
+                    }
+                }
+            }
+        }
+        assert_eq!(inlined_steps, [("call", true), ("program_repeat1", false)]);
+
+        // The token must be used somewhere in the grammar.
+        grammar.variables_to_inline_after = vec![("_arguments".to_string(), Rule::string("["))];
+        match prepare_grammar(&grammar) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Rules can only be inlined after a symbol or a token that is used in the grammar"
+            ),
+            _ => panic!("Expected an error but got no error"),
+        }
+    }
+
+    #[test]
+    fn test_repeat_helpers_are_flagged() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "list".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![
+                        Rule::string("["),
+                        Rule::repeat(Rule::named("item")),
+                        Rule::string("]"),
+                    ]),
+                },
+                Variable {
+                    name: "item".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("x"),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (syntax_grammar, ..) = prepare_grammar(&grammar).unwrap();
+        let helper_names = syntax_grammar
+            .variables
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| syntax_grammar.is_repeat_helper(Symbol::non_terminal(*i)))
+            .map(|(_, variable)| variable.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(helper_names, ["list_repeat1"]);
+        assert!(!syntax_grammar.is_repeat_helper(Symbol::terminal(0)));
+    }
+
+    #[test]
+    fn test_precedence_on_inline_sequence() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "expression".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::choice(vec![
+                        Rule::named("a"),
+                        Rule::prec_left(
+                            Precedence::Integer(2),
+                            Rule::seq(vec![Rule::named("a"), Rule::string("+"), Rule::named("b")]),
+                        ),
+                    ]),
+                },
+                Variable {
+                    name: "a".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("a"), Rule::string("a")]),
+                },
+                Variable {
+                    name: "b".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("b"), Rule::string("b")]),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (syntax_grammar, ..) = prepare_grammar(&grammar).unwrap();
+        let productions = &syntax_grammar.variables[0].productions;
+        assert_eq!(productions.len(), 2);
+
+        let sum = productions.iter().find(|p| p.steps.len() == 3).unwrap();
+        for step in &sum.steps {
+            assert_eq!(step.precedence, Precedence::Integer(2));
+            assert_eq!(step.associativity, Some(Associativity::Left));
+        }
+
+        let single = productions.iter().find(|p| p.steps.len() == 1).unwrap();
+        assert_eq!(single.steps[0].precedence, Precedence::None);
+        assert_eq!(single.steps[0].associativity, None);
+    }
 }
@@ -0,0 +1,90 @@
+use crate::generate::grammars::{Production, SyntaxGrammar};
+use std::cmp::Ordering;
+
+/// Sort the productions of each variable into a canonical order, so that
+/// grammars that differ only in the order of their `choice` members produce
+/// identical parse tables.
+///
+/// The parse table builder never uses the relative order of a variable's
+/// productions to resolve conflicts; conflicts are resolved only by comparing
+/// precedence and associativity, which are stored on the production steps
+/// themselves. So reordering the productions does not change the language
+/// or the way that it is parsed.
+///
+/// This must be called before the grammar's inlined productions are computed,
+/// because the [`InlinedProductionMap`] refers to productions by address.
+///
+/// [`InlinedProductionMap`]: crate::generate::grammars::InlinedProductionMap
+pub fn normalize(grammar: &mut SyntaxGrammar) {
+    for variable in &mut grammar.variables {
+        variable.productions.sort_by(compare_productions);
+    }
+}
+
+fn compare_productions(left: &Production, right: &Production) -> Ordering {
+    left.steps
+        .cmp(&right.steps)
+        .then_with(|| left.dynamic_precedence.cmp(&right.dynamic_precedence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::grammars::{ProductionStep, SyntaxVariable, VariableType};
+    use crate::generate::rules::{Associativity, Precedence, Symbol};
+
+    #[test]
+    fn test_normalize_is_idempotent() {
+        let mut grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "rule0".to_string(),
+                kind: VariableType::Named,
+                productions: vec![
+                    Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::terminal(2)),
+                            ProductionStep::new(Symbol::non_terminal(1)),
+                        ],
+                    },
+                    Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::terminal(1))
+                            .with_prec(Precedence::Integer(2), Some(Associativity::Left))],
+                    },
+                    Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::terminal(1))],
+                    },
+                    Production {
+                        dynamic_precedence: 1,
+                        steps: vec![ProductionStep::new(Symbol::terminal(1))],
+                    },
+                ],
+            }],
+            ..Default::default()
+        };
+
+        normalize(&mut grammar);
+        let productions = grammar.variables[0].productions.clone();
+        assert_eq!(
+            productions
+                .iter()
+                .map(|p| (
+                    p.steps[0].symbol,
+                    p.steps[0].precedence.clone(),
+                    p.dynamic_precedence
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (Symbol::terminal(1), Precedence::None, 0),
+                (Symbol::terminal(1), Precedence::None, 1),
+                (Symbol::terminal(1), Precedence::Integer(2), 0),
+                (Symbol::terminal(2), Precedence::None, 0),
+            ]
+        );
+
+        normalize(&mut grammar);
+        assert_eq!(grammar.variables[0].productions, productions);
+    }
+}
@@ -1,9 +1,19 @@
 use crate::generate::{
-    grammars::{InlinedProductionMap, LexicalGrammar, Production, ProductionStep, SyntaxGrammar},
+    grammars::{InlinedProductionMap, LexicalGrammar, Production, ProductionRef, SyntaxGrammar},
     rules::SymbolType,
 };
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use std::{collections::HashMap, error, fmt};
+
+// Real grammars nest inlined rules only a few levels deep. A grammar that
+// exceeds this limit almost certainly contains an inlined rule that begins
+// with itself, which could otherwise be expanded forever.
+const MAX_INLINE_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InlineError {
+    TooDeep { rule: String, limit: usize },
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct ProductionStepId {
@@ -18,10 +28,12 @@ struct ProductionStepId {
 struct InlinedProductionMapBuilder {
     production_indices_by_step_id: HashMap<ProductionStepId, Vec<usize>>,
     productions: Vec<Production>,
+    origins: Vec<Vec<ProductionRef>>,
+    max_depth: usize,
 }
 
 impl InlinedProductionMapBuilder {
-    fn build(mut self, grammar: &SyntaxGrammar) -> InlinedProductionMap {
+    fn build(mut self, grammar: &SyntaxGrammar) -> Result<InlinedProductionMap, InlineError> {
         let mut step_ids_to_process = Vec::new();
         for (variable_index, variable) in grammar.variables.iter().enumerate() {
             for production_index in 0..variable.productions.len() {
@@ -34,10 +46,11 @@ impl InlinedProductionMapBuilder {
                     let mut i = 0;
                     while i < step_ids_to_process.len() {
                         let step_id = step_ids_to_process[i];
-                        if let Some(step) = self.production_step_for_id(step_id, grammar) {
-                            if grammar.variables_to_inline.contains(&step.symbol) {
+                        let production = self.production_for_id(step_id, grammar);
+                        if step_id.step_index < production.steps.len() {
+                            if is_inlined_at(grammar, production, step_id.step_index) {
                                 let inlined_step_ids = self
-                                    .inline_production_at_step(step_id, grammar)
+                                    .inline_production_at_step(step_id, grammar)?
                                     .iter()
                                     .copied()
                                     .map(|production_index| ProductionStepId {
@@ -77,25 +90,51 @@ impl InlinedProductionMapBuilder {
             })
             .collect();
 
-        InlinedProductionMap {
+        Ok(InlinedProductionMap {
             productions,
             production_map,
-        }
+            origins: self.origins,
+        })
     }
 
     fn inline_production_at_step<'a>(
         &'a mut self,
         step_id: ProductionStepId,
         grammar: &'a SyntaxGrammar,
-    ) -> &'a [usize] {
-        // Build a list of productions produced by inlining rules.
+    ) -> Result<&'a [usize], InlineError> {
+        // Build a list of productions produced by inlining rules. Alongside each
+        // production, track how many inlined rules have been nested at this step.
         let mut i = 0;
         let step_index = step_id.step_index;
         let mut productions_to_add = vec![self.production_for_id(step_id, grammar).clone()];
+        let mut origins = vec![self.origins_for_id(step_id)];
+        let mut depths = vec![0];
         while i < productions_to_add.len() {
             if let Some(step) = productions_to_add[i].steps.get(step_index) {
                 let symbol = step.symbol;
-                if grammar.variables_to_inline.contains(&symbol) {
+                if is_inlined_at(grammar, &productions_to_add[i], step_index) {
+                    let depth = depths[i] + 1;
+                    if depth > self.max_depth {
+                        return Err(InlineError::TooDeep {
+                            rule: grammar.variables[symbol.index].name.clone(),
+                            limit: self.max_depth,
+                        });
+                    }
+                    let inlined_productions = &grammar.variables[symbol.index].productions;
+                    depths.splice(i..=i, inlined_productions.iter().map(|_| depth));
+                    let parent_origins = origins[i].clone();
+                    origins.splice(
+                        i..=i,
+                        (0..inlined_productions.len()).map(|production_index| {
+                            let mut origins = parent_origins.clone();
+                            origins.push(ProductionRef {
+                                variable_index: symbol.index,
+                                production_index,
+                            });
+                            origins
+                        }),
+                    );
+
                     // Remove the production from the vector, replacing it with a placeholder.
                     let production = productions_to_add
                         .splice(i..=i, std::iter::once(&Production::default()).cloned())
@@ -105,7 +144,7 @@ impl InlinedProductionMapBuilder {
                     // Replace the placeholder with the inlined productions.
                     productions_to_add.splice(
                         i..=i,
-                        grammar.variables[symbol.index].productions.iter().map(|p| {
+                        inlined_productions.iter().map(|p| {
                             let mut production = production.clone();
                             let removed_step = production
                                 .steps
@@ -124,6 +163,9 @@ impl InlinedProductionMapBuilder {
                                     inserted_step.field_name = Some(field_name.clone());
                                 }
                             }
+                            // The inlined steps keep their own precedence. The final step
+                            // inherits the precedence and the associativity of the step that
+                            // it replaces, each only if it has none of its own.
                             if let Some(last_inserted_step) = inserted_steps.last_mut() {
                                 if last_inserted_step.precedence.is_none() {
                                     last_inserted_step.precedence = removed_step.precedence;
@@ -145,24 +187,44 @@ impl InlinedProductionMapBuilder {
             i += 1;
         }
 
-        // Store all the computed productions.
+        // Store all the computed productions. A production that was already
+        // computed from other origins is shared between them.
         let result = productions_to_add
             .into_iter()
-            .map(|production| {
-                self.productions
-                    .iter()
-                    .position(|p| *p == production)
-                    .unwrap_or({
-                        self.productions.push(production);
-                        self.productions.len() - 1
-                    })
+            .zip(origins)
+            .map(|(production, origins)| {
+                if let Some(index) = self.productions.iter().position(|p| *p == production) {
+                    for origin in origins {
+                        if !self.origins[index].contains(&origin) {
+                            self.origins[index].push(origin);
+                        }
+                    }
+                    index
+                } else {
+                    self.productions.push(production);
+                    self.origins.push(origins);
+                    self.productions.len() - 1
+                }
             })
             .collect();
 
         // Cache these productions based on the original production step.
-        self.production_indices_by_step_id
+        Ok(self
+            .production_indices_by_step_id
             .entry(step_id)
-            .or_insert(result)
+            .or_insert(result))
+    }
+
+    fn origins_for_id(&self, id: ProductionStepId) -> Vec<ProductionRef> {
+        id.variable_index.map_or_else(
+            || self.origins[id.production_index].clone(),
+            |variable_index| {
+                vec![ProductionRef {
+                    variable_index,
+                    production_index: id.production_index,
+                }]
+            },
+        )
     }
 
     fn production_for_id<'a>(
@@ -175,21 +237,33 @@ impl InlinedProductionMapBuilder {
             |variable_index| &grammar.variables[variable_index].productions[id.production_index],
         )
     }
+}
 
-    fn production_step_for_id<'a>(
-        &'a self,
-        id: ProductionStepId,
-        grammar: &'a SyntaxGrammar,
-    ) -> Option<&'a ProductionStep> {
-        self.production_for_id(id, grammar).steps.get(id.step_index)
-    }
+// Check if the symbol at the given step of a production should be replaced by
+// the productions of the variable that it refers to.
+fn is_inlined_at(grammar: &SyntaxGrammar, production: &Production, step_index: usize) -> bool {
+    let predecessor = step_index
+        .checked_sub(1)
+        .map(|i| production.steps[i].symbol);
+    grammar.is_inlined_after(production.steps[step_index].symbol, predecessor)
+}
+
+pub fn process_inlines(
+    grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> Result<InlinedProductionMap> {
+    process_inlines_with_max_depth(grammar, lexical_grammar, MAX_INLINE_DEPTH)
 }
 
-pub(super) fn process_inlines(
+/// Like [`process_inlines`], but with a custom limit on how deeply inlined
+/// rules can be nested at any one position. Exceeding the limit produces an
+/// [`InlineError::TooDeep`] error.
+pub(super) fn process_inlines_with_max_depth(
     grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
+    max_depth: usize,
 ) -> Result<InlinedProductionMap> {
-    for symbol in &grammar.variables_to_inline {
+    for (symbol, _) in &grammar.variables_to_inline {
         match symbol.kind {
             SymbolType::External => {
                 return Err(anyhow!(
@@ -216,20 +290,37 @@ pub(super) fn process_inlines(
     Ok(InlinedProductionMapBuilder {
         productions: Vec::new(),
         production_indices_by_step_id: HashMap::new(),
+        origins: Vec::new(),
+        max_depth,
+    }
+    .build(grammar)?)
+}
+
+impl fmt::Display for InlineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooDeep { rule, limit } => write!(
+                f,
+                "Inlining rule `{rule}` exceeds the maximum inlining depth of {limit}. Inlined rules cannot begin with themselves.",
+            ),
+        }
     }
-    .build(grammar))
 }
 
+impl error::Error for InlineError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::generate::grammars::{LexicalVariable, SyntaxVariable, VariableType};
+    use crate::generate::grammars::{
+        LexicalVariable, ProductionStep, SyntaxVariable, VariableType,
+    };
     use crate::generate::rules::{Associativity, Precedence, Symbol};
 
     #[test]
     fn test_basic_inlining() {
         let grammar = SyntaxGrammar {
-            variables_to_inline: vec![Symbol::non_terminal(1)],
+            variables_to_inline: vec![(Symbol::non_terminal(1), None)],
             variables: vec![
                 SyntaxVariable {
                     name: "non-terminal-0".to_string(),
@@ -353,9 +444,9 @@ mod tests {
                 },
             ],
             variables_to_inline: vec![
-                Symbol::non_terminal(1),
-                Symbol::non_terminal(2),
-                Symbol::non_terminal(3),
+                (Symbol::non_terminal(1), None),
+                (Symbol::non_terminal(2), None),
+                (Symbol::non_terminal(3), None),
             ],
             ..Default::default()
         };
@@ -416,7 +507,10 @@ mod tests {
     #[test]
     fn test_inlining_with_precedence_and_alias() {
         let grammar = SyntaxGrammar {
-            variables_to_inline: vec![Symbol::non_terminal(1), Symbol::non_terminal(2)],
+            variables_to_inline: vec![
+                (Symbol::non_terminal(1), None),
+                (Symbol::non_terminal(2), None),
+            ],
             variables: vec![
                 SyntaxVariable {
                     name: "non-terminal-0".to_string(),
@@ -509,6 +603,200 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_inlined_final_step_inherits_missing_associativity() {
+        let grammar = SyntaxGrammar {
+            variables_to_inline: vec![(Symbol::non_terminal(1), None)],
+            variables: vec![
+                SyntaxVariable {
+                    name: "non-terminal-0".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::terminal(10)),
+                            // inlined
+                            ProductionStep::new(Symbol::non_terminal(1))
+                                .with_prec(Precedence::Integer(1), Some(Associativity::Left)),
+                        ],
+                    }],
+                },
+                SyntaxVariable {
+                    name: "non-terminal-1".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::terminal(11))
+                                .with_prec(Precedence::Integer(2), None),
+                            ProductionStep::new(Symbol::terminal(12))
+                                .with_prec(Precedence::Integer(2), None),
+                        ],
+                    }],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let inline_map = process_inlines(&grammar, &LexicalGrammar::default()).unwrap();
+        assert_eq!(
+            inline_map
+                .inlined_productions(&grammar.variables[0].productions[0], 1)
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![Production {
+                dynamic_precedence: 0,
+                steps: vec![
+                    ProductionStep::new(Symbol::terminal(10)),
+                    ProductionStep::new(Symbol::terminal(11))
+                        .with_prec(Precedence::Integer(2), None),
+                    ProductionStep::new(Symbol::terminal(12))
+                        .with_prec(Precedence::Integer(2), Some(Associativity::Left)),
+                ]
+            }],
+        );
+    }
+
+    #[test]
+    fn test_inlining_only_after_a_given_symbol() {
+        let open_paren = Symbol::terminal(0);
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let grammar = SyntaxGrammar {
+            variables_to_inline: vec![(Symbol::non_terminal(1), Some(open_paren))],
+            variables: vec![
+                SyntaxVariable {
+                    name: "non-terminal-0".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[open_paren, Symbol::non_terminal(1), Symbol::terminal(1)]),
+                        production(&[Symbol::non_terminal(1), Symbol::terminal(2)]),
+                        production(&[Symbol::terminal(2), Symbol::non_terminal(1)]),
+                    ],
+                },
+                SyntaxVariable {
+                    name: "non-terminal-1".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![
+                        production(&[Symbol::terminal(3)]),
+                        production(&[Symbol::terminal(4)]),
+                    ],
+                },
+            ],
+            ..Default::default()
+        };
+        let inline_map = process_inlines(&grammar, &LexicalGrammar::default()).unwrap();
+
+        // The variable is inlined after the open parenthesis.
+        let productions = &grammar.variables[0].productions;
+        let inlined_productions = inline_map
+            .inlined_productions(&productions[0], 1)
+            .unwrap()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            inlined_productions,
+            [
+                production(&[open_paren, Symbol::terminal(3), Symbol::terminal(1)]),
+                production(&[open_paren, Symbol::terminal(4), Symbol::terminal(1)]),
+            ]
+        );
+
+        // Elsewhere, it remains a reference to the variable.
+        assert!(inline_map.inlined_productions(&productions[1], 0).is_none());
+        assert!(inline_map.inlined_productions(&productions[2], 1).is_none());
+        assert!(!grammar.is_always_inlined(Symbol::non_terminal(1)));
+    }
+
+    #[test]
+    fn test_error_when_inlining_too_deeply() {
+        // Each rule begins with the next one, and all of them are inlined.
+        let rule_count = 10;
+        let grammar = SyntaxGrammar {
+            variables_to_inline: (1..rule_count)
+                .map(|i| (Symbol::non_terminal(i), None))
+                .collect(),
+            variables: (0..rule_count)
+                .map(|i| SyntaxVariable {
+                    name: format!("rule_{i}"),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: if i + 1 < rule_count {
+                            vec![
+                                ProductionStep::new(Symbol::non_terminal(i + 1)),
+                                ProductionStep::new(Symbol::terminal(i)),
+                            ]
+                        } else {
+                            vec![ProductionStep::new(Symbol::terminal(i))]
+                        },
+                    }],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let lexical_grammar = LexicalGrammar::default();
+
+        let inlines = process_inlines_with_max_depth(&grammar, &lexical_grammar, 9).unwrap();
+        let production = inlines
+            .inlined_productions(&grammar.variables[0].productions[0], 0)
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!(production.steps.len(), rule_count);
+
+        let Err(error) = process_inlines_with_max_depth(&grammar, &lexical_grammar, 8) else {
+            panic!("expected an error, but got none");
+        };
+        assert_eq!(
+            error.downcast_ref::<InlineError>(),
+            Some(&InlineError::TooDeep {
+                rule: "rule_9".to_string(),
+                limit: 8,
+            })
+        );
+
+        // A rule that begins with itself can never be fully inlined.
+        let grammar = SyntaxGrammar {
+            variables_to_inline: vec![(Symbol::non_terminal(1), None)],
+            variables: vec![
+                SyntaxVariable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![ProductionStep::new(Symbol::non_terminal(1))],
+                    }],
+                },
+                SyntaxVariable {
+                    name: "list".to_string(),
+                    kind: VariableType::Named,
+                    productions: vec![Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(Symbol::non_terminal(1)),
+                            ProductionStep::new(Symbol::terminal(0)),
+                        ],
+                    }],
+                },
+            ],
+            ..Default::default()
+        };
+        let Err(error) = process_inlines(&grammar, &lexical_grammar) else {
+            panic!("expected an error, but got none");
+        };
+        assert_eq!(
+            error.downcast_ref::<InlineError>(),
+            Some(&InlineError::TooDeep {
+                rule: "list".to_string(),
+                limit: MAX_INLINE_DEPTH,
+            })
+        );
+    }
+
     #[test]
     fn test_error_when_inlining_tokens() {
         let lexical_grammar = LexicalGrammar {
@@ -517,12 +805,14 @@ mod tests {
                 kind: VariableType::Named,
                 implicit_precedence: 0,
                 start_state: 0,
+                case_insensitive: false,
+                lookahead: None,
             }],
             ..Default::default()
         };
 
         let grammar = SyntaxGrammar {
-            variables_to_inline: vec![Symbol::terminal(0)],
+            variables_to_inline: vec![(Symbol::terminal(0), None)],
             variables: vec![SyntaxVariable {
                 name: "non-terminal-0".to_string(),
                 kind: VariableType::Named,
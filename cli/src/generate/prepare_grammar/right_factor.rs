@@ -0,0 +1,270 @@
+use crate::generate::grammars::{
+    PrecedenceEntry, Production, ProductionStep, SyntaxGrammar, SyntaxVariable, VariableType,
+};
+use crate::generate::rules::Symbol;
+use std::collections::{HashMap, HashSet};
+
+// Sharing a single trailing symbol doesn't save any parse states.
+const MIN_SUFFIX_LENGTH: usize = 2;
+
+/// Extract trailing sequences of steps that are shared by several productions of
+/// the same variable into hidden helper variables. For example, this rewrites
+///
+///   X -> a ';' 'end' | b c ';' 'end'
+///
+/// as
+///
+///   X -> a X_suffix1 | b c X_suffix1
+///   X_suffix1 -> ';' 'end'
+///
+/// The helper's step in the original productions carries the precedence and
+/// associativity of the suffix's final step, so the reductions of the original
+/// variable are resolved in the same way. Variables whose names are referenced
+/// by expected conflicts or precedence orderings, and non-terminal extras, are
+/// left alone. So are suffixes whose LAST symbols can include the variable
+/// itself: in a right-recursive rule like `X -> a '+' X | b '+' X`, conflicts are
+/// resolved between items of `X`, and moving those items into a helper would
+/// change which items are compared.
+///
+/// Like [`normalize`], this must be called before the grammar's inlined
+/// productions are computed.
+///
+/// [`normalize`]: super::normalize
+pub fn right_factor(grammar: &mut SyntaxGrammar) {
+    let mut helpers_by_suffix = HashMap::<Vec<ProductionStep>, Symbol>::new();
+    for variable_index in 0..grammar.variables.len() {
+        if !can_factor(grammar, variable_index) {
+            continue;
+        }
+
+        let mut helper_count = 0;
+        while let Some((production_indices, suffix_length)) =
+            find_shared_suffix(grammar, variable_index)
+        {
+            let steps = &grammar.variables[variable_index].productions[production_indices[0]].steps;
+            let suffix = steps[steps.len() - suffix_length..].to_vec();
+            let last_step = suffix.last().unwrap();
+            let helper_step = ProductionStep {
+                symbol: Symbol::non_terminal(grammar.variables.len()),
+                precedence: last_step.precedence.clone(),
+                associativity: last_step.associativity,
+                alias: None,
+                field_name: None,
+            };
+
+            let helper_step = if let Some(helper) = helpers_by_suffix.get(&suffix) {
+                ProductionStep {
+                    symbol: *helper,
+                    ..helper_step
+                }
+            } else {
+                helper_count += 1;
+                let name = format!(
+                    "{}_suffix{helper_count}",
+                    grammar.variables[variable_index].name
+                );
+                helpers_by_suffix.insert(suffix.clone(), helper_step.symbol);
+                grammar.variables.push(SyntaxVariable {
+                    name,
+                    kind: VariableType::Hidden,
+                    productions: vec![Production {
+                        steps: suffix,
+                        dynamic_precedence: 0,
+                    }],
+                });
+                helper_step
+            };
+
+            for production_index in production_indices {
+                let steps =
+                    &mut grammar.variables[variable_index].productions[production_index].steps;
+                steps.truncate(steps.len() - suffix_length);
+                steps.push(helper_step.clone());
+            }
+        }
+    }
+}
+
+fn can_factor(grammar: &SyntaxGrammar, variable_index: usize) -> bool {
+    let symbol = Symbol::non_terminal(variable_index);
+    let name = &grammar.variables[variable_index].name;
+    !grammar.extra_symbols.contains(&symbol)
+        && !grammar
+            .expected_conflicts
+            .iter()
+            .any(|conflict| conflict.contains(&symbol))
+        && !grammar.precedence_orderings.iter().any(|ordering| {
+            ordering
+                .iter()
+                .any(|entry| matches!(entry, PrecedenceEntry::Symbol(n) if n == name))
+        })
+}
+
+// Find the longest suffix that is shared by a group of this variable's
+// productions, each of which must have at least one step before the suffix.
+// Returns the indices of the productions in the group and the length of the
+// suffix.
+fn find_shared_suffix(
+    grammar: &SyntaxGrammar,
+    variable_index: usize,
+) -> Option<(Vec<usize>, usize)> {
+    let productions = &grammar.variables[variable_index].productions;
+    let mut groups = Vec::<(&[ProductionStep], Vec<usize>)>::new();
+    for (i, production) in productions.iter().enumerate() {
+        let steps = &production.steps;
+        if steps.len() > MIN_SUFFIX_LENGTH {
+            let suffix = &steps[steps.len() - MIN_SUFFIX_LENGTH..];
+            if let Some((_, indices)) = groups.iter_mut().find(|(s, _)| *s == suffix) {
+                indices.push(i);
+            } else {
+                groups.push((suffix, vec![i]));
+            }
+        }
+    }
+
+    for (suffix, indices) in groups {
+        if indices.len() < 2 || can_end_with(grammar, suffix.last().unwrap().symbol, variable_index)
+        {
+            continue;
+        }
+
+        let steps_of = |i: usize| &productions[i].steps;
+        let mut length = MIN_SUFFIX_LENGTH;
+        loop {
+            let next_length = length + 1;
+            let step = |i: usize| &steps_of(i)[steps_of(i).len() - next_length];
+            if indices
+                .iter()
+                .all(|i| steps_of(*i).len() > next_length && step(*i) == step(indices[0]))
+            {
+                length = next_length;
+            } else {
+                break;
+            }
+        }
+        return Some((indices, length));
+    }
+    None
+}
+
+// Determine whether the given variable is in the transitive LAST set of the
+// given symbol, ignoring terminals.
+fn can_end_with(grammar: &SyntaxGrammar, symbol: Symbol, variable_index: usize) -> bool {
+    let mut symbols_to_process = vec![symbol];
+    let mut processed_non_terminals = HashSet::new();
+    while let Some(symbol) = symbols_to_process.pop() {
+        if !symbol.is_non_terminal() || !processed_non_terminals.insert(symbol) {
+            continue;
+        }
+        if symbol.index == variable_index {
+            return true;
+        }
+        for production in &grammar.variables[symbol.index].productions {
+            if let Some(step) = production.steps.last() {
+                symbols_to_process.push(step.symbol);
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::rules::{Associativity, Precedence};
+
+    #[test]
+    fn test_right_factor_common_suffix() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let semicolon = Symbol::terminal(0);
+        let end = Symbol::terminal(1);
+        let mut grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "block".to_string(),
+                kind: VariableType::Named,
+                productions: vec![
+                    production(&[Symbol::terminal(2), semicolon, end]),
+                    production(&[Symbol::terminal(3), Symbol::terminal(4), semicolon, end]),
+                    production(&[Symbol::terminal(5)]),
+                ],
+            }],
+            ..Default::default()
+        };
+        grammar.variables[0].productions[0].steps[2] =
+            ProductionStep::new(end).with_prec(Precedence::Integer(1), Some(Associativity::Left));
+        grammar.variables[0].productions[1].steps[3] =
+            ProductionStep::new(end).with_prec(Precedence::Integer(1), Some(Associativity::Left));
+
+        right_factor(&mut grammar);
+
+        let helper = ProductionStep::new(Symbol::non_terminal(1))
+            .with_prec(Precedence::Integer(1), Some(Associativity::Left));
+        assert_eq!(
+            grammar.variables[0].productions,
+            [
+                Production {
+                    dynamic_precedence: 0,
+                    steps: vec![ProductionStep::new(Symbol::terminal(2)), helper.clone()],
+                },
+                Production {
+                    dynamic_precedence: 0,
+                    steps: vec![
+                        ProductionStep::new(Symbol::terminal(3)),
+                        ProductionStep::new(Symbol::terminal(4)),
+                        helper,
+                    ],
+                },
+                production(&[Symbol::terminal(5)]),
+            ]
+        );
+        assert_eq!(grammar.variables[1].name, "block_suffix1");
+        assert_eq!(grammar.variables[1].kind, VariableType::Hidden);
+        assert_eq!(
+            grammar.variables[1].productions,
+            [Production {
+                dynamic_precedence: 0,
+                steps: vec![
+                    ProductionStep::new(semicolon),
+                    ProductionStep::new(end)
+                        .with_prec(Precedence::Integer(1), Some(Associativity::Left)),
+                ],
+            }]
+        );
+
+        // Factoring again has no effect.
+        let variables = grammar.variables.clone();
+        right_factor(&mut grammar);
+        assert_eq!(grammar.variables, variables);
+    }
+
+    #[test]
+    fn test_right_factor_skips_right_recursive_suffixes() {
+        let expression = Symbol::non_terminal(0);
+        let plus = Symbol::terminal(0);
+        let mut grammar = SyntaxGrammar {
+            variables: vec![SyntaxVariable {
+                name: "expression".to_string(),
+                kind: VariableType::Named,
+                productions: [Symbol::terminal(1), Symbol::terminal(2)]
+                    .into_iter()
+                    .map(|operand| Production {
+                        dynamic_precedence: 0,
+                        steps: vec![
+                            ProductionStep::new(operand),
+                            ProductionStep::new(plus),
+                            ProductionStep::new(expression),
+                        ],
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        };
+
+        let variables = grammar.variables.clone();
+        right_factor(&mut grammar);
+        assert_eq!(grammar.variables, variables);
+    }
+}
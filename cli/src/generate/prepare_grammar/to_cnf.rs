@@ -0,0 +1,298 @@
+use crate::generate::grammars::{
+    Production, ProductionStep, SyntaxGrammar, SyntaxVariable, VariableType,
+};
+use crate::generate::rules::{Precedence, Symbol};
+use std::collections::HashMap;
+
+/// Convert a grammar to Chomsky normal form, for analyses that require it. In the
+/// result, every production either consists of two non-terminals or a single
+/// token, apart from an empty production for the start variable if the grammar
+/// can match nothing. The start variable never appears on the right-hand side.
+///
+/// The result matches the same strings of tokens, but not with the same trees:
+/// precedence, aliases and fields are dropped, and so are the extras, conflicts
+/// and other properties that refer to variables by index. The new start rule is
+/// at index 0, the original variables follow it in order, and the helper
+/// variables come last.
+pub fn to_cnf(grammar: &SyntaxGrammar) -> SyntaxGrammar {
+    let start_name = grammar.variables.first().map_or("", |v| v.name.as_str());
+    let mut variables = vec![(
+        format!("_{start_name}_start"),
+        VariableType::Hidden,
+        vec![vec![Symbol::non_terminal(1)]],
+    )];
+    for variable in &grammar.variables {
+        let productions = variable
+            .productions
+            .iter()
+            .map(|production| {
+                production
+                    .steps
+                    .iter()
+                    .map(|step| {
+                        if step.symbol.is_non_terminal() {
+                            Symbol::non_terminal(step.symbol.index + 1)
+                        } else {
+                            step.symbol
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        variables.push((variable.name.clone(), variable.kind, productions));
+    }
+
+    // Replace the tokens in productions of more than one step with helpers that
+    // match just that token, and split long productions into chains of helpers.
+    let mut token_helpers = HashMap::new();
+    for variable_index in 0..variables.len() {
+        let mut helper_count = 0;
+        for production_index in 0..variables[variable_index].2.len() {
+            let mut steps = variables[variable_index].2[production_index].clone();
+            if steps.len() < 2 {
+                continue;
+            }
+            for symbol in &mut steps {
+                if !symbol.is_non_terminal() {
+                    let token = *symbol;
+                    *symbol = *token_helpers.entry(token).or_insert_with(|| {
+                        let kind = if token.is_external() {
+                            "external"
+                        } else {
+                            "token"
+                        };
+                        variables.push((
+                            format!("_{kind}{}_cnf", token.index),
+                            VariableType::Hidden,
+                            vec![vec![token]],
+                        ));
+                        Symbol::non_terminal(variables.len() - 1)
+                    });
+                }
+            }
+            if steps.len() > 2 {
+                let mut tail = steps.pop().unwrap();
+                while steps.len() > 1 {
+                    helper_count += 1;
+                    let previous = steps.pop().unwrap();
+                    variables.push((
+                        format!("_{}_cnf{helper_count}", variables[variable_index].0),
+                        VariableType::Hidden,
+                        vec![vec![previous, tail]],
+                    ));
+                    tail = Symbol::non_terminal(variables.len() - 1);
+                }
+                steps.push(tail);
+            }
+            variables[variable_index].2[production_index] = steps;
+        }
+    }
+
+    // Remove the empty productions, adding variants of the binary productions
+    // that leave out each nullable step.
+    let mut nullable = vec![false; variables.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, (_, _, productions)) in variables.iter().enumerate() {
+            if !nullable[i]
+                && productions.iter().any(|steps| {
+                    steps
+                        .iter()
+                        .all(|symbol| symbol.is_non_terminal() && nullable[symbol.index])
+                })
+            {
+                nullable[i] = true;
+                changed = true;
+            }
+        }
+    }
+    for (_, _, productions) in &mut variables {
+        let mut new_productions = Vec::new();
+        for steps in productions.iter() {
+            let mut variants = vec![steps.clone()];
+            if let [left, right] = steps.as_slice() {
+                if nullable[right.index] {
+                    variants.push(vec![*left]);
+                }
+                if nullable[left.index] {
+                    variants.push(vec![*right]);
+                }
+            }
+            for variant in variants {
+                if !variant.is_empty() && !new_productions.contains(&variant) {
+                    new_productions.push(variant);
+                }
+            }
+        }
+        *productions = new_productions;
+    }
+    if nullable[0] {
+        variables[0].2.push(Vec::new());
+    }
+
+    // Replace each production that consists of a single non-terminal with the
+    // other productions of the variables that it can derive in this way.
+    let is_unit = |steps: &Vec<Symbol>| steps.len() == 1 && steps[0].is_non_terminal();
+    let mut new_productions = Vec::with_capacity(variables.len());
+    for i in 0..variables.len() {
+        let mut reachable = vec![i];
+        let mut productions = Vec::new();
+        let mut j = 0;
+        while j < reachable.len() {
+            for steps in &variables[reachable[j]].2 {
+                if is_unit(steps) {
+                    if !reachable.contains(&steps[0].index) {
+                        reachable.push(steps[0].index);
+                    }
+                } else if !productions.contains(steps) {
+                    productions.push(steps.clone());
+                }
+            }
+            j += 1;
+        }
+        new_productions.push(productions);
+    }
+
+    SyntaxGrammar {
+        variables: variables
+            .into_iter()
+            .zip(new_productions)
+            .map(|((name, kind, _), productions)| SyntaxVariable {
+                name,
+                kind,
+                productions: productions
+                    .into_iter()
+                    .map(|steps| Production {
+                        dynamic_precedence: 0,
+                        steps: steps
+                            .into_iter()
+                            .map(|symbol| ProductionStep {
+                                symbol,
+                                precedence: Precedence::None,
+                                associativity: None,
+                                alias: None,
+                                field_name: None,
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        external_tokens: grammar.external_tokens.clone(),
+        word_token: grammar.word_token,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_to_cnf() {
+        let production = |symbols: &[Symbol]| Production {
+            dynamic_precedence: 0,
+            steps: symbols.iter().copied().map(ProductionStep::new).collect(),
+        };
+        let variable = |name: &str, productions| SyntaxVariable {
+            name: name.to_string(),
+            kind: VariableType::Named,
+            productions,
+        };
+        let (plus, open, close, number, minus) = (
+            Symbol::terminal(0),
+            Symbol::terminal(1),
+            Symbol::terminal(2),
+            Symbol::terminal(3),
+            Symbol::terminal(4),
+        );
+        let (expression, sign) = (Symbol::non_terminal(1), Symbol::non_terminal(2));
+        let syntax_grammar = SyntaxGrammar {
+            variables: vec![
+                // program -> expression | <empty>
+                variable("program", vec![production(&[expression]), production(&[])]),
+                // expression -> expression '+' expression | '(' expression ')' | sign number
+                variable(
+                    "expression",
+                    vec![
+                        production(&[expression, plus, expression]),
+                        production(&[open, expression, close]),
+                        production(&[sign, number]),
+                    ],
+                ),
+                // sign -> '-' | <empty>
+                variable("sign", vec![production(&[minus]), production(&[])]),
+            ],
+            ..Default::default()
+        };
+
+        let cnf_grammar = to_cnf(&syntax_grammar);
+        for (i, variable) in cnf_grammar.variables.iter().enumerate() {
+            for production in &variable.productions {
+                match production.steps.as_slice() {
+                    [] => assert_eq!(i, 0),
+                    [step] => assert!(!step.symbol.is_non_terminal()),
+                    [left, right] => {
+                        assert!(left.symbol.is_non_terminal() && left.symbol.index != 0);
+                        assert!(right.symbol.is_non_terminal() && right.symbol.index != 0);
+                    }
+                    _ => panic!("production of {} is too long", variable.name),
+                }
+            }
+        }
+
+        // Recognize the strings with the CYK algorithm, which requires CNF.
+        let recognizes = |tokens: &[Symbol]| {
+            let start_productions = &cnf_grammar.variables[0].productions;
+            if tokens.is_empty() {
+                return start_productions.iter().any(|p| p.steps.is_empty());
+            }
+            let n = tokens.len();
+            let mut table = vec![vec![HashSet::new(); n + 1]; n];
+            for (i, token) in tokens.iter().enumerate() {
+                for (v, variable) in cnf_grammar.variables.iter().enumerate() {
+                    if variable
+                        .productions
+                        .iter()
+                        .any(|p| matches!(p.steps.as_slice(), [step] if step.symbol == *token))
+                    {
+                        table[i][1].insert(v);
+                    }
+                }
+            }
+            for len in 2..=n {
+                for i in 0..=n - len {
+                    for split in 1..len {
+                        for (v, variable) in cnf_grammar.variables.iter().enumerate() {
+                            if variable.productions.iter().any(|p| {
+                                matches!(p.steps.as_slice(), [left, right]
+                                    if table[i][split].contains(&left.symbol.index)
+                                        && table[i + split][len - split]
+                                            .contains(&right.symbol.index))
+                            }) {
+                                table[i][len].insert(v);
+                            }
+                        }
+                    }
+                }
+            }
+            table[0][n].contains(&0)
+        };
+
+        assert!(recognizes(&[]));
+        assert!(recognizes(&[number]));
+        assert!(recognizes(&[minus, number]));
+        assert!(recognizes(&[number, plus, minus, number]));
+        assert!(recognizes(&[open, minus, number, close]));
+        assert!(recognizes(&[
+            open, number, plus, number, close, plus, number
+        ]));
+        assert!(!recognizes(&[minus]));
+        assert!(!recognizes(&[plus]));
+        assert!(!recognizes(&[number, number]));
+        assert!(!recognizes(&[open, number]));
+        assert!(!recognizes(&[number, plus]));
+    }
+}
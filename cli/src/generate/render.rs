@@ -1,18 +1,23 @@
 use super::{
+    build_tables::build_lex_table::compute_character_classes,
     char_tree::{CharacterTree, Comparator},
     grammars::{ExternalToken, LexicalGrammar, SyntaxGrammar, VariableType},
+    nfa::CharacterSet,
     rules::{Alias, AliasMap, Symbol, SymbolType},
+    table_backend::{render_lexers, render_parse_states, TableBackend},
     tables::{
-        AdvanceAction, FieldLocation, GotoAction, LexState, LexTable, ParseAction, ParseTable,
-        ParseTableEntry,
+        AdvanceAction, FieldLocation, GotoAction, LexState, LexTable, ParseAction, ParseStateId,
+        ParseTable, ParseTableEntry,
     },
 };
+use anyhow::{anyhow, Result};
 use core::ops::Range;
 use std::{
     cmp,
     collections::{HashMap, HashSet},
     fmt::Write,
-    mem::swap,
+    io,
+    mem::take,
 };
 
 const LARGE_CHARACTER_RANGE_COUNT: usize = 8;
@@ -20,6 +25,8 @@ const SMALL_STATE_THRESHOLD: usize = 64;
 const ABI_VERSION_MIN: usize = 13;
 const ABI_VERSION_MAX: usize = tree_sitter::LANGUAGE_VERSION;
 const ABI_VERSION_WITH_PRIMARY_STATES: usize = 14;
+const ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER: usize = 14;
+const STREAMING_BUFFER_SIZE: usize = 64 * 1024;
 
 macro_rules! add {
     ($this: tt, $($arg: tt)*) => {{
@@ -43,6 +50,31 @@ macro_rules! add_line {
     }
 }
 
+// When streaming, write out the buffered code and clear the buffer. This only
+// touches the generator's output fields, so that it can be used while iterating
+// over its tables. The first write error is saved, and any later output is
+// discarded.
+macro_rules! flush {
+    ($this: tt) => {
+        if let Some(writer) = &mut $this.writer {
+            if $this.write_error.is_none() {
+                if let Err(error) = writer.write_all($this.buffer.as_bytes()) {
+                    $this.write_error = Some(error);
+                }
+            }
+            $this.buffer.clear();
+        }
+    };
+}
+
+macro_rules! flush_if_full {
+    ($this: tt) => {
+        if $this.buffer.len() >= STREAMING_BUFFER_SIZE {
+            flush!($this);
+        }
+    };
+}
+
 macro_rules! indent {
     ($this: tt) => {
         $this.indent_level += 1;
@@ -56,8 +88,10 @@ macro_rules! dedent {
     };
 }
 
-struct Generator {
+struct Generator<'a> {
     buffer: String,
+    writer: Option<&'a mut dyn io::Write>,
+    write_error: Option<io::Error>,
     indent_level: usize,
     language_name: String,
     parse_table: ParseTable,
@@ -74,6 +108,13 @@ struct Generator {
     unique_aliases: Vec<Alias>,
     symbol_map: HashMap<Symbol, Symbol>,
     field_names: Vec<String>,
+    ascii_character_classes: Vec<u8>,
+    has_character_class_table: bool,
+    parse_action_list_ids: HashMap<ParseTableEntry, usize>,
+    next_parse_action_list_index: usize,
+    small_state_indices: Vec<usize>,
+    next_small_state_index: usize,
+    state_gotos: Vec<(Symbol, GotoAction)>,
 
     #[allow(unused)]
     abi_version: usize,
@@ -82,6 +123,7 @@ struct Generator {
 struct TransitionSummary {
     is_included: bool,
     ranges: Vec<Range<char>>,
+    class_ranges: Option<Vec<Range<u8>>>,
     call_id: Option<usize>,
 }
 
@@ -91,8 +133,20 @@ struct LargeCharacterSetInfo {
     index: usize,
 }
 
-impl Generator {
+impl<'a> Generator<'a> {
     fn generate(mut self) -> String {
+        self.add_code();
+        self.buffer
+    }
+
+    fn generate_to(mut self, writer: &'a mut dyn io::Write) -> io::Result<()> {
+        self.writer = Some(writer);
+        self.add_code();
+        flush!(self);
+        self.write_error.map_or(Ok(()), Err)
+    }
+
+    fn add_code(&mut self) {
         self.init();
         self.add_includes();
         self.add_pragmas();
@@ -118,15 +172,13 @@ impl Generator {
             self.add_primary_state_id_list();
         }
 
-        let mut main_lex_table = LexTable::default();
-        swap(&mut main_lex_table, &mut self.main_lex_table);
-        self.add_lex_function("ts_lex", main_lex_table, true);
-
-        if self.keyword_capture_token.is_some() {
-            let mut keyword_lex_table = LexTable::default();
-            swap(&mut keyword_lex_table, &mut self.keyword_lex_table);
-            self.add_lex_function("ts_lex_keywords", keyword_lex_table, false);
-        }
+        let main_lex_table = take(&mut self.main_lex_table);
+        let keyword_lex_table = take(&mut self.keyword_lex_table);
+        render_lexers(
+            self,
+            &main_lex_table,
+            self.keyword_capture_token.map(|_| &keyword_lex_table),
+        );
 
         self.add_lex_modes_list();
         self.add_parse_table();
@@ -135,11 +187,14 @@ impl Generator {
             self.add_external_token_enum();
             self.add_external_scanner_symbol_map();
             self.add_external_scanner_states_list();
+            if self.abi_version >= ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER
+                && has_external_token_precedence(&self.syntax_grammar)
+            {
+                self.add_external_scanner_symbol_order_list();
+            }
         }
 
         self.add_parser_export();
-
-        self.buffer
     }
 
     fn init(&mut self) {
@@ -254,6 +309,20 @@ impl Generator {
                 *i <= 1 || s.terminal_entries.len() + s.nonterminal_entries.len() > threshold
             })
             .count();
+
+        // Number the character classes that contain ASCII characters, reserving
+        // zero for the characters that are not in any class.
+        self.ascii_character_classes = vec![0; 128];
+        let mut class_id = 0;
+        for class in compute_character_classes(&self.lexical_grammar).classes {
+            let mut ascii_chars = class.iter().take_while(|c| *c < 128).peekable();
+            if ascii_chars.peek().is_some() {
+                class_id += 1;
+                for c in ascii_chars {
+                    self.ascii_character_classes[c as usize] = class_id;
+                }
+            }
+        }
     }
 
     fn add_includes(&mut self) {
@@ -666,11 +735,12 @@ impl Generator {
     fn add_lex_function(
         &mut self,
         name: &str,
-        lex_table: LexTable,
+        lex_table: &LexTable,
         extract_helper_functions: bool,
     ) {
         let mut ruled_out_chars = HashSet::new();
         let mut large_character_sets = Vec::<LargeCharacterSetInfo>::new();
+        let mut uses_character_classes = false;
 
         // For each lex state, compute a summary of the code that needs to be
         // generated.
@@ -688,9 +758,19 @@ impl Generator {
                     .map(|(chars, action)| {
                         let is_included = !chars.contains(std::char::MAX);
                         let mut ranges;
+                        let mut class_ranges = None;
                         if is_included {
                             ranges = chars.simplify_ignoring(&ruled_out_chars);
                             ruled_out_chars.extend(chars.iter());
+
+                            // Compare the lookahead character's class instead of the
+                            // character itself if that takes fewer comparisons.
+                            class_ranges = self
+                                .character_class_ranges(chars)
+                                .filter(|class_ranges| class_ranges.len() + 1 < ranges.len());
+                            if class_ranges.is_some() {
+                                uses_character_classes = true;
+                            }
                         } else {
                             ranges = chars.clone().negate().simplify_ignoring(&ruled_out_chars);
                             ranges.insert(0, '\0'..'\0');
@@ -699,9 +779,12 @@ impl Generator {
                         // Record any large character sets so that they can be extracted
                         // into helper functions, reducing code duplication.
                         let mut call_id = None;
-                        if extract_helper_functions && ranges.len() > LARGE_CHARACTER_RANGE_COUNT {
+                        if extract_helper_functions
+                            && class_ranges.is_none()
+                            && ranges.len() > LARGE_CHARACTER_RANGE_COUNT
+                        {
                             let char_set_symbol = self
-                                .symbol_for_advance_action(action, &lex_table)
+                                .symbol_for_advance_action(action, lex_table)
                                 .expect("No symbol for lex state");
                             let mut count_for_symbol = 0;
                             for (i, info) in large_character_sets.iter_mut().enumerate() {
@@ -726,6 +809,7 @@ impl Generator {
                         TransitionSummary {
                             is_included,
                             ranges,
+                            class_ranges,
                             call_id,
                         }
                     })
@@ -733,6 +817,12 @@ impl Generator {
             })
             .collect::<Vec<Vec<_>>>();
 
+        // Both lex functions share the table of character classes.
+        if uses_character_classes && !self.has_character_class_table {
+            self.add_character_class_table();
+            self.has_character_class_table = true;
+        }
+
         // Generate a helper function for each large character set.
         let mut sorted_large_char_sets = large_character_sets.iter().collect::<Vec<_>>();
         sorted_large_char_sets.sort_unstable_by_key(|info| (info.symbol, info.index));
@@ -765,11 +855,12 @@ impl Generator {
         add_line!(self, "switch (state) {{");
 
         indent!(self);
-        for (i, state) in lex_table.states.into_iter().enumerate() {
+        for (i, state) in lex_table.states.iter().enumerate() {
             add_line!(self, "case {i}:");
             indent!(self);
             self.add_lex_state(state, &state_transition_summaries[i], &large_character_sets);
             dedent!(self);
+            flush_if_full!(self);
         }
 
         add_line!(self, "default:");
@@ -785,6 +876,54 @@ impl Generator {
         add_line!(self, "");
     }
 
+    // Get the ranges of character class ids that match exactly the given
+    // characters, if they are all ASCII. The end-of-file character is excluded,
+    // because it needs to be checked separately.
+    fn character_class_ranges(&self, chars: &CharacterSet) -> Option<Vec<Range<u8>>> {
+        let mut class_ids = Vec::new();
+        for c in chars.iter() {
+            let class_id = *self.ascii_character_classes.get(c as usize)?;
+            if c == 0 || class_id == 0 {
+                return None;
+            }
+            class_ids.push(class_id);
+        }
+        class_ids.sort_unstable();
+        class_ids.dedup();
+
+        // Every character in the selected classes must be in the set.
+        for (c, class_id) in self.ascii_character_classes.iter().enumerate() {
+            if class_ids.contains(class_id) && !chars.contains(char::from(c as u8)) {
+                return None;
+            }
+        }
+
+        let mut result = Vec::<Range<u8>>::new();
+        for class_id in class_ids {
+            match result.last_mut() {
+                Some(range) if range.end + 1 == class_id => range.end = class_id,
+                _ => result.push(class_id..class_id),
+            }
+        }
+        Some(result)
+    }
+
+    fn add_character_class_table(&mut self) {
+        add_line!(self, "static const uint8_t ts_character_classes[128] = {{");
+        indent!(self);
+        for chunk in self.ascii_character_classes.clone().chunks(16) {
+            add_whitespace!(self);
+            for class_id in chunk {
+                add!(self, "{class_id}, ");
+            }
+            self.buffer.pop();
+            add!(self, "\n");
+        }
+        dedent!(self);
+        add_line!(self, "}};");
+        add_line!(self, "");
+    }
+
     fn symbol_for_advance_action(
         &self,
         action: &AdvanceAction,
@@ -810,19 +949,38 @@ impl Generator {
 
     fn add_lex_state(
         &mut self,
-        state: LexState,
+        state: &LexState,
         transition_info: &[TransitionSummary],
         large_character_sets: &[LargeCharacterSetInfo],
     ) {
         if let Some(accept_action) = state.accept_action {
-            add_line!(self, "ACCEPT_TOKEN({});", self.symbol_ids[&accept_action]);
+            // A token with a lookahead assertion is only accepted if the next
+            // character satisfies it.
+            if let Some(chars) = &state.accept_lookahead {
+                let is_included = !chars.contains(std::char::MAX);
+                let ranges = if is_included {
+                    chars.simplify_ignoring(&HashSet::new())
+                } else {
+                    chars.clone().negate().simplify_ignoring(&HashSet::new())
+                };
+                add_whitespace!(self);
+                add!(self, "if (");
+                self.add_character_range_conditions(&ranges, is_included, 2);
+                add!(
+                    self,
+                    ") ACCEPT_TOKEN({});\n",
+                    self.symbol_ids[&accept_action]
+                );
+            } else {
+                add_line!(self, "ACCEPT_TOKEN({});", self.symbol_ids[&accept_action]);
+            }
         }
 
-        if let Some(eof_action) = state.eof_action {
+        if let Some(eof_action) = &state.eof_action {
             add_line!(self, "if (eof) ADVANCE({});", eof_action.state);
         }
 
-        for (i, (_, action)) in state.advance_actions.into_iter().enumerate() {
+        for (i, (_, action)) in state.advance_actions.iter().enumerate() {
             let transition = &transition_info[i];
             add_whitespace!(self);
 
@@ -840,7 +998,32 @@ impl Generator {
                     self.symbol_ids[&info.symbol],
                     info.index
                 );
-                self.add_advance_action(&action);
+                self.add_advance_action(action);
+                add!(self, "\n");
+                continue;
+            }
+
+            // If the transition's characters are all ASCII, then it may be simpler
+            // to compare their character classes.
+            if let Some(class_ranges) = &transition.class_ranges {
+                add!(self, "if (lookahead < 128 && (");
+                for (i, range) in class_ranges.iter().enumerate() {
+                    if i > 0 {
+                        add!(self, " || ");
+                    }
+                    if range.start == range.end {
+                        add!(self, "ts_character_classes[lookahead] == {}", range.start);
+                    } else {
+                        add!(
+                            self,
+                            "({} <= ts_character_classes[lookahead] && ts_character_classes[lookahead] <= {})",
+                            range.start,
+                            range.end
+                        );
+                    }
+                }
+                add!(self, ")) ");
+                self.add_advance_action(action);
                 add!(self, "\n");
                 continue;
             }
@@ -852,7 +1035,7 @@ impl Generator {
                 self.add_character_range_conditions(&transition.ranges, transition.is_included, 2);
                 add!(self, ") ");
             }
-            self.add_advance_action(&action);
+            self.add_advance_action(action);
             add!(self, "\n");
         }
 
@@ -1090,151 +1273,58 @@ impl Generator {
         add_line!(self, "");
     }
 
-    fn add_parse_table(&mut self) {
-        let mut parse_table_entries = HashMap::new();
-        let mut next_parse_action_list_index = 0;
-
-        self.get_parse_action_list_id(
-            &ParseTableEntry {
-                actions: Vec::new(),
-                reusable: false,
-            },
-            &mut parse_table_entries,
-            &mut next_parse_action_list_index,
-        );
-
+    // For each external lex state, the valid external tokens in the order in
+    // which the scanner should try them. This is only needed when some of the
+    // external tokens were given a precedence.
+    fn add_external_scanner_symbol_order_list(&mut self) {
+        add_line!(self, "UNUSED");
         add_line!(
             self,
-            "static const uint16_t ts_parse_table[LARGE_STATE_COUNT][SYMBOL_COUNT] = {{",
+            "static const uint16_t ts_external_scanner_symbol_order[{}][EXTERNAL_TOKEN_COUNT] = {{",
+            self.parse_table.external_lex_states.len(),
         );
         indent!(self);
-
-        let mut terminal_entries = Vec::new();
-        let mut nonterminal_entries = Vec::new();
-
-        for (i, state) in self
-            .parse_table
-            .states
-            .iter()
-            .enumerate()
-            .take(self.large_state_count)
-        {
-            add_line!(self, "[{}] = {{", i);
-            indent!(self);
-
-            // Ensure the entries are in a deterministic order, since they are
-            // internally represented as a hash map.
-            terminal_entries.clear();
-            nonterminal_entries.clear();
-            terminal_entries.extend(state.terminal_entries.iter());
-            nonterminal_entries.extend(state.nonterminal_entries.iter());
-            terminal_entries.sort_unstable_by_key(|e| self.symbol_order.get(e.0));
-            nonterminal_entries.sort_unstable_by_key(|k| k.0);
-
-            for (symbol, action) in &nonterminal_entries {
-                add_line!(
-                    self,
-                    "[{}] = STATE({}),",
-                    self.symbol_ids[symbol],
-                    match action {
-                        GotoAction::Goto(state) => *state,
-                        GotoAction::ShiftExtra => i,
-                    }
-                );
-            }
-
-            for (symbol, entry) in &terminal_entries {
-                let entry_id = self.get_parse_action_list_id(
-                    entry,
-                    &mut parse_table_entries,
-                    &mut next_parse_action_list_index,
-                );
-                add_line!(self, "[{}] = ACTIONS({entry_id}),", self.symbol_ids[symbol]);
+        for i in 0..self.parse_table.external_lex_states.len() {
+            let tokens = self
+                .parse_table
+                .ordered_external_lex_state(i, &self.syntax_grammar);
+            if !tokens.is_empty() {
+                let ids = tokens
+                    .iter()
+                    .map(|token| {
+                        self.external_token_id(&self.syntax_grammar.external_tokens[token.index])
+                    })
+                    .collect::<Vec<_>>();
+                add_line!(self, "[{}] = {{{}}},", i, ids.join(", "));
             }
-            dedent!(self);
-            add_line!(self, "}},");
         }
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "");
+    }
 
-        if self.large_state_count < self.parse_table.states.len() {
-            add_line!(self, "static const uint16_t ts_small_parse_table[] = {{");
-            indent!(self);
-
-            let mut index = 0;
-            let mut small_state_indices = Vec::new();
-            let mut symbols_by_value = HashMap::<(usize, SymbolType), Vec<Symbol>>::new();
-            for state in self.parse_table.states.iter().skip(self.large_state_count) {
-                small_state_indices.push(index);
-                symbols_by_value.clear();
-
-                terminal_entries.clear();
-                terminal_entries.extend(state.terminal_entries.iter());
-                terminal_entries.sort_unstable_by_key(|e| self.symbol_order.get(e.0));
-
-                // In a given parse state, many lookahead symbols have the same actions.
-                // So in the "small state" representation, group symbols by their action
-                // in order to avoid repeating the action.
-                for (symbol, entry) in &terminal_entries {
-                    let entry_id = self.get_parse_action_list_id(
-                        entry,
-                        &mut parse_table_entries,
-                        &mut next_parse_action_list_index,
-                    );
-                    symbols_by_value
-                        .entry((entry_id, SymbolType::Terminal))
-                        .or_default()
-                        .push(**symbol);
-                }
-                for (symbol, action) in &state.nonterminal_entries {
-                    let state_id = match action {
-                        GotoAction::Goto(i) => *i,
-                        GotoAction::ShiftExtra => {
-                            self.large_state_count + small_state_indices.len() - 1
-                        }
-                    };
-                    symbols_by_value
-                        .entry((state_id, SymbolType::NonTerminal))
-                        .or_default()
-                        .push(*symbol);
-                }
-
-                let mut values_with_symbols = symbols_by_value.drain().collect::<Vec<_>>();
-                values_with_symbols.sort_unstable_by_key(|((value, kind), symbols)| {
-                    (symbols.len(), *kind, *value, symbols[0])
-                });
-
-                add_line!(self, "[{index}] = {},", values_with_symbols.len());
-                indent!(self);
-
-                for ((value, kind), symbols) in &mut values_with_symbols {
-                    if *kind == SymbolType::NonTerminal {
-                        add_line!(self, "STATE({value}), {},", symbols.len());
-                    } else {
-                        add_line!(self, "ACTIONS({value}), {},", symbols.len());
-                    }
-
-                    symbols.sort_unstable();
-                    indent!(self);
-                    for symbol in symbols {
-                        add_line!(self, "{},", self.symbol_ids[symbol]);
-                    }
-                    dedent!(self);
-                }
-
-                dedent!(self);
+    fn add_parse_table(&mut self) {
+        self.get_parse_action_list_id(&ParseTableEntry {
+            actions: Vec::new(),
+            reusable: false,
+        });
 
-                index += 1 + values_with_symbols
-                    .iter()
-                    .map(|(_, symbols)| 2 + symbols.len())
-                    .sum::<usize>();
-            }
+        add_line!(
+            self,
+            "static const uint16_t ts_parse_table[LARGE_STATE_COUNT][SYMBOL_COUNT] = {{",
+        );
+        indent!(self);
 
-            dedent!(self);
-            add_line!(self, "}};");
-            add_line!(self, "");
+        // The states are rendered through the `TableBackend` implementation
+        // below, which moves on to the small parse table after the large states.
+        let parse_table = take(&mut self.parse_table);
+        render_parse_states(self, &parse_table);
+        self.parse_table = parse_table;
+        dedent!(self);
+        add_line!(self, "}};");
+        add_line!(self, "");
 
+        if self.large_state_count < self.parse_table.states.len() {
             add_line!(
                 self,
                 "static const uint32_t ts_small_parse_table_map[] = {{"
@@ -1244,7 +1334,7 @@ impl Generator {
                 add_line!(
                     self,
                     "[SMALL_STATE({i})] = {},",
-                    small_state_indices[i - self.large_state_count]
+                    self.small_state_indices[i - self.large_state_count]
                 );
             }
             dedent!(self);
@@ -1252,7 +1342,7 @@ impl Generator {
             add_line!(self, "");
         }
 
-        let mut parse_table_entries = parse_table_entries
+        let mut parse_table_entries = take(&mut self.parse_action_list_ids)
             .into_iter()
             .map(|(entry, i)| (i, entry))
             .collect::<Vec<_>>();
@@ -1260,6 +1350,99 @@ impl Generator {
         self.add_parse_action_list(parse_table_entries);
     }
 
+    fn add_large_parse_state(
+        &mut self,
+        state_id: ParseStateId,
+        gotos: &[(Symbol, GotoAction)],
+        actions: &[(Symbol, &ParseTableEntry)],
+    ) {
+        add_line!(self, "[{}] = {{", state_id);
+        indent!(self);
+
+        for (symbol, action) in gotos {
+            add_line!(
+                self,
+                "[{}] = STATE({}),",
+                self.symbol_ids[symbol],
+                match action {
+                    GotoAction::Goto(state) => *state,
+                    GotoAction::ShiftExtra => state_id,
+                }
+            );
+        }
+
+        for (symbol, entry) in actions {
+            let entry_id = self.get_parse_action_list_id(entry);
+            add_line!(self, "[{}] = ACTIONS({entry_id}),", self.symbol_ids[symbol]);
+        }
+        dedent!(self);
+        add_line!(self, "}},");
+        flush_if_full!(self);
+    }
+
+    fn add_small_parse_state(
+        &mut self,
+        state_id: ParseStateId,
+        gotos: &[(Symbol, GotoAction)],
+        actions: &[(Symbol, &ParseTableEntry)],
+    ) {
+        let index = self.next_small_state_index;
+        self.small_state_indices.push(index);
+
+        // In a given parse state, many lookahead symbols have the same actions.
+        // So in the "small state" representation, group symbols by their action
+        // in order to avoid repeating the action.
+        let mut symbols_by_value = HashMap::<(usize, SymbolType), Vec<Symbol>>::new();
+        for (symbol, entry) in actions {
+            let entry_id = self.get_parse_action_list_id(entry);
+            symbols_by_value
+                .entry((entry_id, SymbolType::Terminal))
+                .or_default()
+                .push(*symbol);
+        }
+        for (symbol, action) in gotos {
+            let state_id = match action {
+                GotoAction::Goto(i) => *i,
+                GotoAction::ShiftExtra => state_id,
+            };
+            symbols_by_value
+                .entry((state_id, SymbolType::NonTerminal))
+                .or_default()
+                .push(*symbol);
+        }
+
+        let mut values_with_symbols = symbols_by_value.drain().collect::<Vec<_>>();
+        values_with_symbols.sort_unstable_by_key(|((value, kind), symbols)| {
+            (symbols.len(), *kind, *value, symbols[0])
+        });
+
+        add_line!(self, "[{index}] = {},", values_with_symbols.len());
+        indent!(self);
+
+        for ((value, kind), symbols) in &mut values_with_symbols {
+            if *kind == SymbolType::NonTerminal {
+                add_line!(self, "STATE({value}), {},", symbols.len());
+            } else {
+                add_line!(self, "ACTIONS({value}), {},", symbols.len());
+            }
+
+            symbols.sort_unstable();
+            indent!(self);
+            for symbol in symbols {
+                add_line!(self, "{},", self.symbol_ids[symbol]);
+            }
+            dedent!(self);
+        }
+
+        dedent!(self);
+
+        self.next_small_state_index += 1 + values_with_symbols
+            .iter()
+            .map(|(_, symbols)| 2 + symbols.len())
+            .sum::<usize>();
+        flush_if_full!(self);
+    }
+
     fn add_parse_action_list(&mut self, parse_table_entries: Vec<(usize, ParseTableEntry)>) {
         add_line!(
             self,
@@ -1309,6 +1492,7 @@ impl Generator {
                 add!(self, ",");
             }
             add!(self, "\n");
+            flush_if_full!(self);
         }
         dedent!(self);
         add_line!(self, "}};");
@@ -1438,18 +1622,13 @@ impl Generator {
         add_line!(self, "#endif");
     }
 
-    fn get_parse_action_list_id(
-        &self,
-        entry: &ParseTableEntry,
-        parse_table_entries: &mut HashMap<ParseTableEntry, usize>,
-        next_parse_action_list_index: &mut usize,
-    ) -> usize {
-        if let Some(&index) = parse_table_entries.get(entry) {
+    fn get_parse_action_list_id(&mut self, entry: &ParseTableEntry) -> usize {
+        if let Some(&index) = self.parse_action_list_ids.get(entry) {
             index
         } else {
-            let result = *next_parse_action_list_index;
-            parse_table_entries.insert(entry.clone(), result);
-            *next_parse_action_list_index += 1 + entry.actions.len();
+            let result = self.next_parse_action_list_index;
+            self.parse_action_list_ids.insert(entry.clone(), result);
+            self.next_parse_action_list_index += 1 + entry.actions.len();
             result
         }
     }
@@ -1697,6 +1876,129 @@ pub fn render_c_code(
     default_aliases: AliasMap,
     abi_version: usize,
 ) -> String {
+    new_generator(
+        name,
+        parse_table,
+        main_lex_table,
+        keyword_lex_table,
+        keyword_capture_token,
+        syntax_grammar,
+        lexical_grammar,
+        default_aliases,
+        abi_version,
+    )
+    .generate()
+}
+
+/// Like [`render_c_code`], but writes the C code to the given writer as it is
+/// generated, instead of building it in memory. The large tables are written
+/// out in chunks of a bounded size, so only a small part of the output is held
+/// in memory at any time.
+#[allow(clippy::too_many_arguments)]
+pub fn render_c_code_to(
+    mut writer: impl io::Write,
+    name: &str,
+    parse_table: ParseTable,
+    main_lex_table: LexTable,
+    keyword_lex_table: LexTable,
+    keyword_capture_token: Option<Symbol>,
+    syntax_grammar: SyntaxGrammar,
+    lexical_grammar: LexicalGrammar,
+    default_aliases: AliasMap,
+    abi_version: usize,
+) -> io::Result<()> {
+    new_generator(
+        name,
+        parse_table,
+        main_lex_table,
+        keyword_lex_table,
+        keyword_capture_token,
+        syntax_grammar,
+        lexical_grammar,
+        default_aliases,
+        abi_version,
+    )
+    .generate_to(&mut writer)
+}
+
+/// Check that a parser for the given grammar can be generated with the given
+/// ABI version, returning an error if the version is out of range or if the
+/// grammar uses a feature that the version can't represent.
+pub fn check_abi_version(abi_version: usize, syntax_grammar: &SyntaxGrammar) -> Result<()> {
+    if !(ABI_VERSION_MIN..=ABI_VERSION_MAX).contains(&abi_version) {
+        return Err(anyhow!(
+            "This version of Tree-sitter can only generate parsers with ABI version {ABI_VERSION_MIN} - {ABI_VERSION_MAX}, not {abi_version}",
+        ));
+    }
+    if abi_version < ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER
+        && has_external_token_precedence(syntax_grammar)
+    {
+        return Err(anyhow!(
+            "External token precedence requires ABI version {ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER} or later, not {abi_version}",
+        ));
+    }
+    Ok(())
+}
+
+// Check if any of the grammar's external tokens were given a precedence, which
+// determines the order in which the external scanner should try them.
+fn has_external_token_precedence(syntax_grammar: &SyntaxGrammar) -> bool {
+    syntax_grammar
+        .external_tokens
+        .iter()
+        .any(|token| token.precedence != 0)
+}
+
+impl<'a> TableBackend for Generator<'a> {
+    fn render_actions(&mut self, state_id: ParseStateId, actions: &[(Symbol, &ParseTableEntry)]) {
+        // The small parse table follows the large one, in a separate array.
+        if state_id == self.large_state_count {
+            dedent!(self);
+            add_line!(self, "}};");
+            add_line!(self, "");
+            add_line!(self, "static const uint16_t ts_small_parse_table[] = {{");
+            indent!(self);
+        }
+
+        // The parse action lists are numbered in the order of the symbols.
+        let mut actions = actions.to_vec();
+        actions.sort_unstable_by_key(|(symbol, _)| self.symbol_order.get(symbol));
+
+        let gotos = take(&mut self.state_gotos);
+        if state_id < self.large_state_count {
+            self.add_large_parse_state(state_id, &gotos, &actions);
+        } else {
+            self.add_small_parse_state(state_id, &gotos, &actions);
+        }
+        self.state_gotos = gotos;
+    }
+
+    // A state's gotos are rendered along with its actions, because small parse
+    // states group the two together.
+    fn render_gotos(&mut self, _: ParseStateId, gotos: &[(Symbol, GotoAction)]) {
+        self.state_gotos.clear();
+        self.state_gotos.extend_from_slice(gotos);
+    }
+
+    fn render_lexer(&mut self, name: &str, lex_table: &LexTable) {
+        // Large character sets are only extracted into helper functions in the
+        // main lex function.
+        self.add_lex_function(name, lex_table, name == "ts_lex");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn new_generator<'a>(
+    name: &str,
+    parse_table: ParseTable,
+    main_lex_table: LexTable,
+    keyword_lex_table: LexTable,
+    keyword_capture_token: Option<Symbol>,
+    syntax_grammar: SyntaxGrammar,
+    lexical_grammar: LexicalGrammar,
+    default_aliases: AliasMap,
+    abi_version: usize,
+) -> Generator<'a> {
     assert!(
         (ABI_VERSION_MIN..=ABI_VERSION_MAX).contains(&abi_version),
         "This version of Tree-sitter can only generate parsers with ABI version {ABI_VERSION_MIN} - {ABI_VERSION_MAX}, not {abi_version}",
@@ -1704,6 +2006,8 @@ pub fn render_c_code(
 
     Generator {
         buffer: String::new(),
+        writer: None,
+        write_error: None,
         indent_level: 0,
         language_name: name.to_string(),
         large_state_count: 0,
@@ -1720,7 +2024,148 @@ pub fn render_c_code(
         symbol_map: HashMap::new(),
         unique_aliases: Vec::new(),
         field_names: Vec::new(),
+        ascii_character_classes: Vec::new(),
+        has_character_class_table: false,
+        parse_action_list_ids: HashMap::new(),
+        next_parse_action_list_index: 0,
+        small_state_indices: Vec::new(),
+        next_small_state_index: 0,
+        state_gotos: Vec::new(),
         abi_version,
     }
-    .generate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::build_tables::build_test_tables;
+    use crate::generate::grammars::{InputGrammar, Variable};
+    use crate::generate::rules::{Precedence, Rule};
+
+    #[test]
+    fn test_render_c_code_to_matches_render_c_code() {
+        // Enough states that the parse tables are written out in several chunks.
+        let grammar = InputGrammar {
+            name: "test".to_string(),
+            variables: std::iter::once(Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::repeat(Rule::choice(
+                    (0..100)
+                        .map(|i| Rule::NamedSymbol(format!("rule_{i}")))
+                        .collect(),
+                )),
+            })
+            .chain((0..100).map(|i| Variable {
+                name: format!("rule_{i}"),
+                kind: VariableType::Named,
+                rule: Rule::seq(vec![
+                    Rule::String(format!("keyword_{i}")),
+                    Rule::string("("),
+                    Rule::string(")"),
+                ]),
+            }))
+            .collect(),
+            ..Default::default()
+        };
+
+        let mut streamed = Vec::new();
+        let buffered = render(&grammar, tree_sitter::LANGUAGE_VERSION);
+        let tables = build_test_tables(&grammar);
+        render_c_code_to(
+            &mut streamed,
+            &grammar.name,
+            tables.parse_table,
+            tables.main_lex_table,
+            tables.keyword_lex_table,
+            tables.keyword_capture_token,
+            tables.syntax_grammar,
+            tables.lexical_grammar,
+            tables.simple_aliases,
+            tree_sitter::LANGUAGE_VERSION,
+        )
+        .unwrap();
+
+        assert!(buffered.len() > 2 * STREAMING_BUFFER_SIZE);
+        assert_eq!(String::from_utf8(streamed).unwrap(), buffered);
+    }
+
+    #[test]
+    fn test_render_c_code_for_older_abi_version() {
+        let grammar = InputGrammar {
+            name: "test".to_string(),
+            variables: vec![Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::seq(vec![Rule::string("a"), Rule::string("b")]),
+            }],
+            ..Default::default()
+        };
+        let syntax_grammar = SyntaxGrammar::default();
+        check_abi_version(ABI_VERSION_MAX, &syntax_grammar).unwrap();
+        check_abi_version(ABI_VERSION_MIN, &syntax_grammar).unwrap();
+        let current = render(&grammar, ABI_VERSION_MAX);
+        let oldest = render(&grammar, ABI_VERSION_MIN);
+        assert!(current.contains(&format!("#define LANGUAGE_VERSION {ABI_VERSION_MAX}\n")));
+        assert!(oldest.contains(&format!("#define LANGUAGE_VERSION {ABI_VERSION_MIN}\n")));
+        assert!(current.contains(".primary_state_ids = "));
+        assert!(!oldest.contains(".primary_state_ids = "));
+
+        assert!(check_abi_version(ABI_VERSION_MIN - 1, &syntax_grammar).is_err());
+        assert!(check_abi_version(ABI_VERSION_MAX + 1, &syntax_grammar).is_err());
+    }
+
+    #[test]
+    fn test_render_external_scanner_symbol_order() {
+        let mut grammar = InputGrammar {
+            name: "test".to_string(),
+            variables: vec![Variable {
+                name: "program".to_string(),
+                kind: VariableType::Named,
+                rule: Rule::choice(vec![
+                    Rule::named("string_start"),
+                    Rule::named("heredoc_start"),
+                ]),
+            }],
+            external_tokens: vec![Rule::named("string_start"), Rule::named("heredoc_start")],
+            ..Default::default()
+        };
+        let unordered = render(&grammar, ABI_VERSION_MAX);
+        assert!(!unordered.contains("ts_external_scanner_symbol_order"));
+
+        grammar.external_tokens[1] =
+            Rule::prec(Precedence::Integer(1), Rule::named("heredoc_start"));
+        let ordered = render(&grammar, ABI_VERSION_MAX);
+        assert!(ordered.contains(
+            "  [1] = {ts_external_token_heredoc_start, ts_external_token_string_start},\n"
+        ));
+
+        // Older ABI versions can't represent the order.
+        let syntax_grammar = build_test_tables(&grammar).syntax_grammar;
+        check_abi_version(ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER, &syntax_grammar).unwrap();
+        assert_eq!(
+            check_abi_version(ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER - 1, &syntax_grammar)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "External token precedence requires ABI version {ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER} or later, not {}",
+                ABI_VERSION_WITH_EXTERNAL_SCANNER_ORDER - 1
+            )
+        );
+    }
+
+    fn render(grammar: &InputGrammar, abi_version: usize) -> String {
+        let tables = build_test_tables(grammar);
+        render_c_code(
+            &grammar.name,
+            tables.parse_table,
+            tables.main_lex_table,
+            tables.keyword_lex_table,
+            tables.keyword_capture_token,
+            tables.syntax_grammar,
+            tables.lexical_grammar,
+            tables.simple_aliases,
+            abi_version,
+        )
+    }
 }
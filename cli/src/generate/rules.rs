@@ -1,6 +1,10 @@
 use super::grammars::VariableType;
 use smallbitvec::SmallBitVec;
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum SymbolType {
@@ -29,6 +33,9 @@ pub enum Precedence {
     None,
     Integer(i32),
     Name(String),
+    /// The precedence of error-recovery productions, which lose every conflict
+    /// against other productions. It can't be written in a grammar.
+    Recovery,
 }
 
 pub type AliasMap = HashMap<Symbol, Alias>;
@@ -42,6 +49,7 @@ pub struct MetadataParams {
     pub is_string: bool,
     pub is_active: bool,
     pub is_main_token: bool,
+    pub is_case_insensitive: bool,
     pub alias: Option<Alias>,
     pub field_name: Option<String>,
 }
@@ -72,7 +80,7 @@ pub enum Rule {
 // sets of tokens can be efficiently represented as bit vectors with each
 // index corresponding to a token, and each value representing whether or not
 // the token is present in the set.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct TokenSet {
     terminal_bits: SmallBitVec,
     external_bits: SmallBitVec,
@@ -106,6 +114,15 @@ impl Rule {
         })
     }
 
+    /// A token whose letters match in either case, as when a keyword such as
+    /// `SELECT` may also be written as `select` or `Select`.
+    pub fn case_insensitive_token(content: Self) -> Self {
+        add_metadata(content, |params| {
+            params.is_token = true;
+            params.is_case_insensitive = true;
+        })
+    }
+
     pub fn prec(value: Precedence, content: Self) -> Self {
         add_metadata(content, |params| {
             params.precedence = value;
@@ -165,6 +182,11 @@ impl Precedence {
     pub const fn is_none(&self) -> bool {
         matches!(self, Self::None)
     }
+
+    #[must_use]
+    pub const fn is_recovery(&self) -> bool {
+        matches!(self, Self::Recovery)
+    }
 }
 
 #[cfg(test)]
@@ -279,6 +301,16 @@ impl TokenSet {
         }
     }
 
+    /// Create an empty set with room for the given number of terminals, so that
+    /// inserting them doesn't repeatedly grow the set. The set is equal to one
+    /// created with [`TokenSet::new`] until something is inserted.
+    pub fn with_capacity(terminal_count: usize) -> Self {
+        Self {
+            terminal_bits: SmallBitVec::with_capacity(terminal_count),
+            ..Self::new()
+        }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = Symbol> + '_ {
         self.terminal_bits
             .iter()
@@ -437,6 +469,113 @@ impl TokenSet {
     }
 }
 
+// A token set is serialized as a byte of flags for the two end symbols,
+// followed by the terminal and external bit vectors. Each bit vector is
+// written as its length, the number of set bits, and the distance from each
+// set bit to the previous one, all as LEB128 varints. Sparse sets, which are
+// the common case, therefore take only a few bytes.
+const EOF_FLAG: u8 = 1 << 0;
+const END_OF_NONTERMINAL_EXTRA_FLAG: u8 = 1 << 1;
+
+// Symbols are stored as 16-bit integers in the generated parser, so no valid
+// bit vector is longer than this. Checking the length before allocating keeps
+// corrupt input from requesting an arbitrarily large allocation.
+const MAX_BIT_VEC_LEN: usize = 1 << 16;
+
+impl TokenSet {
+    pub fn serialize(&self, mut w: impl Write) -> io::Result<()> {
+        let mut flags = 0;
+        if self.eof {
+            flags |= EOF_FLAG;
+        }
+        if self.end_of_nonterminal_extra {
+            flags |= END_OF_NONTERMINAL_EXTRA_FLAG;
+        }
+        w.write_all(&[flags])?;
+        serialize_bits(&self.terminal_bits, &mut w)?;
+        serialize_bits(&self.external_bits, &mut w)
+    }
+
+    pub fn deserialize(mut r: impl Read) -> io::Result<Self> {
+        let mut flags = [0];
+        r.read_exact(&mut flags)?;
+        if flags[0] & !(EOF_FLAG | END_OF_NONTERMINAL_EXTRA_FLAG) != 0 {
+            return Err(invalid_data("invalid token set flags"));
+        }
+        Ok(Self {
+            eof: flags[0] & EOF_FLAG != 0,
+            end_of_nonterminal_extra: flags[0] & END_OF_NONTERMINAL_EXTRA_FLAG != 0,
+            terminal_bits: deserialize_bits(&mut r)?,
+            external_bits: deserialize_bits(&mut r)?,
+        })
+    }
+}
+
+fn serialize_bits(bits: &SmallBitVec, w: &mut impl Write) -> io::Result<()> {
+    write_varint(w, bits.len())?;
+    write_varint(w, bits.iter().filter(|bit| *bit).count())?;
+    let mut next_index = 0;
+    for (index, bit) in bits.iter().enumerate() {
+        if bit {
+            write_varint(w, index - next_index)?;
+            next_index = index + 1;
+        }
+    }
+    Ok(())
+}
+
+fn deserialize_bits(r: &mut impl Read) -> io::Result<SmallBitVec> {
+    let len = read_varint(r)?;
+    if len > MAX_BIT_VEC_LEN {
+        return Err(invalid_data("token set length is too large"));
+    }
+    let count = read_varint(r)?;
+    let mut result = SmallBitVec::from_elem(len, false);
+    let mut next_index = 0;
+    for _ in 0..count {
+        let index = read_varint(r)?
+            .checked_add(next_index)
+            .filter(|index| *index < len)
+            .ok_or_else(|| invalid_data("token set index out of range"))?;
+        result.set(index, true);
+        next_index = index + 1;
+    }
+    Ok(result)
+}
+
+fn write_varint(w: &mut impl Write, mut value: usize) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(r: &mut impl Read) -> io::Result<usize> {
+    let mut result = 0usize;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0];
+        r.read_exact(&mut byte)?;
+        let bits = usize::from(byte[0] & 0x7f);
+        if shift >= usize::BITS || bits << shift >> shift != bits {
+            return Err(invalid_data("varint is too large"));
+        }
+        result |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
 impl FromIterator<Symbol> for TokenSet {
     fn from_iter<T: IntoIterator<Item = Symbol>>(iter: T) -> Self {
         let mut result = Self::new();
@@ -485,6 +624,60 @@ impl fmt::Display for Precedence {
             Self::Integer(i) => write!(f, "{i}"),
             Self::Name(s) => write!(f, "'{s}'"),
             Self::None => write!(f, "none"),
+            Self::Recovery => write!(f, "recovery"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_set_serialization() {
+        let mut token_set = [
+            Symbol::terminal(3),
+            Symbol::terminal(900),
+            Symbol::external(2),
+            Symbol::end(),
+        ]
+        .into_iter()
+        .collect::<TokenSet>();
+        token_set.insert(Symbol::terminal(1000));
+        token_set.remove(&Symbol::terminal(1000));
+
+        let mut bytes = Vec::new();
+        token_set.serialize(&mut bytes).unwrap();
+        assert_eq!(TokenSet::deserialize(bytes.as_slice()).unwrap(), token_set);
+
+        // A bitmap of the same set would need a byte for every eight tokens.
+        let bitmap_size = 1 + 1001_usize.div_ceil(8) + 3_usize.div_ceil(8);
+        assert!(bytes.len() * 10 < bitmap_size, "{} bytes", bytes.len());
+
+        let mut bytes = Vec::new();
+        TokenSet::new().serialize(&mut bytes).unwrap();
+        assert_eq!(bytes, [0, 0, 0, 0, 0]);
+        assert_eq!(
+            TokenSet::deserialize(bytes.as_slice()).unwrap(),
+            TokenSet::new()
+        );
+    }
+
+    #[test]
+    fn test_token_set_deserialization_errors() {
+        // The set bit is past the end of the bit vector.
+        assert!(TokenSet::deserialize([0, 2, 1, 5, 0, 0].as_slice()).is_err());
+        // The input ends in the middle of the external bits.
+        assert!(TokenSet::deserialize([0, 0, 0, 4].as_slice()).is_err());
+        // The terminal bit vector's length is 2^63, which must be rejected
+        // before anything is allocated.
+        let error = TokenSet::deserialize(
+            [
+                0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01, 0,
+            ]
+            .as_slice(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
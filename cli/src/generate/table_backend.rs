@@ -0,0 +1,172 @@
+use super::rules::Symbol;
+use super::tables::{GotoAction, LexTable, ParseStateId, ParseTable, ParseTableEntry};
+
+/// A target to which the tables of a generated parser can be rendered, such as
+/// source code in another language or a serialized form of the tables. The C
+/// code generator in the `render` module is the default implementation. Use
+/// [`render_tables`] to drive a backend over a parser's tables.
+pub trait TableBackend {
+    /// Render the actions of a parse state for each of its lookahead terminals.
+    fn render_actions(&mut self, state_id: ParseStateId, actions: &[(Symbol, &ParseTableEntry)]);
+
+    /// Render the actions of a parse state for each non-terminal: either the
+    /// state that the parser moves to after reducing the non-terminal, or a
+    /// shift of the non-terminal as an extra.
+    fn render_gotos(&mut self, state_id: ParseStateId, gotos: &[(Symbol, GotoAction)]);
+
+    /// Render a lex table as a lex function with the given name.
+    fn render_lexer(&mut self, name: &str, lex_table: &LexTable);
+}
+
+/// Render the given tables with the given backend, in the same order as the
+/// generated C code: the lex functions, then the parse states.
+pub fn render_tables(
+    backend: &mut impl TableBackend,
+    parse_table: &ParseTable,
+    main_lex_table: &LexTable,
+    keyword_lex_table: Option<&LexTable>,
+) {
+    render_lexers(backend, main_lex_table, keyword_lex_table);
+    render_parse_states(backend, parse_table);
+}
+
+/// Render the lex functions with the given backend. They are named as in the
+/// generated C code, and the keyword lex table is only rendered if the grammar
+/// has a word token.
+pub fn render_lexers(
+    backend: &mut impl TableBackend,
+    main_lex_table: &LexTable,
+    keyword_lex_table: Option<&LexTable>,
+) {
+    backend.render_lexer("ts_lex", main_lex_table);
+    if let Some(keyword_lex_table) = keyword_lex_table {
+        backend.render_lexer("ts_lex_keywords", keyword_lex_table);
+    }
+}
+
+/// Render the parse states with the given backend. The states are visited in
+/// order, with their gotos before their actions, and the entries of each state
+/// are sorted by symbol.
+pub fn render_parse_states(backend: &mut impl TableBackend, parse_table: &ParseTable) {
+    let mut gotos = Vec::new();
+    let mut actions = Vec::new();
+    for (state_id, state) in parse_table.states.iter().enumerate() {
+        gotos.clear();
+        gotos.extend(
+            state
+                .nonterminal_entries
+                .iter()
+                .map(|(symbol, action)| (*symbol, *action)),
+        );
+        gotos.sort_unstable_by_key(|(symbol, _)| *symbol);
+        backend.render_gotos(state_id, &gotos);
+
+        actions.clear();
+        actions.extend(
+            state
+                .terminal_entries
+                .iter()
+                .map(|(symbol, entry)| (*symbol, entry)),
+        );
+        actions.sort_unstable_by_key(|(symbol, _)| *symbol);
+        backend.render_actions(state_id, &actions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::build_tables::{build_test_tables, TestTables};
+    use crate::generate::grammars::{InputGrammar, Variable, VariableType};
+    use crate::generate::rules::Rule;
+
+    #[derive(Default)]
+    struct CountingBackend {
+        action_count: usize,
+        goto_count: usize,
+        shift_extra_count: usize,
+        lexer_names: Vec<String>,
+    }
+
+    impl TableBackend for CountingBackend {
+        fn render_actions(&mut self, _: ParseStateId, actions: &[(Symbol, &ParseTableEntry)]) {
+            self.action_count += actions
+                .iter()
+                .map(|(_, entry)| entry.actions.len())
+                .sum::<usize>();
+        }
+
+        fn render_gotos(&mut self, _: ParseStateId, gotos: &[(Symbol, GotoAction)]) {
+            self.goto_count += gotos.len();
+            self.shift_extra_count += gotos
+                .iter()
+                .filter(|(_, action)| *action == GotoAction::ShiftExtra)
+                .count();
+        }
+
+        fn render_lexer(&mut self, name: &str, _: &LexTable) {
+            self.lexer_names.push(name.to_string());
+        }
+    }
+
+    #[test]
+    fn test_render_tables() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("statement")),
+                },
+                Variable {
+                    name: "statement".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("identifier"), Rule::string(";")]),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "comment".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("#"), Rule::named("identifier")]),
+                },
+            ],
+            extra_symbols: vec![Rule::named("comment")],
+            ..Default::default()
+        };
+        let TestTables {
+            parse_table,
+            main_lex_table,
+            ..
+        } = build_test_tables(&grammar);
+
+        let mut backend = CountingBackend::default();
+        render_tables(&mut backend, &parse_table, &main_lex_table, None);
+        assert_eq!(
+            backend.action_count,
+            parse_table
+                .states
+                .iter()
+                .flat_map(|state| state.terminal_entries.values())
+                .map(|entry| entry.actions.len())
+                .sum::<usize>()
+        );
+        assert!(backend.action_count > 0);
+        assert_eq!(
+            backend.goto_count,
+            parse_table
+                .states
+                .iter()
+                .map(|state| state.nonterminal_entries.len())
+                .sum::<usize>()
+        );
+
+        // Shifting the non-terminal extra is passed on as such, rather than as a
+        // goto.
+        assert!(backend.shift_extra_count > 0);
+        assert_eq!(backend.lexer_names, ["ts_lex"]);
+    }
+}
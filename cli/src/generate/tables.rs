@@ -1,10 +1,21 @@
+use super::grammars::SyntaxGrammar;
 use super::nfa::CharacterSet;
 use super::rules::{Alias, Symbol, TokenSet};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 pub type ProductionInfoId = usize;
 pub type ParseStateId = usize;
 pub type LexStateId = usize;
 
+/// For each symbol, the parse states in which an existing subtree of that symbol
+/// can be reused during an incremental reparse.
+pub type SubtreeReuseMap = BTreeMap<Symbol, Vec<ParseStateId>>;
+
+// These mirror the constants in `lib/src/error_costs.h`.
+const ERROR_STATE_ID: ParseStateId = 0;
+const ERROR_COST_PER_RECOVERY: u32 = 500;
+const ERROR_COST_PER_MISSING_TREE: u32 = 110;
+const ERROR_COST_PER_SKIPPED_TREE: u32 = 100;
+
 use std::hash::BuildHasherDefault;
 
 use indexmap::IndexMap;
@@ -61,13 +72,76 @@ pub struct ProductionInfo {
     pub field_map: BTreeMap<String, Vec<FieldLocation>>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// An entry in a parse table that still has more than one action, because
+/// the corresponding conflict was declared in the grammar.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionConflict {
+    pub state_id: ParseStateId,
+    pub symbol: Symbol,
+    pub actions: Vec<ParseAction>,
+}
+
+/// Summary statistics about the conflicts that remain in a parse table, for
+/// judging how ambiguous a grammar is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AmbiguityMetrics {
+    /// The number of states with at least one conflicting entry.
+    pub conflicting_state_count: usize,
+    /// The number of conflicting entries, averaged over all states.
+    pub conflicts_per_state: f64,
+    /// The rule that is reduced in the most conflicting entries, with the
+    /// number of those entries.
+    pub most_conflicted_rule: Option<(Symbol, usize)>,
+}
+
+/// How a shift/reduce conflict was resolved while building a parse table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Precedence,
+    Associativity,
+    Override,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResolutionWinner {
+    Shift,
+    Reduce,
+}
+
+/// A record of a shift/reduce conflict that was resolved automatically. The
+/// state id refers to the final table, after minimization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolutionEvent {
+    pub state_id: ParseStateId,
+    pub lookahead: Symbol,
+    pub resolution: ConflictResolution,
+    pub winner: ResolutionWinner,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct ParseTable {
     pub states: Vec<ParseState>,
     pub symbols: Vec<Symbol>,
     pub production_infos: Vec<ProductionInfo>,
     pub max_aliased_production_length: usize,
     pub external_lex_states: Vec<TokenSet>,
+    pub(crate) resolutions: Vec<ResolutionEvent>,
+}
+
+/// The LR automaton of a parse table, in a form that can be run over a sequence
+/// of tokens in order to find where the sequence stops being valid.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ViablePrefixDfa {
+    actions: Vec<HashMap<Symbol, ParseAction>>,
+    gotos: Vec<HashMap<Symbol, ParseStateId>>,
+}
+
+/// The longest prefix of a token sequence that can begin a valid input, along
+/// with the parse state that was reached after consuming it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViablePrefix {
+    pub length: usize,
+    pub state_id: ParseStateId,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -79,6 +153,9 @@ pub struct AdvanceAction {
 #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct LexState {
     pub accept_action: Option<Symbol>,
+    /// If present, the accept action only applies when the next character is
+    /// in this set.
+    pub accept_lookahead: Option<CharacterSet>,
     pub eof_action: Option<AdvanceAction>,
     pub advance_actions: Vec<(CharacterSet, AdvanceAction)>,
 }
@@ -97,6 +174,369 @@ impl ParseTableEntry {
     }
 }
 
+impl Default for ParseTableEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParseTable {
+    /// The shift/reduce conflicts that were resolved by precedence or associativity,
+    /// in the order that they were encountered.
+    pub fn resolution_log(&self) -> &[ResolutionEvent] {
+        &self.resolutions
+    }
+
+    /// The external tokens that are valid in the given parse state. The external
+    /// scanner only needs to be called in states where this set is not empty.
+    pub fn valid_external_tokens(&self, state_id: ParseStateId) -> &TokenSet {
+        &self.external_lex_states[self.states[state_id].external_lex_state_id]
+    }
+
+    /// The parse states in which the given external token is valid, in order. This
+    /// is the inverse of [`Self::valid_external_tokens`].
+    pub fn external_token_valid_states(&self, external_token: Symbol) -> Vec<ParseStateId> {
+        (0..self.states.len())
+            .filter(|state_id| {
+                self.valid_external_tokens(*state_id)
+                    .contains(&external_token)
+            })
+            .collect()
+    }
+
+    /// The external tokens that are valid in the given parse state, in the order in
+    /// which the external scanner should try them: by descending precedence and then
+    /// in the order in which they were declared.
+    pub fn ordered_external_tokens(
+        &self,
+        state_id: ParseStateId,
+        syntax_grammar: &SyntaxGrammar,
+    ) -> Vec<Symbol> {
+        let external_lex_state_id = self.states[state_id].external_lex_state_id;
+        self.ordered_external_lex_state(external_lex_state_id, syntax_grammar)
+    }
+
+    /// The tokens in the given external lex state, in the same order as
+    /// [`Self::ordered_external_tokens`].
+    pub fn ordered_external_lex_state(
+        &self,
+        external_lex_state_id: usize,
+        syntax_grammar: &SyntaxGrammar,
+    ) -> Vec<Symbol> {
+        let mut result = self.external_lex_states[external_lex_state_id]
+            .iter()
+            .collect::<Vec<_>>();
+        result.sort_by_key(|token| {
+            std::cmp::Reverse(syntax_grammar.external_tokens[token.index].precedence)
+        });
+        result
+    }
+
+    /// Map each symbol to the parse states in which a previously-parsed subtree of
+    /// that symbol can be pushed directly onto the stack. For nonterminals, these are
+    /// the states with a goto on the symbol. For tokens, these are the states that
+    /// shift the token and whose entry is marked as reusable.
+    pub fn build_subtree_reuse_map(&self) -> SubtreeReuseMap {
+        let mut result = SubtreeReuseMap::new();
+        for (state_id, state) in self.states.iter().enumerate() {
+            for (symbol, entry) in &state.terminal_entries {
+                if entry.reusable
+                    && entry
+                        .actions
+                        .iter()
+                        .any(|action| matches!(action, ParseAction::Shift { .. }))
+                {
+                    result.entry(*symbol).or_default().push(state_id);
+                }
+            }
+            for (symbol, action) in &state.nonterminal_entries {
+                if let GotoAction::Goto(_) = action {
+                    result.entry(*symbol).or_default().push(state_id);
+                }
+            }
+        }
+        result
+    }
+
+    /// Get the action for each lookahead token in each parse state, in a form that
+    /// is independent of any rendering backend. A missing entry represents an
+    /// error. If any entry has multiple actions, those entries are returned as
+    /// conflicts rather than choosing one of the actions arbitrarily.
+    pub fn action_table(&self) -> Result<Vec<HashMap<Symbol, ParseAction>>, Vec<ActionConflict>> {
+        let mut conflicts = Vec::new();
+        let table = self
+            .states
+            .iter()
+            .enumerate()
+            .map(|(state_id, state)| {
+                let mut actions = HashMap::new();
+                for (symbol, entry) in &state.terminal_entries {
+                    match entry.actions.as_slice() {
+                        [] => {}
+                        [action] => {
+                            actions.insert(*symbol, *action);
+                        }
+                        _ => conflicts.push(ActionConflict {
+                            state_id,
+                            symbol: *symbol,
+                            actions: entry.actions.clone(),
+                        }),
+                    }
+                }
+                actions
+            })
+            .collect();
+        if conflicts.is_empty() {
+            Ok(table)
+        } else {
+            Err(conflicts)
+        }
+    }
+
+    /// Aggregate the conflicts reported by [`action_table`] into metrics about the
+    /// ambiguity of the grammar.
+    ///
+    /// [`action_table`]: Self::action_table
+    pub fn ambiguity_metrics(&self) -> AmbiguityMetrics {
+        let Err(conflicts) = self.action_table() else {
+            return AmbiguityMetrics::default();
+        };
+
+        let mut conflicting_states = Vec::new();
+        let mut conflict_counts_by_rule = BTreeMap::<Symbol, usize>::new();
+        for conflict in &conflicts {
+            conflicting_states.push(conflict.state_id);
+            let mut rules = conflict
+                .actions
+                .iter()
+                .filter_map(|action| match action {
+                    ParseAction::Reduce { symbol, .. } => Some(*symbol),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            rules.sort_unstable();
+            rules.dedup();
+            for rule in rules {
+                *conflict_counts_by_rule.entry(rule).or_default() += 1;
+            }
+        }
+        conflicting_states.dedup();
+
+        AmbiguityMetrics {
+            conflicting_state_count: conflicting_states.len(),
+            conflicts_per_state: conflicts.len() as f64 / self.states.len() as f64,
+            // Prefer the earliest rule when several have the same count.
+            most_conflicted_rule: conflict_counts_by_rule
+                .into_iter()
+                .rev()
+                .max_by_key(|(_, count)| *count),
+        }
+    }
+
+    /// Repackage this table's actions and gotos as an automaton for finding the
+    /// viable prefixes of token sequences. Where a declared conflict leaves
+    /// several actions for a token, only the first one is followed.
+    pub fn viable_prefix_states(&self) -> ViablePrefixDfa {
+        ViablePrefixDfa {
+            actions: self
+                .states
+                .iter()
+                .map(|state| {
+                    state
+                        .terminal_entries
+                        .iter()
+                        .filter_map(|(symbol, entry)| Some((*symbol, *entry.actions.first()?)))
+                        .collect()
+                })
+                .collect(),
+            gotos: self
+                .states
+                .iter()
+                .map(|state| {
+                    state
+                        .nonterminal_entries
+                        .iter()
+                        .filter_map(|(symbol, action)| match action {
+                            GotoAction::Goto(state_id) => Some((*symbol, *state_id)),
+                            GotoAction::ShiftExtra => None,
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Group the states of an action table, as returned by [`ParseTable::action_table`],
+/// that have exactly the same action for every lookahead token. States in the
+/// same group can share a single row of the rendered table. Every state is in
+/// exactly one group, and the groups are ordered by their first state.
+pub fn equivalent_action_states(action_table: &[HashMap<Symbol, ParseAction>]) -> Vec<Vec<usize>> {
+    let mut group_ids_by_actions = HashMap::new();
+    let mut groups = Vec::<Vec<usize>>::new();
+    for (state_id, actions) in action_table.iter().enumerate() {
+        let mut actions = actions
+            .iter()
+            .map(|(symbol, action)| (*symbol, *action))
+            .collect::<Vec<_>>();
+        actions.sort_unstable();
+        let group_id = *group_ids_by_actions.entry(actions).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group_id].push(state_id);
+    }
+    groups
+}
+
+/// Estimate the error cost of recovering from each invalid lookahead token in
+/// each parse state, using the same weights as the runtime's error costs.
+///
+/// Two kinds of recovery transitions are considered: inserting a single missing
+/// token after which the lookahead becomes valid, and skipping the trees on
+/// top of the stack until reaching a preceding state in which the lookahead is
+/// valid. Each `(state, lookahead)` pair maps to the cheapest of these. Pairs
+/// with no such transition are omitted, since the only recovery left to the
+/// runtime is to skip the lookahead itself.
+pub fn recovery_costs(
+    states: &[ParseState],
+    syntax_grammar: &SyntaxGrammar,
+) -> HashMap<(usize, Symbol), u32> {
+    let mut predecessors = vec![Vec::new(); states.len()];
+    for state in states {
+        if state.id == ERROR_STATE_ID {
+            continue;
+        }
+        let successors = state
+            .terminal_entries
+            .values()
+            .flat_map(|entry| entry.actions.iter())
+            .filter_map(|action| match action {
+                ParseAction::Shift { state, .. } => Some(*state),
+                _ => None,
+            })
+            .chain(
+                state
+                    .nonterminal_entries
+                    .values()
+                    .filter_map(|action| match action {
+                        GotoAction::Goto(state) => Some(*state),
+                        GotoAction::ShiftExtra => None,
+                    }),
+            );
+        for successor in successors {
+            if !predecessors[successor].contains(&state.id) {
+                predecessors[successor].push(state.id);
+            }
+        }
+    }
+
+    let mut lookaheads = states
+        .iter()
+        .filter(|state| state.id != ERROR_STATE_ID)
+        .flat_map(|state| state.terminal_entries.keys().copied())
+        .filter(|symbol| !syntax_grammar.extra_symbols.contains(symbol))
+        .collect::<Vec<_>>();
+    lookaheads.sort_unstable();
+    lookaheads.dedup();
+
+    let mut result = HashMap::new();
+    let mut depths = vec![None; states.len()];
+    let mut queue = VecDeque::new();
+    for state in states {
+        if state.id == ERROR_STATE_ID {
+            continue;
+        }
+
+        // Find how many trees must be skipped to return to each preceding state.
+        depths.fill(None);
+        depths[state.id] = Some(0);
+        queue.push_back(state.id);
+        while let Some(state_id) = queue.pop_front() {
+            let depth = depths[state_id].unwrap();
+            for predecessor in &predecessors[state_id] {
+                if depths[*predecessor].is_none() {
+                    depths[*predecessor] = Some(depth + 1);
+                    queue.push_back(*predecessor);
+                }
+            }
+        }
+
+        for lookahead in &lookaheads {
+            if state.terminal_entries.contains_key(lookahead) {
+                continue;
+            }
+
+            let insertion_cost = state
+                .terminal_entries
+                .iter()
+                .filter(|(symbol, _)| !syntax_grammar.extra_symbols.contains(symbol))
+                .flat_map(|(_, entry)| entry.actions.iter())
+                .any(|action| match action {
+                    ParseAction::Shift { state, .. } => {
+                        states[*state].terminal_entries.contains_key(lookahead)
+                    }
+                    _ => false,
+                })
+                .then_some(ERROR_COST_PER_MISSING_TREE + ERROR_COST_PER_RECOVERY);
+
+            let skip_cost = states
+                .iter()
+                .filter(|predecessor| predecessor.id != ERROR_STATE_ID)
+                .filter(|predecessor| predecessor.terminal_entries.contains_key(lookahead))
+                .filter_map(|predecessor| depths[predecessor.id])
+                .min()
+                .map(|depth| ERROR_COST_PER_RECOVERY + depth * ERROR_COST_PER_SKIPPED_TREE);
+
+            if let Some(cost) = insertion_cost.into_iter().chain(skip_cost).min() {
+                result.insert((state.id, *lookahead), cost);
+            }
+        }
+    }
+    result
+}
+
+impl ViablePrefixDfa {
+    /// Run the automaton over the given tokens, starting in the initial parse
+    /// state, and stop at the first token for which there is no valid action. If
+    /// every token is consumed, the whole sequence is a viable prefix.
+    pub fn viable_prefix(&self, tokens: &[Symbol]) -> ViablePrefix {
+        let mut stack = vec![1];
+        let mut length = 0;
+        loop {
+            let state_id = *stack.last().unwrap();
+            let lookahead = tokens.get(length).copied().unwrap_or_else(Symbol::end);
+            match self.actions[state_id].get(&lookahead) {
+                Some(ParseAction::Shift { state, .. }) => {
+                    stack.push(*state);
+                    length += 1;
+                }
+                Some(ParseAction::ShiftExtra) => length += 1,
+                Some(ParseAction::Reduce {
+                    symbol,
+                    child_count,
+                    ..
+                }) => {
+                    stack.truncate(stack.len() - child_count);
+                    let Some(state) = self.gotos[*stack.last().unwrap()].get(symbol) else {
+                        return ViablePrefix { length, state_id };
+                    };
+                    stack.push(*state);
+                }
+                Some(ParseAction::Accept) => {
+                    return ViablePrefix { length, state_id };
+                }
+                Some(ParseAction::Recover) | None => {
+                    return ViablePrefix {
+                        length: length.min(tokens.len()),
+                        state_id,
+                    };
+                }
+            }
+        }
+    }
+}
+
 impl ParseState {
     pub fn is_end_of_non_terminal_extra(&self) -> bool {
         self.terminal_entries
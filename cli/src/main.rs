@@ -81,6 +81,22 @@ struct Generate {
         help = "Produce a report of the states for the given rule, use `-` to report every rule"
     )]
     pub report_states_for_rule: Option<String>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the grammar's unresolved conflicts to the given file as JSON"
+    )]
+    pub conflicts_json: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Don't generate the actions of the error recovery state, to shrink the parse table"
+    )]
+    pub no_recovery: bool,
+    #[arg(
+        long,
+        help = "Fail if tokens that match the same string are only distinguished by the order of their rules. Parse conflicts always require precedence, associativity, or a declared conflict"
+    )]
+    pub strict_token_order: bool,
 
     #[arg(
         long,
@@ -423,14 +439,20 @@ fn run() -> Result<()> {
                     }
                 },
             );
-            generate::generate_parser_in_directory(
+            let warnings = generate::generate_parser_in_directory(
                 &current_dir,
                 generate_options.grammar_path.as_deref(),
                 abi_version,
                 !generate_options.no_bindings,
                 generate_options.report_states_for_rule.as_deref(),
                 generate_options.js_runtime.as_deref(),
+                generate_options.conflicts_json.as_deref(),
+                !generate_options.no_recovery,
+                generate_options.strict_token_order,
             )?;
+            for warning in warnings {
+                eprintln!("Warning: {warning}");
+            }
             if generate_options.build {
                 if let Some(path) = generate_options.libdir {
                     loader = loader::Loader::with_parser_lib_path(PathBuf::from(path));
@@ -0,0 +1,234 @@
+use std::fs;
+
+use serde_json::Value;
+
+use crate::generate::analysis::WellformednessError;
+use crate::generate::{generate_parser_for_grammar, generate_parser_in_directory};
+
+#[test]
+fn test_generate_writes_unresolved_conflicts_as_json() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let grammar_path = temp_dir.path().join("grammar.json");
+    let conflicts_path = temp_dir.path().join("conflicts.json");
+    fs::write(
+        &grammar_path,
+        r#"{
+            "name": "ambiguous",
+            "rules": {
+                "expression": {
+                    "type": "CHOICE",
+                    "members": [
+                        {
+                            "type": "SEQ",
+                            "members": [
+                                {"type": "SYMBOL", "name": "expression"},
+                                {"type": "STRING", "value": "+"},
+                                {"type": "SYMBOL", "name": "expression"}
+                            ]
+                        },
+                        {"type": "STRING", "value": "x"}
+                    ]
+                }
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let error = generate_parser_in_directory(
+        temp_dir.path(),
+        grammar_path.to_str(),
+        tree_sitter::LANGUAGE_VERSION,
+        false,
+        None,
+        None,
+        Some(&conflicts_path),
+        true,
+        false,
+    )
+    .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Unresolved conflict for symbol sequence"));
+
+    let conflicts: Value =
+        serde_json::from_str(&fs::read_to_string(&conflicts_path).unwrap()).unwrap();
+    let conflicts = conflicts.as_array().unwrap();
+    assert_eq!(conflicts.len(), 1);
+    let conflict = &conflicts[0];
+    assert!(conflict["state_id"].is_u64());
+    assert_eq!(
+        conflict["preceding_symbols"],
+        serde_json::json!(["expression", "'+'", "expression"])
+    );
+    assert_eq!(conflict["lookahead"], "'+'");
+    assert_eq!(conflict["rules"], serde_json::json!(["expression"]));
+}
+
+#[test]
+fn test_generate_does_not_write_conflicts_json_for_other_errors() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let grammar_path = temp_dir.path().join("grammar.json");
+    let conflicts_path = temp_dir.path().join("conflicts.json");
+    fs::write(
+        &grammar_path,
+        r#"{
+            "name": "order_dependent",
+            "rules": {
+                "program": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "SYMBOL", "name": "int"},
+                        {"type": "SYMBOL", "name": "identifier"}
+                    ]
+                },
+                "int": {"type": "PATTERN", "value": "[0-9]+"},
+                "identifier": {"type": "PATTERN", "value": "[a-z0-9]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+
+    // Strict mode fails after the parse table is built, for a reason other than
+    // a conflict.
+    let error = generate_parser_in_directory(
+        temp_dir.path(),
+        grammar_path.to_str(),
+        tree_sitter::LANGUAGE_VERSION,
+        false,
+        None,
+        None,
+        Some(&conflicts_path),
+        true,
+        true,
+    )
+    .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("is only preferred because it is defined first"));
+    assert!(!conflicts_path.exists());
+}
+
+#[test]
+fn test_generate_reports_unused_rules_and_rejects_non_productive_ones() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let grammar_path = temp_dir.path().join("grammar.json");
+    let generate = |rules: &str| {
+        fs::write(
+            &grammar_path,
+            format!(r#"{{"name": "wellformedness", "rules": {rules}}}"#),
+        )
+        .unwrap();
+        generate_parser_in_directory(
+            temp_dir.path(),
+            grammar_path.to_str(),
+            tree_sitter::LANGUAGE_VERSION,
+            false,
+            None,
+            None,
+            None,
+            true,
+            false,
+        )
+    };
+
+    // A rule that is never used only produces a warning.
+    let warnings = generate(
+        r#"{
+            "program": {"type": "STRING", "value": "a"},
+            "unused": {
+                "type": "SEQ",
+                "members": [
+                    {"type": "STRING", "value": "b"},
+                    {"type": "STRING", "value": "c"}
+                ]
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        warnings,
+        [WellformednessError::Unreachable {
+            variable: "unused".to_string()
+        }]
+    );
+
+    // A rule that can only match an infinite sequence of tokens is an error.
+    let error = generate(
+        r#"{
+            "program": {
+                "type": "CHOICE",
+                "members": [
+                    {"type": "STRING", "value": "a"},
+                    {"type": "SYMBOL", "name": "loop"}
+                ]
+            },
+            "loop": {
+                "type": "SEQ",
+                "members": [
+                    {"type": "STRING", "value": "b"},
+                    {"type": "SYMBOL", "name": "loop"}
+                ]
+            }
+        }"#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Rule 'loop' cannot match any finite input"
+    );
+}
+
+#[test]
+fn test_generate_evaluates_grammar_js_without_a_js_runtime() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let grammar_path = temp_dir.path().join("grammar.js");
+    fs::write(
+        &grammar_path,
+        "module.exports = grammar({\n\
+           name: 'greeting',\n\
+           rules: { program: $ => seq('hello', $.name), name: $ => /[a-z]+/ },\n\
+         });\n",
+    )
+    .unwrap();
+
+    generate_parser_in_directory(
+        temp_dir.path(),
+        None,
+        tree_sitter::LANGUAGE_VERSION,
+        false,
+        None,
+        Some("tree-sitter-test-missing-js-runtime"),
+        None,
+        true,
+        false,
+    )
+    .unwrap();
+
+    let grammar_json = fs::read_to_string(temp_dir.path().join("src/grammar.json")).unwrap();
+    let grammar = serde_json::from_str::<Value>(&grammar_json).unwrap();
+    assert_eq!(grammar["name"], "greeting");
+    assert!(temp_dir.path().join("src/parser.c").exists());
+}
+
+#[test]
+fn test_generate_coalesces_optionals_when_the_parse_table_shrinks() {
+    let grammar_json = r#"{
+        "name": "declarations",
+        "rules": {
+            "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "declaration"}},
+            "declaration": {
+                "type": "SEQ",
+                "members": [
+                    {"type": "STRING", "value": "let"},
+                    {"type": "CHOICE", "members": [{"type": "STRING", "value": "pub"}, {"type": "BLANK"}]},
+                    {"type": "CHOICE", "members": [{"type": "STRING", "value": "mut"}, {"type": "BLANK"}]},
+                    {"type": "CHOICE", "members": [{"type": "STRING", "value": "ref"}, {"type": "BLANK"}]},
+                    {"type": "STRING", "value": "x"}
+                ]
+            }
+        }
+    }"#;
+
+    let (_, c_code) = generate_parser_for_grammar(grammar_json).unwrap();
+    assert!(c_code.contains("sym__declaration_optional1"));
+}
@@ -1,6 +1,7 @@
 mod async_context_test;
 mod corpus_test;
 mod detect_language;
+mod generate_test;
 mod helpers;
 mod highlight_test;
 mod language_test;
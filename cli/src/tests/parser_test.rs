@@ -4,7 +4,7 @@ use super::helpers::{
     fixtures::{get_language, get_test_language},
 };
 use crate::{
-    generate::generate_parser_for_grammar,
+    generate::{generate_parser_for_grammar, generate_parser_for_grammar_with_recovery},
     parse::{perform_edit, Edit},
     tests::helpers::fixtures::fixtures_dir,
 };
@@ -753,6 +753,155 @@ fn test_parsing_with_a_timeout_and_a_reset() {
     );
 }
 
+#[test]
+fn test_parsing_with_a_maximum_error_count() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_maximum_error_count",
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "SEQ",
+                        "members": [
+                            { "type": "SYMBOL", "name": "word" },
+                            { "type": "STRING", "value": ";" }
+                        ]
+                    }
+                },
+                "word": { "type": "PATTERN", "value": "[a-z]+" }
+            },
+            "extras": [ { "type": "PATTERN", "value": "\\s" } ]
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+    parser.set_max_error_count(3);
+    assert_eq!(parser.max_error_count(), 3);
+
+    // Parsing stops once the parser has recovered from too many errors.
+    let source = "a ; @ ".repeat(100);
+    assert!(parser.parse(&source, None).is_none());
+
+    // A parse with fewer errors still succeeds, and so does a parse after a
+    // failure, since the failed parse is not resumed.
+    let tree = parser.parse("a ; @ b ;", None).unwrap();
+    assert!(tree.root_node().has_error());
+    let tree = parser.parse("a ; b ;", None).unwrap();
+    assert!(!tree.root_node().has_error());
+
+    // Each invalid token counts as one error.
+    parser.set_max_error_count(1);
+    assert!(parser.parse("a ; @ b ;", None).is_some());
+    assert!(parser.parse("a ; @ b ; @ c ;", None).is_none());
+
+    // Without a limit, the parser recovers from every error.
+    parser.set_max_error_count(0);
+    let tree = parser.parse(&source, None).unwrap();
+    assert!(tree.root_node().has_error());
+}
+
+#[test]
+fn test_parsing_with_a_maximum_error_count_and_ambiguities() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_maximum_error_count_with_ambiguities",
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "CHOICE",
+                        "members": [
+                            {
+                                "type": "SEQ",
+                                "members": [
+                                    { "type": "SYMBOL", "name": "noun" },
+                                    { "type": "STRING", "value": ";" }
+                                ]
+                            },
+                            {
+                                "type": "SEQ",
+                                "members": [
+                                    { "type": "SYMBOL", "name": "verb" },
+                                    { "type": "STRING", "value": ";" }
+                                ]
+                            }
+                        ]
+                    }
+                },
+                "noun": { "type": "SYMBOL", "name": "word" },
+                "verb": { "type": "SYMBOL", "name": "word" },
+                "word": { "type": "PATTERN", "value": "[a-z]+" }
+            },
+            "conflicts": [["noun", "verb"]],
+            "extras": [ { "type": "PATTERN", "value": "\\s" } ]
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    // The error after the ambiguous word is encountered by both versions of the
+    // parse stack, but it only counts once.
+    parser.set_max_error_count(1);
+    let tree = parser.parse("a ; b @ ;", None).unwrap();
+    assert!(tree.root_node().has_error());
+    assert!(parser.parse("a @ ; b @ ;", None).is_none());
+}
+
+#[test]
+fn test_parsing_invalid_input_without_error_recovery() {
+    let grammar_json = r#"
+        {
+            "name": "test_without_recovery",
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "SEQ",
+                        "members": [
+                            { "type": "SYMBOL", "name": "word" },
+                            { "type": "STRING", "value": ";" }
+                        ]
+                    }
+                },
+                "word": { "type": "PATTERN", "value": "[a-z]+" }
+            },
+            "extras": [ { "type": "PATTERN", "value": "\\s" } ]
+        }
+    "#;
+    let (parser_name, parser_code) =
+        generate_parser_for_grammar_with_recovery(grammar_json, false).unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    // Valid input parses as usual.
+    let tree = parser.parse("a ; b ;", None).unwrap();
+    assert_eq!(tree.root_node().to_sexp(), "(program (word) (word))");
+
+    // Invalid input doesn't crash the parser. Without the error state's actions,
+    // the parser can only recover by skipping the tokens that it can't use.
+    for source in ["a b ;", "a ; ; b", "; a", "a ; @ b ;", "a"] {
+        let tree = parser.parse(source, None).unwrap();
+        assert!(tree.root_node().has_error(), "{source}");
+        assert_eq!(tree.root_node().end_byte(), source.len(), "{source}");
+    }
+}
+
 #[test]
 #[retry(10)]
 fn test_parsing_with_a_timeout_and_implicit_reset() {
@@ -1390,6 +1539,157 @@ fn test_grammars_that_can_hang_on_eof() {
     parser.parse("\"", None).unwrap();
 }
 
+#[test]
+fn test_parsing_keywords_with_a_word_token() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_keywords_with_word_token",
+            "word": "identifier",
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "CHOICE",
+                        "members": [
+                            { "type": "SYMBOL", "name": "if_statement" },
+                            { "type": "SYMBOL", "name": "identifier" }
+                        ]
+                    }
+                },
+                "if_statement": {
+                    "type": "SEQ",
+                    "members": [
+                        { "type": "STRING", "value": "if" },
+                        { "type": "SYMBOL", "name": "identifier" }
+                    ]
+                },
+                "identifier": { "type": "PATTERN", "value": "[a-z]+" }
+            },
+            "extras": [ { "type": "PATTERN", "value": "\\s" } ]
+        }
+        "#,
+    )
+    .unwrap();
+
+    // The keywords are recognized by a separate lex function, which is run on
+    // each word token.
+    assert!(parser_code.contains("ts_lex_keywords"));
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    // Only an exact match of `if` is a keyword, not a word that starts with it.
+    let tree = parser.parse("if x iffy", None).unwrap();
+    let root = tree.root_node();
+    assert_eq!(
+        root.to_sexp(),
+        "(program (if_statement (identifier)) (identifier))"
+    );
+    let keyword = root.child(0).unwrap().child(0).unwrap();
+    assert_eq!(keyword.kind(), "if");
+    assert!(!keyword.is_named());
+}
+
+#[test]
+fn test_parsing_immediate_tokens_after_extras() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_immediate_tokens_after_extras",
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "CHOICE",
+                        "members": [
+                            { "type": "SYMBOL", "name": "word" },
+                            { "type": "SYMBOL", "name": "interpolation" }
+                        ]
+                    }
+                },
+                "interpolation": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "IMMEDIATE_TOKEN",
+                            "content": { "type": "STRING", "value": "${" }
+                        },
+                        { "type": "SYMBOL", "name": "word" },
+                        { "type": "STRING", "value": "}" }
+                    ]
+                },
+                "word": { "type": "PATTERN", "value": "[a-z]+" }
+            },
+            "extras": [ { "type": "PATTERN", "value": "\\s" } ]
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    // Extras are allowed before the tokens that follow the immediate token.
+    let tree = parser.parse("a${ b }", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (word) (interpolation (word)))"
+    );
+
+    // But a preceding space prevents the immediate token from matching.
+    let tree = parser.parse("a ${b}", None).unwrap();
+    assert!(tree.root_node().has_error());
+}
+
+#[test]
+fn test_parsing_tokens_with_lookahead_assertions() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"
+        {
+            "name": "test_tokens_with_lookahead_assertions",
+            "rules": {
+                "program": {
+                    "type": "REPEAT",
+                    "content": {
+                        "type": "CHOICE",
+                        "members": [
+                            { "type": "SYMBOL", "name": "identifier" },
+                            { "type": "SYMBOL", "name": "division" }
+                        ]
+                    }
+                },
+                "division": { "type": "PATTERN", "value": "/(?![/*])" },
+                "identifier": { "type": "PATTERN", "value": "[a-z]+" }
+            },
+            "extras": [ { "type": "PATTERN", "value": "\\s" } ]
+        }
+        "#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&get_test_language(&parser_name, &parser_code, None))
+        .unwrap();
+
+    let tree = parser.parse("a / b / / c /", None).unwrap();
+    assert_eq!(
+        tree.root_node().to_sexp(),
+        "(program (identifier) (division) (identifier) (division) (division) (identifier) (division))"
+    );
+
+    // A slash that begins a comment is not a division operator.
+    let tree = parser.parse("a //b", None).unwrap();
+    assert!(tree.root_node().has_error());
+    let tree = parser.parse("a /*b", None).unwrap();
+    assert!(tree.root_node().has_error());
+}
+
 #[test]
 fn test_parse_stack_recursive_merge_error_cost_calculation_bug() {
     let source_code = r#"
@@ -568,6 +568,7 @@ impl Parser {
     ///  * The parser has not yet had a language assigned with [`Parser::set_language`]
     ///  * The timeout set with [`Parser::set_timeout_micros`] expired
     ///  * The cancellation flag set with [`Parser::set_cancellation_flag`] was flipped
+    ///  * The error limit set with [`Parser::set_max_error_count`] was exceeded
     #[doc(alias = "ts_parser_parse")]
     pub fn parse(&mut self, text: impl AsRef<[u8]>, old_tree: Option<&Tree>) -> Option<Tree> {
         let bytes = text.as_ref();
@@ -735,6 +736,30 @@ impl Parser {
         unsafe { ffi::ts_parser_set_timeout_micros(self.0.as_ptr(), timeout_micros) }
     }
 
+    /// Get the maximum number of errors that the parser will recover from.
+    ///
+    /// This is set via [`set_max_error_count`](Parser::set_max_error_count).
+    #[doc(alias = "ts_parser_max_error_count")]
+    #[must_use]
+    pub fn max_error_count(&self) -> u32 {
+        unsafe { ffi::ts_parser_max_error_count(self.0.as_ptr()) }
+    }
+
+    /// Set the maximum number of errors that the parser should recover from
+    /// before giving up. A value of zero, the default, means that there is no
+    /// limit.
+    ///
+    /// Errors are counted once for each step of the parse in which any version
+    /// of the parse stack had to recover, so an error that several ambiguous
+    /// versions encounter at once only counts once.
+    ///
+    /// If the parser has to recover from more errors than this, it will halt
+    /// early, returning `None`. Unlike a timeout, the parse cannot be resumed.
+    #[doc(alias = "ts_parser_set_max_error_count")]
+    pub fn set_max_error_count(&mut self, max_error_count: u32) {
+        unsafe { ffi::ts_parser_set_max_error_count(self.0.as_ptr(), max_error_count) }
+    }
+
     /// Set the ranges of text that the parser should include when parsing.
     ///
     /// By default, the parser will always include entire documents. This function
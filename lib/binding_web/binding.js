@@ -149,6 +149,14 @@ class ParserImpl {
     C._ts_parser_set_timeout_micros(this[0], timeout);
   }
 
+  getMaxErrorCount() {
+    return C._ts_parser_max_error_count(this[0]);
+  }
+
+  setMaxErrorCount(count) {
+    C._ts_parser_set_max_error_count(this[0], count);
+  }
+
   setLogger(callback) {
     if (!callback) {
       callback = null;
@@ -10,6 +10,8 @@ declare module 'web-tree-sitter' {
     getIncludedRanges(): Parser.Range[];
     getTimeoutMicros(): number;
     setTimeoutMicros(timeout: number): void;
+    getMaxErrorCount(): number;
+    setMaxErrorCount(count: number): void;
     reset(): void;
     getLanguage(): Parser.Language;
     setLanguage(language?: Parser.Language | null): void;
@@ -245,7 +245,7 @@ const TSRange *ts_parser_included_ranges(
  *    `TSInputEncodingUTF8` or `TSInputEncodingUTF16`.
  *
  * This function returns a syntax tree on success, and `NULL` on failure. There
- * are three possible reasons for failure:
+ * are four possible reasons for failure:
  * 1. The parser does not have a language assigned. Check for this using the
       [`ts_parser_language`] function.
  * 2. Parsing was cancelled due to a timeout that was set by an earlier call to
@@ -257,6 +257,9 @@ const TSRange *ts_parser_included_ranges(
  *    earlier call to [`ts_parser_set_cancellation_flag`]. You can resume parsing
  *    from where the parser left out by calling [`ts_parser_parse`] again with
  *    the same arguments.
+ * 4. Parsing was abandoned after recovering from more errors than the limit
+ *    that was set by an earlier call to [`ts_parser_set_max_error_count`].
+ *    Parsing cannot be resumed in this case.
  *
  * [`read`]: TSInput::read
  * [`payload`]: TSInput::payload
@@ -321,6 +324,24 @@ void ts_parser_set_timeout_micros(TSParser *self, uint64_t timeout_micros);
  */
 uint64_t ts_parser_timeout_micros(const TSParser *self);
 
+/**
+ * Set the maximum number of errors that the parser should recover from before
+ * giving up. A value of zero, the default, means that there is no limit.
+ *
+ * Errors are counted once for each step of the parse in which any version of
+ * the parse stack had to recover, so an error that several ambiguous versions
+ * encounter at once only counts once.
+ *
+ * If the parser has to recover from more errors than this, it will halt early,
+ * returning NULL. See [`ts_parser_parse`] for more information.
+ */
+void ts_parser_set_max_error_count(TSParser *self, uint32_t max_error_count);
+
+/**
+ * Get the maximum number of errors that the parser will recover from.
+ */
+uint32_t ts_parser_max_error_count(const TSParser *self);
+
 /**
  * Set the parser's current cancellation flag pointer.
  *
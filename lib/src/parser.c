@@ -106,6 +106,9 @@ struct TSParser {
   TSDuration timeout_duration;
   unsigned accept_count;
   unsigned operation_count;
+  unsigned error_count;
+  unsigned max_error_count;
+  bool did_handle_error;
   const volatile size_t *cancellation_flag;
   Subtree old_tree;
   TSRangeArray included_range_differences;
@@ -1415,6 +1418,7 @@ static void ts_parser__handle_error(
   Subtree lookahead
 ) {
   uint32_t previous_version_count = ts_stack_version_count(self->stack);
+  self->did_handle_error = true;
 
   // Perform any reductions that can happen in this state, regardless of the lookahead. After
   // skipping one or more invalid tokens, the parser might find a token that would have allowed
@@ -1852,6 +1856,9 @@ TSParser *ts_parser_new(void) {
   self->external_scanner_payload = NULL;
   self->end_clock = clock_null();
   self->operation_count = 0;
+  self->error_count = 0;
+  self->max_error_count = 0;
+  self->did_handle_error = false;
   self->old_tree = NULL_SUBTREE;
   self->included_range_differences = (TSRangeArray) array_new();
   self->included_range_difference_index = 0;
@@ -1952,6 +1959,14 @@ void ts_parser_set_timeout_micros(TSParser *self, uint64_t timeout_micros) {
   self->timeout_duration = duration_from_micros(timeout_micros);
 }
 
+uint32_t ts_parser_max_error_count(const TSParser *self) {
+  return self->max_error_count;
+}
+
+void ts_parser_set_max_error_count(TSParser *self, uint32_t max_error_count) {
+  self->max_error_count = max_error_count;
+}
+
 bool ts_parser_set_included_ranges(
   TSParser *self,
   const TSRange *ranges,
@@ -1984,6 +1999,8 @@ void ts_parser_reset(TSParser *self) {
     self->finished_tree = NULL_SUBTREE;
   }
   self->accept_count = 0;
+  self->error_count = 0;
+  self->did_handle_error = false;
   self->has_scanner_error = false;
 }
 
@@ -2076,6 +2093,18 @@ TSTree *ts_parser_parse(
     // removing any versions that are no longer worth pursuing.
     unsigned min_error_cost = ts_parser__condense_stack(self);
 
+    // If a maximum error count was provided, then give up once the parser has
+    // had to recover from more errors than that. An error counts once, however
+    // many versions of the stack had to recover from it.
+    if (self->did_handle_error) {
+      self->did_handle_error = false;
+      self->error_count++;
+    }
+    if (self->max_error_count && self->error_count > self->max_error_count) {
+      LOG("halt_after_errors count:%u", self->error_count);
+      goto exit;
+    }
+
     // If there's already a finished parse tree that's better than any in-progress version,
     // then terminate parsing. Clear the parse stack to remove any extra references to subtrees
     // within the finished tree, ensuring that these subtrees can be safely mutated in-place